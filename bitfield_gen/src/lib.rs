@@ -0,0 +1,719 @@
+//! generates rust accessors for word-packed structures described in a `.bf` file, after seL4's
+//! bitfield generator. a spec is a list of
+//!
+//! ```text
+//! base 64(39,1)                      -- word size, canonical address bits, sign extend high fields
+//! block name(param, ...) {           -- fields from the top bit of the last word down
+//!     field name bits
+//!     field_high name bits           -- keeps the top `bits` bits of a canonical address
+//!     padding bits
+//! }
+//! tagged_union name tag_field {      -- blocks sharing one struct, told apart by tag_field
+//!     tag block_name value
+//! }
+//! ```
+//!
+//! a block outside any union becomes its own struct, with a `new` taking the parameters in the
+//! order given (only when a parameter list is given). a tagged union becomes one struct plus a
+//! `<Name>Tag` enum, with a `new_<block>` per member and accessors that check the tag. every
+//! field gets `get_<field>` and `set_<field>`; one bit fields are bools. round-trip tests for
+//! each block are emitted under `#[cfg(test)]`.
+
+use std::fs;
+use std::path::Path;
+
+const WORD_BITS: usize = 64;
+
+#[derive(Debug, Clone)]
+struct Field {
+    name: String,
+    bits: usize,
+    high: bool,
+    word: usize,
+    offset: usize,
+}
+
+#[derive(Debug)]
+struct Block {
+    name: String,
+    params: Option<Vec<String>>,
+    fields: Vec<Field>,
+    words: usize,
+}
+
+#[derive(Debug)]
+struct Union {
+    name: String,
+    tag_field: String,
+    tags: Vec<(String, usize)>,
+}
+
+#[derive(Debug)]
+struct Spec {
+    canonical_bits: usize,
+    sign_extend: bool,
+    blocks: Vec<Block>,
+    unions: Vec<Union>,
+}
+
+/// read the spec at `spec` and write the generated code to `out`, panicking with the reason if
+/// the spec is malformed. meant to be called from a build script.
+pub fn generate(spec: &Path, out: &Path) {
+    let source = fs::read_to_string(spec).unwrap_or_else(|e| panic!("{}: {}", spec.display(), e));
+    let code = generate_from_str(&source).unwrap_or_else(|e| panic!("{}: {}", spec.display(), e));
+    let header = format!("// generated by bitfield_gen from {}, do not edit\n\n",
+        spec.file_name().unwrap().to_string_lossy());
+    fs::write(out, header + &code).unwrap_or_else(|e| panic!("{}: {}", out.display(), e));
+}
+
+/// the generated code for the spec in `source`
+pub fn generate_from_str(source: &str) -> Result<String, String> {
+    let spec = parse(source)?;
+    check(&spec)?;
+    Ok(emit(&spec))
+}
+
+fn tokenize(source: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    for line in source.lines() {
+        let line = match line.find("--") {
+            Some(index) => &line[..index],
+            None => line,
+        };
+        let mut chars = line.chars().peekable();
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c.is_ascii_alphanumeric() || c == '_' {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_') {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                tokens.push(word);
+            } else {
+                tokens.push(c.to_string());
+                chars.next();
+            }
+        }
+    }
+    tokens
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<String, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("unexpected end of spec")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.pos).map(|t| t.as_str())
+    }
+
+    fn expect(&mut self, want: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token != want {
+            return Err(format!("expected `{}`, found `{}`", want, token));
+        }
+        Ok(())
+    }
+
+    fn ident(&mut self) -> Result<String, String> {
+        let token = self.next()?;
+        let first = token.chars().next().unwrap();
+        if !(first.is_ascii_alphabetic() || first == '_') {
+            return Err(format!("expected a name, found `{}`", token));
+        }
+        Ok(token)
+    }
+
+    fn number(&mut self) -> Result<usize, String> {
+        let token = self.next()?;
+        let parsed = match token.strip_prefix("0x") {
+            Some(hex) => usize::from_str_radix(hex, 16),
+            None => token.parse(),
+        };
+        parsed.map_err(|_| format!("expected a number, found `{}`", token))
+    }
+}
+
+fn parse(source: &str) -> Result<Spec, String> {
+    let mut parser = Parser { tokens: tokenize(source), pos: 0 };
+    let mut spec = Spec { canonical_bits: WORD_BITS, sign_extend: false, blocks: Vec::new(), unions: Vec::new() };
+    while parser.peek().is_some() {
+        match parser.next()?.as_str() {
+            "base" => {
+                let word_bits = parser.number()?;
+                if word_bits != WORD_BITS {
+                    return Err(format!("only base {} is supported", WORD_BITS));
+                }
+                if parser.peek() == Some("(") {
+                    parser.expect("(")?;
+                    spec.canonical_bits = parser.number()?;
+                    parser.expect(",")?;
+                    spec.sign_extend = parser.number()? != 0;
+                    parser.expect(")")?;
+                }
+            }
+            "block" => spec.blocks.push(parse_block(&mut parser)?),
+            "tagged_union" => spec.unions.push(parse_union(&mut parser)?),
+            other => return Err(format!("unexpected `{}`", other)),
+        }
+    }
+    Ok(spec)
+}
+
+fn parse_block(parser: &mut Parser) -> Result<Block, String> {
+    let name = parser.ident()?;
+    let mut params = None;
+    if parser.peek() == Some("(") {
+        parser.expect("(")?;
+        let mut list = Vec::new();
+        while parser.peek() != Some(")") {
+            list.push(parser.ident()?);
+            if parser.peek() == Some(",") {
+                parser.next()?;
+            }
+        }
+        parser.expect(")")?;
+        params = Some(list);
+    }
+
+    // (name, bits, high), with padding as None
+    let mut items: Vec<(Option<String>, usize, bool)> = Vec::new();
+    parser.expect("{")?;
+    loop {
+        match parser.next()?.as_str() {
+            "}" => break,
+            "field" => items.push((Some(parser.ident()?), parser.number()?, false)),
+            "field_high" => items.push((Some(parser.ident()?), parser.number()?, true)),
+            "padding" => items.push((None, parser.number()?, false)),
+            other => return Err(format!("unexpected `{}` in block {}", other, name)),
+        }
+    }
+
+    let total: usize = items.iter().map(|item| item.1).sum();
+    if total == 0 || total & (WORD_BITS - 1) != 0 {
+        return Err(format!("block {} is {} bits, not a whole number of words", name, total));
+    }
+    let mut fields = Vec::new();
+    let mut top = total;
+    for (field_name, bits, high) in items {
+        top -= bits;
+        // padding may run across words, fields may not
+        let field_name = match field_name {
+            Some(field_name) => field_name,
+            None => continue,
+        };
+        if bits == 0 || bits > WORD_BITS {
+            return Err(format!("block {}: {} has bad width {}", name, field_name, bits));
+        }
+        let word = top / WORD_BITS;
+        let offset = top % WORD_BITS;
+        if offset + bits > WORD_BITS {
+            return Err(format!("block {}: {} crosses a word boundary", name, field_name));
+        }
+        fields.push(Field { name: field_name, bits, high, word, offset });
+    }
+    Ok(Block { name, params, fields, words: total / WORD_BITS })
+}
+
+fn parse_union(parser: &mut Parser) -> Result<Union, String> {
+    let name = parser.ident()?;
+    let tag_field = parser.ident()?;
+    let mut tags = Vec::new();
+    parser.expect("{")?;
+    loop {
+        match parser.next()?.as_str() {
+            "}" => break,
+            "tag" => tags.push((parser.ident()?, parser.number()?)),
+            other => return Err(format!("unexpected `{}` in tagged_union {}", other, name)),
+        }
+    }
+    Ok(Union { name, tag_field, tags })
+}
+
+impl Spec {
+    fn block(&self, name: &str) -> Option<&Block> {
+        self.blocks.iter().find(|b| b.name == name)
+    }
+
+    fn union_of(&self, block: &str) -> Option<&Union> {
+        self.unions.iter().find(|u| u.tags.iter().any(|(b, _)| b == block))
+    }
+}
+
+impl Block {
+    fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// the fields set by the constructor, in parameter order
+    fn ctor_fields(&self, tag_field: Option<&str>) -> Vec<&Field> {
+        match &self.params {
+            Some(params) => params.iter().filter(|p| Some(p.as_str()) != tag_field)
+                .map(|p| self.field(p).unwrap()).collect(),
+            None => self.fields.iter().filter(|f| Some(f.name.as_str()) != tag_field).collect(),
+        }
+    }
+}
+
+fn check(spec: &Spec) -> Result<(), String> {
+    for block in &spec.blocks {
+        let mut names: Vec<&str> = block.fields.iter().map(|f| f.name.as_str()).collect();
+        names.sort();
+        if names.windows(2).any(|w| w[0] == w[1]) {
+            return Err(format!("block {} names a field twice", block.name));
+        }
+        for field in block.fields.iter().filter(|f| f.high) {
+            if field.bits > spec.canonical_bits {
+                return Err(format!("block {}: field_high {} is wider than the {} canonical bits",
+                    block.name, field.name, spec.canonical_bits));
+            }
+        }
+        let tag_field = spec.union_of(&block.name).map(|u| u.tag_field.as_str());
+        if let Some(params) = &block.params {
+            for param in params {
+                if block.field(param).is_none() {
+                    return Err(format!("block {} has no field {}", block.name, param));
+                }
+            }
+            for field in &block.fields {
+                if Some(field.name.as_str()) != tag_field && !params.contains(&field.name) {
+                    return Err(format!("block {}: {} is missing from the parameters", block.name, field.name));
+                }
+            }
+        }
+    }
+
+    for union in &spec.unions {
+        let mut tag: Option<&Field> = None;
+        let mut words = None;
+        let mut accessors: Vec<&str> = Vec::new();
+        for (name, value) in &union.tags {
+            let block = spec.block(name).ok_or(format!("tagged_union {}: no block {}", union.name, name))?;
+            if spec.unions.iter().filter(|u| u.tags.iter().any(|(b, _)| b == name)).count() > 1 {
+                return Err(format!("block {} is in more than one tagged_union", name));
+            }
+            let field = block.field(&union.tag_field)
+                .ok_or(format!("block {} has no tag field {}", name, union.tag_field))?;
+            if field.high {
+                return Err(format!("block {}: tag field {} can not be field_high", name, field.name));
+            }
+            if let Some(tag) = tag {
+                if (tag.word, tag.offset, tag.bits) != (field.word, field.offset, field.bits) {
+                    return Err(format!("block {} moves the tag field of {}", name, union.name));
+                }
+            }
+            if *value >= 1 << field.bits {
+                return Err(format!("tag {} of {} does not fit {} bits", value, name, field.bits));
+            }
+            if *words.get_or_insert(block.words) != block.words {
+                return Err(format!("block {} is not the size of the rest of {}", name, union.name));
+            }
+            tag = Some(field);
+            accessors.extend(block.fields.iter().filter(|f| f.name != union.tag_field).map(|f| f.name.as_str()));
+        }
+        let mut values: Vec<usize> = union.tags.iter().map(|t| t.1).collect();
+        values.sort();
+        if values.windows(2).any(|w| w[0] == w[1]) {
+            return Err(format!("tagged_union {} uses a tag value twice", union.name));
+        }
+        accessors.sort();
+        if let Some(w) = accessors.windows(2).find(|w| w[0] == w[1]) {
+            return Err(format!("tagged_union {}: field {} is in two blocks", union.name, w[0]));
+        }
+    }
+    Ok(())
+}
+
+/// `ASID_pool_cap` -> `ASIDPoolCap`
+fn camel(name: &str) -> String {
+    name.split('_').filter(|part| !part.is_empty()).map(|part| {
+        let mut chars = part.chars();
+        let first = chars.next().unwrap().to_ascii_uppercase();
+        core::iter::once(first).chain(chars).collect::<String>()
+    }).collect()
+}
+
+fn mask(bits: usize) -> usize {
+    if bits == WORD_BITS { usize::MAX } else { (1 << bits) - 1 }
+}
+
+struct Emitter<'a> {
+    spec: &'a Spec,
+    out: String,
+}
+
+impl Emitter<'_> {
+    fn line(&mut self, indent: usize, text: &str) {
+        if !text.is_empty() {
+            for _ in 0..indent {
+                self.out.push_str("    ");
+            }
+            self.out.push_str(text);
+        }
+        self.out.push('\n');
+    }
+
+    fn field_mask(&self, field: &Field) -> usize {
+        mask(field.bits) << field.offset
+    }
+
+    /// how far left a value moves to its place in the word; negative for field_high
+    fn shift(&self, field: &Field) -> isize {
+        if field.high {
+            field.offset as isize - (self.spec.canonical_bits - field.bits) as isize
+        } else {
+            field.offset as isize
+        }
+    }
+
+    fn shifted(value: &str, shift: isize) -> String {
+        match shift {
+            0 => value.to_string(),
+            s if s > 0 => format!("({} << {})", value, s),
+            s => format!("({} >> {})", value, -s),
+        }
+    }
+
+    fn value_type(field: &Field) -> &'static str {
+        if field.bits == 1 && !field.high { "bool" } else { "usize" }
+    }
+
+    /// the bits of `value` placed in the word of `field`
+    fn store(&self, field: &Field, value: &str) -> String {
+        let value = if Self::value_type(field) == "bool" { format!("({} as usize)", value) } else { value.to_string() };
+        let placed = Self::shifted(&value, self.shift(field));
+        if field.bits == WORD_BITS {
+            placed
+        } else {
+            format!("{} & {:#x}", placed, self.field_mask(field))
+        }
+    }
+
+    fn emit_getter(&mut self, field: &Field, tag_check: Option<&str>) {
+        let ty = Self::value_type(field);
+        self.line(1, &format!("pub fn get_{}(&self) -> {} {{", field.name, ty));
+        if let Some(check) = tag_check {
+            self.line(2, check);
+        }
+        let word = format!("self.words[{}]", field.word);
+        let masked = if field.bits == WORD_BITS { word } else { format!("({} & {:#x})", word, self.field_mask(field)) };
+        let value = Self::shifted(&masked, -self.shift(field));
+        let value = value.strip_prefix('(').and_then(|v| v.strip_suffix(')')).map_or(value.clone(), str::to_string);
+        if ty == "bool" {
+            self.line(2, &format!("{} != 0", value));
+        } else if field.high && self.spec.sign_extend && self.spec.canonical_bits < WORD_BITS {
+            self.line(2, &format!("let ret = {};", value));
+            self.line(2, &format!("if ret & (1 << {}) != 0 {{", self.spec.canonical_bits - 1));
+            self.line(3, &format!("ret | {:#x}", !mask(self.spec.canonical_bits)));
+            self.line(2, "} else {");
+            self.line(3, "ret");
+            self.line(2, "}");
+        } else {
+            self.line(2, &value);
+        }
+        self.line(1, "}");
+        self.line(0, "");
+    }
+
+    fn emit_setter(&mut self, field: &Field, tag_check: Option<&str>) {
+        self.line(1, &format!("pub fn set_{}(&mut self, v: {}) {{", field.name, Self::value_type(field)));
+        if let Some(check) = tag_check {
+            self.line(2, check);
+        }
+        if field.bits != WORD_BITS {
+            self.line(2, &format!("self.words[{}] &= !{:#x};", field.word, self.field_mask(field)));
+            self.line(2, &format!("self.words[{}] |= {};", field.word, self.store(field, "v")));
+        } else {
+            self.line(2, &format!("self.words[{}] = {};", field.word, self.store(field, "v")));
+        }
+        self.line(1, "}");
+        self.line(0, "");
+    }
+
+    /// a constructor building each word at once; `tag` is the tag field with its value
+    fn emit_ctor(&mut self, name: &str, block: &Block, tag: Option<(&Field, String)>) {
+        let tag_name = tag.as_ref().map(|(f, _)| f.name.as_str());
+        let fields = block.ctor_fields(tag_name);
+        let params: Vec<String> = fields.iter().map(|f| format!("{}: {}", f.name, Self::value_type(f))).collect();
+        self.line(1, &format!("pub fn {}({}) -> Self {{", name, params.join(", ")));
+        self.line(2, &format!("let mut ret = Self {{ words: [0; {}] }};", block.words));
+        for word in 0..block.words {
+            let mut parts = Vec::new();
+            if let Some((field, value)) = &tag {
+                if field.word == word {
+                    parts.push(self.store(field, value));
+                }
+            }
+            for field in fields.iter().filter(|f| f.word == word) {
+                parts.push(self.store(field, &field.name));
+            }
+            match parts.len() {
+                0 => {}
+                1 => self.line(2, &format!("ret.words[{}] = {};", word, parts[0])),
+                _ => {
+                    self.line(2, &format!("ret.words[{}] = {}", word, parts[0]));
+                    for part in &parts[1..parts.len() - 1] {
+                        self.line(3, &format!("| {}", part));
+                    }
+                    self.line(3, &format!("| {};", parts[parts.len() - 1]));
+                }
+            }
+        }
+        self.line(2, "ret");
+        self.line(1, "}");
+        self.line(0, "");
+    }
+
+    fn emit_struct(&mut self, name: &str, words: usize) {
+        self.line(0, "#[repr(C)]");
+        self.line(0, "#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]");
+        self.line(0, &format!("pub struct {} {{", name));
+        self.line(1, &format!("pub words: [usize; {}],", words));
+        self.line(0, "}");
+        self.line(0, "");
+    }
+
+    fn emit_block(&mut self, block: &Block) {
+        let name = camel(&block.name);
+        self.emit_struct(&name, block.words);
+        self.line(0, &format!("impl {} {{", name));
+        if block.params.is_some() {
+            self.emit_ctor("new", block, None);
+        }
+        for field in &block.fields {
+            self.emit_getter(field, None);
+            self.emit_setter(field, None);
+        }
+        self.trim_blank();
+        self.line(0, "}");
+        self.line(0, "");
+    }
+
+    fn emit_union(&mut self, union: &Union) {
+        let name = camel(&union.name);
+        let tag_enum = format!("{}Tag", name);
+        let first = self.spec.block(&union.tags[0].0).unwrap();
+        let tag_field = first.field(&union.tag_field).unwrap().clone();
+
+        self.line(0, "#[derive(Clone, Copy, Debug, PartialEq, Eq)]");
+        self.line(0, &format!("pub enum {} {{", tag_enum));
+        for (block, value) in &union.tags {
+            self.line(1, &format!("{}{} = {},", name, camel(block), value));
+        }
+        self.line(0, "}");
+        self.line(0, "");
+
+        self.emit_struct(&name, first.words);
+        self.line(0, &format!("impl {} {{", name));
+        self.line(1, &format!("pub fn get_{}(&self) -> {} {{", tag_field.name, tag_enum));
+        self.line(2, &format!("match (self.words[{}] & {:#x}) >> {} {{",
+            tag_field.word, self.field_mask(&tag_field), tag_field.offset));
+        for (block, value) in &union.tags {
+            self.line(3, &format!("{} => {}::{}{},", value, tag_enum, name, camel(block)));
+        }
+        self.line(3, &format!("tag => panic!(\"invalid {} {{}}\", tag),", tag_field.name));
+        self.line(2, "}");
+        self.line(1, "}");
+        self.line(0, "");
+
+        for (block_name, _) in &union.tags {
+            let block = self.spec.block(block_name).unwrap();
+            let variant = format!("{}::{}{}", tag_enum, name, camel(block_name));
+            self.emit_ctor(&format!("new_{}", block_name.to_lowercase()), block,
+                Some((&tag_field, format!("({} as usize)", variant))));
+            let check = format!("assert_eq!(self.get_{}(), {});", tag_field.name, variant);
+            for field in block.fields.iter().filter(|f| f.name != union.tag_field) {
+                self.emit_getter(field, Some(&check));
+                self.emit_setter(field, Some(&check));
+            }
+        }
+        self.trim_blank();
+        self.line(0, "}");
+        self.line(0, "");
+    }
+
+    fn trim_blank(&mut self) {
+        while self.out.ends_with("\n\n") {
+            self.out.pop();
+        }
+    }
+
+    /// a value for `field` that survives a round trip; `which` picks one of two that differ
+    /// in every bit the field keeps
+    fn test_value(&self, field: &Field, index: usize, which: bool) -> String {
+        if Self::value_type(field) == "bool" {
+            return ((index & 1 == 0) == which).to_string();
+        }
+        let pattern = 0xa5c3_96e1_7b2d_f048_usize.rotate_left(index as u32 * 7);
+        let pattern = if which { pattern } else { !pattern };
+        let value = pattern & mask(field.bits);
+        if !field.high {
+            return format!("{:#x}", value);
+        }
+        let low = self.spec.canonical_bits - field.bits;
+        let value = value << low;
+        let canonical = mask(self.spec.canonical_bits);
+        if self.spec.sign_extend && self.spec.canonical_bits < WORD_BITS && value & (1 << (self.spec.canonical_bits - 1)) != 0 {
+            format!("{:#x}", value | !canonical)
+        } else {
+            format!("{:#x}", value)
+        }
+    }
+
+    fn emit_test(&mut self, ty: &str, block: &Block, tag: Option<(&str, String)>) {
+        let tag_name = tag.as_ref().map(|(f, _)| *f);
+        let fields = block.ctor_fields(tag_name);
+        self.line(1, "#[test]");
+        self.line(1, &format!("fn {}_round_trip() {{", block.name.to_lowercase()));
+        let first: Vec<String> = fields.iter().enumerate().map(|(i, f)| self.test_value(f, i, true)).collect();
+        let second: Vec<String> = fields.iter().enumerate().map(|(i, f)| self.test_value(f, i, false)).collect();
+        let ctor = match &tag {
+            Some(_) => Some(format!("new_{}", block.name.to_lowercase())),
+            None if block.params.is_some() => Some("new".to_string()),
+            None => None,
+        };
+        let binding = if fields.is_empty() { "let v" } else { "let mut v" };
+        match ctor {
+            Some(ctor) => self.line(2, &format!("{} = {}::{}({});", binding, ty, ctor, first.join(", "))),
+            None => {
+                self.line(2, &format!("{} = {}::default();", binding, ty));
+                for (field, value) in fields.iter().zip(&first) {
+                    self.line(2, &format!("v.set_{}({});", field.name, value));
+                }
+            }
+        }
+        let mut current = first.clone();
+        self.emit_test_checks(&fields, &current, tag.as_ref());
+        // each setter changes its own field and nothing else
+        for (i, field) in fields.iter().enumerate() {
+            self.line(2, &format!("v.set_{}({});", field.name, second[i]));
+            current[i] = second[i].clone();
+            self.emit_test_checks(&fields, &current, tag.as_ref());
+        }
+        self.line(1, "}");
+        self.line(0, "");
+    }
+
+    fn emit_test_checks(&mut self, fields: &[&Field], values: &[String], tag: Option<&(&str, String)>) {
+        if let Some((tag_field, variant)) = tag {
+            self.line(2, &format!("assert_eq!(v.get_{}(), {});", tag_field, variant));
+        }
+        for (field, value) in fields.iter().zip(values) {
+            let check = match value.as_str() {
+                "true" => format!("assert!(v.get_{}());", field.name),
+                "false" => format!("assert!(!v.get_{}());", field.name),
+                _ => format!("assert_eq!(v.get_{}(), {});", field.name, value),
+            };
+            self.line(2, &check);
+        }
+    }
+
+    fn emit_tests(&mut self) {
+        self.line(0, "#[cfg(test)]");
+        self.line(0, "mod tests {");
+        self.line(1, "use super::*;");
+        self.line(0, "");
+        for block in &self.spec.blocks {
+            match self.spec.union_of(&block.name) {
+                Some(union) => {
+                    let name = camel(&union.name);
+                    let variant = format!("{}Tag::{}{}", name, name, camel(&block.name));
+                    self.emit_test(&name, block, Some((&union.tag_field, variant)));
+                }
+                None => self.emit_test(&camel(&block.name), block, None),
+            }
+        }
+        self.trim_blank();
+        self.line(0, "}");
+    }
+}
+
+fn emit(spec: &Spec) -> String {
+    let mut emitter = Emitter { spec, out: String::new() };
+    for block in spec.blocks.iter().filter(|b| spec.union_of(&b.name).is_none()) {
+        emitter.emit_block(block);
+    }
+    for union in &spec.unions {
+        emitter.emit_union(union);
+    }
+    emitter.emit_tests();
+    emitter.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &str = "
+        base 64(39,1)
+        block node(next, flag, prev) {
+            field prev 64
+            padding 25
+            field_high next 37 -- low two bits are always clear
+            padding 1
+            field flag 1
+        }
+        block null_cap { padding 64 field cap_type 5 padding 59 }
+        block frame_cap(base, mapped) {
+            field_high base 39
+            padding 25
+            field cap_type 5
+            padding 20
+            field_high mapped 39
+        }
+        tagged_union cap cap_type {
+            tag null_cap 0
+            tag frame_cap 1
+        }
+    ";
+
+    #[test]
+    fn lays_fields_out_from_the_top() {
+        let spec = parse(SPEC).unwrap();
+        let node = spec.block("node").unwrap();
+        assert_eq!(node.words, 2);
+        let prev = node.field("prev").unwrap();
+        assert_eq!((prev.word, prev.offset), (1, 0));
+        let next = node.field("next").unwrap();
+        assert_eq!((next.word, next.offset), (0, 2));
+        let flag = node.field("flag").unwrap();
+        assert_eq!((flag.word, flag.offset), (0, 0));
+    }
+
+    #[test]
+    fn generates_accessors() {
+        let code = generate_from_str(SPEC).unwrap();
+        assert!(code.contains("pub struct Node {"));
+        assert!(code.contains("pub fn new(next: usize, flag: bool, prev: usize) -> Self {"));
+        assert!(code.contains("pub enum CapTag {"));
+        assert!(code.contains("CapFrameCap = 1,"));
+        assert!(code.contains("pub fn new_frame_cap(base: usize, mapped: usize) -> Self {"));
+        assert!(code.contains("assert_eq!(self.get_cap_type(), CapTag::CapFrameCap);"));
+        // the high field keeps bits 2..39 of the address in place
+        assert!(code.contains("self.words[0] |= v & 0x7ffffffffc;"));
+        assert!(code.contains("fn frame_cap_round_trip() {"));
+    }
+
+    #[test]
+    fn rejects_bad_specs() {
+        assert!(generate_from_str("block a { field x 63 }").is_err());
+        assert!(generate_from_str("block a { field x 32 padding 8 field y 32 padding 56 }").is_err());
+        assert!(generate_from_str("block a(x, z) { field x 64 }").is_err());
+        assert!(generate_from_str("block a { field t 4 padding 60 } block b { field t 5 padding 59 }
+            tagged_union u t { tag a 0 tag b 1 }").is_err());
+        assert!(generate_from_str("block a { field t 4 field x 60 } block b { field t 4 field x 60 }
+            tagged_union u t { tag a 0 tag b 1 }").is_err());
+    }
+}
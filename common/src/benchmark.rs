@@ -0,0 +1,63 @@
+use core::mem::size_of;
+
+// written by a kernel built with the benchmark feature into the frame passed to
+// BenchmarkSetLogBuffer, and read back by user space with the frame mapped.
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum TraceKind {
+    Syscall = 1,
+    Interrupt = 2,
+    Exception = 3,
+    /* the kernel picked `thread` to run next */
+    ThreadSwitch = 4,
+}
+
+impl TraceKind {
+    pub fn from_usize(kind: usize) -> Option<Self> {
+        match kind {
+            1 => Some(TraceKind::Syscall),
+            2 => Some(TraceKind::Interrupt),
+            3 => Some(TraceKind::Exception),
+            4 => Some(TraceKind::ThreadSwitch),
+            _ => None,
+        }
+    }
+}
+
+/// one kernel entry, from the trap to the return to user space. the log is a ring: after
+/// BenchmarkFinalizeLog returned `n`, entry `i` of the last `capacity` ones is at `i % capacity`
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct TraceEntry {
+    pub kind: usize,
+    /// the syscall number, or scause for interrupts and exceptions
+    pub number: usize,
+    /// the invocation label of a Call or Send
+    pub label: usize,
+    /// the tcb running at entry, or the one switched to
+    pub thread: usize,
+    /// rdtime at entry
+    pub time: usize,
+    /// rdcycle at entry
+    pub start_cycle: usize,
+    /// rdcycle at exit minus start_cycle, 0 for thread switches
+    pub cycles: usize,
+    pub hart: usize,
+}
+
+impl TraceEntry {
+    pub fn get_kind(&self) -> Option<TraceKind> {
+        TraceKind::from_usize(self.kind)
+    }
+}
+
+pub const TRACE_ENTRY_BITS: usize = 6;
+
+const _: () = assert!(size_of::<TraceEntry>() == 1 << TRACE_ENTRY_BITS);
+
+// where BenchmarkGetThreadUtilisation leaves its results in the caller's ipc buffer, in cycles
+pub const BENCHMARK_TCB_UTILISATION: usize = 0;
+pub const BENCHMARK_IDLE_LOCALCPU_UTILISATION: usize = 1;
+pub const BENCHMARK_IDLE_TCBCPU_UTILISATION: usize = 2;
+pub const BENCHMARK_TOTAL_UTILISATION: usize = 3;
+pub const BENCHMARK_TCB_NUMBER_SCHEDULES: usize = 4;
@@ -0,0 +1,94 @@
+use core::mem::size_of;
+
+use super::config::{BI_FRAME_SIZE_BITS, CONFIG_MAX_NUM_BOOT_INFO_UNTYPED_CAPS, MAX_NUM_AVAIL_P_REGS};
+use super::types::{NodeId, PhyRegion, SlotRegion, UntypedDesc, Vptr};
+use crate::offset_of;
+
+// everything here is written by the kernel into the bootinfo frame and read back by the root
+// server, so the layouts are fixed with repr(C) and checked below against seL4_BootInfo.
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct BootInfoHeader {
+    pub id: usize,
+    pub len: usize,
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum BootInfoID {
+    Sel4BootInfoHeaderPadding = 0,
+    Sel4BootInfoHeaderX86Vbe = 1,
+    Sel4BootInfoHeaderX86MbmMap = 2,
+    Sel4BootInfoHeaderX86AcpiRsdp = 3,
+    Sel4BootInfoHeaderX86Framebuffer = 4,
+    Sel4BootInfoHeaderX86TscFreq = 5, /* frequency is in MHz */
+    Sel4BootInfoHeaderFdt = 6, /* device tree */
+    Sel4BootInfoHeaderBootModules = 7, /* cpio archive of user images */
+    Sel4BootInfoHeaderPlatform = 8, /* kernel version and memory map */
+    Sel4BootInfoHeaderNum,
+}
+
+/// follows a Sel4BootInfoHeaderPlatform header
+#[repr(C)]
+#[derive(Copy, Clone, Default, Debug)]
+pub struct BootInfoPlatform {
+    pub kernel_version: [usize; 3],
+    pub timebase_freq: usize,
+    pub num_mem: usize,
+    pub mem: [PhyRegion; MAX_NUM_AVAIL_P_REGS],
+}
+
+/// follows a Sel4BootInfoHeaderBootModules header
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct BootInfoBootModules {
+    pub vptr: Vptr,
+    pub size: usize,
+    pub frames: SlotRegion,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct BootInfo {
+    pub extra_len: usize,
+    pub node_id: NodeId,
+    pub num_nodes: usize,
+    pub num_io_pt_levels: usize,
+    pub ipc_buf_ptr: Vptr,
+    pub empty: SlotRegion,
+    pub shared_frames: SlotRegion,
+    pub user_image_frames: SlotRegion,
+    pub user_image_paging: SlotRegion,
+    pub io_space_caps: SlotRegion,
+    pub extra_bi_pages: SlotRegion,
+    pub init_thread_cnode_size_bits: usize,
+    pub init_thread_domain: usize,
+    /// one SchedControl cap per node, in the order of the node ids
+    #[cfg(feature = "mcs")]
+    pub schedcontrol: SlotRegion,
+    pub untyped: SlotRegion,
+    pub untyped_list: [UntypedDesc; CONFIG_MAX_NUM_BOOT_INFO_UNTYPED_CAPS],
+}
+
+const WORD: usize = size_of::<usize>();
+
+const _: () = assert!(size_of::<BootInfoHeader>() == 2 * WORD);
+const _: () = assert!(size_of::<SlotRegion>() == 2 * WORD);
+const _: () = assert!(size_of::<UntypedDesc>() == 2 * WORD);
+const _: () = assert!(size_of::<BootInfoBootModules>() == 4 * WORD);
+const _: () = assert!(size_of::<BootInfoPlatform>() == (5 + 2 * MAX_NUM_AVAIL_P_REGS) * WORD);
+const _: () = assert!(offset_of!(BootInfo, ipc_buf_ptr) == 4 * WORD);
+const _: () = assert!(offset_of!(BootInfo, empty) == 5 * WORD);
+const _: () = assert!(offset_of!(BootInfo, extra_bi_pages) == 15 * WORD);
+const _: () = assert!(offset_of!(BootInfo, init_thread_cnode_size_bits) == 17 * WORD);
+#[cfg(not(feature = "mcs"))]
+const UNTYPED_OFFSET: usize = 19 * WORD;
+#[cfg(feature = "mcs")]
+const UNTYPED_OFFSET: usize = 21 * WORD;
+#[cfg(feature = "mcs")]
+const _: () = assert!(offset_of!(BootInfo, schedcontrol) == 19 * WORD);
+const _: () = assert!(offset_of!(BootInfo, untyped) == UNTYPED_OFFSET);
+const _: () = assert!(offset_of!(BootInfo, untyped_list) == UNTYPED_OFFSET + 2 * WORD);
+const _: () = assert!(size_of::<BootInfo>() == UNTYPED_OFFSET + (2 + 2 * CONFIG_MAX_NUM_BOOT_INFO_UNTYPED_CAPS) * WORD);
+// the extra bootinfo starts on the page after
+const _: () = assert!(size_of::<BootInfo>() <= 1 << BI_FRAME_SIZE_BITS);
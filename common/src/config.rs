@@ -19,11 +19,14 @@ pub const PAGE_BITS: usize = 12;
 pub const ROOT_PAGE_TABLE_SIZE: usize = 1 << PAGE_TABLE_INDEX_BITS;
 pub const SATP_MODE_SV39: usize = 8;
 
-pub const AVAIL_MEM_DEVICE: usize = 1;
-pub const AVAIL_PHY_MEM_START: usize = 0x8000_0000;
-pub const AVAIL_PHY_MEM_END: usize = 0x8800_0000;
+pub const RISCV_4K_PAGE: usize = 0;
+pub const RISCV_MEGA_PAGE: usize = 1;
+pub const RISCV_GIGA_PAGE: usize = 2;
 
-pub const NUM_RESERVED_REGIONS: usize = 3;
+pub const MAX_NUM_AVAIL_P_REGS: usize = 4;
+pub const MAX_NUM_FDT_RESV_REGS: usize = 8;
+
+pub const NUM_RESERVED_REGIONS: usize = 5 + MAX_NUM_FDT_RESV_REGS;
 pub const MAX_NUM_FREEMEM_REG: usize = 16;
 pub const MAX_NUM_RESV_REG: usize = NUM_RESERVED_REGIONS + MAX_NUM_FREEMEM_REG;
 
@@ -32,6 +35,8 @@ pub const SEL4_VSPACE_BITS: usize = PAGE_BITS;
 pub const SEL4_TCB_BITS: usize = 10;
 pub const SEL4_TCB_SIZE_BITS: usize = SEL4_TCB_BITS - 1;
 pub const SEL4_PAGE_BITS: usize = 12;
+pub const SEL4_LARGE_PAGE_BITS: usize = 21;
+pub const SEL4_HUGE_PAGE_BITS: usize = 30;
 pub const BI_FRAME_SIZE_BITS: usize = PAGE_BITS;
 pub const SEL4_ASID_POOL_BITS: usize = 12;
 pub const SEL4_WORD_BITS: usize = 64;
@@ -46,14 +51,12 @@ pub const SEL4_MSG_EXTRA_CAP_BITS: usize = 2;
 pub const SEL4_MSG_MAX_EXTRA_CAPS: usize = (1 << SEL4_MSG_EXTRA_CAP_BITS) - 1;
 
 pub const CONFIG_ROOT_CNODE_SIZE_BITS: usize = 13;
-pub const CONFIG_MAX_NUM_NODES: usize = 1;
-pub const CONFIG_KERNEL_STACK_BITS: usize = 12;
+pub const CONFIG_MAX_NUM_NODES: usize = 4;
+pub const CONFIG_KERNEL_STACK_BITS: usize = 14;
 pub const CONFIG_TIME_SLICE: usize = 5;
 // root server image
-pub const UI_P_REG_START: usize = 0x82000000;
-pub const UI_P_REG_END: usize = 0x82400000;
-pub const UI_PV_OFFSET: usize = 0x81FFF000;
-pub const UI_V_ENTRY: usize = 0x1000;
+pub const UI_ELF_P_START: usize = 0x82000000;
+pub const BOOT_MODULES_P_START: usize = 0x84000000;
 pub const USER_TOP: usize = 0x0000003FFFFFF000;
 
 pub const IT_ASID: usize = 1;
@@ -64,7 +67,7 @@ pub const CONFIG_NUM_PRIORITIES: usize = 256;
 pub const NUM_ASID_POOL_BITS: usize = 7;
 pub const ASID_POOL_INDEX_BITS: usize = 9;
 
-pub const CPU_NUM: usize = 1;
+pub const CPU_NUM: usize = CONFIG_MAX_NUM_NODES;
 pub const CONTEXT_REGISTERS_NUM: usize = 35;
 
 pub const MIN_UNTYPED_BITS: usize = 4;
@@ -92,6 +95,7 @@ pub const MSG_MAX_EXTRA_CAPS: usize = (1 << MSG_MAX_EXTRA_CAP_BITS) - 1;
 
 pub const SEL4_ENDPOINT_BITS: usize = 4;
 pub const SEL4_NOTIFICATION_BITS: usize = 5;
+pub const SEL4_REPLY_BITS: usize = 5;
 
 pub const CONFIG_RETYPE_FAN_OUT_LIMIT: usize = 256;
 
@@ -99,5 +103,4 @@ pub const CONFIG_RESET_CHUNK_BITS: usize = 8;
 
 pub const NULL_PRIO: usize = 0;
 
-pub const CLOCK_FREQ: usize = 12500000;
 pub const TICKS_PER_SEC: usize = 100;
\ No newline at end of file
@@ -0,0 +1,89 @@
+const CPIO_NEWC_MAGIC: &[u8] = b"070701";
+const CPIO_HEADER_SIZE: usize = 110;
+const CPIO_TRAILER: &[u8] = b"TRAILER!!!";
+
+const CPIO_FILE_SIZE_OFFSET: usize = 54;
+const CPIO_NAME_SIZE_OFFSET: usize = 94;
+
+pub struct CpioEntry<'a> {
+    pub name: &'a str,
+    pub data: &'a [u8],
+}
+
+pub struct CpioIter<'a> {
+    archive: &'a [u8],
+    offset: usize,
+}
+
+fn align4(x: usize) -> usize {
+    (x + 3) & !3
+}
+
+fn parse_hex(field: &[u8]) -> Option<usize> {
+    let mut value = 0;
+    for c in field {
+        let digit = (*c as char).to_digit(16)? as usize;
+        value = (value << 4) | digit;
+    }
+    Some(value)
+}
+
+/// parse the entry at `offset`, returning it with the offset of the next one
+fn parse_entry(archive: &[u8], offset: usize) -> Option<(CpioEntry<'_>, usize)> {
+    let header = archive.get(offset..offset + CPIO_HEADER_SIZE)?;
+    if &header[..CPIO_NEWC_MAGIC.len()] != CPIO_NEWC_MAGIC {
+        return None;
+    }
+    let file_size = parse_hex(&header[CPIO_FILE_SIZE_OFFSET..CPIO_FILE_SIZE_OFFSET + 8])?;
+    let name_size = parse_hex(&header[CPIO_NAME_SIZE_OFFSET..CPIO_NAME_SIZE_OFFSET + 8])?;
+    if name_size == 0 {
+        return None;
+    }
+    let name_start = offset + CPIO_HEADER_SIZE;
+    // name_size counts the terminating nul
+    let name = archive.get(name_start..name_start + name_size - 1)?;
+    let data_start = align4(name_start + name_size);
+    let data = archive.get(data_start..data_start + file_size)?;
+    let entry = CpioEntry {
+        name: core::str::from_utf8(name).ok()?,
+        data,
+    };
+    Some((entry, align4(data_start + file_size)))
+}
+
+impl<'a> CpioIter<'a> {
+    pub fn new(archive: &'a [u8]) -> Self {
+        Self { archive, offset: 0 }
+    }
+}
+
+impl<'a> Iterator for CpioIter<'a> {
+    type Item = CpioEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (entry, next) = parse_entry(self.archive, self.offset)?;
+        if entry.name.as_bytes() == CPIO_TRAILER {
+            return None;
+        }
+        self.offset = next;
+        Some(entry)
+    }
+}
+
+/// the length of the archive up to and including its trailer, or None if it is not a valid archive
+pub fn archive_len(archive: &[u8]) -> Option<usize> {
+    let mut offset = 0;
+    loop {
+        let (entry, next) = parse_entry(archive, offset)?;
+        if entry.name.as_bytes() == CPIO_TRAILER {
+            return Some(next);
+        }
+        offset = next;
+    }
+}
+
+pub fn find<'a>(archive: &'a [u8], name: &str) -> Option<&'a [u8]> {
+    CpioIter::new(archive)
+        .find(|entry| entry.name.trim_start_matches("./") == name)
+        .map(|entry| entry.data)
+}
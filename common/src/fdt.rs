@@ -0,0 +1,207 @@
+use crate::types::PhyRegion;
+
+const FDT_MAGIC: u32 = 0xd00dfeed;
+const FDT_BEGIN_NODE: u32 = 0x1;
+const FDT_END_NODE: u32 = 0x2;
+const FDT_PROP: u32 = 0x3;
+const FDT_NOP: u32 = 0x4;
+const FDT_END: u32 = 0x9;
+
+pub const FDT_HEADER_SIZE: usize = 40;
+pub const FDT_MAX_DEPTH: usize = 16;
+
+// #address-cells and #size-cells of a node that does not set them
+pub const FDT_DEFAULT_ADDRESS_CELLS: usize = 2;
+pub const FDT_DEFAULT_SIZE_CELLS: usize = 1;
+
+pub struct Fdt<'a> {
+    data: &'a [u8],
+    off_dt_struct: usize,
+    off_dt_strings: usize,
+    off_mem_rsvmap: usize,
+    size_dt_struct: usize,
+}
+
+pub enum FdtToken<'a> {
+    BeginNode(&'a str),
+    EndNode,
+    Prop(&'a str, &'a [u8]),
+}
+
+pub struct FdtTokenIter<'a> {
+    data: &'a [u8],
+    off_dt_strings: usize,
+    offset: usize,
+    end: usize,
+}
+
+fn be32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn align4(x: usize) -> usize {
+    (x + 3) & !3
+}
+
+fn c_str(data: &[u8], offset: usize) -> Option<&str> {
+    let bytes = data.get(offset..)?;
+    let len = bytes.iter().position(|c| *c == 0)?;
+    core::str::from_utf8(&bytes[..len]).ok()
+}
+
+/// the size of the blob described by `header`, or None if it is not a device tree
+pub fn fdt_total_size(header: &[u8]) -> Option<usize> {
+    if be32(header, 0)? != FDT_MAGIC {
+        return None;
+    }
+    Some(be32(header, 4)? as usize)
+}
+
+/// a big-endian value of `cells` 32-bit cells
+pub fn read_cells(value: &[u8], cells: usize) -> usize {
+    let mut result = 0;
+    for i in 0..cells {
+        result = (result << 32) | be32(value, i * 4).unwrap_or(0) as usize;
+    }
+    result
+}
+
+pub fn reg_iter(value: &[u8], address_cells: usize, size_cells: usize) -> impl Iterator<Item = PhyRegion> + '_ {
+    let entry_size = (address_cells + size_cells) * 4;
+    let count = if entry_size == 0 { 0 } else { value.len() / entry_size };
+    (0..count).map(move |i| {
+        let entry = &value[i * entry_size..];
+        let start = read_cells(entry, address_cells);
+        let size = read_cells(&entry[address_cells * 4..], size_cells);
+        PhyRegion { start, end: start + size }
+    })
+}
+
+pub fn stringlist_contains(value: &[u8], names: &[&str]) -> bool {
+    value.split(|c| *c == 0).any(|s| names.iter().any(|name| name.as_bytes() == s))
+}
+
+impl<'a> Fdt<'a> {
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        let total_size = fdt_total_size(data)?;
+        let data = data.get(..total_size)?;
+        let fdt = Fdt {
+            data,
+            off_dt_struct: be32(data, 8)? as usize,
+            off_dt_strings: be32(data, 12)? as usize,
+            off_mem_rsvmap: be32(data, 16)? as usize,
+            size_dt_struct: be32(data, 36)? as usize,
+        };
+        if fdt.off_dt_struct + fdt.size_dt_struct > total_size || fdt.off_dt_strings > total_size {
+            return None;
+        }
+        Some(fdt)
+    }
+
+    pub fn total_size(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn mem_reserve_iter(&self) -> impl Iterator<Item = PhyRegion> + '_ {
+        let data = self.data;
+        let mut offset = self.off_mem_rsvmap;
+        core::iter::from_fn(move || {
+            let entry = data.get(offset..offset + 16)?;
+            let start = read_cells(entry, 2);
+            let size = read_cells(&entry[8..], 2);
+            if start == 0 && size == 0 {
+                return None;
+            }
+            offset += 16;
+            Some(PhyRegion { start, end: start + size })
+        })
+    }
+
+    /// the structure block; iteration stops at its end or at the first malformed token
+    pub fn tokens(&self) -> FdtTokenIter<'a> {
+        FdtTokenIter {
+            data: self.data,
+            off_dt_strings: self.off_dt_strings,
+            offset: self.off_dt_struct,
+            end: self.off_dt_struct + self.size_dt_struct,
+        }
+    }
+
+    /// the first `reg` entry of the first node compatible with one of `compatible`
+    pub fn find_compatible(&self, compatible: &[&str]) -> Option<PhyRegion> {
+        let mut cells = [(FDT_DEFAULT_ADDRESS_CELLS, FDT_DEFAULT_SIZE_CELLS); FDT_MAX_DEPTH];
+        let mut matched = [false; FDT_MAX_DEPTH];
+        let mut reg: [Option<&'a [u8]>; FDT_MAX_DEPTH] = [None; FDT_MAX_DEPTH];
+        let mut depth = 0;
+        for token in self.tokens() {
+            match token {
+                FdtToken::BeginNode(_) => {
+                    if depth + 1 >= FDT_MAX_DEPTH {
+                        return None;
+                    }
+                    depth += 1;
+                    cells[depth] = (FDT_DEFAULT_ADDRESS_CELLS, FDT_DEFAULT_SIZE_CELLS);
+                    matched[depth] = false;
+                    reg[depth] = None;
+                }
+                FdtToken::EndNode => {
+                    if depth == 0 {
+                        return None;
+                    }
+                    if matched[depth] {
+                        let (address_cells, size_cells) = cells[depth - 1];
+                        if let Some(value) = reg[depth] {
+                            return reg_iter(value, address_cells, size_cells).next();
+                        }
+                    }
+                    depth -= 1;
+                }
+                FdtToken::Prop(name, value) => match name {
+                    "#address-cells" => cells[depth].0 = read_cells(value, 1),
+                    "#size-cells" => cells[depth].1 = read_cells(value, 1),
+                    "compatible" => matched[depth] = stringlist_contains(value, compatible),
+                    "reg" => reg[depth] = Some(value),
+                    _ => {}
+                },
+            }
+        }
+        None
+    }
+}
+
+impl<'a> Iterator for FdtTokenIter<'a> {
+    type Item = FdtToken<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let data = self.data;
+        while self.offset < self.end {
+            let token = be32(data, self.offset)?;
+            self.offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = c_str(data, self.offset)?;
+                    self.offset = align4(self.offset + name.len() + 1);
+                    return Some(FdtToken::BeginNode(name));
+                }
+                FDT_END_NODE => return Some(FdtToken::EndNode),
+                FDT_PROP => {
+                    let len = be32(data, self.offset)? as usize;
+                    let name_off = be32(data, self.offset + 4)? as usize;
+                    let value = data.get(self.offset + 8..self.offset + 8 + len)?;
+                    self.offset = align4(self.offset + 8 + len);
+                    let name = c_str(data, self.off_dt_strings + name_off)?;
+                    return Some(FdtToken::Prop(name, value));
+                }
+                FDT_NOP => {}
+                FDT_END => self.offset = self.end,
+                _ => return None,
+            }
+        }
+        None
+    }
+}
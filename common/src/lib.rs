@@ -1,8 +1,16 @@
 #![no_std]
 
 pub mod types;
+pub mod boot_info;
 pub mod config;
 pub mod utils;
 pub mod message;
 pub mod object;
-pub mod register;
\ No newline at end of file
+pub mod register;
+pub mod cpio;
+pub mod fdt;
+pub mod benchmark;
+pub mod logging;
+#[cfg(feature = "mcs")]
+pub mod sched_context;
+mod structures;
\ No newline at end of file
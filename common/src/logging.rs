@@ -0,0 +1,35 @@
+// the arguments of the DebugSetLogLevel and DebugSetLogSink syscalls
+
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone, Debug)]
+pub enum LogLevel {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl LogLevel {
+    pub fn from_usize(level: usize) -> Option<Self> {
+        match level {
+            0 => Some(LogLevel::Off),
+            1 => Some(LogLevel::Error),
+            2 => Some(LogLevel::Warn),
+            3 => Some(LogLevel::Info),
+            4 => Some(LogLevel::Debug),
+            5 => Some(LogLevel::Trace),
+            _ => None,
+        }
+    }
+}
+
+/// passed instead of a level to drop the filter of a module
+pub const LOG_LEVEL_UNSET: usize = usize::MAX;
+
+pub const LOG_SINK_CONSOLE: usize = 1;
+/// an in-memory ring, drained with DebugReadLog
+pub const LOG_SINK_RING: usize = 2;
+
+/// the longest module path a filter can name
+pub const MAX_LOG_MODULE_LEN: usize = 64;
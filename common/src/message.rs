@@ -6,6 +6,11 @@ pub const NUM_GP_REGISTERS: usize = 16;
 pub const NUM_EXCEPTION_MSG: usize = 2;
 pub const NUM_SYSCALL_MSG: usize = 10;
 
+// the label of the message a receive on a cap it cannot receive from completes with, the cptr in mr0
+pub const SEL4_CAP_FAULT: usize = 1;
+// the label of the reply to a PageMap that found a page table missing on the way, seL4_FailedLookup
+pub const SEL4_FAILED_LOOKUP: usize = 6;
+
 pub const MESSAGE_REGISTERS: [usize; NUM_MSG_REGISTRES] = [
     Register::a2 as usize,
     Register::a3 as usize,
@@ -51,50 +56,25 @@ pub const GP_REGISTERS: [usize; NUM_GP_REGISTERS] = [
     Register::tp as usize,
 ];
 
-#[derive(Default, Clone, Copy, Debug)]
-pub struct MessageInfo {
-    pub words: [usize; 1],
-}
+pub use crate::structures::MessageInfo;
 
 impl MessageInfo {
     pub fn new(label: InvocationLabel, caps_unwrapped: usize, extra_caps: usize, length: usize) -> Self {
-        let mut msg = MessageInfo { words: [0; 1]};
-        msg.words[0] = 0
-            | ((label as usize) & 0xfffffffffffff) << 12
-            | (caps_unwrapped & 0x7) << 9
-            | (extra_caps & 0x3) << 7
-            | (length & 0x7f) << 0;
+        let mut msg = MessageInfo::default();
+        msg.set_label(label as usize);
+        msg.set_caps_unwrapped(caps_unwrapped);
+        msg.set_extra_caps(extra_caps);
+        msg.set_length(length);
         msg
     }
 
     pub fn from_word(word: usize) -> Self {
-        let mut msg = MessageInfo { words: [0; 1]};
-        msg.words[0] = word;
-        msg
+        MessageInfo { words: [word] }
     }
 
     pub fn to_word(&self) -> usize {
         self.words[0]
     }
-
-    pub fn get_label(&self) -> usize {
-        Self::sign_extend((self.words[0] & 0xfffffffffffff000) >> 12, 0x0)
-    }
-
-    pub fn get_extra_caps(&self) -> usize {
-        Self::sign_extend((self.words[0] & 0x180) >> 7, 0x0)
-    }
-
-    pub fn get_length(&self) -> usize {
-        Self::sign_extend((self.words[0] & 0x7f) >> 0, 0x0)
-    }
-
-    fn sign_extend(ret: usize, sign: usize) -> usize {
-        if ret & (1 << 63) != 0 {
-            return ret | sign;
-        }
-        ret
-    }
 }
 
 pub enum InvocationLabel {
@@ -113,27 +93,46 @@ pub enum InvocationLabel {
     TCBResume = 12,
     TCBBindNotification = 13,
     TCBUnbindNotification = 14,
-    TCBSetTLSBase = 15,
-    CNodeRevoke = 16,
-    CNodeDelete = 17,
-    CNodeCancelBadgedSends = 18,
-    CNodeCopy = 19,
-    CNodeMint = 20,
-    CNodeMove = 21,
-    CNodeMutate = 22,
-    CNodeRotate = 23,
-    CNodeSaveCaller = 24,
-    IRQIssueIRQHandler = 25,
-    IRQAckIRQ = 26,
-    IRQSetIRQHandler = 27,
-    IRQClearIRQHandler = 28,
-    DomainSetSet = 29,
-    PageTableMap = 30,
-    PageTableUnmap = 31,
-    PageMap = 32,
-    PageUnmap = 33,
-    PageGetAddress = 34,
-    NInvocationLabels = 35,
+    TCBSetAffinity = 15,
+    TCBSetTLSBase = 16,
+    CNodeRevoke = 17,
+    CNodeDelete = 18,
+    CNodeCancelBadgedSends = 19,
+    CNodeCopy = 20,
+    CNodeMint = 21,
+    CNodeMove = 22,
+    CNodeMutate = 23,
+    CNodeRotate = 24,
+    CNodeSaveCaller = 25,
+    IRQIssueIRQHandler = 26,
+    IRQAckIRQ = 27,
+    IRQSetIRQHandler = 28,
+    IRQClearIRQHandler = 29,
+    DomainSetSet = 30,
+    PageTableMap = 31,
+    PageTableUnmap = 32,
+    PageMap = 33,
+    PageUnmap = 34,
+    PageGetAddress = 35,
+    ASIDControlMakePool = 36,
+    ASIDPoolAssign = 37,
+    #[cfg(not(feature = "mcs"))]
+    NInvocationLabels = 38,
+
+    #[cfg(feature = "mcs")]
+    TCBSetTimeoutEndpoint = 38,
+    #[cfg(feature = "mcs")]
+    SchedControlConfigureFlags = 39,
+    #[cfg(feature = "mcs")]
+    SchedContextBind = 40,
+    #[cfg(feature = "mcs")]
+    SchedContextUnbind = 41,
+    #[cfg(feature = "mcs")]
+    SchedContextUnbindObject = 42,
+    #[cfg(feature = "mcs")]
+    SchedContextConsumed = 43,
+    #[cfg(feature = "mcs")]
+    NInvocationLabels = 44,
 }
 
 impl InvocationLabel {
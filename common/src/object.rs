@@ -7,11 +7,36 @@ pub enum ObjectType {
     EndpointObject = 2,
     NotificationObject = 3,
     CapTableObject = 4,
+    #[cfg(not(feature = "mcs"))]
     NonArchObjectTypeCount = 5,
+    #[cfg(not(feature = "mcs"))]
     Riscv4kpage = 6,
+    #[cfg(not(feature = "mcs"))]
     RiscvMegaPage = 7,
-    RiscvPageTableObject = 8,
-    ObjectTypeCount = 9,
+    #[cfg(not(feature = "mcs"))]
+    RiscvGigaPage = 8,
+    #[cfg(not(feature = "mcs"))]
+    RiscvPageTableObject = 9,
+    #[cfg(not(feature = "mcs"))]
+    ObjectTypeCount = 10,
+
+    // as in seL4, the mcs objects come before the arch ones and move them up
+    #[cfg(feature = "mcs")]
+    SchedContextObject = 5,
+    #[cfg(feature = "mcs")]
+    ReplyObject = 6,
+    #[cfg(feature = "mcs")]
+    NonArchObjectTypeCount = 7,
+    #[cfg(feature = "mcs")]
+    Riscv4kpage = 8,
+    #[cfg(feature = "mcs")]
+    RiscvMegaPage = 9,
+    #[cfg(feature = "mcs")]
+    RiscvGigaPage = 10,
+    #[cfg(feature = "mcs")]
+    RiscvPageTableObject = 11,
+    #[cfg(feature = "mcs")]
+    ObjectTypeCount = 12,
 }
 
 impl ObjectType {
@@ -23,7 +48,7 @@ impl ObjectType {
 
     pub fn is_frame_type(&self) -> bool {
         match self {
-            Self::Riscv4kpage | Self::RiscvMegaPage => {
+            Self::Riscv4kpage | Self::RiscvMegaPage | Self::RiscvGigaPage => {
                 true
             }
             _ => {
@@ -39,7 +64,13 @@ impl ObjectType {
             ObjectType::EndpointObject => SEL4_ENDPOINT_BITS,
             ObjectType::NotificationObject => SEL4_NOTIFICATION_BITS,
             ObjectType::CapTableObject => SEL4_SLOT_BITS + user_object_size,
+            #[cfg(feature = "mcs")]
+            ObjectType::SchedContextObject => user_object_size,
+            #[cfg(feature = "mcs")]
+            ObjectType::ReplyObject => SEL4_REPLY_BITS,
             ObjectType::Riscv4kpage | ObjectType::RiscvPageTableObject => PAGE_BITS,
+            ObjectType::RiscvMegaPage => SEL4_LARGE_PAGE_BITS,
+            ObjectType::RiscvGigaPage => SEL4_HUGE_PAGE_BITS,
             _ => {
                 // error!("invalid object type: {}", t as usize);
                 return 0;
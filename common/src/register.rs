@@ -76,6 +76,8 @@ pub const TLS_BASE: usize = TP;
 pub const CAP_REGISTER: usize = 9;
 pub const BADGE_REGISTER: usize = 9;
 pub const MSG_INFO_REGISTER: usize = 10;
+/// the reply object of Recv and ReplyRecv with mcs (a6)
+pub const REPLY_REGISTER: usize = 15;
 
 pub const SSTATUS_SPP: usize = 0x00000100;
 pub const SSTATUS_FS: usize = 0x00006000;
@@ -0,0 +1,35 @@
+// the limits of SchedControlConfigureFlags and the size of scheduling contexts, for kernels
+// built with mcs. budgets and periods are given in microseconds
+
+use crate::config::{CONFIG_TIME_SLICE, TICKS_PER_SEC};
+
+/// the smallest scheduling context, with room for 2 refills
+pub const SEL4_MIN_SCHED_CONTEXT_BITS: usize = 7;
+/// the part of a scheduling context before its refills
+pub const SEL4_CORE_SCHED_CONTEXT_BYTES: usize = 11 * 8;
+pub const SEL4_REFILL_SIZE_BYTES: usize = 2 * 8;
+/// a budget is handed out in at least this many refills
+pub const MIN_REFILLS: usize = 2;
+
+pub const CONFIG_KERNEL_WCET_US: usize = 10;
+/// the least budget a thread runs on, so it can make it through a kernel entry
+pub const MIN_BUDGET_US: usize = 2 * CONFIG_KERNEL_WCET_US;
+pub const MAX_PERIOD_US: usize = 60 * 60 * 1000 * 1000;
+/// the round robin budget of the root server, and of the threads user_lib starts
+pub const DEFAULT_TIMESLICE_US: usize = CONFIG_TIME_SLICE * 1000 * 1000 / TICKS_PER_SEC;
+
+/// the label of the fault message a thread out of budget sends its timeout handler. the first
+/// word is the badge of its scheduling context, the second the microseconds it consumed
+pub const SEL4_TIMEOUT_FAULT: usize = 5;
+pub const SEL4_TIMEOUT_DATA: usize = 0;
+pub const SEL4_TIMEOUT_CONSUMED: usize = 1;
+pub const SEL4_TIMEOUT_LENGTH: usize = 2;
+
+/// the only flag of SchedControlConfigureFlags. budgets are always replenished as sporadic
+/// servers, the flag is accepted for seL4 compatibility
+pub const SCHED_CONTEXT_SPORADIC: usize = 1;
+
+/// how many refills beyond MIN_REFILLS a scheduling context of `size_bits` has room for
+pub const fn max_extra_refills(size_bits: usize) -> usize {
+    ((1 << size_bits) - SEL4_CORE_SCHED_CONTEXT_BYTES) / SEL4_REFILL_SIZE_BYTES - MIN_REFILLS
+}
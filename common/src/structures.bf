@@ -0,0 +1,10 @@
+-- word-packed structures shared by the kernel and user space, see os/src/structures.bf.
+
+base 64(39,1)
+
+block MessageInfo {
+    field label 52
+    field caps_unwrapped 3
+    field extra_caps 2
+    field length 7
+}
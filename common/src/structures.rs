@@ -0,0 +1,4 @@
+//! the word-packed structures of structures.bf, generated by build.rs.
+#![allow(dead_code)]
+
+include!(concat!(env!("OUT_DIR"), "/structures.rs"));
@@ -1,6 +1,10 @@
-use super::config::{PPTR_BASE_OFFSET, NUM_ASID_POOL_BITS, ASID_POOL_INDEX_BITS, SEL4_MSG_MAX_LEN, SEL4_MSG_MAX_EXTRA_CAPS};
+use core::mem::size_of;
+
+use super::config::{PPTR_BASE_OFFSET, NUM_ASID_POOL_BITS, ASID_POOL_INDEX_BITS, SEL4_MSG_MAX_LEN, SEL4_MSG_MAX_EXTRA_CAPS,
+    SEL4_IPC_BUFFER_SIZE_BITS};
 use super::utils::bool2usize;
 use super::message::MessageInfo;
+use crate::offset_of;
 
 pub type Pptr = usize;
 pub type Vptr = usize;
@@ -17,6 +21,7 @@ pub type NodeId = usize;
 pub type PTEPtr = Pptr;
 pub type APPtr = Pptr;
 
+#[derive(Clone, Copy)]
 pub enum VMAttributes {
     ExecuteNever = 0x1,
     DefaultVMAttributes = 0x0,
@@ -33,6 +38,7 @@ pub struct Region {
     pub end: Pptr,
 }
 
+#[repr(C)]
 #[derive(Default, Debug, Clone, Copy)]
 pub struct PhyRegion {
     pub start: Paddr,
@@ -44,6 +50,7 @@ pub struct VirtRegion {
     pub start: Vptr,
     pub end: Vptr,
 }
+#[repr(C)]
 #[derive(Default, Debug, Clone, Copy)]
 pub struct SlotRegion {
     pub start: SlotPos,
@@ -67,6 +74,7 @@ impl PhyRegion {
 
 
 const PADDING_LEN: usize = 8 - 2 * 1;
+#[repr(C)]
 #[derive(Debug, Clone, Copy)]
 pub struct UntypedDesc {
     pub paddr: Paddr,
@@ -102,10 +110,17 @@ pub enum CNodeSlot {
     SeL4CapDomain = 11,                 /* global domain controller cap */
     SeL4CapSMMUSIDControl = 12,         /*global SMMU SID controller cap, null cap if not supported*/
     SeL4CapSMMUCBControl = 13,          /*global SMMU CB controller cap, null cap if not supported*/
-    SeL4NumInitialCaps = 14
+    #[cfg(feature = "mcs")]
+    SeL4CapInitThreadSC = 14,           /* initial thread's scheduling context cap */
+    #[cfg(not(feature = "mcs"))]
+    SeL4NumInitialCaps = 14,
+    #[cfg(feature = "mcs")]
+    SeL4NumInitialCaps = 15
 }
 
 
+/// seL4_IPCBuffer, shared by a thread and the kernel
+#[repr(C)]
 pub struct IpcBuffer {
     pub tag: MessageInfo,
     pub msg: [usize; SEL4_MSG_MAX_LEN],
@@ -116,6 +131,12 @@ pub struct IpcBuffer {
     pub receive_depth: usize,
 }
 
+const _: () = assert!(size_of::<IpcBuffer>() == 1 << SEL4_IPC_BUFFER_SIZE_BITS);
+const _: () = assert!(offset_of!(IpcBuffer, msg) == size_of::<usize>());
+const _: () = assert!(offset_of!(IpcBuffer, caps_or_badges) == (SEL4_MSG_MAX_LEN + 2) * size_of::<usize>());
+const _: () = assert!(offset_of!(IpcBuffer, receive_depth) == size_of::<IpcBuffer>() - size_of::<usize>());
+
+#[derive(Clone, Copy)]
 pub struct CapRights {
     pub word: [usize; 1],
 }
@@ -1,6 +1,7 @@
 use crate::{types::Paddr, config::PPTR_BASE_OFFSET};
 
-use super::config::{CONFIG_PT_LEVELS, PAGE_TABLE_INDEX_BITS, PAGE_BITS, WORD_RADIX, L2_BITMAP_SIZE};
+use super::config::{CONFIG_PT_LEVELS, PAGE_TABLE_INDEX_BITS, PAGE_BITS, WORD_RADIX, L2_BITMAP_SIZE, RISCV_4K_PAGE, RISCV_MEGA_PAGE,
+    RISCV_GIGA_PAGE, SEL4_PAGE_BITS, SEL4_LARGE_PAGE_BITS, SEL4_HUGE_PAGE_BITS};
 
 #[inline]
 pub fn mask(n: usize) -> usize {
@@ -52,11 +53,6 @@ pub fn bool2usize(flag: bool) -> usize {
     if flag { 1 } else { 0 }
 }
 
-#[inline]
-pub fn hart_id() -> usize {
-    0
-}
-
 #[inline]
 pub fn sign_extend(ret: usize, sign: usize) -> usize {
     if ret & (1 << 38) != 0 {
@@ -65,11 +61,15 @@ pub fn sign_extend(ret: usize, sign: usize) -> usize {
     ret
 }
 
+/// None for a size that is not one of the RISCV_*_PAGE ones
 #[inline]
-pub fn page_bits_for_size(page_size: usize) -> usize {
-    // TODO: different page size
-    assert!(page_size == 0);
-    return PAGE_BITS;
+pub fn page_bits_for_size(page_size: usize) -> Option<usize> {
+    match page_size {
+        RISCV_4K_PAGE => Some(SEL4_PAGE_BITS),
+        RISCV_MEGA_PAGE => Some(SEL4_LARGE_PAGE_BITS),
+        RISCV_GIGA_PAGE => Some(SEL4_HUGE_PAGE_BITS),
+        _ => None,
+    }
 }
 
 #[inline]
@@ -107,4 +107,14 @@ pub fn convert_to_type_ref<T>(addr: usize) -> &'static T {
 #[inline]
 pub fn addr_from_pptr(pptr: usize) -> Paddr {
     pptr - PPTR_BASE_OFFSET
-}
\ No newline at end of file
+}
+/// byte offset of `$field` in `$ty`, usable in consts; core::mem::offset_of is newer than our toolchain
+#[macro_export]
+macro_rules! offset_of {
+    ($ty:ty, $field:ident) => {{
+        let uninit = core::mem::MaybeUninit::<$ty>::uninit();
+        let base = uninit.as_ptr();
+        #[allow(unused_unsafe)]
+        unsafe { (core::ptr::addr_of!((*base).$field) as *const u8).offset_from(base as *const u8) as usize }
+    }};
+}
@@ -0,0 +1,236 @@
+use core::cmp::{max, min};
+
+use log::error;
+
+use common::config::{MAX_NUM_FREEMEM_REG, MAX_NUM_RESV_REG};
+use common::types::{Region, PhyRegion};
+use super::NdksBoot;
+
+/// keep the reserved regions sorted and disjoint, as init_freemem expects
+pub fn add_reserved_region(res_reg: &mut [Region], count: usize, reg: Region) -> usize {
+    if reg.start >= reg.end {
+        return count;
+    }
+    assert!(count < res_reg.len());
+    let mut i = count;
+    while i > 0 && res_reg[i - 1].start > reg.start {
+        res_reg[i] = res_reg[i - 1];
+        i -= 1;
+    }
+    res_reg[i] = reg;
+
+    let mut n = 1;
+    for j in 1..count + 1 {
+        if res_reg[j].start <= res_reg[n - 1].end {
+            res_reg[n - 1].end = max(res_reg[n - 1].end, res_reg[j].end);
+        } else {
+            res_reg[n] = res_reg[j];
+            n += 1;
+        }
+    }
+    n
+}
+
+/// carve the sorted, disjoint `reserved` regions out of `avail_reg`, recording what is left in
+/// ndks_boot.freemem and everything that is not free in ndks_boot.reserved
+pub fn init_freemem(avail_reg: &mut [Region], reserved: &[Region], ndks_boot: &mut NdksBoot) {
+    let n_available = avail_reg.len();
+    let n_reserved = reserved.len();
+    let mut a = 0;
+    let mut r = 0;
+    while a < n_available && r < n_reserved {
+        if reserved[r].start == reserved[r].end {
+            r += 1;
+        } else if avail_reg[a].start >= avail_reg[a].end {
+            a += 1;
+        } else if reserved[r].end <= avail_reg[a].start {
+            reserve_region(PhyRegion::pptr_to_paddr_reg(reserved[r]), ndks_boot);
+            r += 1;
+        } else if reserved[r].start >= avail_reg[a].end {
+            insert_region(avail_reg[a], ndks_boot);
+            a += 1;
+        } else {
+            if reserved[r].start <= avail_reg[a].start {
+                avail_reg[a].start = min(avail_reg[a].end, reserved[r].end);
+                reserve_region(PhyRegion::pptr_to_paddr_reg(reserved[r]), ndks_boot);
+                r += 1;
+            } else {
+                assert!(reserved[r].start < avail_reg[a].end);
+                let mut m = avail_reg[a];
+                m.end = reserved[r].start;
+                insert_region(m, ndks_boot);
+                if avail_reg[a].end >= reserved[r].end {
+                    avail_reg[a].start = reserved[r].end;
+                    reserve_region(PhyRegion::pptr_to_paddr_reg(reserved[r]), ndks_boot);
+                    r += 1;
+                } else {
+                    a += 1;
+                }
+            }
+        }
+    }
+
+    while r < n_reserved {
+        if reserved[r].start < reserved[r].end {
+            reserve_region(PhyRegion::pptr_to_paddr_reg(reserved[r]), ndks_boot);
+        }
+        r += 1;
+    }
+
+    while a < n_available {
+        if avail_reg[a].start < avail_reg[a].end {
+            insert_region(avail_reg[a], ndks_boot);
+        }
+        a += 1;
+    }
+
+}
+
+fn reserve_region(reg: PhyRegion, ndks_boot: &mut NdksBoot) {
+    let mut i = 0;
+    assert!(reg.start <= reg.end);
+    if reg.start == reg.end {
+        return;
+    }
+
+    while i < ndks_boot.resv_count {
+
+        if ndks_boot.reserved[i].start == reg.end {
+            ndks_boot.reserved[i].start = reg.start;
+            merge_regions(ndks_boot);
+            return;
+        }
+
+        if ndks_boot.reserved[i].end == reg.start {
+            ndks_boot.reserved[i].end = reg.end;
+            merge_regions(ndks_boot);
+            return;
+        }
+
+        if ndks_boot.reserved[i].start > reg.end {
+            if ndks_boot.resv_count + 1 > MAX_NUM_RESV_REG {
+                error!("[reserve_region]error!");
+                panic!("too many reserved regions");
+            }
+
+            let mut j = ndks_boot.resv_count;
+            while j > i {
+                ndks_boot.reserved[j] = ndks_boot.reserved[j - 1];
+                j -= 1;
+            }
+            ndks_boot.reserved[i] = reg;
+            ndks_boot.resv_count += 1;
+            return;
+        }
+
+        i += 1;
+    }
+    if i + 1 == MAX_NUM_RESV_REG {
+        error!("[reserve_region]error!");
+        assert_eq!(1, 0);
+    }
+
+    ndks_boot.reserved[i] = reg;
+    ndks_boot.resv_count += 1;
+}
+
+fn merge_regions(ndks_boot: &mut NdksBoot) {
+    let mut i = 1;
+    while i < ndks_boot.resv_count {
+        if ndks_boot.reserved[i - 1].end == ndks_boot.reserved[i].start {
+            ndks_boot.reserved[i - 1].end = ndks_boot.reserved[i].end;
+            let mut j = i + 1;
+            while j < ndks_boot.resv_count {
+                ndks_boot.reserved[j - 1] = ndks_boot.reserved[j];
+                j += 1;
+            }
+            ndks_boot.resv_count -= 1;
+        } else {
+            i += 1;
+        }
+    }
+}
+
+fn insert_region(reg: Region, ndks_boot: &mut NdksBoot) {
+    assert!(reg.start <= reg.end);
+    if reg.start == reg.end {
+        return;
+    }
+
+    for i in 0..MAX_NUM_FREEMEM_REG {
+        if ndks_boot.freemem[i].start == ndks_boot.freemem[i].end {
+            reserve_region(PhyRegion::pptr_to_paddr_reg(reg), ndks_boot);
+            ndks_boot.freemem[i] = reg;
+            return;
+        }
+    }
+    error!("[insert_region] error!");
+    panic!("too many free regions");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+    use common::config::{NUM_RESERVED_REGIONS, PPTR_BASE_OFFSET};
+
+    fn pptr_reg(start: usize, end: usize) -> Region {
+        Region { start: start + PPTR_BASE_OFFSET, end: end + PPTR_BASE_OFFSET }
+    }
+
+    fn bounds<T: Copy>(regions: &[T], f: impl Fn(T) -> (usize, usize)) -> Vec<(usize, usize)> {
+        regions.iter().map(|reg| f(*reg)).collect()
+    }
+
+    fn freemem(ndks_boot: &NdksBoot) -> Vec<(usize, usize)> {
+        ndks_boot.freemem.iter().filter(|reg| reg.start != reg.end)
+            .map(|reg| (reg.start - PPTR_BASE_OFFSET, reg.end - PPTR_BASE_OFFSET)).collect()
+    }
+
+    #[test]
+    fn add_reserved_region_sorts_and_merges() {
+        let mut res_reg = [Region::default(); NUM_RESERVED_REGIONS];
+        let mut n = 0;
+        n = add_reserved_region(&mut res_reg, n, Region { start: 0x5000, end: 0x6000 });
+        n = add_reserved_region(&mut res_reg, n, Region { start: 0x1000, end: 0x2000 });
+        // empty regions are dropped
+        n = add_reserved_region(&mut res_reg, n, Region { start: 0x3000, end: 0x3000 });
+        assert_eq!(bounds(&res_reg[..n], |reg| (reg.start, reg.end)), [(0x1000, 0x2000), (0x5000, 0x6000)]);
+        // touching and overlapping regions become one
+        n = add_reserved_region(&mut res_reg, n, Region { start: 0x2000, end: 0x3000 });
+        n = add_reserved_region(&mut res_reg, n, Region { start: 0x4800, end: 0x5800 });
+        assert_eq!(bounds(&res_reg[..n], |reg| (reg.start, reg.end)), [(0x1000, 0x3000), (0x4800, 0x6000)]);
+        n = add_reserved_region(&mut res_reg, n, Region { start: 0x0, end: 0x7000 });
+        assert_eq!(bounds(&res_reg[..n], |reg| (reg.start, reg.end)), [(0x0, 0x7000)]);
+    }
+
+    #[test]
+    fn init_freemem_carves_out_reserved_regions() {
+        let mut avail = [pptr_reg(0x8000_0000, 0x8800_0000)];
+        let reserved = [pptr_reg(0x8000_0000, 0x8040_0000), pptr_reg(0x8100_0000, 0x8110_0000)];
+        let mut ndks_boot = NdksBoot::default();
+        init_freemem(&mut avail, &reserved, &mut ndks_boot);
+
+        assert_eq!(freemem(&ndks_boot), [(0x8040_0000, 0x8100_0000), (0x8110_0000, 0x8800_0000)]);
+        // free memory is recorded as reserved too, since it all ends up handed out as untyped
+        assert_eq!(bounds(&ndks_boot.reserved[..ndks_boot.resv_count], |reg| (reg.start, reg.end)),
+                   [(0x8000_0000, 0x8800_0000)]);
+    }
+
+    #[test]
+    fn init_freemem_with_reserved_regions_across_banks() {
+        let mut avail = [pptr_reg(0x8000_0000, 0x8100_0000), pptr_reg(0x9000_0000, 0x9100_0000)];
+        let reserved = [
+            pptr_reg(0x1000, 0x2000),
+            pptr_reg(0x80f0_0000, 0x8200_0000),
+            pptr_reg(0x9000_0000, 0x9000_1000),
+            pptr_reg(0xa000_0000, 0xa000_1000),
+        ];
+        let mut ndks_boot = NdksBoot::default();
+        init_freemem(&mut avail, &reserved, &mut ndks_boot);
+
+        assert_eq!(freemem(&ndks_boot), [(0x8000_0000, 0x80f0_0000), (0x9000_1000, 0x9100_0000)]);
+        assert_eq!(bounds(&ndks_boot.reserved[..ndks_boot.resv_count], |reg| (reg.start, reg.end)),
+                   [(0x1000, 0x2000), (0x8000_0000, 0x8200_0000), (0x9000_0000, 0x9100_0000), (0xa000_0000, 0xa000_1000)]);
+    }
+}
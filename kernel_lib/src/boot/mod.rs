@@ -0,0 +1,5 @@
+mod ndks_boot;
+mod init_freemem;
+
+pub use ndks_boot::NdksBoot;
+pub use init_freemem::{add_reserved_region, init_freemem};
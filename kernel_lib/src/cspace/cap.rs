@@ -0,0 +1,514 @@
+#[cfg(feature = "mcs")]
+use common::config::SEL4_REPLY_BITS;
+use common::config::{CONFIG_RESET_CHUNK_BITS, MIN_UNTYPED_BITS, SEL4_ASID_POOL_BITS, SEL4_ENDPOINT_BITS,
+    SEL4_NOTIFICATION_BITS, SEL4_PAGE_BITS, SEL4_SLOT_BITS, SEL4_TCB_BITS, WORD_BITS};
+use common::types::{CapRights, Pptr};
+use common::utils::{bit, mask, page_bits_for_size, round_down, convert_to_mut_type_ref};
+use log::debug;
+
+use super::cap_data::CapData;
+use super::mdb::MDBNode;
+use crate::mm::VmRights;
+pub use crate::structures::{Cap, CapTag};
+
+#[derive(Copy, Clone)]
+pub struct CapTableEntry {
+    pub cap: Cap,
+    pub mdb_node: MDBNode,
+}
+
+impl CapTableEntry {
+    pub fn ensure_empty_slot(&self) -> bool {
+        self.cap.get_cap_type() == CapTag::CapNullCap
+    }
+
+    pub fn ensure_no_child(&self) -> bool {
+        if self.mdb_node.get_mdb_next() != 0 {
+            let next = unsafe {
+                &mut *(self.mdb_node.get_mdb_next() as *mut CapTableEntry)
+            };
+            if self.is_mdb_parent_of(next) {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn reset_untyped_cap(&mut self) -> bool {
+        assert_eq!(self.cap.get_cap_type(), CapTag::CapUntypedCap);
+        let prev_cap = self.cap;
+        let block_size = prev_cap.get_untyped_block_size();
+        let region_base = prev_cap.get_untyped_ptr();
+        let chunk = CONFIG_RESET_CHUNK_BITS;
+        let offset = prev_cap.get_untyped_free_index() << MIN_UNTYPED_BITS;
+        let device_mem = prev_cap.get_untyped_is_device();
+        if offset == 0 {
+            return true;
+        }
+        if device_mem || block_size < chunk {
+            if !device_mem {
+                let end = region_base + bit(block_size);
+                (region_base..end).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+            }
+            self.cap.set_untyped_free_index(0);
+        } else {
+            let mut local_offset = round_down(offset - 1, chunk);
+            debug!("local_offset: {}, region_base: {:#x}", local_offset, region_base);
+            let stride = bit(chunk);
+            loop {
+                let start = region_base + local_offset;
+                let end = start + stride;
+                (start as usize..end as usize).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+                self.cap.set_untyped_free_index((local_offset as usize) >> MIN_UNTYPED_BITS);
+                // TODO: preemption point
+                if local_offset == 0 {
+                    break;
+                }
+                local_offset -= stride;
+            }
+        }
+        true
+    }
+
+    pub fn is_mdb_parent_of(&self, other: &CapTableEntry) -> bool {
+        if !self.mdb_node.get_mdb_revocable() {
+            return false;
+        }
+
+        if !self.cap.same_region_as(&other.cap) {
+            return false;
+        }
+
+        match self.cap.get_cap_type() {
+            CapTag::CapEndpointCap => {
+                let badge = self.cap.get_ep_badge();
+                if badge == 0 {
+                    return true;
+                }
+                return badge == other.cap.get_ep_badge() && !other.mdb_node.get_mdb_first_badged();
+            }
+
+            CapTag::CapNotificationCap => {
+                let badge = self.cap.get_nt_fn_badge();
+                if badge == 0 {
+                    return true;
+                }
+                return badge == other.cap.get_nt_fn_badge() && !other.mdb_node.get_mdb_first_badged();
+            }
+
+            _ => {
+
+            }
+        }
+
+        true
+    }
+
+    pub fn is_final_cap(&self) -> bool {
+        let mdb = self.mdb_node;
+        let prev_is_same_obj = if mdb.get_mdb_prev() == 0 {
+            false
+        } else {
+            let prev = convert_to_mut_type_ref::<CapTableEntry>(mdb.get_mdb_prev());
+            prev.cap.same_obj_as(&self.cap)
+        };
+
+        if prev_is_same_obj {
+            false
+        } else if mdb.get_mdb_next() == 0 {
+            true
+        } else {
+            let next = convert_to_mut_type_ref::<CapTableEntry>(mdb.get_mdb_next());
+            !self.cap.same_obj_as(&next.cap)
+        }
+    }
+
+    pub fn is_long_running_delete(&self) -> bool {
+        if self.cap.get_cap_type() == CapTag::CapNullCap || !self.is_final_cap() {
+            return false;
+        }
+        matches!(self.cap.get_cap_type(), CapTag::CapThreadCap | CapTag::CapZombieCap | CapTag::CapCNodeCap)
+    }
+
+    pub fn emplty_slot(&mut self, _cleanup_info: Cap) {
+        if self.cap.get_cap_type() != CapTag::CapNullCap {
+            let mdb_node = self.mdb_node;
+            if mdb_node.get_mdb_prev() != 0 {
+                let prev = convert_to_mut_type_ref::<CapTableEntry>(mdb_node.get_mdb_prev());
+                prev.mdb_node.set_mdb_next(mdb_node.get_mdb_next());
+            }
+
+            if mdb_node.get_mdb_next() != 0 {
+                let next = convert_to_mut_type_ref::<CapTableEntry>(mdb_node.get_mdb_next());
+                next.mdb_node.set_mdb_prev(mdb_node.get_mdb_prev());
+                next.mdb_node.set_mdb_first_badged(next.mdb_node.get_mdb_first_badged() || mdb_node.get_mdb_first_badged());
+            }
+            
+            self.cap = Cap::new_null_cap();
+            self.mdb_node = MDBNode::null_mdbnode();
+            // postCapDeletion need to do;
+        }
+    }
+}
+
+impl Cap {
+    /// with mcs a reply cap names a reply object rather than the thread to answer
+    #[cfg(feature = "mcs")]
+    pub fn new_reply_object_cap(can_grant: bool, reply: Pptr) -> Self {
+        Cap::new_reply_cap(can_grant, false, reply)
+    }
+
+    #[cfg(feature = "mcs")]
+    pub fn get_reply_ptr(&self) -> Pptr {
+        self.get_reply_tcb_ptr()
+    }
+
+    pub fn get_untyped_ref(&self, index: usize) -> Pptr {
+        assert_eq!(self.get_cap_type(), CapTag::CapUntypedCap);
+        self.get_untyped_ptr() + (index << MIN_UNTYPED_BITS)
+    }
+
+    pub fn update_cap_data(&mut self, preserve: bool, new_data: usize) {
+        match self.get_cap_type() {
+            CapTag::CapEndpointCap => {
+                if !preserve && self.get_ep_badge() == 0 {
+                    self.set_ep_badge(new_data);
+                }
+            }
+
+            CapTag::CapNotificationCap => {
+                if !preserve && self.get_nt_fn_badge() == 0 {
+                    self.set_nt_fn_badge(new_data);
+                }
+            }
+
+            CapTag::CapCNodeCap => {
+                let w = CapData::new(new_data);
+                let guard_size = w.get_guard_size();
+                if guard_size +  self.get_cnode_radix() <= WORD_BITS {
+                    let guard = w.get_guard() & mask(guard_size);
+                    self.set_cnode_guard(guard);
+                    self.set_cnode_guard_size(guard_size);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    pub fn mask_cap_rights(&self, rights: CapRights) -> Cap {
+        let mut new_cap = *self;
+        match self.get_cap_type() {
+            CapTag::CapEndpointCap => {
+                new_cap.set_ep_can_send(self.get_ep_can_send() && rights.get_allow_write());
+                new_cap.set_ep_can_receive(self.get_ep_can_receive() && rights.get_allow_read());
+                new_cap.set_ep_can_grant(self.get_ep_can_grant() && rights.get_allow_grant());
+                new_cap.set_ep_can_grant_reply(self.get_ep_can_grant_reply() && rights.get_allow_grant_reply());
+            }
+
+            CapTag::CapNotificationCap => {
+                new_cap.set_nt_fn_can_send(self.get_nt_fn_can_send() && rights.get_allow_write());
+                new_cap.set_nt_fn_can_receive(self.get_nt_fn_can_receive() && rights.get_allow_read());
+            }
+
+            CapTag::CapFrameCap => {
+                let vm_rights = VmRights::from_usize(self.get_frame_vm_right()).mask_vm_rights(rights);
+                new_cap.set_frame_vm_right(vm_rights as usize);
+            }
+            _ => {}
+        }
+        new_cap
+    }
+
+    pub fn same_obj_as(&self, other: &Self) -> bool {
+        if self.get_cap_type() == CapTag::CapUntypedCap {
+            return false;
+        }
+
+        if self.get_cap_type() == CapTag::CapIrqControlCap && other.get_cap_type() == CapTag::CapIrqHandlerCap {
+            return false;
+        }
+
+        if self.get_cap_type() == CapTag::CapFrameCap && other.get_cap_type() == CapTag::CapFrameCap {
+            return self.get_frame_base_ptr() == other.get_frame_base_ptr() &&
+                    self.get_frame_size() == other.get_frame_size() &&
+                    self.get_frame_is_device() == other.get_frame_is_device();
+        }
+        self.same_region_as(other)
+    }
+
+    pub fn same_region_as(&self, other: &Self) -> bool {
+        match self.get_cap_type() {
+            CapTag::CapUntypedCap => {
+                if other.is_physical() {
+                    let self_base = self.get_cap_pptr();
+                    let other_base = other.get_cap_pptr();
+
+                    let self_top = self_base + mask(self.get_untyped_block_size());
+                    let other_top = other_base + mask(other.get_cap_size_bits());
+                    return self_base <= other_base && other_top <= self_top && other_base <= other_top;
+                }
+            }
+
+            CapTag::CapEndpointCap | CapTag::CapNotificationCap | CapTag::CapThreadCap |
+            CapTag::CapPageTableCap | CapTag::CapASIDPoolCap => {
+                if other.get_cap_type() == self.get_cap_type() {
+                    return self.get_cap_pptr() == other.get_cap_pptr();
+                }
+            }
+
+            CapTag::CapCNodeCap => {
+                if other.get_cap_type() == CapTag::CapCNodeCap {
+                    return self.get_cnode_ptr() == other.get_cnode_ptr() && self.get_cnode_radix() == other.get_cnode_radix();
+                }
+            }
+
+            CapTag::CapReplyCap => {
+                if other.get_cap_type() == CapTag::CapReplyCap {
+                    return self.get_reply_tcb_ptr() == other.get_reply_tcb_ptr();
+                }
+            }
+
+            CapTag::CapDomainCap | CapTag::CapASIDControlCap => {
+                return other.get_cap_type() == self.get_cap_type();
+            }
+
+            CapTag::CapSchedContextCap => {
+                if other.get_cap_type() == CapTag::CapSchedContextCap {
+                    return self.get_sc_ptr() == other.get_sc_ptr() && self.get_sc_size_bits() == other.get_sc_size_bits();
+                }
+            }
+
+            CapTag::CapSchedControlCap => {
+                if other.get_cap_type() == CapTag::CapSchedControlCap {
+                    return self.get_sched_control_core() == other.get_sched_control_core();
+                }
+            }
+
+            CapTag::CapIrqControlCap => {
+                return other.get_cap_type() == CapTag::CapIrqControlCap || other.get_cap_type() == CapTag::CapIrqHandlerCap;
+            }
+
+            CapTag::CapIrqHandlerCap => {
+                if other.get_cap_type() == CapTag::CapIrqHandlerCap {
+                    return self.get_irq_handler() == other.get_irq_handler();
+                }
+            }
+
+            CapTag::CapFrameCap => {
+                if other.get_cap_type() == CapTag::CapFrameCap {
+                    let bot_a = self.get_frame_base_ptr();
+                    let bot_b = other.get_frame_base_ptr();
+                    let top_a = bot_a + mask(self.get_cap_size_bits());
+                    let top_b = bot_b + mask(other.get_cap_size_bits());
+                    return bot_a <= bot_b  && top_a >= top_b && bot_b <= top_b;
+                }
+            }
+
+            _ => {
+                return false;
+            }
+        }
+        false
+    }
+
+    pub fn is_physical(&self) -> bool {
+        match self.get_cap_type() {
+            CapTag::CapUntypedCap | CapTag::CapEndpointCap | CapTag::CapNotificationCap |
+            CapTag::CapCNodeCap | CapTag::CapThreadCap | CapTag::CapZombieCap | CapTag::CapFrameCap |
+            CapTag::CapPageTableCap | CapTag::CapASIDPoolCap | CapTag::CapSchedContextCap => true,
+            #[cfg(feature = "mcs")]
+            CapTag::CapReplyCap => true,
+            _ => false,
+        }
+    }
+
+    pub fn get_cap_pptr(&self) -> Pptr {
+        match self.get_cap_type() {
+            CapTag::CapUntypedCap => self.get_untyped_ptr(),
+            CapTag::CapCNodeCap => self.get_cnode_ptr(),
+            CapTag::CapPageTableCap => self.get_pt_base_ptr(),
+            CapTag::CapASIDPoolCap => self.get_asid_pool(),
+            CapTag::CapFrameCap => self.get_frame_base_ptr(),
+            CapTag::CapNotificationCap => self.get_nt_fn_ptr(),
+            CapTag::CapEndpointCap => self.get_ep_ptr(),
+            CapTag::CapThreadCap => self.get_tcb_ptr(),
+            CapTag::CapSchedContextCap => self.get_sc_ptr(),
+            #[cfg(feature = "mcs")]
+            CapTag::CapReplyCap => self.get_reply_ptr(),
+            _ => { panic!("invalid type") }
+        }
+    }
+
+    pub fn get_cap_size_bits(&self) -> usize {
+        match self.get_cap_type() {
+            CapTag::CapUntypedCap => self.get_untyped_block_size(),
+            CapTag::CapEndpointCap => SEL4_ENDPOINT_BITS,
+            CapTag::CapNotificationCap => SEL4_NOTIFICATION_BITS,
+            CapTag::CapCNodeCap => self.get_cnode_radix() + SEL4_SLOT_BITS,
+            CapTag::CapThreadCap => SEL4_TCB_BITS,
+            CapTag::CapZombieCap => {
+                panic!("invalid type")
+            }
+            CapTag::CapFrameCap => page_bits_for_size(self.get_frame_size()).unwrap_or(0),
+            CapTag::CapPageTableCap => SEL4_PAGE_BITS,
+            CapTag::CapASIDPoolCap => SEL4_ASID_POOL_BITS,
+            CapTag::CapSchedContextCap => self.get_sc_size_bits(),
+            #[cfg(feature = "mcs")]
+            CapTag::CapReplyCap => SEL4_REPLY_BITS,
+            _ => 0,
+        }
+    }
+}
+
+
+pub fn is_cap_revocable(derived_cap: Cap, src_cap: Cap) -> bool {
+    match derived_cap.get_cap_type() {
+        CapTag::CapUntypedCap => {
+            true
+        }
+        CapTag::CapEndpointCap => {
+            derived_cap.get_ep_badge() != src_cap.get_ep_badge()
+        }
+        CapTag::CapNotificationCap => {
+            derived_cap.get_nt_fn_badge() != src_cap.get_nt_fn_badge()
+        }
+        CapTag::CapIrqHandlerCap => {
+            src_cap.get_cap_type() == CapTag::CapIrqControlCap
+        }
+        _ => {
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::config::{PPTR_BASE, RISCV_4K_PAGE, RISCV_MEGA_PAGE, RISCV_GIGA_PAGE, SEL4_LARGE_PAGE_BITS};
+
+    const KERNEL_PTR: Pptr = PPTR_BASE + 0x8020_0000;
+
+    #[test]
+    fn kernel_pointers_are_sign_extended() {
+        let ep = Cap::new_endpoint_cap(0x55, false, true, true, false, KERNEL_PTR);
+        assert_eq!(ep.get_cap_type(), CapTag::CapEndpointCap);
+        assert_eq!(ep.get_ep_ptr(), KERNEL_PTR);
+        assert_eq!(ep.get_cap_pptr(), KERNEL_PTR);
+        assert_eq!(ep.get_ep_badge(), 0x55);
+        assert!(ep.get_ep_can_grant() && ep.get_ep_can_send());
+        assert!(!ep.get_ep_can_grant_reply() && !ep.get_ep_can_receive());
+
+        let cnode = Cap::new_cnode_cap(10, 54, 0x3, KERNEL_PTR);
+        assert_eq!(cnode.get_cnode_ptr(), KERNEL_PTR);
+        assert_eq!((cnode.get_cnode_radix(), cnode.get_cnode_guard_size(), cnode.get_cnode_guard()), (10, 54, 0x3));
+        assert_eq!(cnode.get_cap_size_bits(), 10 + SEL4_SLOT_BITS);
+    }
+
+    #[test]
+    fn untyped_contains_what_was_retyped_from_it() {
+        let untyped = Cap::new_untyped_cap(0, false, 21, KERNEL_PTR);
+        let inside = Cap::new_frame_cap(0, KERNEL_PTR + 0x1000, RISCV_4K_PAGE, VmRights::VMReadWrite as usize, false, 0);
+        let last = Cap::new_frame_cap(0, KERNEL_PTR + bit(21) - 0x1000, RISCV_4K_PAGE, VmRights::VMReadWrite as usize, false, 0);
+        let outside = Cap::new_frame_cap(0, KERNEL_PTR + bit(21), RISCV_4K_PAGE, VmRights::VMReadWrite as usize, false, 0);
+        let too_big = Cap::new_frame_cap(0, KERNEL_PTR + 0x1000, RISCV_MEGA_PAGE, VmRights::VMReadWrite as usize, false, 0);
+        assert!(untyped.same_region_as(&inside));
+        assert!(untyped.same_region_as(&last));
+        assert!(!untyped.same_region_as(&outside));
+        assert!(!untyped.same_region_as(&too_big));
+        assert!(!inside.same_region_as(&untyped));
+        // an untyped is never the same object as anything, even itself
+        assert!(!untyped.same_obj_as(&untyped));
+        assert_eq!(untyped.get_untyped_ref(2), KERNEL_PTR + (2 << MIN_UNTYPED_BITS));
+    }
+
+    #[test]
+    fn frames_are_the_same_object_only_with_the_same_size() {
+        let small = Cap::new_frame_cap(1, KERNEL_PTR, RISCV_4K_PAGE, VmRights::VMReadOnly as usize, false, 0x1000);
+        let copy = Cap::new_frame_cap(2, KERNEL_PTR, RISCV_4K_PAGE, VmRights::VMReadWrite as usize, false, 0x2000);
+        let large = Cap::new_frame_cap(1, KERNEL_PTR, RISCV_MEGA_PAGE, VmRights::VMReadOnly as usize, false, 0x1000);
+        assert!(small.same_obj_as(&copy));
+        assert!(!small.same_obj_as(&large));
+        assert!(large.same_region_as(&small));
+        assert_eq!(large.get_cap_size_bits(), SEL4_LARGE_PAGE_BITS);
+
+        // a size no frame has covers nothing rather than panicking
+        let bad = Cap::new_frame_cap(1, KERNEL_PTR, RISCV_GIGA_PAGE + 1, VmRights::VMReadOnly as usize, false, 0);
+        assert_eq!(bad.get_cap_size_bits(), 0);
+    }
+
+    #[test]
+    fn update_cap_data_only_badges_once() {
+        let mut ep = Cap::new_endpoint_cap(0, true, true, true, true, KERNEL_PTR);
+        ep.update_cap_data(false, 7);
+        assert_eq!(ep.get_ep_badge(), 7);
+        ep.update_cap_data(false, 9);
+        assert_eq!(ep.get_ep_badge(), 7);
+
+        let mut ntfn = Cap::new_notification_cap(0, true, true, KERNEL_PTR);
+        ntfn.update_cap_data(true, 3);
+        assert_eq!(ntfn.get_nt_fn_badge(), 0);
+    }
+
+    #[test]
+    fn update_cap_data_sets_the_cnode_guard() {
+        let mut cnode = Cap::new_cnode_cap(12, 0, 0, KERNEL_PTR);
+        // guard 0x5 of 4 bits, packed as guard << 6 | guard_size
+        cnode.update_cap_data(false, 0x5 << 6 | 4);
+        assert_eq!((cnode.get_cnode_guard(), cnode.get_cnode_guard_size()), (0x5, 4));
+
+        // a guard that would not fit next to the radix is ignored
+        cnode.update_cap_data(false, 0x1 << 6 | (WORD_BITS - 11));
+        assert_eq!((cnode.get_cnode_guard(), cnode.get_cnode_guard_size()), (0x5, 4));
+    }
+
+    #[test]
+    fn mask_cap_rights_drops_rights() {
+        let ep = Cap::new_endpoint_cap(0, true, true, true, true, KERNEL_PTR);
+        let read_only = ep.mask_cap_rights(CapRights::new(0, 0, 1, 0));
+        assert!(read_only.get_ep_can_receive());
+        assert!(!read_only.get_ep_can_send() && !read_only.get_ep_can_grant() && !read_only.get_ep_can_grant_reply());
+        assert_eq!(read_only.get_ep_ptr(), KERNEL_PTR);
+
+        let frame = Cap::new_frame_cap(0, KERNEL_PTR, RISCV_4K_PAGE, VmRights::VMReadWrite as usize, false, 0);
+        let masked = frame.mask_cap_rights(CapRights::new(0, 0, 1, 0));
+        assert!(VmRights::from_usize(masked.get_frame_vm_right()) == VmRights::VMReadOnly);
+    }
+
+    #[test]
+    fn sched_context_caps_cover_their_refills() {
+        let untyped = Cap::new_untyped_cap(0, false, 12, KERNEL_PTR);
+        let sc = Cap::new_sched_context_cap(KERNEL_PTR + 0x100, 8);
+        assert_eq!(sc.get_cap_type(), CapTag::CapSchedContextCap);
+        assert_eq!((sc.get_cap_pptr(), sc.get_cap_size_bits()), (KERNEL_PTR + 0x100, 8));
+        assert!(untyped.same_region_as(&sc));
+        assert!(sc.same_obj_as(&Cap::new_sched_context_cap(KERNEL_PTR + 0x100, 8)));
+        assert!(!sc.same_obj_as(&Cap::new_sched_context_cap(KERNEL_PTR + 0x100, 7)));
+
+        let control = Cap::new_sched_control_cap(1);
+        assert!(!control.is_physical());
+        assert!(control.same_obj_as(&Cap::new_sched_control_cap(1)));
+        assert!(!control.same_obj_as(&Cap::new_sched_control_cap(0)));
+    }
+
+    #[cfg(feature = "mcs")]
+    #[test]
+    fn reply_object_caps_are_retyped_from_untyped() {
+        let untyped = Cap::new_untyped_cap(0, false, 12, KERNEL_PTR);
+        let reply = Cap::new_reply_object_cap(true, KERNEL_PTR + 0x20);
+        assert!(reply.is_physical() && reply.get_reply_can_grant());
+        assert_eq!((reply.get_cap_pptr(), reply.get_cap_size_bits()), (KERNEL_PTR + 0x20, SEL4_REPLY_BITS));
+        assert!(untyped.same_region_as(&reply));
+        assert!(!untyped.same_region_as(&Cap::new_reply_object_cap(true, KERNEL_PTR + bit(12))));
+    }
+
+    #[test]
+    fn revocable_when_badged_or_untyped() {
+        let src = Cap::new_endpoint_cap(0, true, true, true, true, KERNEL_PTR);
+        let mut badged = src;
+        badged.set_ep_badge(1);
+        assert!(is_cap_revocable(badged, src));
+        assert!(!is_cap_revocable(src, src));
+        let untyped = Cap::new_untyped_cap(0, false, 12, KERNEL_PTR);
+        assert!(is_cap_revocable(untyped, untyped));
+    }
+}
@@ -0,0 +1,7 @@
+pub use crate::structures::CapData;
+
+impl CapData {
+    pub fn new(data: usize) -> Self {
+        CapData { words: [data] }
+    }
+}
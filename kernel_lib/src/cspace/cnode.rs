@@ -37,5 +37,11 @@ pub enum TCBCNodeIndex {
     TCBReply = 2,
     TCBCaller = 3,
     TCBBuffer = 4,
+    #[cfg(not(feature = "mcs"))]
     TCBCNodeEntries = 5,
+    /// the endpoint the thread sends a timeout fault to when it runs out of budget
+    #[cfg(feature = "mcs")]
+    TCBTimeoutHandler = 5,
+    #[cfg(feature = "mcs")]
+    TCBCNodeEntries = 6,
 }
\ No newline at end of file
@@ -0,0 +1,318 @@
+use core::mem::size_of;
+use common::config::{MIN_UNTYPED_BITS, SEL4_TCB_BITS};
+use common::types::Pptr;
+use common::utils::{bit, mask};
+use crate::cspace::{Cap, CapTag, CapTableEntry, TCBCNodeIndex};
+
+// cnodes and tcbs, and caps to objects other than untypeds, a single check can keep track of
+pub const MAX_CONTAINERS: usize = 1024;
+pub const MAX_REGIONS: usize = 16384;
+
+/// the first broken invariant check_cspace comes across, with the slots involved
+#[derive(Debug, PartialEq, Eq)]
+pub enum InvariantViolation {
+    /// a null cap still linked into the mdb
+    LinkedNullCap(Pptr),
+    /// the next slot does not point back through its prev
+    BrokenNext(Pptr, Pptr),
+    /// the prev slot does not point forward through its next
+    BrokenPrev(Pptr, Pptr),
+    /// a derived cap outside the run of children right after its parent
+    OrphanedChild(Pptr),
+    /// an untyped and a child of it above its free index
+    ChildAboveFreeIndex(Pptr, Pptr),
+    /// two caps to different objects whose memory overlaps
+    OverlappingObjects(Pptr, Pptr),
+    /// more cnodes and tcbs than the checker has room for
+    TooManyContainers,
+    /// more caps to objects than the checker has room for
+    TooManyRegions,
+}
+
+use InvariantViolation::*;
+
+fn slot_at(ptr: Pptr) -> &'static CapTableEntry {
+    unsafe { &*(ptr as *const CapTableEntry) }
+}
+
+fn addr(slot: &CapTableEntry) -> Pptr {
+    slot as *const CapTableEntry as Pptr
+}
+
+#[derive(Clone, Copy)]
+struct Region {
+    base: Pptr,
+    top: Pptr,
+    slot: Pptr,
+}
+
+/// the room one check needs, too big for a kernel stack, so the caller keeps it somewhere static
+pub struct CheckTables {
+    containers: [(Pptr, usize); MAX_CONTAINERS],
+    regions: [Region; MAX_REGIONS],
+}
+
+impl CheckTables {
+    pub const fn new() -> Self {
+        Self {
+            containers: [(0, 0); MAX_CONTAINERS],
+            regions: [Region { base: 0, top: 0, slot: 0 }; MAX_REGIONS],
+        }
+    }
+}
+
+impl Default for CheckTables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// the cnodes and tcb cnodes reachable from the root, as (first slot, number of slots)
+struct Reachable<'a> {
+    containers: &'a mut [(Pptr, usize); MAX_CONTAINERS],
+    len: usize,
+}
+
+impl<'a> Reachable<'a> {
+    fn new(root: Cap, containers: &'a mut [(Pptr, usize); MAX_CONTAINERS]) -> Result<Self, InvariantViolation> {
+        let mut reachable = Self { containers, len: 0 };
+        reachable.add(root)?;
+        let mut i = 0;
+        while i < reachable.len {
+            let (base, count) = reachable.containers[i];
+            for j in 0..count {
+                reachable.add(slot_at(base + j * size_of::<CapTableEntry>()).cap)?;
+            }
+            i += 1;
+        }
+        Ok(reachable)
+    }
+
+    fn add(&mut self, cap: Cap) -> Result<(), InvariantViolation> {
+        let container = match cap.get_cap_type() {
+            CapTag::CapCNodeCap => (cap.get_cnode_ptr(), bit(cap.get_cnode_radix())),
+            CapTag::CapThreadCap => (cap.get_tcb_ptr() & !mask(SEL4_TCB_BITS), TCBCNodeIndex::TCBCNodeEntries as usize),
+            _ => return Ok(()),
+        };
+        if self.containers[..self.len].iter().any(|c| c.0 == container.0) {
+            return Ok(());
+        }
+        if self.len == MAX_CONTAINERS {
+            return Err(TooManyContainers);
+        }
+        self.containers[self.len] = container;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn slots(&self) -> impl Iterator<Item = &'static CapTableEntry> + '_ {
+        self.containers[..self.len].iter().flat_map(|&(base, count)| {
+            (0..count).map(move |i| slot_at(base + i * size_of::<CapTableEntry>()))
+        })
+    }
+}
+
+// the memory behind a cap, for caps to objects other than untypeds
+fn object_region(cap: &Cap) -> Option<(Pptr, Pptr)> {
+    match cap.get_cap_type() {
+        CapTag::CapUntypedCap | CapTag::CapZombieCap => None,
+        _ if cap.is_physical() => {
+            let size_bits = cap.get_cap_size_bits();
+            let base = cap.get_cap_pptr() & !mask(size_bits);
+            Some((base, base + bit(size_bits)))
+        }
+        _ => None,
+    }
+}
+
+fn check_links(slot: &CapTableEntry) -> Result<(), InvariantViolation> {
+    let mdb = slot.mdb_node;
+    if slot.cap.get_cap_type() == CapTag::CapNullCap {
+        if mdb.get_mdb_next() != 0 || mdb.get_mdb_prev() != 0 {
+            return Err(LinkedNullCap(addr(slot)));
+        }
+        return Ok(());
+    }
+    if mdb.get_mdb_next() != 0 && slot_at(mdb.get_mdb_next()).mdb_node.get_mdb_prev() != addr(slot) {
+        return Err(BrokenNext(addr(slot), mdb.get_mdb_next()));
+    }
+    if mdb.get_mdb_prev() != 0 && slot_at(mdb.get_mdb_prev()).mdb_node.get_mdb_next() != addr(slot) {
+        return Err(BrokenPrev(addr(slot), mdb.get_mdb_prev()));
+    }
+    Ok(())
+}
+
+// a derived cap has a parent before it, and everything in between is a child of that parent too
+fn check_parent(slot: &CapTableEntry) -> Result<(), InvariantViolation> {
+    if slot.cap.get_cap_type() == CapTag::CapNullCap || slot.mdb_node.get_mdb_revocable() {
+        return Ok(());
+    }
+    let mut parent = slot.mdb_node.get_mdb_prev();
+    while parent != 0 && !slot_at(parent).is_mdb_parent_of(slot) {
+        parent = slot_at(parent).mdb_node.get_mdb_prev();
+    }
+    if parent == 0 {
+        return Err(OrphanedChild(addr(slot)));
+    }
+    let mut sibling = slot_at(parent).mdb_node.get_mdb_next();
+    while sibling != addr(slot) {
+        if !slot_at(parent).is_mdb_parent_of(slot_at(sibling)) {
+            return Err(OrphanedChild(addr(slot)));
+        }
+        sibling = slot_at(sibling).mdb_node.get_mdb_next();
+    }
+    Ok(())
+}
+
+fn check_free_index(slot: &CapTableEntry) -> Result<(), InvariantViolation> {
+    if slot.cap.get_cap_type() != CapTag::CapUntypedCap {
+        return Ok(());
+    }
+    let free = slot.cap.get_untyped_ptr() + (slot.cap.get_untyped_free_index() << MIN_UNTYPED_BITS);
+    let mut child = slot.mdb_node.get_mdb_next();
+    while child != 0 && slot.is_mdb_parent_of(slot_at(child)) {
+        let cap = slot_at(child).cap;
+        let top = if cap.get_cap_type() == CapTag::CapUntypedCap {
+            Some(cap.get_untyped_ptr() + bit(cap.get_untyped_block_size()))
+        } else {
+            object_region(&cap).map(|(_, top)| top)
+        };
+        if matches!(top, Some(top) if top > free) {
+            return Err(ChildAboveFreeIndex(addr(slot), child));
+        }
+        child = slot_at(child).mdb_node.get_mdb_next();
+    }
+    Ok(())
+}
+
+// caps may share an object, but objects may not share memory. sorted by base, every region
+// must be the same object as, or start above, the one reaching highest before it
+fn check_overlap(reachable: &Reachable, regions: &mut [Region; MAX_REGIONS]) -> Result<(), InvariantViolation> {
+    let mut len = 0;
+    for slot in reachable.slots() {
+        if let Some((base, top)) = object_region(&slot.cap) {
+            if len == MAX_REGIONS {
+                return Err(TooManyRegions);
+            }
+            regions[len] = Region { base, top, slot: addr(slot) };
+            len += 1;
+        }
+    }
+    let regions = &mut regions[..len];
+    regions.sort_unstable_by_key(|region| (region.base, region.top, region.slot));
+
+    let mut highest = Region { base: 0, top: 0, slot: 0 };
+    for region in regions.iter() {
+        if region.base < highest.top {
+            let same = region.base == highest.base && region.top == highest.top
+                && slot_at(region.slot).cap.get_cap_type() == slot_at(highest.slot).cap.get_cap_type();
+            if !same {
+                return Err(OverlappingObjects(highest.slot, region.slot));
+            }
+        }
+        if region.top > highest.top {
+            highest = *region;
+        }
+    }
+    Ok(())
+}
+
+/// walk every cnode reachable from `root`, through cnode and thread caps, and check the mdb links
+/// and the objects of each slot. slow, it is meant for debug builds
+pub fn check_cspace(root: Cap, tables: &mut CheckTables) -> Result<(), InvariantViolation> {
+    let reachable = Reachable::new(root, &mut tables.containers)?;
+    for slot in reachable.slots() {
+        check_links(slot)?;
+        check_parent(slot)?;
+        check_free_index(slot)?;
+    }
+    check_overlap(&reachable, &mut tables.regions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::config::{PPTR_BASE, SEL4_WORD_BITS};
+    use crate::cspace::{cte_insert, insert_new_cap, CNode};
+    use crate::test_utils::alloc_low;
+
+    const RADIX: usize = 4;
+    const UT_PTR: Pptr = PPTR_BASE + 0x8040_0000;
+    const EP_PTR: Pptr = PPTR_BASE + 0x8030_0000;
+
+    fn slot(cnode: *mut CNode, index: usize) -> &'static mut CapTableEntry {
+        unsafe { &mut (&mut *cnode)[index] }
+    }
+
+    fn cnode_cap(cnode: *mut CNode) -> Cap {
+        Cap::new_cnode_cap(RADIX, SEL4_WORD_BITS - RADIX, 0, cnode as Pptr)
+    }
+
+    fn endpoint(ptr: Pptr) -> Cap {
+        Cap::new_endpoint_cap(0, true, true, true, true, ptr)
+    }
+
+    fn check(cnode: *mut CNode) -> Result<(), InvariantViolation> {
+        check_cspace(cnode_cap(cnode), &mut Box::new(CheckTables::new()))
+    }
+
+    // an untyped in slot 0 with a used up first half, retyped into two endpoints
+    fn retyped_cnode() -> *mut CNode {
+        let cnode: *mut CNode = alloc_low::<CNode>();
+        unsafe { (*cnode).write(0, Cap::new_untyped_cap(bit(12 - MIN_UNTYPED_BITS), false, 13, UT_PTR)) };
+        insert_new_cap(slot(cnode, 0), slot(cnode, 1), endpoint(UT_PTR));
+        insert_new_cap(slot(cnode, 0), slot(cnode, 2), endpoint(UT_PTR + 0x10));
+        cnode
+    }
+
+    #[test]
+    fn a_consistent_cspace_passes() {
+        let cnode = retyped_cnode();
+        cte_insert(endpoint(UT_PTR), slot(cnode, 1), slot(cnode, 3));
+        // the cnode holds a cap to itself, which is walked once
+        unsafe { (*cnode).write(4, cnode_cap(cnode)) };
+        assert_eq!(check(cnode), Ok(()));
+    }
+
+    #[test]
+    fn one_sided_links_are_found() {
+        // 0 -> 2 -> 1, with 2 no longer pointing on to 1
+        let cnode = retyped_cnode();
+        slot(cnode, 2).mdb_node.set_mdb_next(0);
+        assert_eq!(check(cnode), Err(BrokenPrev(addr(slot(cnode, 1)), addr(slot(cnode, 2)))));
+
+        let cnode = retyped_cnode();
+        slot(cnode, 5).mdb_node.set_mdb_prev(addr(slot(cnode, 1)));
+        assert_eq!(check(cnode), Err(LinkedNullCap(addr(slot(cnode, 5)))));
+    }
+
+    #[test]
+    fn a_copy_without_its_parent_is_orphaned() {
+        let cnode: *mut CNode = alloc_low::<CNode>();
+        unsafe {
+            (*cnode).write(0, endpoint(EP_PTR));
+            (*cnode).write(1, endpoint(EP_PTR + 0x100));
+        }
+        cte_insert(endpoint(EP_PTR + 0x100), slot(cnode, 1), slot(cnode, 2));
+        assert_eq!(check(cnode), Ok(()));
+        // relink the copy after a cap to another object
+        slot(cnode, 1).mdb_node.set_mdb_next(0);
+        slot(cnode, 0).mdb_node.set_mdb_next(addr(slot(cnode, 2)));
+        slot(cnode, 2).mdb_node.set_mdb_prev(addr(slot(cnode, 0)));
+        assert_eq!(check(cnode), Err(OrphanedChild(addr(slot(cnode, 2)))));
+    }
+
+    #[test]
+    fn children_must_sit_below_the_free_index() {
+        let cnode = retyped_cnode();
+        insert_new_cap(slot(cnode, 0), slot(cnode, 3), endpoint(UT_PTR + bit(12)));
+        assert_eq!(check(cnode), Err(ChildAboveFreeIndex(addr(slot(cnode, 0)), addr(slot(cnode, 3)))));
+    }
+
+    #[test]
+    fn overlapping_objects_are_found() {
+        let cnode = retyped_cnode();
+        unsafe { (*cnode).write(3, Cap::new_notification_cap(0, true, true, UT_PTR)) };
+        assert_eq!(check(cnode), Err(OverlappingObjects(addr(slot(cnode, 1)), addr(slot(cnode, 3)))));
+    }
+}
@@ -0,0 +1,7 @@
+pub use crate::structures::MDBNode;
+
+impl MDBNode {
+    pub fn null_mdbnode() -> Self {
+        Self::new(0, false, false, 0)
+    }
+}
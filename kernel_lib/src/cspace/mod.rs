@@ -0,0 +1,229 @@
+use log::error;
+use common::config::WORD_RADIX;
+use common::types::Pptr;
+use common::utils::mask;
+
+mod cnode;
+mod cap;
+mod cap_data;
+mod mdb;
+mod invariants;
+pub use cap::{Cap, CapTag, CapTableEntry, is_cap_revocable};
+pub use cnode::{CNode, TCBCNodeIndex};
+pub use cap_data::CapData;
+pub use mdb::MDBNode;
+pub use invariants::{check_cspace, CheckTables, InvariantViolation};
+use crate::cspace::CapTag::CapCNodeCap;
+use crate::untyped::set_untyped_cap_as_full;
+
+pub fn cte_insert(new_cap: Cap, src_slot: &mut CapTableEntry, dest_slot: &mut CapTableEntry) {
+    let mut new_mdb = src_slot.mdb_node;
+    let src_cap = src_slot.cap;
+
+    let new_cap_is_revocable = is_cap_revocable(new_cap, src_cap);
+    new_mdb.set_mdb_prev(src_slot as *const CapTableEntry as Pptr);
+    new_mdb.set_mdb_revocable(new_cap_is_revocable);
+    new_mdb.set_mdb_first_badged(new_cap_is_revocable);
+
+    set_untyped_cap_as_full(src_cap, new_cap, src_slot);
+
+    dest_slot.cap = new_cap;
+    dest_slot.mdb_node = new_mdb;
+    let ref_src_mdb = &mut src_slot.mdb_node;
+    ref_src_mdb.set_mdb_next(dest_slot as *const CapTableEntry as Pptr);
+
+    if new_mdb.get_mdb_next() != 0 {
+        let prev_of_new_mdb = unsafe {
+            &mut (&mut *(new_mdb.get_mdb_next() as *mut CapTableEntry)).mdb_node
+        };
+        prev_of_new_mdb.set_mdb_prev(dest_slot as *const CapTableEntry as Pptr);
+    }
+}
+
+pub fn resolve_address_bits(node_cap: Cap, cap_ptr: usize, n_bits: usize) -> Option<*mut CapTableEntry> {
+    if node_cap.get_cap_type() != CapCNodeCap {
+        error!("cptr: {}, type: {:?}", cap_ptr, node_cap.get_cap_type());
+        return None;
+    }
+    let mut local_n_bits = n_bits;
+    let mut local_node_cap = node_cap;
+    loop {
+        let radix_bits = local_node_cap.get_cnode_radix();
+        let guard_bits = local_node_cap.get_cnode_guard_size();
+        let level_bits = radix_bits + guard_bits;
+        assert_ne!(level_bits, 0);
+
+        if guard_bits > local_n_bits {
+            return None;
+        }
+        let cap_guard = local_node_cap.get_cnode_guard();
+        let guard = (cap_ptr >> ((local_n_bits - guard_bits) & mask(WORD_RADIX))) & mask(guard_bits);
+        if guard != cap_guard {
+            return None;
+        }
+
+        if level_bits > local_n_bits {
+            return None;
+        }
+        let offset = (cap_ptr >> (local_n_bits - level_bits)) & mask(radix_bits);
+        let slot = unsafe {
+            &mut (&mut *(local_node_cap.get_cap_pptr() as *mut CNode))[offset]
+        };
+        if local_n_bits == level_bits {
+            return Some(slot as *mut CapTableEntry);
+        }
+        local_n_bits -= level_bits;
+        local_node_cap =  slot.cap;
+
+        if local_node_cap.get_cap_type() != CapCNodeCap {
+            return Some(slot as *mut CapTableEntry);
+        }
+    }
+}
+
+pub fn insert_new_cap(parent: &mut CapTableEntry, slot: &mut CapTableEntry, cap: Cap) {
+    let next = parent.mdb_node.get_mdb_next();
+    slot.cap = cap;
+    slot.mdb_node = MDBNode::new(next, true, true, parent as *mut CapTableEntry as usize);
+    if next != 0 {
+        unsafe {
+            (&mut *(next as *mut CapTableEntry)).mdb_node.set_mdb_prev(slot as *mut CapTableEntry as usize);
+        }
+    }
+    parent.mdb_node.set_mdb_next(slot as *mut CapTableEntry as usize);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::config::{PPTR_BASE, WORD_BITS};
+    use crate::test_utils::alloc_low;
+
+    const EP_PTR: Pptr = PPTR_BASE + 0x8030_0000;
+
+    fn slot(cnode: *mut CNode, index: usize) -> &'static mut CapTableEntry {
+        unsafe { &mut (&mut *cnode)[index] }
+    }
+
+    fn addr(slot: &CapTableEntry) -> Pptr {
+        slot as *const CapTableEntry as Pptr
+    }
+
+    fn endpoint(badge: usize) -> Cap {
+        Cap::new_endpoint_cap(badge, true, true, true, true, EP_PTR)
+    }
+
+    #[test]
+    fn cte_insert_links_the_copy_after_its_source() {
+        let cnode: *mut CNode = alloc_low::<CNode>();
+        unsafe { (*cnode).write(0, endpoint(0)) };
+        cte_insert(endpoint(0), slot(cnode, 0), slot(cnode, 1));
+        cte_insert(endpoint(0), slot(cnode, 0), slot(cnode, 2));
+
+        // 0 -> 2 -> 1
+        assert_eq!(slot(cnode, 0).mdb_node.get_mdb_next(), addr(slot(cnode, 2)));
+        assert_eq!(slot(cnode, 2).mdb_node.get_mdb_prev(), addr(slot(cnode, 0)));
+        assert_eq!(slot(cnode, 2).mdb_node.get_mdb_next(), addr(slot(cnode, 1)));
+        assert_eq!(slot(cnode, 1).mdb_node.get_mdb_prev(), addr(slot(cnode, 2)));
+        assert_eq!(slot(cnode, 1).mdb_node.get_mdb_next(), 0);
+
+        // plain copies are not revocable, so only the original is a parent
+        assert!(!slot(cnode, 1).mdb_node.get_mdb_revocable());
+        assert!(slot(cnode, 0).is_mdb_parent_of(slot(cnode, 2)));
+        assert!(!slot(cnode, 2).is_mdb_parent_of(slot(cnode, 1)));
+        assert!(!slot(cnode, 0).ensure_no_child());
+        assert!(slot(cnode, 1).ensure_no_child());
+        assert!(!slot(cnode, 1).is_final_cap());
+    }
+
+    #[test]
+    fn emplty_slot_unlinks_and_passes_on_first_badged() {
+        let cnode: *mut CNode = alloc_low::<CNode>();
+        unsafe { (*cnode).write(0, endpoint(0)) };
+        cte_insert(endpoint(0), slot(cnode, 0), slot(cnode, 1));
+        cte_insert(endpoint(0), slot(cnode, 0), slot(cnode, 2));
+        slot(cnode, 2).mdb_node.set_mdb_first_badged(true);
+
+        slot(cnode, 2).emplty_slot(Cap::new_null_cap());
+        assert!(slot(cnode, 2).ensure_empty_slot());
+        assert_eq!(slot(cnode, 2).mdb_node.get_mdb_next(), 0);
+        assert_eq!(slot(cnode, 0).mdb_node.get_mdb_next(), addr(slot(cnode, 1)));
+        assert_eq!(slot(cnode, 1).mdb_node.get_mdb_prev(), addr(slot(cnode, 0)));
+        assert!(slot(cnode, 1).mdb_node.get_mdb_first_badged());
+
+        slot(cnode, 1).emplty_slot(Cap::new_null_cap());
+        assert_eq!(slot(cnode, 0).mdb_node.get_mdb_next(), 0);
+        assert!(slot(cnode, 0).is_final_cap());
+    }
+
+    #[test]
+    fn badges_decide_endpoint_parents() {
+        let cnode: *mut CNode = alloc_low::<CNode>();
+        unsafe { (*cnode).write(0, endpoint(0)) };
+        // mint two badged caps from the original, then copy the first one
+        cte_insert(endpoint(5), slot(cnode, 0), slot(cnode, 1));
+        cte_insert(endpoint(6), slot(cnode, 0), slot(cnode, 3));
+        cte_insert(endpoint(5), slot(cnode, 1), slot(cnode, 2));
+
+        assert!(slot(cnode, 1).mdb_node.get_mdb_revocable() && slot(cnode, 1).mdb_node.get_mdb_first_badged());
+        assert!(!slot(cnode, 2).mdb_node.get_mdb_revocable());
+        assert!(slot(cnode, 0).is_mdb_parent_of(slot(cnode, 1)));
+        assert!(slot(cnode, 1).is_mdb_parent_of(slot(cnode, 2)));
+        assert!(!slot(cnode, 1).is_mdb_parent_of(slot(cnode, 3)));
+        // a fresh badged cap is never the child of an earlier cap with the same badge
+        assert!(!slot(cnode, 2).is_mdb_parent_of(slot(cnode, 1)));
+    }
+
+    #[test]
+    fn insert_new_cap_goes_straight_after_the_parent() {
+        let cnode: *mut CNode = alloc_low::<CNode>();
+        unsafe { (*cnode).write(0, Cap::new_untyped_cap(0, false, 16, EP_PTR)) };
+        insert_new_cap(slot(cnode, 0), slot(cnode, 1), endpoint(0));
+        insert_new_cap(slot(cnode, 0), slot(cnode, 2), Cap::new_endpoint_cap(0, true, true, true, true, EP_PTR + 0x10));
+
+        assert_eq!(slot(cnode, 0).mdb_node.get_mdb_next(), addr(slot(cnode, 2)));
+        assert_eq!(slot(cnode, 2).mdb_node.get_mdb_next(), addr(slot(cnode, 1)));
+        assert_eq!(slot(cnode, 1).mdb_node.get_mdb_prev(), addr(slot(cnode, 2)));
+        assert!(slot(cnode, 1).mdb_node.get_mdb_revocable());
+        assert!(slot(cnode, 0).is_mdb_parent_of(slot(cnode, 1)));
+        assert!(!slot(cnode, 0).ensure_no_child());
+    }
+
+    fn cnode_cap(cnode: *mut CNode, radix: usize, guard_size: usize, guard: usize) -> Cap {
+        Cap::new_cnode_cap(radix, guard_size, guard, cnode as Pptr)
+    }
+
+    #[test]
+    fn resolve_address_bits_in_a_single_level_root() {
+        let root: *mut CNode = alloc_low::<CNode>();
+        // the root server's layout: the guard covers everything above the radix
+        let radix = 13;
+        let root_cap = cnode_cap(root, radix, WORD_BITS - radix, 0);
+        unsafe { (*root).write(5, endpoint(0)) };
+
+        assert_eq!(resolve_address_bits(root_cap, 5, WORD_BITS), Some(slot(root, 5) as *mut CapTableEntry));
+        assert_eq!(resolve_address_bits(root_cap, 1 << 20 | 5, WORD_BITS), None);
+        assert_eq!(resolve_address_bits(root_cap, 5, radix), None);
+        assert_eq!(resolve_address_bits(endpoint(0), 5, WORD_BITS), None);
+    }
+
+    #[test]
+    fn resolve_address_bits_through_two_levels() {
+        let root: *mut CNode = alloc_low::<CNode>();
+        let leaf: *mut CNode = alloc_low::<CNode>();
+        let root_cap = cnode_cap(root, 4, 0, 0);
+        // 4 bits of root index, then a 2 bit guard of 0b01 and 4 bits of leaf index
+        unsafe {
+            (*root).write(3, cnode_cap(leaf, 4, 2, 0b01));
+            (*root).write(6, endpoint(0));
+            (*leaf).write(5, endpoint(1));
+        }
+
+        assert_eq!(resolve_address_bits(root_cap, 0x3 << 6 | 0b01 << 4 | 0x5, 10), Some(slot(leaf, 5) as *mut CapTableEntry));
+        assert_eq!(resolve_address_bits(root_cap, 0x3 << 6 | 0b10 << 4 | 0x5, 10), None);
+        // stopping after the root level gives the slot holding the leaf cnode cap
+        assert_eq!(resolve_address_bits(root_cap, 0x3, 4), Some(slot(root, 3) as *mut CapTableEntry));
+        // a slot that is not a cnode ends the walk early
+        assert_eq!(resolve_address_bits(root_cap, 0x6 << 6 | 0x15, 10), Some(slot(root, 6) as *mut CapTableEntry));
+    }
+}
@@ -0,0 +1,14 @@
+//! the parts of the kernel that are plain data structure logic: cap packing and the mdb, cnode
+//! lookup, thread queues, the ready queue bitmaps and boot memory bookkeeping. nothing here
+//! touches hardware, so it also builds for the host and `cargo test` runs its tests on linux.
+#![cfg_attr(not(test), no_std)]
+
+mod structures;
+pub mod cspace;
+pub mod mm;
+pub mod scheduler;
+pub mod boot;
+pub mod untyped;
+
+#[cfg(test)]
+mod test_utils;
@@ -0,0 +1,38 @@
+use common::types::CapRights;
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum VmRights {
+    VMKernelOnly = 1,
+    VMReadOnly = 2,
+    VMReadWrite = 3
+}
+
+impl VmRights {
+    pub fn mask_vm_rights(&self, cap_rights_mask: CapRights) -> Self {
+        if *self == VmRights::VMReadOnly && cap_rights_mask.get_allow_read() {
+            return VmRights::VMReadOnly;
+        }
+        if *self == VmRights::VMReadWrite && cap_rights_mask.get_allow_read() {
+            if !cap_rights_mask.get_allow_write() {
+                return VmRights::VMReadOnly;
+            } else {
+                return VmRights::VMReadWrite;
+            }
+        }
+        VmRights::VMKernelOnly
+    }
+
+    pub fn from_usize(value: usize) -> Self {
+        unsafe {
+            core::mem::transmute::<u8, VmRights>(value  as u8)
+        }
+    }
+
+    pub fn get_write(&self) -> bool {
+        *self == VmRights::VMReadWrite
+    }
+
+    pub fn get_read(&self) -> bool {
+        *self != VmRights::VMKernelOnly
+    }
+}
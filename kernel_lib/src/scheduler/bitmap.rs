@@ -0,0 +1,99 @@
+use common::config::{L2_BITMAP_SIZE, WORD_BITS, WORD_RADIX};
+use common::utils::{bit, invert_l1_index, l1_index_2_prio, mask, prio_2_l1_index};
+
+/// which priorities of one domain have a non-empty ready queue: bit i of `l1` says word i of the
+/// priorities has a thread, and that word is stored at `l2[invert_l1_index(i)]`, as in seL4
+#[derive(Clone, Copy)]
+pub struct ReadyQueuesBitmap {
+    l1: usize,
+    l2: [usize; L2_BITMAP_SIZE],
+}
+
+impl Default for ReadyQueuesBitmap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ReadyQueuesBitmap {
+    pub const fn new() -> Self {
+        ReadyQueuesBitmap { l1: 0, l2: [0; L2_BITMAP_SIZE] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.l1 == 0
+    }
+
+    pub fn add(&mut self, prio: usize) {
+        let l1_index = prio_2_l1_index(prio);
+        let l1_index_invert = invert_l1_index(l1_index);
+        self.l1 |= bit(l1_index);
+        self.l2[l1_index_invert] |= bit(prio & mask(WORD_RADIX));
+    }
+
+    pub fn remove(&mut self, prio: usize) {
+        let l1_index = prio_2_l1_index(prio);
+        let l1_index_invert = invert_l1_index(l1_index);
+        self.l2[l1_index_invert] &= !bit(prio & mask(WORD_RADIX));
+        if self.l2[l1_index_invert] == 0 {
+            self.l1 &= !bit(l1_index);
+        }
+    }
+
+    pub fn get_highest_prio(&self) -> usize {
+        let l1_index = WORD_BITS - 1 - (self.l1.leading_zeros() as usize);
+        let l1_index_invert = invert_l1_index(l1_index);
+        assert!(self.l2[l1_index_invert] != 0);
+        let l2_index = WORD_BITS - 1 - (self.l2[l1_index_invert].leading_zeros() as usize);
+        l1_index_2_prio(l1_index) | l2_index
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::config::CONFIG_NUM_PRIORITIES;
+
+    #[test]
+    fn l1_index_helpers_agree() {
+        for prio in [0, 1, 63, 64, 130, CONFIG_NUM_PRIORITIES - 1] {
+            let l1_index = prio_2_l1_index(prio);
+            assert_eq!(l1_index, prio / WORD_BITS);
+            assert_eq!(l1_index_2_prio(l1_index), prio & !mask(WORD_RADIX));
+            assert_eq!(invert_l1_index(l1_index), L2_BITMAP_SIZE - 1 - l1_index);
+        }
+    }
+
+    #[test]
+    fn highest_prio_across_words() {
+        let mut bitmap = ReadyQueuesBitmap::new();
+        assert!(bitmap.is_empty());
+        for prio in [3, 70, 64, 200] {
+            bitmap.add(prio);
+        }
+        assert!(!bitmap.is_empty());
+        assert_eq!(bitmap.get_highest_prio(), 200);
+        bitmap.remove(200);
+        assert_eq!(bitmap.get_highest_prio(), 70);
+        bitmap.remove(70);
+        assert_eq!(bitmap.get_highest_prio(), 64);
+        bitmap.remove(64);
+        assert_eq!(bitmap.get_highest_prio(), 3);
+        bitmap.remove(3);
+        assert!(bitmap.is_empty());
+    }
+
+    #[test]
+    fn l1_bit_stays_while_its_word_has_priorities() {
+        let mut bitmap = ReadyQueuesBitmap::new();
+        bitmap.add(CONFIG_NUM_PRIORITIES - 1);
+        bitmap.add(CONFIG_NUM_PRIORITIES - 2);
+        bitmap.remove(CONFIG_NUM_PRIORITIES - 1);
+        assert_eq!(bitmap.get_highest_prio(), CONFIG_NUM_PRIORITIES - 2);
+        // removing a priority that was never added leaves the rest alone
+        bitmap.remove(0);
+        assert_eq!(bitmap.get_highest_prio(), CONFIG_NUM_PRIORITIES - 2);
+        bitmap.remove(CONFIG_NUM_PRIORITIES - 2);
+        assert!(bitmap.is_empty());
+    }
+}
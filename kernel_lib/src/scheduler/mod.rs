@@ -0,0 +1,5 @@
+mod queue;
+mod bitmap;
+
+pub use queue::{TCBQueue, TCBQueueNode};
+pub use bitmap::ReadyQueuesBitmap;
@@ -0,0 +1,159 @@
+use common::types::Pptr;
+use common::utils::convert_to_mut_type_ref;
+
+/// the ep next/prev links a thread is chained through while it waits on an endpoint or notification
+pub trait TCBQueueNode {
+    fn get_ep_next(&self) -> Pptr;
+    fn set_ep_next(&mut self, next: Pptr);
+    fn get_ep_prev(&self) -> Pptr;
+    fn set_ep_prev(&mut self, prev: Pptr);
+}
+
+pub struct TCBQueue<T> {
+    pub head: *mut T,
+    pub end: *mut T,
+}
+
+impl<T> Clone for TCBQueue<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for TCBQueue<T> {}
+
+impl<T: TCBQueueNode + 'static> TCBQueue<T> {
+    pub fn new(head: Pptr, end: Pptr) -> Self {
+        TCBQueue {
+            head: head as *mut T,
+            end: end as *mut T,
+        }
+    }
+
+    pub fn en_queue(&mut self, tcb: &mut T) {
+        if self.head.is_null() {
+            self.head = tcb as *mut T;
+        } else {
+            unsafe {
+                (*self.end).set_ep_next(tcb as *mut T as Pptr);
+            }
+        }
+        tcb.set_ep_prev(self.end as Pptr);
+        tcb.set_ep_next(0);
+        self.end = tcb as *mut T;
+    }
+
+    pub fn de_queue(&mut self, tcb: &mut T) {
+        if tcb.get_ep_prev() != 0 {
+            let prev = convert_to_mut_type_ref::<T>(tcb.get_ep_prev());
+            prev.set_ep_next(tcb.get_ep_next());
+        } else {
+            self.head = tcb.get_ep_next() as *mut T;
+        }
+
+        if tcb.get_ep_next() != 0 {
+            let next = convert_to_mut_type_ref::<T>(tcb.get_ep_next());
+            next.set_ep_prev(tcb.get_ep_prev());
+        } else {
+            self.end = tcb.get_ep_prev() as *mut T;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::boxed::Box;
+    use std::vec::Vec;
+
+    #[derive(Default)]
+    struct Node {
+        id: usize,
+        next: Pptr,
+        prev: Pptr,
+    }
+
+    impl TCBQueueNode for Node {
+        fn get_ep_next(&self) -> Pptr {
+            self.next
+        }
+
+        fn set_ep_next(&mut self, next: Pptr) {
+            self.next = next;
+        }
+
+        fn get_ep_prev(&self) -> Pptr {
+            self.prev
+        }
+
+        fn set_ep_prev(&mut self, prev: Pptr) {
+            self.prev = prev;
+        }
+    }
+
+    fn nodes(n: usize) -> Vec<&'static mut Node> {
+        (0..n).map(|id| Box::leak(Box::new(Node { id, ..Node::default() }))).collect()
+    }
+
+    fn ids(queue: &TCBQueue<Node>) -> Vec<usize> {
+        let mut ids = Vec::new();
+        let mut cur = queue.head;
+        while !cur.is_null() {
+            let node = unsafe { &*cur };
+            if node.next == 0 {
+                assert_eq!(cur, queue.end);
+            }
+            ids.push(node.id);
+            cur = node.next as *mut Node;
+        }
+        ids
+    }
+
+    #[test]
+    fn en_queue_appends() {
+        let mut queue = TCBQueue::<Node>::new(0, 0);
+        for node in nodes(3) {
+            queue.en_queue(node);
+        }
+        assert_eq!(ids(&queue), [0, 1, 2]);
+        assert_eq!(unsafe { (*queue.head).prev }, 0);
+    }
+
+    #[test]
+    fn de_queue_from_the_middle_and_both_ends() {
+        let mut queue = TCBQueue::<Node>::new(0, 0);
+        let ptrs: Vec<*mut Node> = nodes(4).into_iter().map(|node| node as *mut Node).collect();
+        let node = |i: usize| {
+            let ptr = ptrs[i];
+            unsafe { &mut *ptr }
+        };
+        for i in 0..4 {
+            queue.en_queue(node(i));
+        }
+
+        queue.de_queue(node(1));
+        assert_eq!(ids(&queue), [0, 2, 3]);
+        queue.de_queue(node(0));
+        assert_eq!(ids(&queue), [2, 3]);
+        assert_eq!(unsafe { (*queue.head).prev }, 0);
+        queue.de_queue(node(3));
+        assert_eq!(ids(&queue), [2]);
+        queue.de_queue(node(2));
+        assert!(queue.head.is_null() && queue.end.is_null());
+
+        // the queue is usable again once empty
+        queue.en_queue(node(3));
+        assert_eq!(ids(&queue), [3]);
+    }
+
+    #[test]
+    fn queue_survives_a_round_trip_through_words() {
+        let mut queue = TCBQueue::<Node>::new(0, 0);
+        for node in nodes(2) {
+            queue.en_queue(node);
+        }
+        // endpoints and notifications keep their queue as two words
+        let stored = TCBQueue::<Node>::new(queue.head as Pptr, queue.end as Pptr);
+        assert_eq!(ids(&stored), [0, 1]);
+    }
+}
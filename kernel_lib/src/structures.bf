@@ -0,0 +1,196 @@
+-- word-packed cspace structures, turned into rust by bitfield_gen from build.rs.
+-- fields are listed from the top bit of the last word down, as in seL4's structures_64.bf;
+-- field_high keeps the top bits of a 39 bit canonical address and sign extends it on read.
+
+base 64(39,1)
+
+-- caps
+
+block null_cap {
+    padding 64
+
+    field cap_type 5
+    padding 59
+}
+
+block untyped_cap(untyped_free_index, untyped_is_device, untyped_block_size, untyped_ptr) {
+    field untyped_free_index 39
+    padding 18
+    field untyped_is_device 1
+    field untyped_block_size 6
+
+    field cap_type 5
+    padding 20
+    field_high untyped_ptr 39
+}
+
+block endpoint_cap(ep_badge, ep_can_grant_reply, ep_can_grant, ep_can_send, ep_can_receive, ep_ptr) {
+    field ep_badge 64
+
+    field cap_type 5
+    field ep_can_grant_reply 1
+    field ep_can_grant 1
+    field ep_can_receive 1
+    field ep_can_send 1
+    padding 16
+    field_high ep_ptr 39
+}
+
+block notification_cap(nt_fn_badge, nt_fn_can_receive, nt_fn_can_send, nt_fn_ptr) {
+    field nt_fn_badge 64
+
+    field cap_type 5
+    field nt_fn_can_receive 1
+    field nt_fn_can_send 1
+    padding 18
+    field_high nt_fn_ptr 39
+}
+
+block reply_cap(reply_can_grant, reply_master, reply_tcb_ptr) {
+    field reply_tcb_ptr 64
+
+    field cap_type 5
+    padding 57
+    field reply_can_grant 1
+    field reply_master 1
+}
+
+block CNode_cap(cnode_radix, cnode_guard_size, cnode_guard, cnode_ptr) {
+    field cnode_guard 64
+
+    field cap_type 5
+    field cnode_guard_size 6
+    field cnode_radix 6
+    padding 9
+    field_high cnode_ptr 38
+}
+
+block thread_cap(tcb_ptr) {
+    padding 64
+
+    field cap_type 5
+    padding 20
+    field_high tcb_ptr 39
+}
+
+block irq_control_cap {
+    padding 64
+
+    field cap_type 5
+    padding 59
+}
+
+block irq_handler_cap(irq_handler) {
+    padding 52
+    field irq_handler 12
+
+    field cap_type 5
+    padding 59
+}
+
+block zombie_cap(zombie_id, zombie_type) {
+    field zombie_id 64
+
+    field cap_type 5
+    padding 53
+    field zombie_type 6
+}
+
+block domain_cap {
+    padding 64
+
+    field cap_type 5
+    padding 59
+}
+
+block frame_cap(frame_mapped_asid, frame_base_ptr, frame_size, frame_vm_right, frame_is_device, frame_mapped_addr) {
+    field frame_mapped_asid 16
+    field_high frame_base_ptr 39
+    padding 9
+
+    field cap_type 5
+    field frame_size 2
+    field frame_vm_right 2
+    field frame_is_device 1
+    padding 15
+    field_high frame_mapped_addr 39
+}
+
+block page_table_cap(pt_mapped_asid, pt_base_ptr, pt_is_mapped, pt_mapped_addr) {
+    field pt_mapped_asid 16
+    field_high pt_base_ptr 39
+    padding 9
+
+    field cap_type 5
+    padding 19
+    field pt_is_mapped 1
+    field_high pt_mapped_addr 39
+}
+
+block ASID_control_cap {
+    padding 64
+
+    field cap_type 5
+    padding 59
+}
+
+block ASID_pool_cap(asid_base, asid_pool) {
+    padding 64
+
+    field cap_type 5
+    field asid_base 16
+    padding 6
+    field_high asid_pool 37
+}
+
+block sched_context_cap(sc_ptr, sc_size_bits) {
+    field_high sc_ptr 39
+    padding 19
+    field sc_size_bits 6
+
+    field cap_type 5
+    padding 59
+}
+
+block sched_control_cap(sched_control_core) {
+    field sched_control_core 64
+
+    field cap_type 5
+    padding 59
+}
+
+tagged_union cap cap_type {
+    tag null_cap 0
+    tag untyped_cap 2
+    tag endpoint_cap 4
+    tag notification_cap 6
+    tag reply_cap 8
+    tag CNode_cap 10
+    tag thread_cap 12
+    tag irq_control_cap 14
+    tag irq_handler_cap 16
+    tag zombie_cap 18
+    tag domain_cap 20
+    tag sched_context_cap 22
+    tag sched_control_cap 24
+
+    tag frame_cap 1
+    tag page_table_cap 3
+    tag ASID_control_cap 11
+    tag ASID_pool_cap 13
+}
+
+-- what update_cap_data reads from a cnode cap's badge
+block CapData {
+    field guard 58
+    field guard_size 6
+}
+
+block MDBNode(mdb_next, mdb_revocable, mdb_first_badged, mdb_prev) {
+    padding 25
+    field_high mdb_next 37
+    field mdb_revocable 1
+    field mdb_first_badged 1
+
+    field mdb_prev 64
+}
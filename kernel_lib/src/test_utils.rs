@@ -0,0 +1,22 @@
+//! helpers for the host tests. caps and mdb nodes keep pointers in 39 bit canonical fields, so
+//! objects the tests link through them must sit below 1 << 38, which the x86_64 linux heap is not.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+const LOW_BASE: usize = 0x10_0000_0000;
+const LOW_STRIDE: usize = 1 << 24;
+
+static NEXT_LOW: AtomicUsize = AtomicUsize::new(0);
+
+/// a zeroed `T` mapped at a fresh address below 1 << 38; it is never unmapped
+pub fn alloc_low<T>() -> &'static mut T {
+    let size = core::mem::size_of::<T>();
+    assert!(size <= LOW_STRIDE);
+    let hint = LOW_BASE + NEXT_LOW.fetch_add(1, Ordering::SeqCst) * LOW_STRIDE;
+    let ptr = unsafe {
+        libc::mmap(hint as *mut libc::c_void, size.max(1), libc::PROT_READ | libc::PROT_WRITE,
+                   libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED_NOREPLACE, -1, 0)
+    };
+    assert_eq!(ptr as usize, hint, "can't map test memory at {:#x}", hint);
+    unsafe { &mut *(ptr as *mut T) }
+}
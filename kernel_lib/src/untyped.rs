@@ -0,0 +1,88 @@
+use common::config::{MAX_UNTYPED_BITS, MIN_UNTYPED_BITS, WORD_BITS};
+use common::types::{Pptr, Region};
+use common::utils::bit;
+
+use crate::cspace::{Cap, CapTableEntry, CapTag};
+
+/// split `reg` into the largest aligned power-of-two blocks it holds, calling `provide` with the
+/// base and size bits of each block big enough to become an untyped cap
+pub fn for_each_untyped_in_region(reg: Region, mut provide: impl FnMut(Pptr, usize)) {
+    let mut start = reg.start;
+    while start < reg.end {
+
+        let mut size_bits = WORD_BITS - 1 - ((reg.end - start).leading_zeros() as usize);
+        if size_bits > MAX_UNTYPED_BITS {
+            size_bits = MAX_UNTYPED_BITS;
+        }
+        if reg.start != 0 {
+            let align_bits = start.trailing_zeros() as usize;
+            if size_bits > align_bits {
+                size_bits = align_bits;
+            }
+        }
+
+        if size_bits >= MIN_UNTYPED_BITS {
+            provide(start, size_bits);
+        }
+
+        start += bit(size_bits);
+    }
+}
+
+pub fn set_untyped_cap_as_full(src_cap: Cap, new_cap: Cap, src_slot: &mut CapTableEntry) {
+    if src_cap.get_cap_type() == CapTag::CapUntypedCap && new_cap.get_cap_type() == CapTag::CapUntypedCap &&
+        src_cap.get_cap_pptr() == new_cap.get_cap_pptr() &&
+        src_cap.get_untyped_block_size() == new_cap.get_untyped_block_size() {
+        let ref_cap = &mut src_slot.cap;
+        ref_cap.set_untyped_free_index(max_free_index(src_cap.get_untyped_block_size()));
+    }
+}
+
+
+#[inline]
+pub fn max_free_index(size_bits: usize) -> usize {
+    bit(size_bits - MIN_UNTYPED_BITS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::vec::Vec;
+    use common::config::PPTR_BASE;
+    use crate::cspace::MDBNode;
+
+    fn untyped_blocks(start: usize, end: usize) -> Vec<(usize, usize)> {
+        let mut blocks = Vec::new();
+        for_each_untyped_in_region(Region { start, end }, |pptr, size_bits| blocks.push((pptr, size_bits)));
+        blocks
+    }
+
+    #[test]
+    fn regions_split_into_aligned_blocks() {
+        assert_eq!(untyped_blocks(0x1000, 0x8000), [(0x1000, 12), (0x2000, 13), (0x4000, 14)]);
+        assert_eq!(untyped_blocks(0x1000, 0x1000), []);
+    }
+
+    #[test]
+    fn pieces_below_min_untyped_are_skipped() {
+        assert_eq!(untyped_blocks(0x1008, 0x1020), [(0x1010, 4)]);
+    }
+
+    #[test]
+    fn blocks_are_capped_at_max_untyped() {
+        let start = bit(MAX_UNTYPED_BITS + 1);
+        assert_eq!(untyped_blocks(start, start + bit(MAX_UNTYPED_BITS + 1)),
+                   [(start, MAX_UNTYPED_BITS), (start + bit(MAX_UNTYPED_BITS), MAX_UNTYPED_BITS)]);
+    }
+
+    #[test]
+    fn copying_an_untyped_marks_the_source_full() {
+        let untyped = Cap::new_untyped_cap(0, false, 16, PPTR_BASE);
+        let mut slot = CapTableEntry { cap: untyped, mdb_node: MDBNode::null_mdbnode() };
+        set_untyped_cap_as_full(untyped, Cap::new_untyped_cap(0, false, 15, PPTR_BASE), &mut slot);
+        assert_eq!(slot.cap.get_untyped_free_index(), 0);
+        set_untyped_cap_as_full(untyped, untyped, &mut slot);
+        assert_eq!(slot.cap.get_untyped_free_index(), max_free_index(16));
+        assert_eq!(max_free_index(16), 1 << (16 - MIN_UNTYPED_BITS));
+    }
+}
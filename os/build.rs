@@ -0,0 +1,8 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    println!("cargo:rerun-if-changed=src/structures.bf");
+    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("structures.rs");
+    bitfield_gen::generate(Path::new("src/structures.bf"), &out);
+}
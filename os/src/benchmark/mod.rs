@@ -0,0 +1,154 @@
+//! kernel entry tracepoints, logged into a frame user space hands over with BenchmarkSetLogBuffer,
+//! and the cycles each thread ran for
+
+mod utilisation;
+
+use core::ptr::addr_of_mut;
+use common::benchmark::{TraceEntry, TraceKind, TRACE_ENTRY_BITS};
+use common::config::CPU_NUM;
+use common::message::MessageInfo;
+use common::register::CAP_REGISTER;
+use common::types::{Cptr, Pptr};
+use common::utils::{bit, page_bits_for_size};
+use log::error;
+use riscv::register::{cycle, time};
+use syscall::{SYS_CALL, SYS_SEND, SYS_NB_SEND, SYS_BENCHMARK_RESET_LOG, SYS_BENCHMARK_FINALIZE_LOG,
+    SYS_BENCHMARK_SET_LOG_BUFFER, SYS_BENCHMARK_GET_THREAD_UTILISATION, SYS_BENCHMARK_RESET_THREAD_UTILISATION};
+use crate::cspace::CapTag;
+use crate::scheduler::{get_current_mut_tcb, KS_CUR_THREAD, TCB};
+use crate::smp::hart_id;
+use self::utilisation::{get_thread_utilisation, reset_thread_utilisation, reset_utilisation, utilisation_switch};
+
+struct LogBuffer {
+    pptr: Pptr,
+    // in entries, a power of two
+    capacity: usize,
+    index: usize,
+    logging: bool,
+}
+
+// the log is only touched under the big kernel lock. the entries in flight are per hart, as
+// they start before the lock is taken
+static mut KS_LOG: LogBuffer = LogBuffer { pptr: 0, capacity: 0, index: 0, logging: false };
+static mut KS_ENTRY: [Option<TraceEntry>; CPU_NUM] = [None; CPU_NUM];
+
+fn get_log() -> &'static mut LogBuffer {
+    unsafe {
+        &mut *addr_of_mut!(KS_LOG)
+    }
+}
+
+fn log(entry: TraceEntry) {
+    let log = get_log();
+    if !log.logging {
+        return;
+    }
+    let slot = log.pptr + ((log.index & (log.capacity - 1)) << TRACE_ENTRY_BITS);
+    unsafe {
+        *(slot as *mut TraceEntry) = entry;
+    }
+    log.index += 1;
+}
+
+/// start timing a kernel entry on this hart
+pub fn kernel_entry(kind: TraceKind, number: usize, msg_info: usize) {
+    let label = match number as isize {
+        SYS_CALL | SYS_SEND | SYS_NB_SEND if kind == TraceKind::Syscall => MessageInfo::from_word(msg_info).get_label(),
+        _ => 0,
+    };
+    let entry = TraceEntry {
+        kind: kind as usize,
+        number,
+        label,
+        thread: unsafe { KS_CUR_THREAD[hart_id()] },
+        time: time::read(),
+        start_cycle: cycle::read(),
+        cycles: 0,
+        hart: hart_id(),
+    };
+    unsafe {
+        KS_ENTRY[hart_id()] = Some(entry);
+    }
+}
+
+/// log the entry in flight on this hart, on the way back to user space
+pub fn kernel_exit() {
+    if let Some(mut entry) = unsafe { KS_ENTRY[hart_id()].take() } {
+        entry.cycles = cycle::read().wrapping_sub(entry.start_cycle);
+        log(entry);
+    }
+}
+
+pub fn thread_switch(tcb: &mut TCB) {
+    utilisation_switch(tcb);
+    log(TraceEntry {
+        kind: TraceKind::ThreadSwitch as usize,
+        thread: tcb as *const TCB as usize,
+        time: time::read(),
+        start_cycle: cycle::read(),
+        hart: hart_id(),
+        ..Default::default()
+    });
+}
+
+fn set_log_buffer(frame: Cptr) -> isize {
+    match get_current_mut_tcb().lookup_cap_and_slot(frame) {
+        Some((cap, _)) if cap.get_cap_type() == CapTag::CapFrameCap && !cap.get_frame_is_device() => {
+            let Some(page_bits) = page_bits_for_size(cap.get_frame_size()) else {
+                error!("[set_log_buffer] invalid frame size: {:#x}", frame);
+                return -1;
+            };
+            let log = get_log();
+            log.pptr = cap.get_frame_base_ptr();
+            log.capacity = bit(page_bits - TRACE_ENTRY_BITS);
+            log.index = 0;
+            log.logging = false;
+            0
+        }
+        // before the frame goes away
+        Some((cap, _)) if cap.get_cap_type() == CapTag::CapNullCap => {
+            clear_log_buffer(get_log().pptr);
+            0
+        }
+        _ => {
+            error!("[set_log_buffer] not a frame cap: {:#x}", frame);
+            -1
+        }
+    }
+}
+
+/// stop logging into the frame at `pptr` if the log is there, as the frame is going away
+pub fn clear_log_buffer(pptr: Pptr) {
+    let log = get_log();
+    if log.pptr == pptr {
+        *log = LogBuffer { pptr: 0, capacity: 0, index: 0, logging: false };
+    }
+}
+
+/// the benchmark syscalls, which answer in a0
+pub fn handle_benchmark_syscall(syscall: isize) {
+    let thread = get_current_mut_tcb();
+    let log = get_log();
+    let ret = match syscall {
+        SYS_BENCHMARK_SET_LOG_BUFFER => set_log_buffer(thread.get_register(CAP_REGISTER)) as usize,
+        SYS_BENCHMARK_RESET_LOG => {
+            reset_utilisation();
+            if log.capacity == 0 {
+                error!("[benchmark_reset_log] no log buffer");
+                -1isize as usize
+            } else {
+                log.index = 0;
+                log.logging = true;
+                0
+            }
+        }
+        SYS_BENCHMARK_FINALIZE_LOG => {
+            log.logging = false;
+            log.index
+        }
+        SYS_BENCHMARK_GET_THREAD_UTILISATION => get_thread_utilisation(thread.get_register(CAP_REGISTER)) as usize,
+        SYS_BENCHMARK_RESET_THREAD_UTILISATION => reset_thread_utilisation(thread.get_register(CAP_REGISTER)) as usize,
+        _ => unreachable!(),
+    };
+    thread.set_register(CAP_REGISTER, ret);
+}
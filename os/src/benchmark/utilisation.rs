@@ -0,0 +1,88 @@
+//! cycles each thread, and each hart's idle thread, has run for. cycle counters are per hart, so
+//! every hart only compares readings of its own
+
+use common::benchmark::{BENCHMARK_TCB_UTILISATION, BENCHMARK_IDLE_LOCALCPU_UTILISATION,
+    BENCHMARK_IDLE_TCBCPU_UTILISATION, BENCHMARK_TOTAL_UTILISATION, BENCHMARK_TCB_NUMBER_SCHEDULES};
+use common::config::CPU_NUM;
+use common::types::{Cptr, IpcBuffer, Pptr};
+use common::utils::convert_to_mut_type_ref;
+use log::error;
+use riscv::register::cycle;
+use crate::cspace::CapTag;
+use crate::scheduler::{get_current_mut_tcb, get_idle_thread, KS_CUR_THREAD, TCB};
+use crate::smp::hart_id;
+
+// when the current thread of each hart got it, and when its utilisation was last reset
+static mut KS_ENTER: [usize; CPU_NUM] = [0; CPU_NUM];
+static mut KS_BENCHMARK_START: [usize; CPU_NUM] = [0; CPU_NUM];
+
+/// charge the thread leaving this hart, and count a schedule of `to`
+pub fn utilisation_switch(to: &mut TCB) {
+    let now = cycle::read();
+    unsafe {
+        if KS_CUR_THREAD[hart_id()] != 0 {
+            let from = convert_to_mut_type_ref::<TCB>(KS_CUR_THREAD[hart_id()]);
+            from.tcb_utilisation += now.wrapping_sub(KS_ENTER[hart_id()]);
+        }
+        KS_ENTER[hart_id()] = now;
+    }
+    to.tcb_schedules += 1;
+}
+
+/// start the total and the idle thread's utilisation of this hart over
+pub fn reset_utilisation() {
+    let now = cycle::read();
+    unsafe {
+        KS_BENCHMARK_START[hart_id()] = now;
+        KS_ENTER[hart_id()] = now;
+    }
+    get_idle_thread(hart_id()).tcb_utilisation = 0;
+}
+
+fn lookup_tcb(tcb: Cptr) -> Option<&'static mut TCB> {
+    match get_current_mut_tcb().lookup_cap_and_slot(tcb) {
+        Some((cap, _)) if cap.get_cap_type() == CapTag::CapThreadCap => {
+            Some(convert_to_mut_type_ref::<TCB>(cap.get_tcb_ptr()))
+        }
+        _ => {
+            error!("[benchmark] not a thread cap: {:#x}", tcb);
+            None
+        }
+    }
+}
+
+/// BenchmarkGetThreadUtilisation: fill in the caller's ipc buffer, counting the current thread of
+/// this hart up to now
+pub fn get_thread_utilisation(tcb: Cptr) -> isize {
+    let Some(target) = lookup_tcb(tcb) else {
+        return -1;
+    };
+    let Some(buffer) = get_current_mut_tcb().lookup_ipc_buffer(true) else {
+        error!("[get_thread_utilisation] no ipc buffer");
+        return -1;
+    };
+    let now = cycle::read();
+    let mut utilisation = target.tcb_utilisation;
+    unsafe {
+        if target as *const TCB as Pptr == KS_CUR_THREAD[hart_id()] {
+            utilisation += now.wrapping_sub(KS_ENTER[hart_id()]);
+        }
+    }
+    let mrs = unsafe { &mut (*(buffer as *mut IpcBuffer)).msg };
+    mrs[BENCHMARK_TCB_UTILISATION] = utilisation;
+    mrs[BENCHMARK_IDLE_LOCALCPU_UTILISATION] = get_idle_thread(hart_id()).tcb_utilisation;
+    mrs[BENCHMARK_IDLE_TCBCPU_UTILISATION] = get_idle_thread(target.tcb_affinity).tcb_utilisation;
+    mrs[BENCHMARK_TOTAL_UTILISATION] = now.wrapping_sub(unsafe { KS_BENCHMARK_START[hart_id()] });
+    mrs[BENCHMARK_TCB_NUMBER_SCHEDULES] = target.tcb_schedules;
+    0
+}
+
+/// BenchmarkResetThreadUtilisation
+pub fn reset_thread_utilisation(tcb: Cptr) -> isize {
+    let Some(target) = lookup_tcb(tcb) else {
+        return -1;
+    };
+    target.tcb_utilisation = 0;
+    target.tcb_schedules = 0;
+    0
+}
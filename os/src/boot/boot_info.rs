@@ -1,41 +1,12 @@
-use common::config::{CONFIG_MAX_NUM_BOOT_INFO_UNTYPED_CAPS, PAGE_BITS, SEL4_WORD_BITS};
-use common::types::{NodeId, Vptr, SlotRegion, UntypedDesc};
+use common::config::{PAGE_BITS, SEL4_WORD_BITS};
 use common::utils::{bit, round_up};
 
-#[derive(Copy, Clone)]
-pub struct BootInfoHeader {
-    pub id: usize,
-    pub len: usize,
-}
-
-pub enum BootInfoID {
-    Sel4BootInfoHeaderPadding = 0,
-    Sel4BootInfoHeaderX86Vbe = 1,
-    Sel4BootInfoHeaderX86MbmMap = 2,
-    Sel4BootInfoHeaderX86AcpiRsdp = 3,
-    Sel4BootInfoHeaderX86Framebuffer = 4,
-    Sel4BootInfoHeaderX86TscFreq = 5, /* frequency is in MHz */
-    Sel4BootInfoHeaderFdt = 6, /* device tree */
-    Sel4BootInfoHeaderNum,
-}
+pub use common::boot_info::{BootInfo, BootInfoHeader, BootInfoID, BootInfoPlatform, BootInfoBootModules};
 
-#[derive(Debug)]
-pub struct BootInfo {
-    pub extra_len: usize,
-    pub node_id: NodeId,
-    pub num_nodes: usize,
-    pub num_io_pt_levels: usize,
-    pub ipc_buf_ptr: Vptr,
-    pub empty: SlotRegion,
-    pub shared_frames: SlotRegion,
-    pub user_image_frames: SlotRegion,
-    pub user_image_paging: SlotRegion,
-    pub io_space_caps: SlotRegion,
-    pub extra_bi_pages: SlotRegion,
-    pub init_thread_cnode_size_bits: usize,
-    pub init_thread_domain: usize,
-    pub untyped: SlotRegion,
-    pub untyped_list: [UntypedDesc; CONFIG_MAX_NUM_BOOT_INFO_UNTYPED_CAPS],
+/// a chunk is its header and payload, padded so that the next header stays word aligned
+pub fn extra_bi_chunk_size(payload_size: usize) -> usize {
+    let word_size_bits = core::mem::size_of::<usize>().trailing_zeros() as usize;
+    round_up(core::mem::size_of::<BootInfoHeader>() + payload_size, word_size_bits)
 }
 
 pub fn calculate_extra_bi_size_bits(extra_size: usize) -> usize {
@@ -0,0 +1,102 @@
+use core::cmp::{max, min};
+
+use common::config::{PAGE_BITS, PPTR_BASE_OFFSET};
+use common::types::{Paddr, Region, VirtRegion, Vptr};
+use common::utils::{round_down, round_up};
+use log::debug;
+use xmas_elf::program::Type;
+use xmas_elf::ElfFile;
+
+pub const MAX_NUM_UI_SEGMENTS: usize = 8;
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct UserImageSegment {
+    pub v_reg: VirtRegion,
+    pub writable: bool,
+    pub executable: bool,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct UserImage {
+    pub p_reg: Region,
+    pub v_reg: VirtRegion,
+    pub pv_offset: isize,
+    pub entry: Vptr,
+    pub segments: [UserImageSegment; MAX_NUM_UI_SEGMENTS],
+    pub num_segments: usize,
+}
+
+impl UserImage {
+    /// the rights of a page are the union of the rights of the segments that cover it
+    pub fn page_rights(&self, vptr: Vptr) -> Option<(bool, bool)> {
+        let mut rights = None;
+        for segment in &self.segments[..self.num_segments] {
+            if vptr >= segment.v_reg.start && vptr < segment.v_reg.end {
+                let (writable, executable) = rights.unwrap_or((false, false));
+                rights = Some((writable || segment.writable, executable || segment.executable));
+            }
+        }
+        rights
+    }
+}
+
+/// parse the elf file at `elf_paddr` and copy its PT_LOAD segments to the frames right after it,
+/// zeroing whatever the file does not cover.
+pub fn load_user_image(elf_paddr: Paddr, limit: Paddr) -> UserImage {
+    let elf_data = unsafe {
+        core::slice::from_raw_parts((elf_paddr + PPTR_BASE_OFFSET) as *const u8, limit - elf_paddr)
+    };
+    let elf = ElfFile::new(elf_data).expect("[kernel] invalid root server elf");
+    let header = &elf.header.pt2;
+
+    let mut image = UserImage::default();
+    image.entry = header.entry_point() as Vptr;
+    let mut file_end = max(header.ph_offset() as usize + header.ph_count() as usize * header.ph_entry_size() as usize,
+                           header.sh_offset() as usize + header.sh_count() as usize * header.sh_entry_size() as usize);
+    let mut v_start = usize::MAX;
+    let mut v_end = 0;
+    for ph in elf.program_iter() {
+        if ph.get_type() != Ok(Type::Load) || ph.mem_size() == 0 {
+            continue;
+        }
+        assert!(image.num_segments < MAX_NUM_UI_SEGMENTS, "[kernel] too many segments in root server elf");
+        let start = ph.virtual_addr() as usize;
+        let end = start + ph.mem_size() as usize;
+        image.segments[image.num_segments] = UserImageSegment {
+            v_reg: VirtRegion { start: round_down(start, PAGE_BITS), end: round_up(end, PAGE_BITS) },
+            writable: ph.flags().is_write(),
+            executable: ph.flags().is_execute(),
+        };
+        image.num_segments += 1;
+        v_start = min(v_start, round_down(start, PAGE_BITS));
+        v_end = max(v_end, round_up(end, PAGE_BITS));
+        file_end = max(file_end, ph.offset() as usize + ph.file_size() as usize);
+    }
+    assert!(image.num_segments > 0, "[kernel] no loadable segment in root server elf");
+
+    let p_start = round_up(elf_paddr + file_end, PAGE_BITS);
+    let p_end = p_start + v_end - v_start;
+    assert!(p_end <= limit, "[kernel] no room to load the root server");
+    image.v_reg = VirtRegion { start: v_start, end: v_end };
+    image.pv_offset = p_start as isize - v_start as isize;
+    image.p_reg = Region { start: p_start + PPTR_BASE_OFFSET, end: p_end + PPTR_BASE_OFFSET };
+
+    (image.p_reg.start..image.p_reg.end).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+    for ph in elf.program_iter() {
+        if ph.get_type() != Ok(Type::Load) || ph.file_size() == 0 {
+            continue;
+        }
+        let src = &elf_data[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize];
+        let dst = (ph.virtual_addr() as isize + image.pv_offset) as usize + PPTR_BASE_OFFSET;
+        unsafe {
+            core::ptr::copy_nonoverlapping(src.as_ptr(), dst as *mut u8, src.len());
+        }
+    }
+
+    debug!("root server elf: {:#x} ... {:#x}, entry: {:#x}", elf_paddr, elf_paddr + file_end, image.entry);
+    for segment in &image.segments[..image.num_segments] {
+        debug!("segment: {:#x} ... {:#x}, w: {}, x: {}", segment.v_reg.start, segment.v_reg.end,
+               segment.writable, segment.executable);
+    }
+    image
+}
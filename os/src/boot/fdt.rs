@@ -0,0 +1,184 @@
+use common::config::{MAX_NUM_AVAIL_P_REGS, MAX_NUM_FDT_RESV_REGS, PPTR_BASE_OFFSET};
+use common::fdt::{Fdt, FdtToken, fdt_total_size, read_cells, reg_iter, FDT_HEADER_SIZE, FDT_MAX_DEPTH,
+                  FDT_DEFAULT_ADDRESS_CELLS, FDT_DEFAULT_SIZE_CELLS};
+use common::types::{Paddr, PhyRegion};
+use core::cmp::{max, min};
+use log::{debug, error};
+
+const PLIC_COMPATIBLE: [&str; 2] = ["riscv,plic0", "sifive,plic-1.0.0"];
+const CLINT_COMPATIBLE: [&str; 2] = ["riscv,clint0", "sifive,clint0"];
+const UART_COMPATIBLE: [&str; 1] = ["ns16550a"];
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct PlatformInfo {
+    pub fdt: PhyRegion,
+    pub mem: [PhyRegion; MAX_NUM_AVAIL_P_REGS],
+    pub num_mem: usize,
+    pub reserved: [PhyRegion; MAX_NUM_FDT_RESV_REGS],
+    pub num_reserved: usize,
+    // linux,initrd-start and linux,initrd-end of /chosen, empty without them
+    pub initrd: PhyRegion,
+    pub plic: PhyRegion,
+    pub clint: PhyRegion,
+    pub uart: PhyRegion,
+    pub timebase_freq: usize,
+}
+
+impl PlatformInfo {
+    fn add_mem(&mut self, reg: PhyRegion) {
+        if reg.start >= reg.end {
+            return;
+        }
+        if self.num_mem == MAX_NUM_AVAIL_P_REGS {
+            error!("fdt: too many memory banks, dropping {:#x} ... {:#x}", reg.start, reg.end);
+            return;
+        }
+        let mut i = self.num_mem;
+        while i > 0 && self.mem[i - 1].start > reg.start {
+            self.mem[i] = self.mem[i - 1];
+            i -= 1;
+        }
+        self.mem[i] = reg;
+        self.num_mem += 1;
+    }
+
+    fn add_reserved(&mut self, reg: PhyRegion) {
+        if reg.start >= reg.end {
+            return;
+        }
+        if self.num_reserved == MAX_NUM_FDT_RESV_REGS {
+            // still kept away from the allocator, along with whatever lies in between
+            let last = &mut self.reserved[self.num_reserved - 1];
+            error!("fdt: too many reserved regions, merging {:#x} ... {:#x} into {:#x} ... {:#x}",
+                   reg.start, reg.end, last.start, last.end);
+            last.start = min(last.start, reg.start);
+            last.end = max(last.end, reg.end);
+            return;
+        }
+        self.reserved[self.num_reserved] = reg;
+        self.num_reserved += 1;
+    }
+}
+
+#[derive(Clone, Copy)]
+struct FdtNode<'a> {
+    address_cells: usize,
+    size_cells: usize,
+    reg: Option<&'a [u8]>,
+    is_memory: bool,
+    is_reserved_memory: bool,
+    in_reserved_memory: bool,
+    is_chosen: bool,
+}
+
+impl Default for FdtNode<'_> {
+    fn default() -> Self {
+        FdtNode {
+            address_cells: FDT_DEFAULT_ADDRESS_CELLS,
+            size_cells: FDT_DEFAULT_SIZE_CELLS,
+            reg: None,
+            is_memory: false,
+            is_reserved_memory: false,
+            in_reserved_memory: false,
+            is_chosen: false,
+        }
+    }
+}
+
+fn finalise_node(info: &mut PlatformInfo, node: &FdtNode, parent: &FdtNode) {
+    if let Some(reg) = node.reg {
+        if node.is_memory {
+            reg_iter(reg, parent.address_cells, parent.size_cells).for_each(|r| info.add_mem(r));
+        }
+        if node.in_reserved_memory {
+            reg_iter(reg, parent.address_cells, parent.size_cells).for_each(|r| info.add_reserved(r));
+        }
+    }
+}
+
+pub fn parse_fdt(dtb_paddr: Paddr) -> Option<PlatformInfo> {
+    let base = dtb_paddr + PPTR_BASE_OFFSET;
+    let header = unsafe { core::slice::from_raw_parts(base as *const u8, FDT_HEADER_SIZE) };
+    let total_size = match fdt_total_size(header) {
+        Some(size) if dtb_paddr != 0 => size,
+        _ => {
+            error!("invalid fdt at {:#x}", dtb_paddr);
+            return None;
+        }
+    };
+    let fdt = Fdt::new(unsafe { core::slice::from_raw_parts(base as *const u8, total_size) })?;
+
+    let mut info = PlatformInfo::default();
+    info.fdt = PhyRegion { start: dtb_paddr, end: dtb_paddr + total_size };
+    fdt.mem_reserve_iter().for_each(|r| info.add_reserved(r));
+
+    let mut stack = [FdtNode::default(); FDT_MAX_DEPTH];
+    let mut depth = 0;
+    for token in fdt.tokens() {
+        match token {
+            FdtToken::BeginNode(name) => {
+                if depth + 1 >= FDT_MAX_DEPTH {
+                    error!("fdt: nodes nested too deeply");
+                    return None;
+                }
+                let parent = if depth == 0 { FdtNode::default() } else { stack[depth - 1] };
+                let node_name = name.split('@').next().unwrap_or(name);
+                stack[depth] = FdtNode {
+                    is_reserved_memory: depth == 1 && node_name == "reserved-memory",
+                    in_reserved_memory: parent.is_reserved_memory,
+                    is_chosen: depth == 1 && node_name == "chosen",
+                    ..Default::default()
+                };
+                depth += 1;
+            }
+            FdtToken::EndNode => {
+                if depth == 0 {
+                    error!("fdt: unbalanced end node");
+                    return None;
+                }
+                depth -= 1;
+                let parent = if depth == 0 { FdtNode::default() } else { stack[depth - 1] };
+                let node = stack[depth];
+                finalise_node(&mut info, &node, &parent);
+            }
+            FdtToken::Prop(name, value) => {
+                if depth == 0 {
+                    continue;
+                }
+                let node = &mut stack[depth - 1];
+                match name {
+                    "#address-cells" => node.address_cells = read_cells(value, 1),
+                    "#size-cells" => node.size_cells = read_cells(value, 1),
+                    "reg" => node.reg = Some(value),
+                    "device_type" => node.is_memory = value == b"memory\0",
+                    "linux,initrd-start" if node.is_chosen => info.initrd.start = read_cells(value, value.len() / 4),
+                    "linux,initrd-end" if node.is_chosen => info.initrd.end = read_cells(value, value.len() / 4),
+                    "timebase-frequency" => {
+                        if info.timebase_freq == 0 {
+                            info.timebase_freq = read_cells(value, value.len() / 4);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    info.plic = fdt.find_compatible(&PLIC_COMPATIBLE).unwrap_or_default();
+    info.clint = fdt.find_compatible(&CLINT_COMPATIBLE).unwrap_or_default();
+    info.uart = fdt.find_compatible(&UART_COMPATIBLE).unwrap_or_default();
+
+    debug!("fdt: {:#x} ... {:#x}", info.fdt.start, info.fdt.end);
+    for i in 0..info.num_mem {
+        debug!("fdt memory_{}: {:#x} ... {:#x}", i, info.mem[i].start, info.mem[i].end);
+    }
+    for i in 0..info.num_reserved {
+        debug!("fdt reserved_{}: {:#x} ... {:#x}", i, info.reserved[i].start, info.reserved[i].end);
+    }
+    if info.initrd.start < info.initrd.end {
+        debug!("fdt initrd: {:#x} ... {:#x}", info.initrd.start, info.initrd.end);
+    }
+    debug!("fdt plic: {:#x}, clint: {:#x}, uart: {:#x}, timebase: {}",
+           info.plic.start, info.clint.start, info.uart.start, info.timebase_freq);
+    Some(info)
+}
@@ -1,17 +1,22 @@
 mod init_freemem;
-mod ndks_boot;
 mod boot_info;
+mod fdt;
+mod elf;
 
-use common::config::{NUM_RESERVED_REGIONS, MAX_NUM_FREEMEM_REG, AVAIL_PHY_MEM_START, AVAIL_PHY_MEM_END, AVAIL_MEM_DEVICE, CONFIG_ROOT_CNODE_SIZE_BITS};
-use common::types::{Region, PhyRegion, VirtRegion, APPtr, ASIDSizeConstants, SlotRegion, Vptr};
+use common::config::{NUM_RESERVED_REGIONS, MAX_NUM_FREEMEM_REG, CONFIG_ROOT_CNODE_SIZE_BITS};
+use common::types::{Region, PhyRegion, VirtRegion, APPtr, ASIDSizeConstants, SlotRegion, Vptr, Paddr};
+use core::cmp::min;
 use lazy_static::*;
-use log::debug;
+use log::{debug, error};
 use spin::Mutex;
-use ndks_boot::NdksBoot;
-use common::config::{BI_FRAME_SIZE_BITS, PAGE_BITS, UI_P_REG_END, UI_P_REG_START, UI_PV_OFFSET, USER_TOP};
-use common::utils::bit;
-
-pub use boot_info::{calculate_extra_bi_size_bits, BootInfo, BootInfoID, BootInfoHeader};
+use kernel_lib::boot::NdksBoot;
+use common::config::{BI_FRAME_SIZE_BITS, BOOT_MODULES_P_START, PAGE_BITS, PPTR_BASE_OFFSET, UI_ELF_P_START, USER_TOP};
+use common::cpio;
+use common::utils::{bit, round_up};
+
+pub use fdt::PlatformInfo;
+pub use elf::UserImage;
+pub use boot_info::{calculate_extra_bi_size_bits, extra_bi_chunk_size, BootInfo, BootInfoID, BootInfoHeader, BootInfoBootModules, BootInfoPlatform};
 use crate::cspace::Cap;
 use crate::untyped::create_untyped_for_region;
 
@@ -20,7 +25,7 @@ lazy_static!{
 
     pub static ref NDKS_BOOT: Mutex<NdksBoot> = Mutex::new(NdksBoot::default());
 
-    static ref AVAIL_P_REGS: Mutex<[PhyRegion; AVAIL_MEM_DEVICE]> = Mutex::new([PhyRegion{start: AVAIL_PHY_MEM_START, end: AVAIL_PHY_MEM_END}; AVAIL_MEM_DEVICE]);
+    pub static ref PLATFORM_INFO: Mutex<PlatformInfo> = Mutex::new(PlatformInfo::default());
 
     static ref AVAIL_REG: Mutex<[Region; MAX_NUM_FREEMEM_REG]> = Mutex::new([Region::default(); MAX_NUM_FREEMEM_REG]);
 
@@ -28,15 +33,78 @@ lazy_static!{
 }
 
 
-fn boot_mem_init(ui_reg: Region) {
-    init_freemem::init(ui_reg);
+fn boot_mem_init(ui_reg: Region, modules_reg: Region) {
+    init_freemem::init(ui_reg, modules_reg);
 }
 
 fn root_server_init(it_v_reg: VirtRegion, extra_bi_size_bits: usize, ipc_buf_vptr: Vptr, extra_bi_size: usize,
-                    extra_bi_offset: usize, bi_frame_vptr: Vptr, extra_bi_frame_vptr: Vptr, ui_reg: Region,
-                    ui_vp_offset: isize) -> Cap {
+                    extra_bi_offset: usize, bi_frame_vptr: Vptr, extra_bi_frame_vptr: Vptr, ui_image: &UserImage,
+                    modules_reg: Region, modules_vptr: Vptr) -> Cap {
     crate::root_server::init(it_v_reg, extra_bi_size_bits, ipc_buf_vptr, extra_bi_size, extra_bi_offset,
-                             bi_frame_vptr, extra_bi_frame_vptr, ui_reg, ui_vp_offset)
+                             bi_frame_vptr, extra_bi_frame_vptr, ui_image, modules_reg, modules_vptr)
+}
+
+/// the end of the free ram the boot loader may have put an image at `paddr` in, None if `paddr` is
+/// not in a memory bank or lies in the kernel or a region the device tree keeps
+fn load_limit(platform_info: &PlatformInfo, paddr: Paddr) -> Option<Paddr> {
+    let mut limit = 0;
+    for bank in &platform_info.mem[..platform_info.num_mem] {
+        if bank.start <= paddr && paddr < bank.end {
+            limit = bank.end;
+        }
+    }
+    if limit == 0 {
+        return None;
+    }
+    let initrd = if platform_info.initrd.start < platform_info.initrd.end { Some(&platform_info.initrd) } else { None };
+    let reserved = platform_info.reserved[..platform_info.num_reserved].iter()
+        .chain(core::iter::once(&platform_info.fdt))
+        .chain(initrd);
+    for reg in reserved.chain(core::iter::once(&init_freemem::kernel_p_reg())) {
+        if reg.start <= paddr && paddr < reg.end {
+            return None;
+        }
+        if reg.start > paddr && reg.start < limit {
+            limit = reg.start;
+        }
+    }
+    Some(limit)
+}
+
+/// the boot module archive: the initrd of /chosen if the boot loader reported one, otherwise
+/// whatever it may have put at BOOT_MODULES_P_START. empty if there is none
+fn find_boot_modules(platform_info: &PlatformInfo) -> Region {
+    let initrd = platform_info.initrd;
+    let (start, limit) = if initrd.start < initrd.end {
+        let in_ram = platform_info.mem[..platform_info.num_mem].iter()
+            .any(|bank| bank.start <= initrd.start && initrd.end <= bank.end);
+        let kernel = init_freemem::kernel_p_reg();
+        if !in_ram || (initrd.start < kernel.end && kernel.start < initrd.end) {
+            error!("[kernel] initrd {:#x} ... {:#x} is not in free ram", initrd.start, initrd.end);
+            return Region::default();
+        }
+        (initrd.start, initrd.end)
+    } else {
+        match load_limit(platform_info, BOOT_MODULES_P_START) {
+            Some(limit) => (BOOT_MODULES_P_START, limit),
+            None => return Region::default(),
+        }
+    };
+    let archive = unsafe {
+        core::slice::from_raw_parts((start + PPTR_BASE_OFFSET) as *const u8, limit - start)
+    };
+    match cpio::archive_len(archive) {
+        Some(len) => {
+            for entry in cpio::CpioIter::new(archive) {
+                debug!("boot module: {}, size: {:#x}", entry.name, entry.data.len());
+            }
+            Region::paddr_to_pptr_reg(PhyRegion {
+                start,
+                end: round_up(start + len, PAGE_BITS),
+            })
+        }
+        None => Region::default(),
+    }
 }
 
 fn create_untypeds(root_cnode_cap: Cap) {
@@ -84,34 +152,52 @@ fn boot_info_finalise() {
     };
 }
 
-pub fn init() {
-    let ui_reg = Region::paddr_to_pptr_reg(PhyRegion {
-        start: UI_P_REG_START,
-        end: UI_P_REG_END,
-    });
-
-    let ui_v_reg = VirtRegion {
-        start: UI_P_REG_START - UI_PV_OFFSET,
-        end: UI_P_REG_END - UI_PV_OFFSET,
+pub fn init(dtb_paddr: Paddr) {
+    let platform_info = match fdt::parse_fdt(dtb_paddr) {
+        Some(info) => info,
+        None => panic!("[kernel] no usable device tree at {:#x}", dtb_paddr),
     };
+    assert!(platform_info.num_mem > 0, "[kernel] no memory bank in device tree");
+    assert!(platform_info.timebase_freq != 0, "[kernel] no timebase-frequency in device tree");
+    crate::interrupt::set_timebase_freq(platform_info.timebase_freq);
+    *PLATFORM_INFO.lock() = platform_info;
+
+    let modules_reg = find_boot_modules(&platform_info);
+    let ui_limit = load_limit(&platform_info, UI_ELF_P_START).expect("[kernel] root server elf is not in free ram");
+    // the elf ends where the boot modules start, if they come after it
+    let modules_p_start = if modules_reg.start != modules_reg.end {
+        PhyRegion::pptr_to_paddr_reg(modules_reg).start
+    } else {
+        BOOT_MODULES_P_START
+    };
+    let ui_limit = if modules_p_start > UI_ELF_P_START { min(ui_limit, modules_p_start) } else { ui_limit };
+    let ui_image = elf::load_user_image(UI_ELF_P_START, ui_limit);
+    let ui_v_reg = ui_image.v_reg;
 
     let ipc_buf_vptr = ui_v_reg.end;
     let bi_frame_vptr = ipc_buf_vptr + bit(PAGE_BITS);
     let extra_bi_frame_vptr = bi_frame_vptr + bit(BI_FRAME_SIZE_BITS);
-    let extra_bi_size = core::mem::size_of::<BootInfoHeader>();
+    // padding, device tree, platform info and boot modules
+    let mut extra_bi_size = core::mem::size_of::<BootInfoHeader>();
     let extra_bi_offset: usize = 0;
+    extra_bi_size += extra_bi_chunk_size(platform_info.fdt.end - platform_info.fdt.start);
+    extra_bi_size += extra_bi_chunk_size(core::mem::size_of::<BootInfoPlatform>());
+    if modules_reg.start != modules_reg.end {
+        extra_bi_size += extra_bi_chunk_size(core::mem::size_of::<BootInfoBootModules>());
+    }
 
     let extra_bi_size_bits = calculate_extra_bi_size_bits(extra_bi_size);
+    let modules_vptr = extra_bi_frame_vptr + bit(extra_bi_size_bits);
 
     let it_v_reg = VirtRegion {
         start: ui_v_reg.start,
-        end: extra_bi_frame_vptr + bit(extra_bi_size_bits),
+        end: modules_vptr + (modules_reg.end - modules_reg.start),
     };
     assert!(it_v_reg.end < USER_TOP);
 
-    boot_mem_init(ui_reg);
+    boot_mem_init(ui_image.p_reg, modules_reg);
     let root_cnode_cap = root_server_init(it_v_reg, extra_bi_size_bits, ipc_buf_vptr, extra_bi_size, extra_bi_offset,
-                     bi_frame_vptr, extra_bi_frame_vptr, ui_reg, UI_PV_OFFSET as isize);
+                     bi_frame_vptr, extra_bi_frame_vptr, &ui_image, modules_reg, modules_vptr);
     create_untypeds(root_cnode_cap);
     
     boot_info_finalise();
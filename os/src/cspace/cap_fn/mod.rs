@@ -1,15 +0,0 @@
-mod cnode;
-mod asid_control;
-mod asid_pool;
-mod domain;
-mod endpoint;
-mod frame;
-mod irq_control;
-mod irq_handler;
-mod notification;
-mod null;
-mod page_table;
-mod reply;
-mod thread;
-mod untyped;
-mod zombie;
\ No newline at end of file
@@ -1,5 +0,0 @@
-use super::super::cap::{Cap};
-
-impl Cap {
-    
-}
\ No newline at end of file
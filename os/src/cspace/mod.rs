@@ -1,23 +1,21 @@
 use log::{debug, error};
-use common::config::{CONFIG_ROOT_CNODE_SIZE_BITS, IT_ASID, SEL4_WORD_BITS, WORD_BITS, WORD_RADIX};
+#[cfg(feature = "check_invariants")]
+use spin::Mutex;
+#[cfg(feature = "check_invariants")]
+use kernel_lib::cspace::CheckTables;
+use common::config::{CONFIG_ROOT_CNODE_SIZE_BITS, IT_ASID, SEL4_WORD_BITS, WORD_BITS};
 use common::types::CNodeSlot::{SeL4CapInitThreadCNode, SeL4CapDomain, SeL4CapInitThreadVspace, SeL4CapBootInfoFrame, SeL4CapInitThreadASIDPool, SeL4CapASIDControl};
 use crate::root_server::ROOT_SERVER;
 use common::types::{ASIDSizeConstants, SlotPos, Pptr, Vptr, CNodeSlot, Cptr};
 
-mod cnode;
 mod cap;
-mod cap_data;
-mod cap_fn;
-mod mdb;
-pub use cap::{Cap, CapTag, CapTableEntry};
-pub use cnode::{CNode, TCBCNodeIndex};
+pub use cap::{cte_delete, cte_revoke};
+pub use kernel_lib::cspace::{Cap, CapTag, CapTableEntry, CNode, TCBCNodeIndex, MDBNode, cte_insert, insert_new_cap,
+    resolve_address_bits};
 use crate::boot::NDKS_BOOT;
-use crate::cspace::cap::is_cap_revocable;
 use crate::cspace::CapTag::CapCNodeCap;
-use crate::untyped::set_untyped_cap_as_full;
 use crate::mm::VmRights;
-use common::utils::{bit, mask};
-pub use mdb::MDBNode;
+use common::utils::bit;
 
 pub fn create_root_cnode() -> Cap {
     let cap = Cap::new_cnode_cap(CONFIG_ROOT_CNODE_SIZE_BITS,
@@ -59,12 +57,12 @@ pub fn create_init_thread_cap(cnode_cap: Cap, tcb_ptr: Pptr) -> Cap {
 }
 
 pub fn create_bi_frame_cap(cnode_cap: Cap, vptr: Vptr, boot_info_ptr: Pptr) -> Cap {
-    create_frame_cap(cnode_cap, vptr, boot_info_ptr, SeL4CapBootInfoFrame as usize, IT_ASID)
+    create_frame_cap(cnode_cap, vptr, boot_info_ptr, SeL4CapBootInfoFrame as usize, IT_ASID, VmRights::VMReadWrite)
 }
 
-pub fn create_frame_cap(cnode_cap: Cap, vptr: Vptr, pptr: Pptr, index: usize, asid: usize) -> Cap {
+pub fn create_frame_cap(cnode_cap: Cap, vptr: Vptr, pptr: Pptr, index: usize, asid: usize, vm_rights: VmRights) -> Cap {
     let cap = Cap::new_frame_cap(asid, pptr,
-                                 0, VmRights::VMReadWrite as usize, false, vptr);
+                                 0, vm_rights as usize, false, vptr);
     write_slot(cnode_cap.get_cap_pptr(), index, cap);
     cap
 }
@@ -87,6 +85,22 @@ pub fn create_untyped_cap(cnode_cap: Cap, slot: SlotPos, free_index: usize, is_d
     cap
 }
 
+#[cfg(feature = "check_invariants")]
+static CHECK_TABLES: Mutex<CheckTables> = Mutex::new(CheckTables::new());
+
+/// panic if the cspace reachable from the root server's cnode breaks an mdb invariant, or is too
+/// big for the checker to follow
+#[cfg(feature = "check_invariants")]
+pub fn check_invariants() {
+    let root = Cap::new_cnode_cap(CONFIG_ROOT_CNODE_SIZE_BITS,
+                                  SEL4_WORD_BITS - CONFIG_ROOT_CNODE_SIZE_BITS,
+                                  0,
+                                  ROOT_SERVER.lock().cnode as usize);
+    if let Err(violation) = kernel_lib::cspace::check_cspace(root, &mut CHECK_TABLES.lock()) {
+        panic!("[check_invariants] {:x?}", violation);
+    }
+}
+
 pub fn write_slot(cnode_ptr: Pptr, index: usize, cap: Cap) {
     let cnode = unsafe {
         &mut *(cnode_ptr as *mut CNode)
@@ -98,11 +112,19 @@ pub fn derive_cap(_slot: &mut CapTableEntry, cap: Cap) -> (bool, Cap) {
     return match cap.get_cap_type() {
         CapTag::CapFrameCap => {
             let mut new_cap = cap;
-            new_cap.set_frame_mapped_address(0);
+            new_cap.set_frame_mapped_addr(0);
             (true, new_cap)
         }
 
-        CapTag::CapUntypedCap | CapTag::CapZombieCap | CapTag::CapIrqControlCap | CapTag::CapReplyCap => {
+        CapTag::CapUntypedCap | CapTag::CapZombieCap | CapTag::CapIrqControlCap => {
+            error!("[derive_cap] unsupported: {:?}", cap.get_cap_type());
+            (false, Cap::new_null_cap())
+        }
+
+        // the caller caps of a thread stay where the kernel put them, while reply objects are
+        // copied like any other object
+        #[cfg(not(feature = "mcs"))]
+        CapTag::CapReplyCap => {
             error!("[derive_cap] unsupported: {:?}", cap.get_cap_type());
             (false, Cap::new_null_cap())
         }
@@ -114,68 +136,6 @@ pub fn derive_cap(_slot: &mut CapTableEntry, cap: Cap) -> (bool, Cap) {
     }
 }
 
-pub fn cte_insert(new_cap: Cap, src_slot: &mut CapTableEntry, dest_slot: &mut CapTableEntry) {
-    let mut new_mdb = src_slot.mdb_node;
-    let src_cap = src_slot.cap;
-
-    let new_cap_is_revocable = is_cap_revocable(new_cap, src_cap);
-    new_mdb.set_mdb_prev(src_cap.get_cap_pptr() as *const CapTableEntry as Pptr);
-    new_mdb.set_mdb_revocable(new_cap_is_revocable);
-    new_mdb.set_mdb_first_badged(new_cap_is_revocable);
-
-    set_untyped_cap_as_full(src_cap, new_cap, src_slot);
-
-    dest_slot.cap = new_cap;
-    dest_slot.mdb_node = new_mdb;
-    let ref_src_mdb = &mut src_slot.mdb_node;
-    ref_src_mdb.set_mdb_next(dest_slot as *const CapTableEntry as Pptr);
-
-    if new_mdb.get_mdb_next() != 0 {
-        let prev_of_new_mdb = unsafe {
-            &mut (&mut *(new_mdb.get_mdb_next() as *mut CapTableEntry)).mdb_node
-        };
-        prev_of_new_mdb.set_mdb_prev(dest_slot as *const CapTableEntry as Pptr);
-    }
-}
-
-pub fn resolve_address_bits(node_cap: Cap, cap_ptr: usize, n_bits: usize) -> Option<*mut CapTableEntry> {
-    if node_cap.get_cap_type() != CapCNodeCap {
-        error!("cptr: {}, type: {:?}", cap_ptr, node_cap.get_cap_type());
-        return None;
-    }
-    let mut local_n_bits = n_bits;
-    let mut local_node_cap = node_cap;
-    loop {
-        let radix_bits = local_node_cap.get_cnode_radix();
-        let guard_bits = local_node_cap.get_cnode_guard_size();
-        let level_bits = radix_bits + guard_bits;
-        assert_ne!(level_bits, 0);
-
-        let cap_guard = local_node_cap.get_cnode_guard();
-        let guard = (cap_ptr >> ((local_n_bits - guard_bits) & mask(WORD_RADIX))) & mask(guard_bits);
-        if guard_bits > local_n_bits || guard != cap_guard {
-            return None;
-        }
-
-        if level_bits > local_n_bits {
-            return None;
-        }
-        let offset = (cap_ptr >> (local_n_bits - level_bits)) & mask(radix_bits);
-        let slot = unsafe {
-            &mut (&mut *(local_node_cap.get_cap_pptr() as *mut CNode))[offset]
-        };
-        if local_n_bits == level_bits {
-            return Some(slot as *mut CapTableEntry);
-        }
-        local_n_bits -= level_bits;
-        local_node_cap =  slot.cap;
-
-        if local_node_cap.get_cap_type() != CapCNodeCap {
-            return Some(slot as *mut CapTableEntry);
-        }
-    }
-}
-
 pub fn lookup_slot_for_cnode_op(_is_source: bool, root: Cap, cap_ptr: Cptr, depth: usize) -> Option<*mut CapTableEntry> {
     if root.get_cap_type() != CapCNodeCap || depth < 1 || depth > WORD_BITS {
         return None;
@@ -187,15 +147,3 @@ pub fn lookup_slot_for_cnode_op(_is_source: bool, root: Cap, cap_ptr: Cptr, dept
 pub fn lookup_target_slot(root: Cap, cap_ptr: Cptr, depth: usize) -> Option<*mut CapTableEntry> {
     lookup_slot_for_cnode_op(false, root, cap_ptr, depth)
 }
-
-pub fn insert_new_cap(parent: &mut CapTableEntry, slot: &mut CapTableEntry, cap: Cap) {
-    let next = parent.mdb_node.get_mdb_next();
-    slot.cap = cap;
-    slot.mdb_node = MDBNode::new(next, true, true, parent as *mut CapTableEntry as usize);
-    if next != 0 {
-        unsafe {
-            (&mut *(next as *mut CapTableEntry)).mdb_node.set_mdb_prev(slot as *mut CapTableEntry as usize);
-        }
-    }
-    parent.mdb_node.set_mdb_next(slot as *mut CapTableEntry as usize);
-}
@@ -2,6 +2,14 @@
     .globl _start
 _start:
     la sp, boot_stack_top
+    la s0, rust_main
+    j set_boot_pt
+
+    # started through SBI HSM: a0 = hartid, a1 = physical kernel stack top
+    .globl _secondary_start
+_secondary_start:
+    mv sp, a1
+    la s0, rust_secondary_main
 
 set_boot_pt:
     la  t0, boot_page_table_sv39
@@ -10,7 +18,7 @@ set_boot_pt:
     or   t0, t0, t1
     csrw satp, t0
     sfence.vma
-    la   t0, rust_main
+    mv   t0, s0
     li   t1, 0xffffffff00000000
     add  t0, t0, t1
     add  sp, sp, t1
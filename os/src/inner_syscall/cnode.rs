@@ -0,0 +1,100 @@
+use common::{types::{Pptr, CapRights}, message::InvocationLabel, utils::convert_to_mut_type_ref};
+use crate::{cspace::{CapTableEntry, Cap, CapTag, derive_cap, cte_insert, cte_delete, cte_revoke, lookup_target_slot,
+    lookup_slot_for_cnode_op},
+    scheduler::{ThreadStateEnum::ThreadStateRestart, set_thread_state}};
+use log::error;
+
+use super::{CUR_EXTRA_CAPS, get_syscall_arg};
+
+pub fn decode_cnode_invocation(inv_label: usize, length: usize, cap: Cap, buffer: Pptr) {
+    if length < 2 {
+        error!("CNode operation: Truncated message.");
+        return;
+    }
+
+    let index = get_syscall_arg(0, buffer);
+    let depth = get_syscall_arg(1, buffer);
+    let dest_slot = match lookup_target_slot(cap, index, depth) {
+        Some(slot) => convert_to_mut_type_ref::<CapTableEntry>(slot as usize),
+        _ => {
+            error!("CNode operation: Target slot invalid.");
+            return;
+        }
+    };
+
+    let is_mint = match InvocationLabel::from_usize(inv_label) {
+        InvocationLabel::CNodeCopy => false,
+        InvocationLabel::CNodeMint => true,
+        InvocationLabel::CNodeRevoke => {
+            set_thread_state(ThreadStateRestart);
+            invoke_cnode_revoke(dest_slot);
+            return;
+        }
+        InvocationLabel::CNodeDelete => {
+            set_thread_state(ThreadStateRestart);
+            invoke_cnode_delete(dest_slot);
+            return;
+        }
+        _ => {
+            error!("CNodeCap: Illegal Operation attempted.");
+            return;
+        }
+    };
+
+    if length < 5 || unsafe { CUR_EXTRA_CAPS[0] == 0 } {
+        error!("CNode Copy/Mint: Truncated message.");
+        return;
+    }
+
+    if !dest_slot.ensure_empty_slot() {
+        error!("CNode Copy/Mint: Destination not empty.");
+        return;
+    }
+
+    let src_index = get_syscall_arg(2, buffer);
+    let src_depth = get_syscall_arg(3, buffer);
+    let src_root = unsafe { convert_to_mut_type_ref::<CapTableEntry>(CUR_EXTRA_CAPS[0]).cap };
+    let src_slot = match lookup_slot_for_cnode_op(true, src_root, src_index, src_depth) {
+        Some(slot) => convert_to_mut_type_ref::<CapTableEntry>(slot as usize),
+        _ => {
+            error!("CNode Copy/Mint: Invalid source slot.");
+            return;
+        }
+    };
+
+    if src_slot.cap.get_cap_type() == CapTag::CapNullCap {
+        error!("CNode Copy/Mint: Source slot invalid or empty.");
+        return;
+    }
+
+    let rights = CapRights::from_word(get_syscall_arg(4, buffer));
+    let mut new_cap = src_slot.cap.mask_cap_rights(rights);
+    if is_mint {
+        if length < 6 {
+            error!("CNode Mint: Truncated message.");
+            return;
+        }
+        new_cap.update_cap_data(false, get_syscall_arg(5, buffer));
+    }
+
+    let (ok, derived_cap) = derive_cap(src_slot, new_cap);
+    if !ok || derived_cap.get_cap_type() == CapTag::CapNullCap {
+        error!("CNode Copy/Mint: Mint cap would be null: {:?}", new_cap.get_cap_type());
+        return;
+    }
+
+    set_thread_state(ThreadStateRestart);
+    cte_insert(derived_cap, src_slot, dest_slot);
+}
+
+fn invoke_cnode_revoke(slot: &mut CapTableEntry) {
+    if !cte_revoke(slot) {
+        error!("CNode Revoke: failed to delete a child cap");
+    }
+}
+
+fn invoke_cnode_delete(slot: &mut CapTableEntry) {
+    if !cte_delete(slot, true) {
+        error!("CNode Delete: failed to delete the cap");
+    }
+}
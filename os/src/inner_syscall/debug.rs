@@ -0,0 +1,78 @@
+use core::mem::size_of;
+use core::slice;
+use common::config::SEL4_MSG_MAX_LEN;
+use common::logging::{LogLevel, LOG_LEVEL_UNSET, MAX_LOG_MODULE_LEN};
+use common::register::{CAP_REGISTER, MSG_INFO_REGISTER};
+use common::types::IpcBuffer;
+use log::error;
+use syscall::{SYS_DEBUG_SET_LOG_LEVEL, SYS_DEBUG_SET_LOG_SINK, SYS_DEBUG_READ_LOG};
+use crate::logging::{read_log, set_log_level, set_log_sinks};
+use crate::scheduler::get_current_mut_tcb;
+
+const MSG_BYTES: usize = SEL4_MSG_MAX_LEN * size_of::<usize>();
+
+// the message of the caller's ipc buffer, as bytes
+fn msg_bytes(is_receiver: bool) -> Option<&'static mut [u8]> {
+    let buffer = get_current_mut_tcb().lookup_ipc_buffer(is_receiver)?;
+    unsafe {
+        let msg = (*(buffer as *mut IpcBuffer)).msg.as_mut_ptr() as *mut u8;
+        Some(slice::from_raw_parts_mut(msg, MSG_BYTES))
+    }
+}
+
+fn debug_set_log_level(level: usize, module_len: usize) -> isize {
+    let level = match (level, LogLevel::from_usize(level)) {
+        (LOG_LEVEL_UNSET, _) => None,
+        (_, Some(level)) => Some(level),
+        _ => {
+            error!("[debug_set_log_level] invalid level: {}", level);
+            return -1;
+        }
+    };
+    if module_len > MAX_LOG_MODULE_LEN {
+        error!("[debug_set_log_level] module name too long: {}", module_len);
+        return -1;
+    }
+    let module = if module_len == 0 {
+        &[][..]
+    } else {
+        let Some(msg) = msg_bytes(false) else {
+            error!("[debug_set_log_level] no ipc buffer");
+            return -1;
+        };
+        &msg[..module_len]
+    };
+    if set_log_level(module, level).is_err() {
+        error!("[debug_set_log_level] failed to set the level of {:?}", core::str::from_utf8(module));
+        return -1;
+    }
+    0
+}
+
+fn debug_read_log(max_len: usize) -> usize {
+    let Some(msg) = msg_bytes(true) else {
+        error!("[debug_read_log] no ipc buffer");
+        return 0;
+    };
+    read_log(&mut msg[..max_len.min(MSG_BYTES)])
+}
+
+/// the log control syscalls, which answer in a0
+pub fn handle_debug_syscall(syscall: isize) {
+    let thread = get_current_mut_tcb();
+    let arg = thread.get_register(CAP_REGISTER);
+    let ret = match syscall {
+        SYS_DEBUG_SET_LOG_LEVEL => debug_set_log_level(arg, thread.get_register(MSG_INFO_REGISTER)) as usize,
+        SYS_DEBUG_SET_LOG_SINK => {
+            if set_log_sinks(arg).is_err() {
+                error!("[debug_set_log_sink] invalid sinks: {:#x}", arg);
+                -1isize as usize
+            } else {
+                0
+            }
+        }
+        SYS_DEBUG_READ_LOG => debug_read_log(arg),
+        _ => unreachable!(),
+    };
+    thread.set_register(CAP_REGISTER, ret);
+}
@@ -1,17 +1,23 @@
 use common::register::{CAP_REGISTER, MSG_INFO_REGISTER};
 use log::error;
 use common::message::{MessageInfo, NUM_MSG_REGISTRES};
-use common::config::{MSG_MAX_EXTRA_CAPS, SEL4_MSG_MAX_LEN};
 use crate::cspace::{Cap, CapTableEntry, CapTag};
 use crate::sbi::shutdown;
-use crate::scheduler::{TCB, get_current_mut_tcb};
+use crate::scheduler::{TCB, Notification, EndPoint, get_current_mut_tcb, send_signal, send_ipc, set_thread_state};
+#[cfg(feature = "mcs")]
+use crate::scheduler::{Reply, do_reply_transfer};
 use crate::scheduler::ThreadStateEnum::{ThreadStateRestart, ThreadStateRunning};
 use common::types::Pptr;
+use common::utils::convert_to_mut_type_ref;
 use crate::inner_syscall::CUR_EXTRA_CAPS;
+use crate::ipc::lookup_extra_caps;
 
+use super::cnode::decode_cnode_invocation;
 use super::tcb::decode_tcb_invocation;
 use super::untyped::decode_untyped_invocation;
-use super::vspace::{decode_frame_invocation, decode_page_table_invocation};
+#[cfg(feature = "mcs")]
+use super::sched_context::{decode_sched_control_invocation, decode_sched_context_invocation};
+use super::vspace::{decode_frame_invocation, decode_page_table_invocation, decode_asid_control_invocation, decode_asid_pool_invocation};
 
 pub fn handle_invocation(is_call: bool , is_blocking: bool) {
     let thread = get_current_mut_tcb();
@@ -57,11 +63,15 @@ pub fn handle_invocation(is_call: bool , is_blocking: bool) {
 }
 
 fn decode_invocation(inv_label: usize, length: usize, _cap_index: usize, slot: &mut CapTableEntry,
-                         cap: Cap, _block: bool, call: bool, buffer: Pptr) {
+                         cap: Cap, block: bool, call: bool, buffer: Pptr) {
     match cap.get_cap_type() {
         CapTag::CapThreadCap => {
             decode_tcb_invocation(inv_label, length, cap, slot, call, buffer);
         }
+        CapTag::CapCNodeCap => {
+            decode_cnode_invocation(inv_label, length, cap, buffer);
+        }
+
         CapTag::CapUntypedCap => {
             decode_untyped_invocation(inv_label, length, slot, cap, call, buffer);
         }
@@ -73,42 +83,63 @@ fn decode_invocation(inv_label: usize, length: usize, _cap_index: usize, slot: &
         CapTag::CapPageTableCap =>  {
             decode_page_table_invocation(inv_label, length, slot, cap, buffer);
         }
-        _ => {
 
+        CapTag::CapASIDControlCap => {
+            decode_asid_control_invocation(inv_label, length, buffer);
         }
-    }
-}
 
-fn look_up_extra_caps(tcb: &TCB, ipc_buffer: Option<Pptr>, msg: MessageInfo) -> bool {
-    let length = msg.get_extra_caps();
-    if length == 0 || ipc_buffer.is_none() {
-        unsafe { CUR_EXTRA_CAPS[0] = 0; }
-        return true;
-    }
-    let mut i: usize = 0;
-    while i < length {
-        let cptr = get_extra_cap_ptr(ipc_buffer.unwrap(), i);
-        match tcb.lookup_slot(cptr) {
-            Some(slot) => {
-                unsafe { CUR_EXTRA_CAPS[i] = slot as usize; }
+        CapTag::CapASIDPoolCap => {
+            decode_asid_pool_invocation(inv_label, cap);
+        }
+
+        CapTag::CapEndpointCap => {
+            if !cap.get_ep_can_send() {
+                error!("Attempted to invoke a read-only endpoint cap.");
+                return;
             }
-            _ => {
-                return false;
+            set_thread_state(ThreadStateRestart);
+            send_ipc(block, call, cap.get_ep_badge(), cap.get_ep_can_grant(), cap.get_ep_can_grant_reply(),
+                     get_current_mut_tcb(), convert_to_mut_type_ref::<EndPoint>(cap.get_ep_ptr()));
+        }
+
+        CapTag::CapNotificationCap => {
+            if !cap.get_nt_fn_can_send() {
+                error!("Attempted to invoke a read-only notification cap.");
+                return;
             }
+            set_thread_state(ThreadStateRestart);
+            send_signal(convert_to_mut_type_ref::<Notification>(cap.get_nt_fn_ptr()), cap.get_nt_fn_badge());
         }
-        i += 1;
-    }
-    if i < MSG_MAX_EXTRA_CAPS {
-        unsafe {
-            CUR_EXTRA_CAPS[i] = 0;
+
+        // replying with mcs is a send on the reply object
+        #[cfg(feature = "mcs")]
+        CapTag::CapReplyCap => {
+            set_thread_state(ThreadStateRestart);
+            do_reply_transfer(get_current_mut_tcb(), convert_to_mut_type_ref::<Reply>(cap.get_reply_ptr()),
+                              cap.get_reply_can_grant());
+        }
+
+        #[cfg(feature = "mcs")]
+        CapTag::CapSchedControlCap => {
+            decode_sched_control_invocation(inv_label, length, cap, buffer);
+        }
+
+        #[cfg(feature = "mcs")]
+        CapTag::CapSchedContextCap => {
+            decode_sched_context_invocation(inv_label, cap, call);
+        }
+        _ => {
+
         }
     }
-    true
 }
 
-fn get_extra_cap_ptr(buffer_ptr: Pptr, index: usize) -> usize {
-    unsafe {
-        *((buffer_ptr + (core::mem::size_of::<usize>() * (SEL4_MSG_MAX_LEN + 2 + index))) as *const usize)
+fn look_up_extra_caps(tcb: &TCB, ipc_buffer: Option<Pptr>, msg: MessageInfo) -> bool {
+    match lookup_extra_caps(tcb, ipc_buffer, msg) {
+        Some(caps) => {
+            unsafe { CUR_EXTRA_CAPS = caps; }
+            true
+        }
+        None => false,
     }
 }
-
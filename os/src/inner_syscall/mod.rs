@@ -3,13 +3,18 @@ mod invocation;
 mod syscall;
 mod untyped;
 mod tcb;
+mod cnode;
 mod vspace;
+mod debug;
+#[cfg(feature = "mcs")]
+mod sched_context;
 
 use common::config::MSG_MAX_EXTRA_CAPS;
 use common::message::NUM_MSG_REGISTRES;
 use common::register::Register;
 use common::types::{Pptr, IpcBuffer};
-use common::utils::{convert_to_mut_type_ref, hart_id};
+use common::utils::convert_to_mut_type_ref;
+use crate::smp::hart_id;
 pub use slowpath::slowpath;
 
 use crate::scheduler::{KS_CUR_THREAD, TCB};
@@ -0,0 +1,180 @@
+use common::message::{InvocationLabel, MessageInfo, MESSAGE_REGISTERS};
+use common::register::{BADGE_REGISTER, MSG_INFO_REGISTER};
+use common::sched_context::{MIN_BUDGET_US, MAX_PERIOD_US, MIN_REFILLS, max_extra_refills};
+use common::config::CONFIG_MAX_NUM_NODES;
+use common::types::Pptr;
+use common::utils::convert_to_mut_type_ref;
+use log::error;
+use crate::cspace::{Cap, CapTableEntry, CapTag};
+use crate::interrupt::{us_to_ticks, ticks_to_us};
+use crate::scheduler::{SchedContext, Notification, TCB, set_thread_state, get_current_mut_tcb,
+    sched_context_bind_tcb, sched_context_unbind_tcb, sched_context_bind_ntfn, sched_context_unbind_ntfn,
+    sched_context_commit_if_current, sched_context_consumed};
+use crate::scheduler::ThreadStateEnum::{ThreadStateRestart, ThreadStateRunning};
+use crate::smp::is_cpu_online;
+
+use super::{CUR_EXTRA_CAPS, get_syscall_arg};
+
+pub fn decode_sched_control_invocation(inv_label: usize, length: usize, cap: Cap, buffer: Pptr) {
+    if inv_label != InvocationLabel::SchedControlConfigureFlags as usize {
+        error!("SchedControl: Illegal operation.");
+        return;
+    }
+    if length < 5 || unsafe { CUR_EXTRA_CAPS[0] == 0 } {
+        error!("SchedControl_ConfigureFlags: Truncated message.");
+        return;
+    }
+
+    let budget_us = get_syscall_arg(0, buffer);
+    let period_us = get_syscall_arg(1, buffer);
+    let extra_refills = get_syscall_arg(2, buffer);
+    let badge = get_syscall_arg(3, buffer);
+    let _flags = get_syscall_arg(4, buffer);
+
+    let target_cap = convert_to_mut_type_ref::<CapTableEntry>(unsafe { CUR_EXTRA_CAPS[0] }).cap;
+    if target_cap.get_cap_type() != CapTag::CapSchedContextCap {
+        error!("SchedControl_ConfigureFlags: target cap not a scheduling context cap");
+        return;
+    }
+    if budget_us < MIN_BUDGET_US || budget_us > MAX_PERIOD_US {
+        error!("SchedControl_ConfigureFlags: budget out of range: {}", budget_us);
+        return;
+    }
+    if period_us < budget_us || period_us > MAX_PERIOD_US {
+        error!("SchedControl_ConfigureFlags: period out of range: {}", period_us);
+        return;
+    }
+    if extra_refills > max_extra_refills(target_cap.get_sc_size_bits()) {
+        error!("SchedControl_ConfigureFlags: too many extra refills: {}", extra_refills);
+        return;
+    }
+    let core = cap.get_sched_control_core();
+    if core >= CONFIG_MAX_NUM_NODES || !is_cpu_online(core) {
+        error!("SchedControl_ConfigureFlags: core {} is not online", core);
+        return;
+    }
+
+    set_thread_state(ThreadStateRestart);
+    let sc = convert_to_mut_type_ref::<SchedContext>(target_cap.get_sc_ptr());
+    invoke_sched_control_configure_flags(sc, core, us_to_ticks(budget_us), us_to_ticks(period_us),
+                                         MIN_REFILLS + extra_refills, badge);
+}
+
+fn invoke_sched_control_configure_flags(sc: &mut SchedContext, core: usize, budget: usize, period: usize,
+                                        max_refills: usize, badge: usize) {
+    if sc.sc_tcb != 0 {
+        convert_to_mut_type_ref::<TCB>(sc.sc_tcb).de_queue_from_sched();
+    }
+    sched_context_commit_if_current(sc);
+    sc.refill_new(max_refills, budget, period);
+    sc.sc_badge = badge;
+    sc.sc_core = core;
+    if sc.sc_tcb != 0 {
+        // moves the thread to the core and back onto the queues
+        convert_to_mut_type_ref::<TCB>(sc.sc_tcb).set_affinity(core);
+    }
+}
+
+pub fn decode_sched_context_invocation(inv_label: usize, cap: Cap, call: bool) {
+    let sc = convert_to_mut_type_ref::<SchedContext>(cap.get_sc_ptr());
+    match InvocationLabel::from_usize(inv_label) {
+        InvocationLabel::SchedContextBind => {
+            decode_sched_context_bind(sc);
+        }
+
+        InvocationLabel::SchedContextUnbind => {
+            set_thread_state(ThreadStateRestart);
+            sched_context_unbind_tcb(sc);
+            sched_context_unbind_ntfn(sc);
+        }
+
+        InvocationLabel::SchedContextUnbindObject => {
+            decode_sched_context_unbind_object(sc);
+        }
+
+        InvocationLabel::SchedContextConsumed => {
+            set_thread_state(ThreadStateRestart);
+            invoke_sched_context_consumed(sc, call);
+        }
+
+        _ => {
+            error!("SchedContext: Illegal operation.");
+        }
+    }
+}
+
+fn decode_sched_context_bind(sc: &mut SchedContext) {
+    if unsafe { CUR_EXTRA_CAPS[0] == 0 } {
+        error!("SchedContext_Bind: Truncated message.");
+        return;
+    }
+    let cap = convert_to_mut_type_ref::<CapTableEntry>(unsafe { CUR_EXTRA_CAPS[0] }).cap;
+    match cap.get_cap_type() {
+        CapTag::CapThreadCap => {
+            let tcb = convert_to_mut_type_ref::<TCB>(cap.get_tcb_ptr());
+            if sc.sc_tcb != 0 || tcb.tcb_sched_context != 0 {
+                error!("SchedContext_Bind: scheduling context or thread already bound");
+                return;
+            }
+            set_thread_state(ThreadStateRestart);
+            sched_context_bind_tcb(sc, tcb);
+        }
+
+        CapTag::CapNotificationCap => {
+            let ntfn = convert_to_mut_type_ref::<Notification>(cap.get_nt_fn_ptr());
+            if sc.sc_notification != 0 || ntfn.get_ntfn_sched_context() != 0 {
+                error!("SchedContext_Bind: scheduling context or notification already bound");
+                return;
+            }
+            set_thread_state(ThreadStateRestart);
+            sched_context_bind_ntfn(sc, ntfn);
+        }
+
+        _ => {
+            error!("SchedContext_Bind: invalid cap: {:?}", cap.get_cap_type());
+        }
+    }
+}
+
+fn decode_sched_context_unbind_object(sc: &mut SchedContext) {
+    if unsafe { CUR_EXTRA_CAPS[0] == 0 } {
+        error!("SchedContext_UnbindObject: Truncated message.");
+        return;
+    }
+    let cap = convert_to_mut_type_ref::<CapTableEntry>(unsafe { CUR_EXTRA_CAPS[0] }).cap;
+    match cap.get_cap_type() {
+        CapTag::CapThreadCap => {
+            if sc.sc_tcb != cap.get_tcb_ptr() {
+                error!("SchedContext_UnbindObject: object not bound");
+                return;
+            }
+            set_thread_state(ThreadStateRestart);
+            sched_context_unbind_tcb(sc);
+        }
+
+        CapTag::CapNotificationCap => {
+            if sc.sc_notification != cap.get_nt_fn_ptr() {
+                error!("SchedContext_UnbindObject: object not bound");
+                return;
+            }
+            set_thread_state(ThreadStateRestart);
+            sched_context_unbind_ntfn(sc);
+        }
+
+        _ => {
+            error!("SchedContext_UnbindObject: invalid cap: {:?}", cap.get_cap_type());
+        }
+    }
+}
+
+fn invoke_sched_context_consumed(sc: &mut SchedContext, call: bool) {
+    let consumed = ticks_to_us(sched_context_consumed(sc));
+    let current_tcb = get_current_mut_tcb();
+    if call {
+        current_tcb.set_register(BADGE_REGISTER, 0);
+        current_tcb.set_register(MESSAGE_REGISTERS[0], consumed);
+        current_tcb.set_register(MSG_INFO_REGISTER,
+            MessageInfo::new(InvocationLabel::InvalidInvocation, 0, 0, 1).to_word());
+    }
+    current_tcb.set_thread_state(ThreadStateRunning);
+}
@@ -2,20 +2,36 @@ use crate::sbi;
 use crate::scheduler::get_current_tcb;
 use crate::trap::restore_user_context;
 use common::register::CAP_REGISTER;
-use log::debug;
+use log::{debug, info};
 use crate::inner_syscall::syscall::handle_syscall;
+use crate::inner_syscall::debug::handle_debug_syscall;
 
-use syscall::SYS_PUT_CHAR;
+use syscall::{SYS_PUT_CHAR, SYS_DEBUG_HALT, SYS_DEBUG_SET_LOG_LEVEL, SYS_DEBUG_SET_LOG_SINK, SYS_DEBUG_READ_LOG};
 
 pub fn slowpath(syscall: isize) {
     match syscall {
         SYS_PUT_CHAR => {
             sbi::console_putchar(get_current_tcb().get_register(CAP_REGISTER));
         }
+        SYS_DEBUG_HALT => {
+            let exit_code = get_current_tcb().get_register(CAP_REGISTER);
+            info!("[kernel] halted by user with exit code {}", exit_code);
+            sbi::shutdown(exit_code != 0);
+        }
+        SYS_DEBUG_SET_LOG_LEVEL | SYS_DEBUG_SET_LOG_SINK | SYS_DEBUG_READ_LOG => {
+            handle_debug_syscall(syscall);
+        }
+        #[cfg(feature = "benchmark")]
+        syscall::SYS_BENCHMARK_RESET_LOG | syscall::SYS_BENCHMARK_FINALIZE_LOG | syscall::SYS_BENCHMARK_SET_LOG_BUFFER |
+        syscall::SYS_BENCHMARK_GET_THREAD_UTILISATION | syscall::SYS_BENCHMARK_RESET_THREAD_UTILISATION => {
+            crate::benchmark::handle_benchmark_syscall(syscall);
+        }
         _ => {
             debug!("handle inner_syscall");
             handle_syscall(syscall);
         }
     }
+    #[cfg(feature = "check_invariants")]
+    crate::cspace::check_invariants();
     restore_user_context();
 }
\ No newline at end of file
@@ -1,15 +1,158 @@
-use crate::{inner_syscall::invocation::handle_invocation, scheduler::{schedule, activate_thread}};
-use syscall::SYS_CALL;
+use common::message::{MessageInfo, MESSAGE_REGISTERS, SEL4_CAP_FAULT};
+use common::register::{BADGE_REGISTER, CAP_REGISTER, MSG_INFO_REGISTER};
+use common::types::Cptr;
+#[cfg(feature = "mcs")]
+use common::{register::REPLY_REGISTER, types::Pptr};
+use common::utils::convert_to_mut_type_ref;
+use log::error;
+use crate::{inner_syscall::invocation::handle_invocation, scheduler::{schedule, activate_thread, get_current_mut_tcb, receive_signal, re_schedule}};
+use crate::scheduler::{TCB, receive_ipc, do_reply_transfer};
+#[cfg(not(feature = "mcs"))]
+use crate::scheduler::TCBCNode;
+#[cfg(feature = "mcs")]
+use crate::scheduler::Reply;
+use crate::cspace::CapTag;
+#[cfg(not(feature = "mcs"))]
+use crate::cspace::TCBCNodeIndex;
+#[cfg(not(feature = "mcs"))]
+use syscall::SYS_REPLY;
+use syscall::{SYS_CALL, SYS_SEND, SYS_NB_SEND, SYS_RECV, SYS_NB_RECV, SYS_REPLY_RECV, SYS_YIELD};
 pub fn handle_syscall(syscall: isize) {
     match syscall {
         SYS_CALL => {
             handle_invocation(true, true);
         }
+        SYS_SEND => {
+            handle_invocation(false, true);
+        }
+        SYS_NB_SEND => {
+            handle_invocation(false, false);
+        }
+        SYS_RECV => {
+            handle_recv(true);
+        }
+        SYS_NB_RECV => {
+            handle_recv(false);
+        }
+        #[cfg(not(feature = "mcs"))]
+        SYS_REPLY => {
+            handle_reply();
+        }
+        SYS_REPLY_RECV => {
+            handle_reply();
+            handle_recv(true);
+        }
+        SYS_YIELD => {
+            handle_yield();
+        }
         _ => {
-
+            error!("[handle_syscall] unknown syscall: {}", syscall);
         }
     }
     schedule();
     activate_thread();
     
-}
\ No newline at end of file
+}
+
+fn handle_recv(is_blocking: bool) {
+    let thread = get_current_mut_tcb();
+    let cptr = thread.get_register(CAP_REGISTER);
+    match thread.lookup_cap_and_slot(cptr) {
+        Some((cap, _)) if cap.get_cap_type() == CapTag::CapNotificationCap => {
+            if !cap.get_nt_fn_can_receive() {
+                error!("[handle_recv] notification cap cannot receive");
+                cap_fault(thread, cptr);
+                return;
+            }
+            receive_signal(thread, cap, is_blocking);
+        }
+        Some((cap, _)) if cap.get_cap_type() == CapTag::CapEndpointCap => {
+            if !cap.get_ep_can_receive() {
+                error!("[handle_recv] endpoint cap cannot receive");
+                cap_fault(thread, cptr);
+                return;
+            }
+            #[cfg(not(feature = "mcs"))]
+            {
+                thread.delete_caller_cap();
+                receive_ipc(thread, cap, is_blocking);
+            }
+            #[cfg(feature = "mcs")]
+            {
+                let Some(reply) = lookup_reply() else {
+                    error!("[handle_recv] invalid reply cap: {:#x}", thread.get_register(REPLY_REGISTER));
+                    cap_fault(thread, thread.get_register(REPLY_REGISTER));
+                    return;
+                };
+                receive_ipc(thread, cap, is_blocking, reply);
+            }
+        }
+        _ => {
+            error!("[handle_recv] not an endpoint or notification cap: {:#x}", cptr);
+            cap_fault(thread, cptr);
+        }
+    }
+}
+
+// without fault handlers to send it to, the receive completes at once with the fault as its message
+fn cap_fault(thread: &mut TCB, cptr: Cptr) {
+    let mut tag = MessageInfo::from_word(0);
+    tag.set_label(SEL4_CAP_FAULT);
+    tag.set_length(1);
+    thread.set_register(MESSAGE_REGISTERS[0], cptr);
+    thread.set_register(BADGE_REGISTER, 0);
+    thread.set_register(MSG_INFO_REGISTER, tag.to_word());
+}
+
+// answer whoever the current thread received the last call from, if it has not already
+#[cfg(not(feature = "mcs"))]
+fn handle_reply() {
+    let thread = get_current_mut_tcb();
+    let caller_slot = &mut convert_to_mut_type_ref::<TCBCNode>(thread.get_cnode_ptr_of_this())[TCBCNodeIndex::TCBCaller as usize];
+    let caller_cap = caller_slot.cap;
+    match caller_cap.get_cap_type() {
+        CapTag::CapReplyCap if !caller_cap.get_reply_master() => {
+            let caller = convert_to_mut_type_ref::<TCB>(caller_cap.get_reply_tcb_ptr());
+            do_reply_transfer(thread, caller, caller_slot, caller_cap.get_reply_can_grant());
+        }
+        CapTag::CapNullCap => {}
+        _ => {
+            error!("[handle_reply] invalid caller cap: {:?}", caller_cap.get_cap_type());
+        }
+    }
+}
+
+// the reply object the current thread names in the reply register, 0 for the null cap
+#[cfg(feature = "mcs")]
+fn lookup_reply() -> Option<Pptr> {
+    let thread = get_current_mut_tcb();
+    let (cap, _) = thread.lookup_cap_and_slot(thread.get_register(REPLY_REGISTER))?;
+    match cap.get_cap_type() {
+        CapTag::CapReplyCap => Some(cap.get_reply_ptr()),
+        CapTag::CapNullCap => Some(0),
+        _ => None,
+    }
+}
+
+// answer the caller blocked on the reply object in the reply register, if any
+#[cfg(feature = "mcs")]
+fn handle_reply() {
+    let thread = get_current_mut_tcb();
+    let cptr = thread.get_register(REPLY_REGISTER);
+    match thread.lookup_cap_and_slot(cptr) {
+        Some((cap, _)) if cap.get_cap_type() == CapTag::CapReplyCap => {
+            do_reply_transfer(thread, convert_to_mut_type_ref::<Reply>(cap.get_reply_ptr()), cap.get_reply_can_grant());
+        }
+        Some((cap, _)) if cap.get_cap_type() == CapTag::CapNullCap => {}
+        _ => {
+            error!("[handle_reply] invalid reply cap: {:#x}", cptr);
+        }
+    }
+}
+
+fn handle_yield() {
+    let thread = get_current_mut_tcb();
+    thread.de_queue_from_sched();
+    thread.append_to_sched();
+    re_schedule();
+}
@@ -1,14 +1,15 @@
 use common::{message::{InvocationLabel, NUM_FRAME_REGISTERS, NUM_GP_REGISTERS, NUM_MSG_REGISTRES,
     MESSAGE_REGISTERS, FRAME_REGISTERS, GP_REGISTERS, MessageInfo},
-    utils::{convert_to_mut_type_ref, hart_id, convert_to_type_ref}, 
-            types::{Pptr, Cptr, IpcBuffer}, register::{BADGE_REGISTER, MSG_INFO_REGISTER}};
+    utils::{convert_to_mut_type_ref, convert_to_type_ref}, 
+            types::{Pptr, Cptr, IpcBuffer}, register::{BADGE_REGISTER, MSG_INFO_REGISTER}, config::CONFIG_MAX_NUM_NODES};
 use crate::{scheduler::{ThreadStateEnum::ThreadStateRestart, re_schedule,
         set_thread_state, get_current_mut_tcb}, cspace::{CapTableEntry, Cap, derive_cap, TCBCNodeIndex, 
-            CapTag, cte_insert}, ipc::check_valid_ipcbuf, mm::is_valid_vtable_root};
+            CapTag, cte_insert, cte_delete}, ipc::check_valid_ipcbuf, mm::is_valid_vtable_root};
 use log::{debug, error};
 use crate::scheduler::TCBCNode;
 
 use crate::scheduler::{KS_CUR_THREAD, TCB};
+use crate::smp::{hart_id, is_cpu_online};
 
 use super::{CUR_EXTRA_CAPS, get_syscall_arg};
 
@@ -43,6 +44,15 @@ pub fn decode_tcb_invocation(inv_label: usize, length: usize, cap: Cap, slot: &m
             let target = convert_to_mut_type_ref::<TCB>(cap.get_tcb_ptr());
             invoke_tcb_resume(target);
         }
+
+        InvocationLabel::TCBSetAffinity => {
+            decode_tcb_set_affinity(cap, length, buffer);
+        }
+
+        #[cfg(feature = "mcs")]
+        InvocationLabel::TCBSetTimeoutEndpoint => {
+            decode_tcb_set_timeout_endpoint(cap, slot);
+        }
         _ => {
 
         }
@@ -167,6 +177,79 @@ fn decode_tcb_set_priority(cap: Cap, length: usize, buffer: Pptr) {
     invoke_tcb_thread_update_priority(target_tcb, new_prio);
 }
 
+fn decode_tcb_set_affinity(cap: Cap, length: usize, buffer: Pptr) {
+    if length < 1 {
+        error!("TCB SetAffinity: Truncated message.");
+        return;
+    }
+
+    let affinity = get_syscall_arg(0, buffer);
+    if affinity >= CONFIG_MAX_NUM_NODES || !is_cpu_online(affinity) {
+        error!("TCB SetAffinity: Requested CPU does not exist.");
+        return;
+    }
+
+    let target_tcb = convert_to_mut_type_ref::<TCB>(cap.get_tcb_ptr());
+    // a thread runs on the core its scheduling context was configured for
+    #[cfg(feature = "mcs")]
+    if target_tcb.tcb_sched_context != 0 {
+        error!("TCB SetAffinity: thread has a scheduling context.");
+        return;
+    }
+
+    set_thread_state(ThreadStateRestart);
+    invoke_tcb_set_affinity(target_tcb, affinity);
+}
+
+// the endpoint the thread sends its timeout faults to, or the null cap for none
+#[cfg(feature = "mcs")]
+fn decode_tcb_set_timeout_endpoint(cap: Cap, slot: &mut CapTableEntry) {
+    if unsafe { CUR_EXTRA_CAPS[0] == 0 } {
+        error!("TCB SetTimeoutEndpoint: Truncated message.");
+        return;
+    }
+    let handler_slot = convert_to_mut_type_ref::<CapTableEntry>(unsafe { CUR_EXTRA_CAPS[0] });
+    let handler_cap = handler_slot.cap;
+    let valid = match handler_cap.get_cap_type() {
+        CapTag::CapEndpointCap => handler_cap.get_ep_can_send() &&
+            (handler_cap.get_ep_can_grant() || handler_cap.get_ep_can_grant_reply()),
+        CapTag::CapNullCap => true,
+        _ => false,
+    };
+    if !valid {
+        error!("TCB SetTimeoutEndpoint: timeout handler not an endpoint with send and grant rights.");
+        return;
+    }
+    let ret = derive_cap(handler_slot, handler_cap);
+    if !ret.0 {
+        error!("TCB SetTimeoutEndpoint: derive_cap failed");
+        return;
+    }
+
+    set_thread_state(ThreadStateRestart);
+    let target = convert_to_mut_type_ref::<TCB>(cap.get_tcb_ptr());
+    invoke_tcb_set_timeout_endpoint(target, slot, ret.1, handler_slot);
+}
+
+#[cfg(feature = "mcs")]
+fn invoke_tcb_set_timeout_endpoint(target: &mut TCB, slot: &mut CapTableEntry, handler_cap: Cap,
+    handler_src_slot: &mut CapTableEntry) {
+    let tcap = Cap::new_thread_cap(target as *mut TCB as usize);
+    let tcb_cnode_table = convert_to_mut_type_ref::<TCBCNode>(target.get_cnode_ptr_of_this());
+    let handler_slot = &mut tcb_cnode_table[TCBCNodeIndex::TCBTimeoutHandler as usize];
+    if !cte_delete(handler_slot, true) {
+        error!("error to delete timeout handler");
+        return;
+    }
+    if handler_cap.same_obj_as(&handler_src_slot.cap) && tcap.same_obj_as(&slot.cap) {
+        cte_insert(handler_cap, handler_src_slot, handler_slot);
+    }
+}
+
+fn invoke_tcb_set_affinity(thread: &mut TCB, affinity: usize) {
+    thread.set_affinity(affinity);
+}
+
 fn invoke_tcb_thread_update_space(target: &mut TCB, slot: &mut CapTableEntry, faultep: Cptr, croot_new_cap: Cap,
     croot_src_slot: &mut CapTableEntry, vroot_new_cap: Cap, vroot_src_slot: &mut CapTableEntry) -> bool {
 
@@ -175,7 +258,7 @@ fn invoke_tcb_thread_update_space(target: &mut TCB, slot: &mut CapTableEntry, fa
 
     target.tcb_fault_handler = faultep;
     let croot_slot = &mut tcb_cnode_table[TCBCNodeIndex::TCBCTable as usize];
-    if !croot_slot.delete(true) {
+    if !cte_delete(croot_slot, true) {
         error!("error to delete cspace cap");
         return false;
     }
@@ -184,7 +267,7 @@ fn invoke_tcb_thread_update_space(target: &mut TCB, slot: &mut CapTableEntry, fa
     }
 
     let vroot_slot = &mut tcb_cnode_table[TCBCNodeIndex::TCBVTable as usize];
-    if !vroot_slot.delete(true) {
+    if !cte_delete(vroot_slot, true) {
         error!("error to delete vspace cap");
         return false;
     }
@@ -205,7 +288,7 @@ fn invoke_tcb_thread_update_ipc_buffer(target: &mut TCB, slot: &mut CapTableEntr
     let tcap = Cap::new_thread_cap(target as *mut TCB as usize);
     let tcb_cnode_table = convert_to_mut_type_ref::<TCBCNode>(target.get_cnode_ptr_of_this());
     let buffer_slot = &mut tcb_cnode_table[TCBCNodeIndex::TCBBuffer as usize];
-    if !buffer_slot.delete(true) {
+    if !cte_delete(buffer_slot, true) {
         error!("error to delete ipcbuffer");
         return false;
     }
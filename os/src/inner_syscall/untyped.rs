@@ -1,6 +1,7 @@
 use common::{types::Pptr, message::InvocationLabel, object::{ObjectType, get_object_size}, config::*, utils::{convert_to_mut_type_ref, aligned_up, bit}};
 use common::object::ObjectType::*;
 use crate::{scheduler::{ThreadStateEnum::ThreadStateRestart, TCB}, cspace::{CNode, insert_new_cap}, mm::VmRights};
+use crate::smp::hart_id;
 use crate::cspace::CapTag::CapCNodeCap;
 use log::{debug, error};
 
@@ -41,6 +42,12 @@ pub fn decode_untyped_invocation(inv_label: usize, length: usize, slot: &mut Cap
         return;
     }
 
+    #[cfg(feature = "mcs")]
+    if new_type == SchedContextObject && user_obj_size < common::sched_context::SEL4_MIN_SCHED_CONTEXT_BITS {
+        error!("Untyped Retype: Requested a scheduling context too small.");
+        return;
+    }
+
     let node_cap: Cap;
 
     if node_depth == 0 {
@@ -61,7 +68,7 @@ pub fn decode_untyped_invocation(inv_label: usize, length: usize, slot: &mut Cap
     }
     assert_eq!(node_cap.get_cap_type(), CapCNodeCap);
     let node_size = (1 as usize ) << node_cap.get_cnode_radix();
-    assert!(node_offset < node_size - 1);
+    assert!(node_offset <= node_size - 1);
 
     assert!(node_window >= 1 && node_window <= CONFIG_RETYPE_FAN_OUT_LIMIT);
     assert!(node_window <= node_size - node_offset);
@@ -84,11 +91,11 @@ pub fn decode_untyped_invocation(inv_label: usize, length: usize, slot: &mut Cap
 
     let free_ref = cap.get_untyped_ref(free_index);
 
-    let untyped_free_bytes = bit(cap.get_untyped_cap_block_size()) - (free_index << MIN_UNTYPED_BITS);
+    let untyped_free_bytes = bit(cap.get_untyped_block_size()) - (free_index << MIN_UNTYPED_BITS);
 
     if (untyped_free_bytes >> object_size) < node_window {
         error!("Untyped Retype: Insufficient memory: {} : {} : {} : {} : {}",
-                free_index << MIN_UNTYPED_BITS, cap.get_untyped_cap_block_size(), untyped_free_bytes, object_size, node_window);
+                free_index << MIN_UNTYPED_BITS, cap.get_untyped_block_size(), untyped_free_bytes, object_size, node_window);
         return;
     }
 
@@ -115,7 +122,7 @@ pub fn invoke_untyped_retype(src_slot: &mut CapTableEntry, reset: bool, retyped_
 
     let totol_obj_size = dest_length << get_object_size(new_type, user_size);
     let free_ref = retyped_base + totol_obj_size;
-    src_slot.cap.set_untyped_cap_free_index((free_ref - retyped_base) >> MIN_UNTYPED_BITS);
+    src_slot.cap.set_untyped_free_index((free_ref - src_slot.cap.get_untyped_ptr()) >> MIN_UNTYPED_BITS);
     create_new_objects(new_type, src_slot, dest_cnode, dest_offset, dest_length, retyped_base, user_size, device_mem);
 }
 
@@ -136,13 +143,45 @@ pub fn create_object(new_type: ObjectType,  region_base: Pptr, user_size: usize,
     }
     debug!("region_base: {:#x}", region_base);
     match new_type {
+        ObjectType::UntypedObject => {
+            return Cap::new_untyped_cap(0, device_mem, user_size, region_base);
+        }
+
         ObjectType::TCBObject => {
             let tcb = convert_to_mut_type_ref::<TCB>(region_base + TCB_OFFSET);
             tcb.init_context();
             tcb.tcb_domain = 0;
             tcb.tcb_time_slice = CONFIG_TIME_SLICE;
+            tcb.tcb_affinity = hart_id();
             return Cap::new_thread_cap(region_base + TCB_OFFSET);
         }
+
+        ObjectType::EndpointObject => {
+            (region_base..region_base + bit(SEL4_ENDPOINT_BITS)).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+            return Cap::new_endpoint_cap(0, true, true, true, true, region_base);
+        }
+
+        ObjectType::NotificationObject => {
+            (region_base..region_base + bit(SEL4_NOTIFICATION_BITS)).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+            return Cap::new_notification_cap(0, true, true, region_base);
+        }
+
+        ObjectType::CapTableObject => {
+            (region_base..region_base + bit(user_size + SEL4_SLOT_BITS)).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+            return Cap::new_cnode_cap(user_size, 0, 0, region_base);
+        }
+
+        #[cfg(feature = "mcs")]
+        ObjectType::SchedContextObject => {
+            (region_base..region_base + bit(user_size)).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+            return Cap::new_sched_context_cap(region_base, user_size);
+        }
+
+        #[cfg(feature = "mcs")]
+        ObjectType::ReplyObject => {
+            (region_base..region_base + bit(SEL4_REPLY_BITS)).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+            return Cap::new_reply_object_cap(true, region_base);
+        }
         _ => {
 
         }
@@ -153,7 +192,17 @@ pub fn create_object(new_type: ObjectType,  region_base: Pptr, user_size: usize,
 pub fn create_arch_object(new_type: ObjectType,  region_base: Pptr, _user_size: usize, device_mem: bool) -> Cap {
     match new_type {
         ObjectType::Riscv4kpage => {
-            Cap::new_frame_cap(0, region_base,  0,
+            Cap::new_frame_cap(0, region_base,  RISCV_4K_PAGE,
+                VmRights::VMReadWrite as usize, device_mem, 0)
+        }
+
+        ObjectType::RiscvMegaPage => {
+            Cap::new_frame_cap(0, region_base,  RISCV_MEGA_PAGE,
+                VmRights::VMReadWrite as usize, device_mem, 0)
+        }
+
+        ObjectType::RiscvGigaPage => {
+            Cap::new_frame_cap(0, region_base,  RISCV_GIGA_PAGE,
                 VmRights::VMReadWrite as usize, device_mem, 0)
         }
 
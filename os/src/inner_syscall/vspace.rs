@@ -1,12 +1,17 @@
-use common::{types::{Pptr, CapRights}, message::InvocationLabel, utils::{convert_to_mut_type_ref, bit, mask, page_bits_for_size, addr_from_pptr}, config::{USER_TOP, PAGE_BITS}};
-
-use crate::{cspace::{CapTableEntry, Cap, CapTag}, mm::{PageTableEntry, find_vspace_for_asid, look_up_pt_slot2, VmRights, PTEFlags}, scheduler::{ThreadStateEnum, set_thread_state}};
+use common::{types::{Pptr, CapRights, ASIDSizeConstants}, message::{InvocationLabel, MessageInfo, MESSAGE_REGISTERS, SEL4_FAILED_LOOKUP},
+    register::{BADGE_REGISTER, MSG_INFO_REGISTER}, utils::{convert_to_mut_type_ref, bit, mask, page_bits_for_size, addr_from_pptr},
+    config::{USER_TOP, PAGE_BITS, ROOT_PAGE_TABLE_SIZE, SEL4_ASID_POOL_BITS}};
+
+use crate::{cspace::{CapTableEntry, Cap, CapTag, lookup_target_slot, cte_insert}, mm::{PageTableEntry, find_vspace_for_asid, look_up_pt_slot2, VmRights, PTEFlags,
+    ASIDPool, get_asid_pool_by_index, set_asid_pool_by_index, copy_global_mappings, unmap_page, unmap_page_table,
+    flush_page, flush_asid},
+    scheduler::{ThreadStateEnum, set_thread_state, get_current_mut_tcb}, untyped::max_free_index};
 use super::{CUR_EXTRA_CAPS, get_syscall_arg};
 use log::error;
 use crate::mm::VMAttributes;
 
 pub fn decode_frame_invocation(label: usize, length: usize, cte: &mut CapTableEntry, cap: Cap,
-    _call: bool, buffer: Pptr) {
+    call: bool, buffer: Pptr) {
 
     match InvocationLabel::from_usize(label) {
         InvocationLabel::PageMap => {
@@ -28,9 +33,16 @@ pub fn decode_frame_invocation(label: usize, length: usize, cte: &mut CapTableEn
             }
 
             let frame_size = cap.get_frame_size();
+            let page_bits = match page_bits_for_size(frame_size) {
+                Some(page_bits) => page_bits,
+                None => {
+                    error!("RISCVPageMap: Invalid frame size: {}", frame_size);
+                    return;
+                }
+            };
             let cap_vm_rights = VmRights::from_usize(cap.get_frame_vm_right());
 
-            let lvl1pt = convert_to_mut_type_ref::<PageTableEntry>(lvl1pt_cap.get_pt_based_ptr());
+            let lvl1pt = convert_to_mut_type_ref::<PageTableEntry>(lvl1pt_cap.get_pt_base_ptr());
             let asid = lvl1pt_cap.get_pt_mapped_asid();
 
             match find_vspace_for_asid(asid) {
@@ -40,22 +52,23 @@ pub fn decode_frame_invocation(label: usize, length: usize, cte: &mut CapTableEn
                         return;
                     }
 
-                    let vtop = vaddr + bit(frame_size) - 1;
+                    let vtop = vaddr + bit(page_bits) - 1;
 
                     if vtop >= USER_TOP {
                         error!("RISCVPageMap, out of USER TOP");
                         return;
                     }
 
-                    if vaddr & mask(page_bits_for_size(frame_size)) != 0 {
+                    if vaddr & mask(page_bits) != 0 {
                         error!("RISCVPageMap, AlignmentError");
                         return;
                     }
 
                     let (bit_left, pte_ptr) = look_up_pt_slot2(lvl1pt, vaddr);
                     let lookup_pte = convert_to_mut_type_ref::<PageTableEntry>(pte_ptr);
-                    if bit_left != page_bits_for_size(frame_size) {
+                    if bit_left != page_bits {
                         error!("RISCVPageMap, FailedLookup: {:#x} : {} : {}", vaddr, bit_left, frame_size);
+                        reply_failed_lookup(call);
                         return;
                     }
 
@@ -85,7 +98,7 @@ pub fn decode_frame_invocation(label: usize, length: usize, cte: &mut CapTableEn
                     let vm_rights = cap_vm_rights.mask_vm_rights(CapRights::from_word(rights_mask));
                     let frame_paddr = addr_from_pptr(cap.get_frame_base_ptr());
                     let mut local_cap = cap;
-                    local_cap.set_frame_mapped_address(vaddr);
+                    local_cap.set_frame_mapped_addr(vaddr);
                     local_cap.set_frame_mapped_asid(asid);
 
                     let executable = !vm_attributes.get_excute_never();
@@ -105,15 +118,52 @@ pub fn decode_frame_invocation(label: usize, length: usize, cte: &mut CapTableEn
 
         }
 
+        InvocationLabel::PageUnmap => {
+            set_thread_state(ThreadStateEnum::ThreadStateRestart);
+            perform_page_invocation_unmap(cap, cte);
+        }
+
+        InvocationLabel::PageGetAddress => {
+            set_thread_state(ThreadStateEnum::ThreadStateRestart);
+            perform_page_get_address(cap.get_frame_base_ptr(), call);
+        }
+
         _ => {
-            panic!("[decode_frame_invocation] unsupported!");
+            error!("RISCVPage: Illegal operation.");
         }
     }
     
 }
 
 
-pub fn decode_page_table_invocation(_label: usize, length: usize, cte: &mut CapTableEntry, cap: Cap, buffer: Pptr) {
+pub fn decode_page_table_invocation(label: usize, length: usize, cte: &mut CapTableEntry, cap: Cap, buffer: Pptr) {
+    if label == InvocationLabel::PageTableUnmap as usize {
+        if !cte.is_final_cap() {
+            error!("RISCVPageTableUnmap: cannot unmap if more than one cap exists");
+            return;
+        }
+
+        if cap.get_pt_is_mapped() {
+            let asid = cap.get_pt_mapped_asid();
+            let pte_ptr = cap.get_pt_base_ptr();
+            if let Some(vspace_root) = find_vspace_for_asid(asid) {
+                if vspace_root as *mut PageTableEntry as usize == pte_ptr {
+                    error!("RISCVPageTableUnmap: cannot call unmap on top level PageTable");
+                    return;
+                }
+            }
+        }
+
+        set_thread_state(ThreadStateEnum::ThreadStateRestart);
+        perform_page_table_invocation_unmap(cap, cte);
+        return;
+    }
+
+    if label != InvocationLabel::PageTableMap as usize {
+        error!("RISCVPageTable: Illegal Operation");
+        return;
+    }
+
     if length < 2 || unsafe { CUR_EXTRA_CAPS[0] == 0 } {
         error!("RISCVPageTable: truncated message");
         return;
@@ -134,7 +184,7 @@ pub fn decode_page_table_invocation(_label: usize, length: usize, cte: &mut CapT
         return;
     }
 
-    let lvl1pt = convert_to_mut_type_ref::<PageTableEntry>(lvl1pt_cap.get_pt_based_ptr());
+    let lvl1pt = convert_to_mut_type_ref::<PageTableEntry>(lvl1pt_cap.get_pt_base_ptr());
     let asid = lvl1pt_cap.get_pt_mapped_asid();
 
     if vaddr >= USER_TOP {
@@ -156,12 +206,12 @@ pub fn decode_page_table_invocation(_label: usize, length: usize, cte: &mut CapT
                 return;
             }
 
-            let paddr = addr_from_pptr(cap.get_pt_based_ptr());
+            let paddr = addr_from_pptr(cap.get_pt_base_ptr());
             let pte = PageTableEntry::new(paddr >> PAGE_BITS, 0, PTEFlags::V);
             let mut local_cap = cap;
-            local_cap.set_pt_is_mapped(1);
+            local_cap.set_pt_is_mapped(true);
             local_cap.set_pt_mapped_asid(asid);
-            local_cap.set_pt_mapped_address(vaddr & !mask(bits_left));
+            local_cap.set_pt_mapped_addr(vaddr & !mask(bits_left));
             
             set_thread_state(ThreadStateEnum::ThreadStateRestart);
             perform_page_table_invocation(local_cap, cte, pte, lookup_pte);
@@ -180,6 +230,7 @@ fn perform_page_invocation(cap: Cap, ct_slot: &mut CapTableEntry, pte: PageTable
 
     ct_slot.cap = cap;
     base.update(pte);
+    flush_page(cap.get_frame_mapped_asid(), cap.get_frame_mapped_addr());
 }
 
 fn perform_page_table_invocation(cap: Cap, ct_slot: &mut CapTableEntry, pte: PageTableEntry,
@@ -187,4 +238,167 @@ fn perform_page_table_invocation(cap: Cap, ct_slot: &mut CapTableEntry, pte: Pag
 
     ct_slot.cap = cap;
     base.update(pte);
-}
\ No newline at end of file
+    flush_asid(cap.get_pt_mapped_asid());
+}
+
+fn perform_page_invocation_unmap(cap: Cap, ct_slot: &mut CapTableEntry) {
+    if cap.get_frame_mapped_asid() != 0 {
+        unmap_page(cap.get_frame_size(), cap.get_frame_mapped_asid(), cap.get_frame_mapped_addr(),
+                   cap.get_frame_base_ptr());
+    }
+
+    ct_slot.cap.set_frame_mapped_addr(0);
+    ct_slot.cap.set_frame_mapped_asid(0);
+}
+
+fn perform_page_table_invocation_unmap(cap: Cap, ct_slot: &mut CapTableEntry) {
+    if cap.get_pt_is_mapped() {
+        let pt = cap.get_pt_base_ptr();
+        unmap_page_table(cap.get_pt_mapped_asid(), cap.get_pt_mapped_addr(), pt);
+        (pt..pt + bit(PAGE_BITS)).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+    }
+
+    ct_slot.cap.set_pt_is_mapped(false);
+}
+
+// so the caller can tell a missing page table from any other error
+fn reply_failed_lookup(call: bool) {
+    if call {
+        let thread = get_current_mut_tcb();
+        let mut tag = MessageInfo::from_word(0);
+        tag.set_label(SEL4_FAILED_LOOKUP);
+        thread.set_register(BADGE_REGISTER, 0);
+        thread.set_register(MSG_INFO_REGISTER, tag.to_word());
+    }
+}
+
+fn perform_page_get_address(base_ptr: Pptr, call: bool) {
+    let thread = get_current_mut_tcb();
+    if call {
+        thread.set_register(BADGE_REGISTER, 0);
+        thread.set_register(MESSAGE_REGISTERS[0], addr_from_pptr(base_ptr));
+        thread.set_register(MSG_INFO_REGISTER,
+            MessageInfo::new(InvocationLabel::InvalidInvocation, 0, 0, 1).to_word());
+    }
+    thread.set_thread_state(ThreadStateEnum::ThreadStateRunning);
+}
+
+pub fn decode_asid_control_invocation(label: usize, length: usize, buffer: Pptr) {
+    if label != InvocationLabel::ASIDControlMakePool as usize {
+        error!("RISCVASIDControlInvocation: Illegal operation.");
+        return;
+    }
+
+    if length < 2 || unsafe { CUR_EXTRA_CAPS[0] == 0 || CUR_EXTRA_CAPS[1] == 0 } {
+        error!("RISCVASIDControlMakePool: Truncated message.");
+        return;
+    }
+
+    let index = get_syscall_arg(0, buffer);
+    let depth = get_syscall_arg(1, buffer);
+    let parent_slot = convert_to_mut_type_ref::<CapTableEntry>(unsafe { CUR_EXTRA_CAPS[0] });
+    let untyped = parent_slot.cap;
+    let root = convert_to_mut_type_ref::<CapTableEntry>(unsafe { CUR_EXTRA_CAPS[1] }).cap;
+
+    let mut i = 0;
+    while i < bit(ASIDSizeConstants::ASIDHighBits as usize) && get_asid_pool_by_index(i) != 0 {
+        i += 1;
+    }
+    if i == bit(ASIDSizeConstants::ASIDHighBits as usize) {
+        error!("RISCVASIDControlMakePool: No free ASID pool.");
+        return;
+    }
+    let asid_base = i << ASIDSizeConstants::ASIDLowBits as usize;
+
+    if untyped.get_cap_type() != CapTag::CapUntypedCap || untyped.get_untyped_block_size() != SEL4_ASID_POOL_BITS
+        || untyped.get_untyped_is_device() {
+        error!("RISCVASIDControlMakePool: Invalid untyped cap.");
+        return;
+    }
+
+    if !parent_slot.ensure_no_child() {
+        error!("RISCVASIDControlMakePool: Untyped has children, revoke first.");
+        return;
+    }
+
+    let dest_slot = match lookup_target_slot(root, index, depth) {
+        Some(slot) => convert_to_mut_type_ref::<CapTableEntry>(slot as usize),
+        _ => {
+            error!("RISCVASIDControlMakePool: Failed to lookup destination slot.");
+            return;
+        }
+    };
+
+    if !dest_slot.ensure_empty_slot() {
+        error!("RISCVASIDControlMakePool: Destination slot not empty.");
+        return;
+    }
+
+    set_thread_state(ThreadStateEnum::ThreadStateRestart);
+    perform_asid_control_invocation(untyped.get_untyped_ptr(), dest_slot, parent_slot, asid_base);
+}
+
+pub fn decode_asid_pool_invocation(label: usize, cap: Cap) {
+    if label != InvocationLabel::ASIDPoolAssign as usize {
+        error!("RISCVASIDPool: Illegal operation.");
+        return;
+    }
+
+    if unsafe { CUR_EXTRA_CAPS[0] == 0 } {
+        error!("RISCVASIDPoolAssign: Truncated message.");
+        return;
+    }
+
+    let vspace_slot = convert_to_mut_type_ref::<CapTableEntry>(unsafe { CUR_EXTRA_CAPS[0] });
+    let vspace_cap = vspace_slot.cap;
+
+    if vspace_cap.get_cap_type() != CapTag::CapPageTableCap || vspace_cap.get_pt_is_mapped() {
+        error!("RISCVASIDPoolAssign: Invalid vspace root.");
+        return;
+    }
+
+    let pool_ptr = get_asid_pool_by_index(cap.get_asid_base() >> ASIDSizeConstants::ASIDLowBits as usize);
+    if pool_ptr == 0 {
+        error!("RISCVASIDPoolAssign: Failed to lookup ASID pool.");
+        return;
+    }
+
+    if pool_ptr != cap.get_asid_pool() {
+        error!("RISCVASIDPoolAssign: Failed to lookup ASID pool.");
+        return;
+    }
+
+    let pool = convert_to_mut_type_ref::<ASIDPool>(pool_ptr);
+    let asid_base = cap.get_asid_base();
+    let mut i = 0;
+    while i < bit(ASIDSizeConstants::ASIDLowBits as usize) && (asid_base + i == 0 || pool.get(asid_base + i) != 0) {
+        i += 1;
+    }
+
+    if i == bit(ASIDSizeConstants::ASIDLowBits as usize) {
+        error!("RISCVASIDPoolAssign: No free ASID.");
+        return;
+    }
+
+    set_thread_state(ThreadStateEnum::ThreadStateRestart);
+    perform_asid_pool_invocation(asid_base + i, pool, vspace_slot);
+}
+
+fn perform_asid_control_invocation(frame: Pptr, slot: &mut CapTableEntry, parent: &mut CapTableEntry, asid_base: usize) {
+    parent.cap.set_untyped_free_index(max_free_index(parent.cap.get_untyped_block_size()));
+    (frame..frame + bit(SEL4_ASID_POOL_BITS)).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
+    cte_insert(Cap::new_asid_pool_cap(asid_base, frame), parent, slot);
+    set_asid_pool_by_index(asid_base >> ASIDSizeConstants::ASIDLowBits as usize, frame);
+}
+
+fn perform_asid_pool_invocation(asid: usize, pool: &mut ASIDPool, vspace_slot: &mut CapTableEntry) {
+    let mut cap = vspace_slot.cap;
+    let region_base = cap.get_pt_base_ptr();
+    cap.set_pt_mapped_asid(asid);
+    cap.set_pt_mapped_addr(0);
+    cap.set_pt_is_mapped(true);
+    vspace_slot.cap = cap;
+
+    copy_global_mappings(convert_to_mut_type_ref::<[PageTableEntry; ROOT_PAGE_TABLE_SIZE]>(region_base));
+    pool.write(asid, region_base);
+}
@@ -2,13 +2,20 @@ mod timer;
 
 use common::register::Register;
 use log::error;
-use riscv::register::{scause::{self, Interrupt, Trap, Exception}, stval};
+use riscv::register::{scause::{self, Interrupt, Trap, Exception}, stval, sie};
 
-use crate::{scheduler::{timer_tick, schedule, activate_thread, get_current_tcb}, trap::restore_user_context, sbi::shutdown};
+use crate::{scheduler::{timer_tick, schedule, activate_thread, get_current_tcb, re_schedule}, trap::restore_user_context, sbi::shutdown};
+use crate::smp::clear_ipi;
 
 use self::timer::set_next_trigger;
+pub use self::timer::{set_timebase_freq, get_time};
+#[cfg(feature = "mcs")]
+pub use self::timer::{set_next_trigger_before, us_to_ticks, ticks_to_us};
 
 pub fn init() {
+    unsafe {
+        sie::set_ssoft();
+    }
     timer::init();
 }
 
@@ -20,6 +27,10 @@ pub fn handle_interrupt() {
             timer_tick();
             set_next_trigger();
         }
+        Trap::Interrupt(Interrupt::SupervisorSoft) => {
+            clear_ipi();
+            re_schedule();
+        }
         Trap::Exception(Exception::IllegalInstruction) => {
             error!(
                 "[kernel] {:?} in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.",
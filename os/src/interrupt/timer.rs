@@ -1,9 +1,18 @@
-use common::config::{CLOCK_FREQ, TICKS_PER_SEC};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use common::config::TICKS_PER_SEC;
 use riscv::register::{sie, time};
 
 use crate::sbi::set_timer;
 
+static TIMEBASE_FREQ: AtomicUsize = AtomicUsize::new(0);
+
+pub fn set_timebase_freq(freq: usize) {
+    TIMEBASE_FREQ.store(freq, Ordering::Relaxed);
+}
+
 pub fn init() {
+    assert!(TIMEBASE_FREQ.load(Ordering::Relaxed) != 0);
     unsafe {
         sie::set_stimer();
     }
@@ -15,5 +24,21 @@ pub fn get_time() -> usize {
 }
 
 pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC)
-}
\ No newline at end of file
+    set_timer(get_time() + TIMEBASE_FREQ.load(Ordering::Relaxed) / TICKS_PER_SEC)
+}
+
+/// the next tick, or `deadline` if that comes first
+#[cfg(feature = "mcs")]
+pub fn set_next_trigger_before(deadline: usize) {
+    set_timer(deadline.min(get_time() + TIMEBASE_FREQ.load(Ordering::Relaxed) / TICKS_PER_SEC))
+}
+
+#[cfg(feature = "mcs")]
+pub fn us_to_ticks(us: usize) -> usize {
+    us * TIMEBASE_FREQ.load(Ordering::Relaxed) / 1_000_000
+}
+
+#[cfg(feature = "mcs")]
+pub fn ticks_to_us(ticks: usize) -> usize {
+    ticks * 1_000_000 / TIMEBASE_FREQ.load(Ordering::Relaxed)
+}
@@ -1,6 +1,13 @@
-use common::{types::Vptr, utils::is_aligned, config::SEL4_IPC_BUFFER_SIZE_BITS};
+use common::{types::{Vptr, Pptr, IpcBuffer}, utils::{is_aligned, convert_to_mut_type_ref}, config::{SEL4_IPC_BUFFER_SIZE_BITS, MSG_MAX_EXTRA_CAPS, SEL4_MSG_MAX_LEN}};
+use common::message::{MessageInfo, MESSAGE_REGISTERS, NUM_MSG_REGISTRES};
+use common::register::{BADGE_REGISTER, MSG_INFO_REGISTER};
 
-use crate::cspace::{Cap, CapTag};
+use crate::cspace::{Cap, CapTag, CapTableEntry, cte_insert, derive_cap, lookup_target_slot};
+use crate::scheduler::TCB;
+#[cfg(feature = "mcs")]
+use common::sched_context::{SEL4_TIMEOUT_FAULT, SEL4_TIMEOUT_DATA, SEL4_TIMEOUT_CONSUMED, SEL4_TIMEOUT_LENGTH};
+#[cfg(feature = "mcs")]
+use crate::{interrupt::ticks_to_us, scheduler::sched_context_consumed};
 
 pub fn check_valid_ipcbuf(vptr: Vptr, cap: Cap) -> bool {
     if cap.get_cap_type() != CapTag::CapFrameCap || cap.get_frame_is_device() || !is_aligned(vptr, SEL4_IPC_BUFFER_SIZE_BITS) {
@@ -8,4 +15,115 @@ pub fn check_valid_ipcbuf(vptr: Vptr, cap: Cap) -> bool {
     }
 
     return true;
-}
\ No newline at end of file
+}
+
+/// the slots of the extra caps `msg` names in `tcb`'s ipc buffer, ended by a 0 if there are fewer
+/// than MSG_MAX_EXTRA_CAPS. None if one cannot be found
+pub fn lookup_extra_caps(tcb: &TCB, ipc_buffer: Option<Pptr>, msg: MessageInfo) -> Option<[Pptr; MSG_MAX_EXTRA_CAPS]> {
+    let mut caps = [0; MSG_MAX_EXTRA_CAPS];
+    let Some(buffer) = ipc_buffer else {
+        return Some(caps);
+    };
+    let buffer = convert_to_mut_type_ref::<IpcBuffer>(buffer);
+    for i in 0..msg.get_extra_caps() {
+        caps[i] = tcb.lookup_slot(buffer.caps_or_badges[i])? as Pptr;
+    }
+    Some(caps)
+}
+
+/// pass the message of `sender` to `receiver` as if it came through `endpoint`, 0 for a reply,
+/// with the extra caps only if `can_grant`. a faulting sender passes its fault instead
+pub fn do_ipc_transfer(sender: &mut TCB, endpoint: Pptr, badge: usize, can_grant: bool, receiver: &mut TCB) {
+    #[cfg(feature = "mcs")]
+    if sender.tcb_fault.has_fault() {
+        do_fault_transfer(sender, badge, receiver);
+        return;
+    }
+    let receive_buffer = receiver.lookup_ipc_buffer(true);
+    let send_buffer = sender.lookup_ipc_buffer(false);
+    let mut tag = MessageInfo::from_word(sender.get_register(MSG_INFO_REGISTER));
+    let caps = if can_grant {
+        lookup_extra_caps(sender, send_buffer, tag).unwrap_or_default()
+    } else {
+        [0; MSG_MAX_EXTRA_CAPS]
+    };
+    let length = copy_mrs(sender, send_buffer, receiver, receive_buffer, tag.get_length());
+    transfer_caps(&mut tag, &caps, endpoint, receiver, receive_buffer);
+    tag.set_length(length);
+    receiver.set_register(MSG_INFO_REGISTER, tag.to_word());
+    receiver.set_register(BADGE_REGISTER, badge);
+}
+
+// a timeout fault: the badge of the scheduling context and the microseconds it ran for
+#[cfg(feature = "mcs")]
+fn do_fault_transfer(sender: &mut TCB, badge: usize, receiver: &mut TCB) {
+    assert!(sender.tcb_fault.is_timeout());
+    let consumed = sender.sched_context().map_or(0, |sc| ticks_to_us(sched_context_consumed(sc)));
+    receiver.set_register(MESSAGE_REGISTERS[SEL4_TIMEOUT_DATA], sender.tcb_fault.get_timeout_badge());
+    receiver.set_register(MESSAGE_REGISTERS[SEL4_TIMEOUT_CONSUMED], consumed);
+    let mut tag = MessageInfo::default();
+    tag.set_label(SEL4_TIMEOUT_FAULT);
+    tag.set_length(SEL4_TIMEOUT_LENGTH);
+    receiver.set_register(MSG_INFO_REGISTER, tag.to_word());
+    receiver.set_register(BADGE_REGISTER, badge);
+}
+
+// the registers always, the rest of the message only if both sides have a buffer
+fn copy_mrs(sender: &TCB, send_buffer: Option<Pptr>, receiver: &mut TCB, receive_buffer: Option<Pptr>, n: usize) -> usize {
+    for &reg in MESSAGE_REGISTERS.iter().take(n) {
+        receiver.set_register(reg, sender.get_register(reg));
+    }
+    let (Some(send_buffer), Some(receive_buffer)) = (send_buffer, receive_buffer) else {
+        return n.min(NUM_MSG_REGISTRES);
+    };
+    let n = n.min(SEL4_MSG_MAX_LEN);
+    let send_buffer = convert_to_mut_type_ref::<IpcBuffer>(send_buffer);
+    let receive_buffer = convert_to_mut_type_ref::<IpcBuffer>(receive_buffer);
+    for i in NUM_MSG_REGISTRES..n {
+        receive_buffer.msg[i] = send_buffer.msg[i];
+    }
+    n
+}
+
+// caps to the endpoint itself only pass their badge. any other cap goes into the one slot the
+// receiver named in its buffer, and the transfer stops at the first cap that cannot be moved
+fn transfer_caps(tag: &mut MessageInfo, caps: &[Pptr; MSG_MAX_EXTRA_CAPS], endpoint: Pptr, receiver: &TCB,
+                 receive_buffer: Option<Pptr>) {
+    tag.set_extra_caps(0);
+    tag.set_caps_unwrapped(0);
+    let Some(receive_buffer) = receive_buffer else {
+        return;
+    };
+    let buffer = convert_to_mut_type_ref::<IpcBuffer>(receive_buffer);
+    let mut dest_slot = get_receive_slot(receiver, buffer);
+    let mut i = 0;
+    while i < MSG_MAX_EXTRA_CAPS && caps[i] != 0 {
+        let slot = convert_to_mut_type_ref::<CapTableEntry>(caps[i]);
+        let cap = slot.cap;
+        if cap.get_cap_type() == CapTag::CapEndpointCap && cap.get_ep_ptr() == endpoint {
+            buffer.caps_or_badges[i] = cap.get_ep_badge();
+            tag.set_caps_unwrapped(tag.get_caps_unwrapped() | (1 << i));
+        } else {
+            let Some(dest) = dest_slot.take() else {
+                break;
+            };
+            let (ok, new_cap) = derive_cap(slot, cap);
+            if !ok || new_cap.get_cap_type() == CapTag::CapNullCap {
+                break;
+            }
+            cte_insert(new_cap, slot, dest);
+        }
+        i += 1;
+    }
+    tag.set_extra_caps(i);
+}
+
+fn get_receive_slot(receiver: &TCB, buffer: &IpcBuffer) -> Option<&'static mut CapTableEntry> {
+    let (cnode, _) = receiver.lookup_cap_and_slot(buffer.receive_cnode)?;
+    let slot = convert_to_mut_type_ref::<CapTableEntry>(
+        lookup_target_slot(cnode, buffer.receive_index, buffer.receive_depth)? as Pptr);
+    if slot.cap.get_cap_type() != CapTag::CapNullCap {
+        return None;
+    }
+    Some(slot)
+}
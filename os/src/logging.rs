@@ -2,46 +2,207 @@
 
 本模块利用 log crate 为你提供了日志功能，使用方式见 lib.
 
+编译时的 LOG 环境变量给出默认等级和按模块的等级，如 `LOG=INFO,os::scheduler=DEBUG`；
+运行时可以通过 DebugSetLogLevel 和 DebugSetLogSink 系统调用修改。
+
 */
 
+use core::fmt::{self, Write};
+use common::logging::{LogLevel, LOG_SINK_CONSOLE, LOG_SINK_RING, MAX_LOG_MODULE_LEN};
 use log::{self, Level, LevelFilter, Log, Metadata, Record};
+use spin::Mutex;
+
+const MAX_MODULE_FILTERS: usize = 8;
+const LOG_RING_SIZE: usize = 16 * 1024;
+
+#[derive(Copy, Clone)]
+struct ModuleFilter {
+    module: [u8; MAX_LOG_MODULE_LEN],
+    len: usize,
+    level: LevelFilter,
+}
+
+impl ModuleFilter {
+    const fn empty() -> Self {
+        Self { module: [0; MAX_LOG_MODULE_LEN], len: 0, level: LevelFilter::Off }
+    }
+
+    fn module(&self) -> &[u8] {
+        &self.module[..self.len]
+    }
+
+    // `os::scheduler` covers `os::scheduler` and `os::scheduler::tcb`, not `os::scheduler_x`
+    fn covers(&self, target: &str) -> bool {
+        let target = target.as_bytes();
+        target.starts_with(self.module()) && (target.len() == self.len || target[self.len..].starts_with(b"::"))
+    }
+}
+
+// the oldest bytes are overwritten once it is full
+struct LogRing {
+    buffer: [u8; LOG_RING_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl LogRing {
+    fn push(&mut self, byte: u8) {
+        self.buffer[(self.head + self.len) % LOG_RING_SIZE] = byte;
+        if self.len == LOG_RING_SIZE {
+            self.head = (self.head + 1) % LOG_RING_SIZE;
+        } else {
+            self.len += 1;
+        }
+    }
+
+    fn drain(&mut self, out: &mut [u8]) -> usize {
+        let count = out.len().min(self.len);
+        for byte in out[..count].iter_mut() {
+            *byte = self.buffer[self.head];
+            self.head = (self.head + 1) % LOG_RING_SIZE;
+        }
+        self.len -= count;
+        count
+    }
+}
+
+impl Write for LogRing {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.push(byte);
+        }
+        Ok(())
+    }
+}
+
+struct LogState {
+    default: LevelFilter,
+    filters: [ModuleFilter; MAX_MODULE_FILTERS],
+    sinks: usize,
+    ring: LogRing,
+}
+
+static LOG_STATE: Mutex<LogState> = Mutex::new(LogState {
+    default: LevelFilter::Off,
+    filters: [ModuleFilter::empty(); MAX_MODULE_FILTERS],
+    sinks: LOG_SINK_CONSOLE,
+    ring: LogRing { buffer: [0; LOG_RING_SIZE], head: 0, len: 0 },
+});
+
+impl LogState {
+    // the longest module filter covering the target wins over the default
+    fn level_of(&self, target: &str) -> LevelFilter {
+        self.filters.iter()
+            .filter(|filter| filter.len != 0 && filter.covers(target))
+            .max_by_key(|filter| filter.len)
+            .map_or(self.default, |filter| filter.level)
+    }
+
+    fn set_level(&mut self, module: &[u8], level: Option<LevelFilter>) -> Result<(), ()> {
+        if module.is_empty() {
+            self.default = level.ok_or(())?;
+        } else if let Some(filter) = self.filters.iter_mut().find(|filter| filter.len != 0 && filter.module() == module) {
+            match level {
+                Some(level) => filter.level = level,
+                None => filter.len = 0,
+            }
+        } else if let Some(level) = level {
+            if module.len() > MAX_LOG_MODULE_LEN {
+                return Err(());
+            }
+            let filter = self.filters.iter_mut().find(|filter| filter.len == 0).ok_or(())?;
+            filter.module[..module.len()].copy_from_slice(module);
+            filter.len = module.len();
+            filter.level = level;
+        }
+        // let records through log's own check for whichever module wants the most
+        let max = self.filters.iter().filter(|filter| filter.len != 0).map(|filter| filter.level).fold(self.default, Ord::max);
+        log::set_max_level(max);
+        Ok(())
+    }
+}
 
 struct SimpleLogger;
 
 impl Log for SimpleLogger {
-    fn enabled(&self, _metadata: &Metadata) -> bool {
-        true
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= LOG_STATE.lock().level_of(metadata.target())
     }
     fn log(&self, record: &Record) {
-        if !self.enabled(record.metadata()) {
+        let mut state = LOG_STATE.lock();
+        if record.level() > state.level_of(record.target()) {
             return;
         }
-        let color = match record.level() {
-            Level::Error => 31, // Red
-            Level::Warn => 93,  // BrightYellow
-            Level::Info => 34,  // Blue
-            Level::Debug => 32, // Green
-            Level::Trace => 90, // BrightBlack
-        };
-        println!(
-            "\u{1B}[{}m[{:>5}] {}\u{1B}[0m",
-            color,
-            record.level(),
-            record.args(),
-        );
+        if state.sinks & LOG_SINK_CONSOLE != 0 {
+            let color = match record.level() {
+                Level::Error => 31, // Red
+                Level::Warn => 93,  // BrightYellow
+                Level::Info => 34,  // Blue
+                Level::Debug => 32, // Green
+                Level::Trace => 90, // BrightBlack
+            };
+            println!(
+                "\u{1B}[{}m[{:>5}] {}\u{1B}[0m",
+                color,
+                record.level(),
+                record.args(),
+            );
+        }
+        if state.sinks & LOG_SINK_RING != 0 {
+            let _ = writeln!(state.ring, "[{:>5}] {}", record.level(), record.args());
+        }
     }
     fn flush(&self) {}
 }
 
+fn to_level_filter(level: LogLevel) -> LevelFilter {
+    match level {
+        LogLevel::Off => LevelFilter::Off,
+        LogLevel::Error => LevelFilter::Error,
+        LogLevel::Warn => LevelFilter::Warn,
+        LogLevel::Info => LevelFilter::Info,
+        LogLevel::Debug => LevelFilter::Debug,
+        LogLevel::Trace => LevelFilter::Trace,
+    }
+}
+
+fn parse_level(level: &str) -> Option<LevelFilter> {
+    ["OFF", "ERROR", "WARN", "INFO", "DEBUG", "TRACE"].iter()
+        .position(|name| name.eq_ignore_ascii_case(level.trim()))
+        .and_then(LogLevel::from_usize)
+        .map(to_level_filter)
+}
+
+/// set the level of the records from `module` and the modules under it, or the default level if
+/// `module` is empty. `None` drops the filter of the module
+pub fn set_log_level(module: &[u8], level: Option<LogLevel>) -> Result<(), ()> {
+    LOG_STATE.lock().set_level(module, level.map(to_level_filter))
+}
+
+/// send the records to a mask of LOG_SINK_CONSOLE and LOG_SINK_RING
+pub fn set_log_sinks(sinks: usize) -> Result<(), ()> {
+    if sinks & !(LOG_SINK_CONSOLE | LOG_SINK_RING) != 0 {
+        return Err(());
+    }
+    LOG_STATE.lock().sinks = sinks;
+    Ok(())
+}
+
+/// move the oldest bytes of the log ring into `out`, returning how many
+pub fn read_log(out: &mut [u8]) -> usize {
+    LOG_STATE.lock().ring.drain(out)
+}
+
 pub fn init() {
     static LOGGER: SimpleLogger = SimpleLogger;
     log::set_logger(&LOGGER).unwrap();
-    log::set_max_level(match option_env!("LOG") {
-        Some("ERROR") => LevelFilter::Error,
-        Some("WARN") => LevelFilter::Warn,
-        Some("INFO") => LevelFilter::Info,
-        Some("DEBUG") => LevelFilter::Debug,
-        Some("TRACE") => LevelFilter::Trace,
-        _ => LevelFilter::Off,
-    });
+    let mut state = LOG_STATE.lock();
+    log::set_max_level(LevelFilter::Off);
+    for directive in option_env!("LOG").unwrap_or("").split(',').filter(|d| !d.trim().is_empty()) {
+        let (module, level) = directive.split_once('=').unwrap_or(("", directive));
+        let parsed = parse_level(level).ok_or(()).and_then(|level| state.set_level(module.trim().as_bytes(), Some(level)));
+        if parsed.is_err() {
+            println!("[kernel] ignoring LOG directive {}", directive);
+        }
+    }
 }
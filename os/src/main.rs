@@ -39,6 +39,10 @@ mod trap;
 mod inner_syscall;
 mod object;
 mod interrupt;
+mod smp;
+mod structures;
+#[cfg(feature = "benchmark")]
+mod benchmark;
 
 
 global_asm!(include_str!("entry.asm"));
@@ -54,7 +58,7 @@ pub fn clear_bss() {
 
 /// the rust entry-point of os
 #[no_mangle]
-pub fn rust_main() -> ! {
+pub fn rust_main(hart_id: usize, dtb_paddr: usize) -> ! {
     extern "C" {
         fn stext(); // begin addr of text segment
         fn etext(); // end addr of text segment
@@ -70,9 +74,12 @@ pub fn rust_main() -> ! {
     }
 
     clear_bss();
+    smp::init_cpu(hart_id);
+    smp::BKL.acquire();
     logging::init();
     mm::init();
-    boot::init();
+    smp::start_secondary_harts(hart_id);
+    boot::init(dtb_paddr);
     println!("[kernel] Hello, world!");
     trace!(
         "[kernel] .text [{:#x}, {:#x})",
@@ -104,3 +111,18 @@ pub fn rust_main() -> ! {
     // CI autotest failed : sbi::shutdown(true)
     sbi::shutdown(false)
 }
+
+/// the rust entry-point of the other harts, started by the boot hart
+#[no_mangle]
+pub fn rust_secondary_main(hart_id: usize) -> ! {
+    mm::init_secondary();
+    smp::init_cpu(hart_id);
+    smp::BKL.acquire();
+    debug!("[kernel] hart {} online", hart_id);
+    trap::init();
+    scheduler::init_secondary_core_state();
+    scheduler::schedule();
+    scheduler::activate_thread();
+    trap::restore_user_context();
+    sbi::shutdown(false)
+}
@@ -1,5 +1,7 @@
-use common::{types::{PTEPtr, ASIDSizeConstants}, utils::{convert_to_mut_type_ref, mask}};
+use common::{types::{PTEPtr, ASIDSizeConstants, Pptr}, utils::{convert_to_mut_type_ref, mask, bit}};
 use crate::boot::KS_ASID_TABLE;
+use crate::scheduler::{set_vm_root, get_current_tcb};
+use super::flush_asid;
 use log::{debug, error};
 
 use super::PageTableEntry;
@@ -9,14 +11,24 @@ pub struct ASIDPool {
 
 impl ASIDPool {
     pub fn write(&mut self, asid: usize, pte_ptr: PTEPtr) {
-        self.array[asid] = pte_ptr;
+        self.array[asid & mask(ASIDSizeConstants::ASIDLowBits as usize)] = pte_ptr;
     }
+
+    pub fn get(&self, asid: usize) -> PTEPtr {
+        self.array[asid & mask(ASIDSizeConstants::ASIDLowBits as usize)]
+    }
+}
+
+pub fn get_asid_pool_by_index(index: usize) -> Pptr {
+    KS_ASID_TABLE.lock()[index]
+}
+
+pub fn set_asid_pool_by_index(index: usize, pool_ptr: Pptr) {
+    KS_ASID_TABLE.lock()[index] = pool_ptr;
 }
 
 pub fn find_vspace_for_asid(asid: usize) -> Option<&'static mut PageTableEntry> {
-    let pool_ptr = unsafe {
-        KS_ASID_TABLE.lock()[asid >> ASIDSizeConstants::ASIDLowBits as usize]
-    };
+    let pool_ptr = get_asid_pool_by_index(asid >> ASIDSizeConstants::ASIDLowBits as usize);
 
     if pool_ptr == 0 {
         return None;
@@ -24,10 +36,40 @@ pub fn find_vspace_for_asid(asid: usize) -> Option<&'static mut PageTableEntry>
 
     let asid_pool = convert_to_mut_type_ref::<ASIDPool>(pool_ptr);
 
-    let vspace_root = asid_pool.array[asid & mask(ASIDSizeConstants::ASIDLowBits as usize)];
+    let vspace_root = asid_pool.get(asid);
     if vspace_root == 0 {
         return None;
     }
 
     return Some(convert_to_mut_type_ref::<PageTableEntry>(vspace_root));
-}
\ No newline at end of file
+}
+
+pub fn delete_asid(asid: usize, vspace: Pptr) {
+    let pool_ptr = get_asid_pool_by_index(asid >> ASIDSizeConstants::ASIDLowBits as usize);
+    if pool_ptr == 0 {
+        return;
+    }
+
+    let asid_pool = convert_to_mut_type_ref::<ASIDPool>(pool_ptr);
+    if asid_pool.get(asid) == vspace {
+        flush_asid(asid);
+        asid_pool.write(asid, 0);
+        set_vm_root(get_current_tcb());
+    }
+}
+
+pub fn delete_asid_pool(asid_base: usize, pool: Pptr) {
+    let index = asid_base >> ASIDSizeConstants::ASIDLowBits as usize;
+    if get_asid_pool_by_index(index) != pool {
+        return;
+    }
+
+    let asid_pool = convert_to_mut_type_ref::<ASIDPool>(pool);
+    for offset in 0..bit(ASIDSizeConstants::ASIDLowBits as usize) {
+        if asid_pool.get(asid_base + offset) != 0 {
+            flush_asid(asid_base + offset);
+        }
+    }
+    set_asid_pool_by_index(index, 0);
+    set_vm_root(get_current_tcb());
+}
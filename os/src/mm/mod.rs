@@ -2,20 +2,32 @@ mod page_table;
 mod asid;
 use log::debug;
 pub use page_table::{PageTableEntry, VMAttributes, VmRights, PTEFlags};
-pub use asid::{ASIDPool, find_vspace_for_asid};
+pub use asid::{ASIDPool, find_vspace_for_asid, get_asid_pool_by_index, set_asid_pool_by_index, delete_asid, delete_asid_pool};
 
 use riscv::register::satp;
-use riscv::asm::sfence_vma_all;
+use riscv::asm::{sfence_vma_all, sfence_vma};
 use spin::Mutex;
 use common::utils::*;
 
-use common::config::{CONFIG_PT_LEVELS, PPTR_BASE, PPTR_TOP, PADDR_BASE, PPTR_BASE_OFFSET, ROOT_PAGE_TABLE_SIZE, KERNEL_ELF_BASE, KERNEL_ELF_PADDR_BASE, PAGE_BITS, PV_BASE_OFFSET, PAGE_TABLE_INDEX_BITS};
+use common::config::{PAGE_SIZE, CONFIG_PT_LEVELS, PPTR_BASE, PPTR_TOP, PADDR_BASE, PPTR_BASE_OFFSET, ROOT_PAGE_TABLE_SIZE, KERNEL_ELF_BASE, KERNEL_ELF_PADDR_BASE, PAGE_BITS, PV_BASE_OFFSET, PAGE_TABLE_INDEX_BITS};
 use crate::cspace::{Cap, CapTag};
+use crate::sbi::remote_sfence_vma_asid;
+use crate::smp::other_online_harts_mask;
 use common::types::{Pptr, Vptr, Paddr, VirtRegion};
 
 pub fn init() {
     map_kernel_window();
     activate_kernel_vspace();
+    unsafe {
+        sfence_vma_all();
+    }
+}
+
+pub fn init_secondary() {
+    activate_kernel_vspace();
+    unsafe {
+        sfence_vma_all();
+    }
 }
 #[no_mangle]
 #[link_section = ".bss.root_pagetable"]
@@ -83,10 +95,11 @@ pub fn activate_kernel_vspace() {
     set_vspace_root(root_page_table_paddr, 0);
 }
 
+/// Translations are tagged with the ASID in satp and invalidated whenever a mapping
+/// changes, so switching address spaces needs no flush.
 pub fn set_vspace_root(paddr: Paddr, asid: usize) {
     unsafe {
         satp::set(satp::Mode::Sv39, asid, paddr >> PAGE_BITS);
-        sfence_vma_all();
     }
 }
 
@@ -133,7 +146,7 @@ pub fn map_it_pt_cap(vspace_cap: Cap, pt_cap: Cap) {
     }
 }
 
-pub fn map_frame_cap(vspace_cap: Cap, cap: Cap) {
+pub fn map_frame_cap(vspace_cap: Cap, cap: Cap, executable: bool) {
     let lvl1pt = convert_to_mut_type_ref::<[PageTableEntry; ROOT_PAGE_TABLE_SIZE]>(vspace_cap.get_cap_pptr());
     let frame_pptr = cap.get_cap_pptr();
     let frame_vptr = cap.get_frame_mapped_addr();
@@ -141,8 +154,8 @@ pub fn map_frame_cap(vspace_cap: Cap, cap: Cap) {
     let (pt_bits_left, pte_pptr) = look_up_pt_slot(lvl1pt, frame_vptr);
     assert_eq!(pt_bits_left, PAGE_BITS);
     let target_slot = convert_to_mut_type_ref::<PageTableEntry>(pte_pptr);
-    let flag = PTEFlags::D | PTEFlags::A | PTEFlags::U | PTEFlags::R | PTEFlags::W | PTEFlags::X | PTEFlags::V;
-    *target_slot = PageTableEntry::new((frame_pptr - PPTR_BASE_OFFSET) >> PAGE_BITS, 0, flag);
+    *target_slot = PageTableEntry::make_user_pte(frame_pptr - PPTR_BASE_OFFSET, executable,
+                                                 VmRights::from_usize(cap.get_frame_vm_right()));
     unsafe {
         sfence_vma_all();
     }
@@ -172,4 +185,70 @@ pub fn look_up_pt_slot2(lvl1pt: &mut PageTableEntry, vptr: Vptr) -> (usize, Pptr
 
 pub fn is_valid_vtable_root(cap: Cap) -> bool {
     return cap.get_cap_type() == CapTag::CapPageTableCap && cap.get_pt_is_mapped()
-}
\ No newline at end of file
+}
+pub fn unmap_page(page_size: usize, asid: usize, vptr: Vptr, pptr: Pptr) {
+    let lvl1pt = match find_vspace_for_asid(asid) {
+        Some(vspace_root) => vspace_root,
+        _ => return,
+    };
+
+    let (bits_left, pte_ptr) = look_up_pt_slot2(lvl1pt, vptr);
+    if Some(bits_left) != page_bits_for_size(page_size) {
+        return;
+    }
+
+    let pte = convert_to_mut_type_ref::<PageTableEntry>(pte_ptr);
+    if !pte.is_valid() || pte.is_pte_page_table() || pte.ppn() != addr_from_pptr(pptr) >> PAGE_BITS {
+        return;
+    }
+
+    *pte = PageTableEntry::empty();
+    flush_page(asid, vptr);
+}
+
+pub fn unmap_page_table(asid: usize, vptr: Vptr, target_pt: Pptr) {
+    let lvl1pt = match find_vspace_for_asid(asid) {
+        Some(vspace_root) => vspace_root,
+        _ => return,
+    };
+
+    let mut pt = lvl1pt as *mut PageTableEntry as Pptr;
+    let mut pt_slot: Pptr = 0;
+    let mut i = 0;
+    while i < CONFIG_PT_LEVELS - 1 && pt != target_pt {
+        pt_slot = pt + get_pt_index(vptr, i) * core::mem::size_of::<PageTableEntry>();
+        let pte = convert_to_mut_type_ref::<PageTableEntry>(pt_slot);
+        if !pte.is_pte_page_table() {
+            return;
+        }
+        pt = pte.get_pptr_from_hw_pte();
+        i += 1;
+    }
+
+    if pt != target_pt {
+        return;
+    }
+
+    *convert_to_mut_type_ref::<PageTableEntry>(pt_slot) = PageTableEntry::empty();
+    flush_asid(asid);
+}
+
+pub fn flush_page(asid: usize, vptr: Vptr) {
+    unsafe {
+        sfence_vma(asid, vptr);
+    }
+    let hart_mask = other_online_harts_mask();
+    if hart_mask != 0 {
+        remote_sfence_vma_asid(hart_mask, vptr, PAGE_SIZE, asid);
+    }
+}
+
+pub fn flush_asid(asid: usize) {
+    unsafe {
+        core::arch::asm!("sfence.vma x0, {}", in(reg) asid);
+    }
+    let hart_mask = other_online_harts_mask();
+    if hart_mask != 0 {
+        remote_sfence_vma_asid(hart_mask, 0, usize::MAX, asid);
+    }
+}
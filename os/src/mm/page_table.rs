@@ -1,8 +1,8 @@
 use bitflags::*;
 use common::config::{PAGE_BITS, PPTR_BASE_OFFSET};
-use common::types::{Pptr, CapRights};
+use common::types::Pptr;
+pub use kernel_lib::mm::VmRights;
 use common::utils::sign_extend;
-use riscv::asm::sfence_vma_all;
 bitflags! {
     pub struct PTEFlags: u8 {
         const V = 1 << 0;
@@ -16,17 +16,22 @@ bitflags! {
     }
 }
 
-#[derive(Copy, Clone)]
-#[repr(C)]
-pub struct PageTableEntry {
-    pub bits: usize,
-}
+pub use crate::structures::PageTableEntry;
 
 impl PageTableEntry {
     pub fn new(ppn: usize, sw: usize, flags: PTEFlags) -> Self {
-        PageTableEntry {
-            bits: ppn << 10 | (sw & 0x3) << 8 | flags.bits as usize,
-        }
+        let mut pte = Self::empty();
+        pte.set_ppn(ppn);
+        pte.set_sw(sw);
+        pte.set_dirty(flags.contains(PTEFlags::D));
+        pte.set_accessed(flags.contains(PTEFlags::A));
+        pte.set_global(flags.contains(PTEFlags::G));
+        pte.set_user(flags.contains(PTEFlags::U));
+        pte.set_execute(flags.contains(PTEFlags::X));
+        pte.set_write(flags.contains(PTEFlags::W));
+        pte.set_read(flags.contains(PTEFlags::R));
+        pte.set_valid(flags.contains(PTEFlags::V));
+        pte
     }
 
     pub fn pte_next(paddr: usize, is_leaf: bool) -> Self {
@@ -63,31 +68,25 @@ impl PageTableEntry {
 
     pub fn update(&mut self, pte: Self) {
         *self = pte;
-        unsafe {
-            sfence_vma_all();
-        }
     }
 
     pub fn empty() -> Self {
-        PageTableEntry { bits: 0 }
+        PageTableEntry { words: [0] }
     }
     pub fn ppn(&self) -> usize {
-        (self.bits >> 10 & ((1usize << 44) - 1)).into()
-    }
-    pub fn flags(&self) -> PTEFlags {
-        PTEFlags::from_bits(self.bits as u8).unwrap()
+        self.get_ppn()
     }
     pub fn is_valid(&self) -> bool {
-        (self.flags() & PTEFlags::V) != PTEFlags::empty()
+        self.get_valid()
     }
     pub fn readable(&self) -> bool {
-        (self.flags() & PTEFlags::R) != PTEFlags::empty()
+        self.get_read()
     }
     pub fn writable(&self) -> bool {
-        (self.flags() & PTEFlags::W) != PTEFlags::empty()
+        self.get_write()
     }
     pub fn executable(&self) -> bool {
-        (self.flags() & PTEFlags::X) != PTEFlags::empty()
+        self.get_execute()
     }
 
     pub fn is_pte_page_table(&self) -> bool {
@@ -115,41 +114,3 @@ impl VMAttributes {
         sign_extend(self.word[0] & 0x1, 0x0) != 0
     }
 }
-
-
-#[derive(PartialEq, Eq, Clone, Copy)]
-pub enum VmRights {
-    VMKernelOnly = 1,
-    VMReadOnly = 2,
-    VMReadWrite = 3
-}
-
-impl VmRights {
-    pub fn mask_vm_rights(&self, cap_rights_mask: CapRights) -> Self {
-        if *self == VmRights::VMReadOnly && cap_rights_mask.get_allow_read() {
-            return VmRights::VMReadOnly;
-        }
-        if *self == VmRights::VMReadWrite && cap_rights_mask.get_allow_read() {
-            if !cap_rights_mask.get_allow_write() {
-                return VmRights::VMReadOnly;
-            } else {
-                return VmRights::VMReadWrite;
-            }
-        }
-        return VmRights::VMKernelOnly;
-    }
-
-    pub fn from_usize(value: usize) -> Self {
-        unsafe {
-            core::mem::transmute::<u8, VmRights>(value  as u8)
-        }
-    }
-
-    pub fn get_write(&self) -> bool {
-        *self == VmRights::VMReadWrite
-    }
-
-    pub fn get_read(&self) -> bool {
-        *self != VmRights::VMKernelOnly
-    }
-}
\ No newline at end of file
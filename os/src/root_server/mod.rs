@@ -5,13 +5,15 @@ use core::sync::atomic::Ordering::SeqCst;
 use lazy_static::*;
 use log::{debug, error};
 use spin::Mutex;
+use crate::smp::num_nodes;
 use crate::cspace::{create_asid_pool_cap, create_asid_control_cap};
-use crate::boot::{BootInfo, BootInfoHeader, BootInfoID, NDKS_BOOT, KS_ASID_TABLE};
-use common::config::{CONFIG_MAX_NUM_NODES, CONFIG_PT_LEVELS, IT_ASID, MAX_NUM_FREEMEM_REG, PAGE_BITS, PPTR_BASE, ROOT_PAGE_TABLE_SIZE, UI_V_ENTRY,
+use crate::boot::{BootInfo, BootInfoHeader, BootInfoID, BootInfoBootModules, BootInfoPlatform, NDKS_BOOT, PLATFORM_INFO,
+                  UserImage, extra_bi_chunk_size};
+use common::config::{CONFIG_PT_LEVELS, IT_ASID, MAX_NUM_FREEMEM_REG, PAGE_BITS, PPTR_BASE, ROOT_PAGE_TABLE_SIZE,
     CONFIG_ROOT_CNODE_SIZE_BITS, SEL4_SLOT_BITS, SEL4_VSPACE_BITS, SEL4_TCB_BITS, SEL4_PAGE_BITS, BI_FRAME_SIZE_BITS, SEL4_ASID_POOL_BITS};
 use crate::cspace::{Cap, CapTag, create_bi_frame_cap, create_domain_cap, create_frame_cap, create_it_pt_cap, create_page_table_cap, create_root_cnode};
 use common::types::{CNodeSlot, ASIDSizeConstants};
-use crate::mm::{copy_global_mappings, get_n_paging, map_frame_cap, map_it_pt_cap, PageTableEntry, ASIDPool};
+use crate::mm::{copy_global_mappings, get_n_paging, map_frame_cap, map_it_pt_cap, PageTableEntry, ASIDPool, set_asid_pool_by_index, VmRights};
 use crate::scheduler::{KS_DOM_SCHEDULE, KS_DOM_SCHEDULE_IDX, create_idle_thread, create_initial_thread, init_core_state};
 use common::types::{NodeId, Pptr, Vptr, SlotRegion, VirtRegion, Region};
 use common::utils::{get_lvl_page_size, get_lvl_page_size_bits, round_down, bit, convert_to_mut_type_ref};
@@ -24,18 +26,23 @@ lazy_static! {
 }
 
 pub fn init(it_v_reg: VirtRegion, extra_bi_size_bits: usize, ipc_buf_vptr: Vptr, extra_bi_size: usize,
-            extra_bi_offset: usize, bi_frame_vptr: Vptr, extra_bi_frame_vptr: Vptr, ui_reg: Region,
-            ui_vp_offset: isize) -> Cap {
+            extra_bi_offset: usize, bi_frame_vptr: Vptr, extra_bi_frame_vptr: Vptr, ui_image: &UserImage,
+            modules_reg: Region, modules_vptr: Vptr) -> Cap {
     root_server_init(it_v_reg, extra_bi_size_bits);
-    populate_bi_frame(0, CONFIG_MAX_NUM_NODES, ipc_buf_vptr, extra_bi_size_bits, extra_bi_size);
-
-    init_boot_info_header(extra_bi_size, extra_bi_offset);
+    populate_bi_frame(0, num_nodes(), ipc_buf_vptr, extra_bi_size_bits, extra_bi_size);
 
     let (root_cnode_cap, it_vspace_cap, ipc_buf_cap) =  create_all_caps(it_v_reg, bi_frame_vptr,
                                                                         extra_bi_size, extra_bi_frame_vptr,
-                                                                        ipc_buf_vptr, ui_reg, ui_vp_offset);
+                                                                        ipc_buf_vptr, ui_image);
+    let mut extra_bi_offset = add_fdt_extra_bi(extra_bi_offset);
+    extra_bi_offset = add_platform_extra_bi(extra_bi_offset);
+    if modules_reg.start != modules_reg.end {
+        extra_bi_offset = create_boot_modules_frame_caps(root_cnode_cap, it_vspace_cap, modules_reg, modules_vptr,
+                                                         extra_bi_offset);
+    }
+    init_boot_info_header(extra_bi_size, extra_bi_offset);
     create_idle_thread();
-    let tcb = create_initial_thread(root_cnode_cap, it_vspace_cap, UI_V_ENTRY,
+    let tcb = create_initial_thread(root_cnode_cap, it_vspace_cap, ui_image.entry,
                                     bi_frame_vptr, ipc_buf_vptr, ipc_buf_cap);
 
     init_core_state(tcb);
@@ -81,8 +88,7 @@ pub fn root_server_init(it_v_reg: VirtRegion, extra_bi_size_bits: usize) {
 }
 
 fn create_all_caps(it_v_reg: VirtRegion, bi_frame_vptr: Vptr, extra_bi_size: usize,
-                   extra_bi_frame_vptr: Vptr,ipc_buf_vptr: Vptr, ui_reg: Region,
-                   ui_vp_offset: isize) -> (Cap, Cap, Cap) {
+                   extra_bi_frame_vptr: Vptr,ipc_buf_vptr: Vptr, ui_image: &UserImage) -> (Cap, Cap, Cap) {
     let root_cnode_cap = create_root_cnode();
     if root_cnode_cap.get_cap_type() == CapTag::CapNullCap {
         error!("root c-node creation failed");
@@ -93,7 +99,7 @@ fn create_all_caps(it_v_reg: VirtRegion, bi_frame_vptr: Vptr, extra_bi_size: usi
     let it_vspace_cap = create_it_address_space(root_cnode_cap, it_v_reg).unwrap();
 
     let bi_frame_cap = create_bi_frame_cap(root_cnode_cap, bi_frame_vptr, ROOT_SERVER.lock().boot_info);
-    map_frame_cap(it_vspace_cap, bi_frame_cap);
+    map_frame_cap(it_vspace_cap, bi_frame_cap, false);
 
     let boot_info = unsafe {
         convert_to_mut_type_ref::<BootInfo>(NDKS_BOOT.lock().boot_info_ptr)
@@ -110,42 +116,144 @@ fn create_all_caps(it_v_reg: VirtRegion, bi_frame_vptr: Vptr, extra_bi_size: usi
     let ipc_buf_ptr = ROOT_SERVER.lock().ipc_buf;
     (ipc_buf_ptr as usize..(ipc_buf_ptr + bit(PAGE_BITS)) as usize).for_each(|a| unsafe { (a as *mut u8).write_volatile(0) });
     let ipc_buf_cap = create_frame_cap(root_cnode_cap, ipc_buf_vptr, ROOT_SERVER.lock().ipc_buf,
-                                      CNodeSlot::SeL4CapInitThreadIpcBuffer as usize, IT_ASID);
-    map_frame_cap(it_vspace_cap, ipc_buf_cap);
+                                      CNodeSlot::SeL4CapInitThreadIpcBuffer as usize, IT_ASID, VmRights::VMReadWrite);
+    map_frame_cap(it_vspace_cap, ipc_buf_cap, false);
 
-    let user_image_frames_reg = create_frame_caps_of_region(root_cnode_cap, it_vspace_cap, ui_reg, ui_vp_offset, IT_ASID);
-    boot_info.user_image_frames = user_image_frames_reg;
+    boot_info.user_image_frames = create_user_image_frame_caps(root_cnode_cap, it_vspace_cap, ui_image);
 
     let it_ap_cap = create_asid_pool_cap(root_cnode_cap, IT_ASID, ROOT_SERVER.lock().asid_pool);
     create_asid_control_cap(root_cnode_cap);
+    #[cfg(feature = "mcs")]
+    {
+        boot_info.schedcontrol = create_sched_control_caps(root_cnode_cap);
+    }
     let asid_pool = convert_to_mut_type_ref::<ASIDPool>(it_ap_cap.get_cap_pptr());
     asid_pool.write(IT_ASID, it_vspace_cap.get_cap_pptr());
-    KS_ASID_TABLE.lock()[IT_ASID >> ASIDSizeConstants::ASIDLowBits as usize] = asid_pool as *const ASIDPool as usize;
+    set_asid_pool_by_index(IT_ASID >> ASIDSizeConstants::ASIDLowBits as usize, asid_pool as *const ASIDPool as usize);
     (root_cnode_cap, it_vspace_cap, ipc_buf_cap)
 }
 
+// one per online core, in the order of their ids
+#[cfg(feature = "mcs")]
+fn create_sched_control_caps(root_cnode_cap: Cap) -> SlotRegion {
+    let slot_before = NDKS_BOOT.lock().slot_pos_cur;
+    for core in (0..common::config::CPU_NUM).filter(|core| crate::smp::is_cpu_online(*core)) {
+        let slot = NDKS_BOOT.lock().slot_pos_cur;
+        crate::cspace::write_slot(root_cnode_cap.get_cap_pptr(), slot, Cap::new_sched_control_cap(core));
+        NDKS_BOOT.lock().slot_pos_cur += 1;
+    }
+    let slot_after = NDKS_BOOT.lock().slot_pos_cur;
+    SlotRegion {
+        start: slot_before,
+        end: slot_after,
+    }
+}
+
 fn maybe_create_extra_bi_frame_cap(root_cnode_cap: Cap, vspace_cap: Cap, extra_bi_size: usize,
                                    extra_bi_frame_vptr: Vptr) -> Option<SlotRegion> {
     if extra_bi_size > 0 {
         let mut start = ROOT_SERVER.lock().extra_bi;
         let end = start + extra_bi_size;
         let pv_offset = ((start - PPTR_BASE) as isize) - (extra_bi_frame_vptr as isize);
-        return Some(create_frame_caps_of_region(root_cnode_cap, vspace_cap, Region { start, end }, pv_offset, IT_ASID));
+        return Some(create_frame_caps_of_region(root_cnode_cap, vspace_cap, Region { start, end }, pv_offset, IT_ASID,
+                                                VmRights::VMReadWrite, false));
     }
     None
 }
 
+fn create_boot_modules_frame_caps(root_cnode_cap: Cap, vspace_cap: Cap, modules_reg: Region, modules_vptr: Vptr,
+                                  extra_bi_offset: usize) -> usize {
+    let pv_offset = ((modules_reg.start - PPTR_BASE) as isize) - (modules_vptr as isize);
+    let frames = create_frame_caps_of_region(root_cnode_cap, vspace_cap, modules_reg, pv_offset, IT_ASID,
+                                             VmRights::VMReadOnly, false);
+    let modules = BootInfoBootModules {
+        vptr: modules_vptr,
+        size: modules_reg.end - modules_reg.start,
+        frames,
+    };
+    debug!("boot modules: {:#x} ... {:#x}, frames: {:?}", modules_vptr, modules_vptr + modules.size, frames);
+    add_extra_bi_chunk(extra_bi_offset, BootInfoID::Sel4BootInfoHeaderBootModules, struct_as_bytes(&modules))
+}
+
+fn add_fdt_extra_bi(extra_bi_offset: usize) -> usize {
+    let fdt = PLATFORM_INFO.lock().fdt;
+    let fdt_bytes = unsafe {
+        core::slice::from_raw_parts((fdt.start + PPTR_BASE) as *const u8, fdt.end - fdt.start)
+    };
+    add_extra_bi_chunk(extra_bi_offset, BootInfoID::Sel4BootInfoHeaderFdt, fdt_bytes)
+}
+
+fn add_platform_extra_bi(extra_bi_offset: usize) -> usize {
+    let platform_info = PLATFORM_INFO.lock();
+    let platform = BootInfoPlatform {
+        kernel_version: [
+            env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+            env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+            env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+        ],
+        timebase_freq: platform_info.timebase_freq,
+        num_mem: platform_info.num_mem,
+        mem: platform_info.mem,
+    };
+    add_extra_bi_chunk(extra_bi_offset, BootInfoID::Sel4BootInfoHeaderPlatform, struct_as_bytes(&platform))
+}
 
-fn create_frame_caps_of_region(root_cnode_cap: Cap,vspace_cap: Cap, reg: Region, pv_offset: isize, asid: usize) -> SlotRegion {
+fn struct_as_bytes<T>(value: &T) -> &[u8] {
+    unsafe {
+        core::slice::from_raw_parts(value as *const T as *const u8, core::mem::size_of::<T>())
+    }
+}
+
+fn add_extra_bi_chunk(extra_bi_offset: usize, id: BootInfoID, payload: &[u8]) -> usize {
+    let header_size = core::mem::size_of::<BootInfoHeader>();
+    let extra_bi = ROOT_SERVER.lock().extra_bi + extra_bi_offset;
+    let header = convert_to_mut_type_ref::<BootInfoHeader>(extra_bi);
+    header.id = id as usize;
+    header.len = extra_bi_chunk_size(payload.len());
+    unsafe {
+        core::ptr::copy_nonoverlapping(payload.as_ptr(), (extra_bi + header_size) as *mut u8, payload.len());
+    }
+    extra_bi_offset + header.len
+}
+
+fn create_user_image_frame_caps(root_cnode_cap: Cap, vspace_cap: Cap, ui_image: &UserImage) -> SlotRegion {
+    let slot_before = NDKS_BOOT.lock().slot_pos_cur;
+    let mut vptr = ui_image.v_reg.start;
+    while vptr < ui_image.v_reg.end {
+        let rights = ui_image.page_rights(vptr);
+        let mut run_end = vptr + bit(PAGE_BITS);
+        while run_end < ui_image.v_reg.end && ui_image.page_rights(run_end) == rights {
+            run_end += bit(PAGE_BITS);
+        }
+        if let Some((writable, executable)) = rights {
+            let vm_rights = if writable { VmRights::VMReadWrite } else { VmRights::VMReadOnly };
+            let reg = Region {
+                start: (vptr as isize + ui_image.pv_offset) as usize + PPTR_BASE,
+                end: (run_end as isize + ui_image.pv_offset) as usize + PPTR_BASE,
+            };
+            create_frame_caps_of_region(root_cnode_cap, vspace_cap, reg, ui_image.pv_offset, IT_ASID, vm_rights, executable);
+        }
+        vptr = run_end;
+    }
+    let slot_after = NDKS_BOOT.lock().slot_pos_cur;
+    SlotRegion {
+        start: slot_before,
+        end: slot_after,
+    }
+}
+
+
+fn create_frame_caps_of_region(root_cnode_cap: Cap,vspace_cap: Cap, reg: Region, pv_offset: isize, asid: usize,
+                               vm_rights: VmRights, executable: bool) -> SlotRegion {
     let slot_before = NDKS_BOOT.lock().slot_pos_cur;
     debug!("[create_frame_caps_of_region] reg: {:#x} ... {:#x}", (reg.start - PPTR_BASE) as isize - pv_offset, (reg.end - PPTR_BASE) as isize - pv_offset);
     let mut start = reg.start;
     while start < reg.end {
 
         let frame_cap = create_frame_cap(root_cnode_cap, ((start - PPTR_BASE) as isize - pv_offset) as usize,
-                                            start, NDKS_BOOT.lock().slot_pos_cur, asid);
+                                            start, NDKS_BOOT.lock().slot_pos_cur, asid, vm_rights);
         NDKS_BOOT.lock().slot_pos_cur += 1;
-        map_frame_cap(vspace_cap, frame_cap);
+        map_frame_cap(vspace_cap, frame_cap, executable);
         start += bit(PAGE_BITS);
     }
     let slot_after = NDKS_BOOT.lock().slot_pos_cur;
@@ -249,6 +357,10 @@ fn calculate_root_server_size(it_v_reg: VirtRegion, extra_bi_size_bits: usize) -
         0
     };
     size += bit(SEL4_VSPACE_BITS);
+    #[cfg(feature = "mcs")]
+    {
+        size += bit(common::sched_context::SEL4_MIN_SCHED_CONTEXT_BITS);
+    }
 
     size + get_n_paging(it_v_reg) * bit(SEL4_PAGE_BITS)
 }
@@ -279,6 +391,10 @@ fn create_root_server_objects(start: usize, it_v_reg: VirtRegion, extra_bi_size_
     ROOT_SERVER.lock().paging = Region {start, end};
 
     ROOT_SERVER.lock().tcb = alloc_root_server_obj(SEL4_TCB_BITS, 1);
+    #[cfg(feature = "mcs")]
+    {
+        ROOT_SERVER.lock().sc = alloc_root_server_obj(common::sched_context::SEL4_MIN_SCHED_CONTEXT_BITS, 1);
+    }
 
     {
         let root_server_mm = ROOT_SERVER_MEM.lock();
@@ -9,5 +9,7 @@ pub struct RootServer {
     pub boot_info: Pptr,
     pub extra_bi: Pptr,
     pub tcb: Pptr,
+    #[cfg(feature = "mcs")]
+    pub sc: Pptr,
     pub paging: Region,
 }
\ No newline at end of file
@@ -26,3 +26,16 @@ pub fn shutdown(failure: bool) -> ! {
 pub fn set_timer(timer: usize) {
     sbi_rt::set_timer(timer as _);
 }
+
+/// use sbi hsm call to start a stopped hart at `start_addr` with `opaque` in a1
+pub fn hart_start(hart_id: usize, start_addr: usize, opaque: usize) -> bool {
+    sbi_rt::hart_start(hart_id, start_addr, opaque).is_ok()
+}
+
+pub fn send_ipi(hart_mask: usize) {
+    sbi_rt::send_ipi(hart_mask, 0);
+}
+
+pub fn remote_sfence_vma_asid(hart_mask: usize, start: usize, size: usize, asid: usize) {
+    sbi_rt::remote_sfence_vma_asid(hart_mask, 0, start, size, asid);
+}
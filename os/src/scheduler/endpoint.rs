@@ -1,21 +1,19 @@
-use crate::scheduler::tcb::TCBQueue;
-use common::types::Pptr;
-use common::utils::sign_extend;
-
-pub struct EndPoint {
-    words: [usize; 2],
-}
+use crate::scheduler::tcb::{TCB, TCBQueue, ThreadStateEnum};
+use crate::cspace::Cap;
+#[cfg(not(feature = "mcs"))]
+use crate::cspace::{CapTableEntry, cte_delete};
+use crate::ipc::do_ipc_transfer;
+use common::{types::Pptr, utils::convert_to_mut_type_ref, register::BADGE_REGISTER};
+pub use crate::structures::EndPoint;
+#[cfg(feature = "mcs")]
+use log::error;
+use super::{possible_switch_to, re_schedule};
+#[cfg(feature = "mcs")]
+use super::reply::{Reply, reply_push, reply_remove, reply_unlink};
+#[cfg(feature = "mcs")]
+use super::sched_context::{timeout_handler, handle_timeout, postpone};
 
 impl EndPoint {
-    pub fn get_queue_head(&self) -> Pptr {
-        sign_extend(self.words[1] & 0xffffffffffffffff, 0x0)
-    }
-
-    pub fn set_queue_head(&mut self, pptr: Pptr) {
-        self.words[1] &= !0xffffffffffffffff;
-        self.words[1] |= pptr & 0xffffffffffffffff;
-    }
-
     pub fn get_queue(&self) -> TCBQueue {
         TCBQueue::new(self.get_queue_head(), self.get_queue_tail())
     }
@@ -25,24 +23,26 @@ impl EndPoint {
         self.set_queue_tail(queue.end as Pptr);
     }
 
-    pub fn get_queue_tail(&self) -> Pptr {
-        sign_extend(self.words[0] & 0x7ffffffffc, 0xffffff8000000000)
-    }
-
-    pub fn set_queue_tail(&mut self, pptr: Pptr) {
-        self.words[0] &= !0x7ffffffffc;
-        self.words[0] |= pptr & 0x7ffffffffc;
-    }
-
     pub fn get_state(&self) -> EndPointState {
         unsafe {
-            core::mem::transmute::<u8, EndPointState>(sign_extend(self.words[0] & 0x3, 0x0) as u8)
+            core::mem::transmute::<u8, EndPointState>(self.get_ep_state() as u8)
         }
     }
 
     pub fn set_state(&mut self, state: EndPointState) {
-        self.words[0] &= !0x3;
-        self.words[0] |= (state as usize) & 0x3;
+        self.set_ep_state(state as usize);
+    }
+
+    // the first thread of the queue, leaving the endpoint idle if it was the last
+    fn pop_queue(&mut self) -> &'static mut TCB {
+        let mut queue = self.get_queue();
+        let thread = unsafe { &mut *queue.head };
+        queue.de_queue(thread);
+        self.set_queue(&queue);
+        if queue.head.is_null() {
+            self.set_state(EndPointState::EPStateIdle);
+        }
+        thread
     }
 }
 
@@ -51,4 +51,187 @@ pub enum EndPointState {
     EPStateIdle = 0,
     EPStateSend = 1,
     EPStateRecv = 2,
-}
\ No newline at end of file
+}
+
+pub fn send_ipc(blocking: bool, do_call: bool, badge: usize, can_grant: bool, can_grant_reply: bool,
+                thread: &mut TCB, ep: &mut EndPoint) {
+    let ep_ptr = ep as *mut EndPoint as Pptr;
+    match ep.get_state() {
+        EndPointState::EPStateIdle | EndPointState::EPStateSend => {
+            if blocking {
+                thread.tcb_state.set_blocking_object(ep_ptr);
+                thread.tcb_state.set_blocking_ipc_badge(badge);
+                thread.tcb_state.set_blocking_ipc_can_grant(can_grant);
+                thread.tcb_state.set_blocking_ipc_can_grant_reply(can_grant_reply);
+                thread.tcb_state.set_blocking_ipc_is_call(do_call);
+                thread.set_thread_state(ThreadStateEnum::ThreadStateBlockedOnSend);
+                let mut queue = ep.get_queue();
+                queue.en_queue(thread);
+                ep.set_state(EndPointState::EPStateSend);
+                ep.set_queue(&queue);
+            }
+        }
+
+        EndPointState::EPStateRecv => {
+            let dest = ep.pop_queue();
+            do_ipc_transfer(thread, ep_ptr, badge, can_grant, dest);
+            #[cfg(not(feature = "mcs"))]
+            if do_call {
+                if can_grant || can_grant_reply {
+                    let reply_can_grant = dest.tcb_state.is_get_blocking_ipc_can_grant();
+                    thread.setup_caller_cap(dest, reply_can_grant);
+                } else {
+                    thread.set_thread_state(ThreadStateEnum::ThreadStateInactive);
+                }
+            }
+            #[cfg(feature = "mcs")]
+            {
+                let reply = dest.tcb_state.get_reply_object();
+                if reply != 0 {
+                    reply_unlink(convert_to_mut_type_ref::<Reply>(reply), dest);
+                }
+                // a caller lends its scheduling context to a passive receiver, a faulting thread never does
+                if do_call || thread.tcb_fault.has_fault() {
+                    if reply != 0 && (can_grant || can_grant_reply) {
+                        reply_push(thread, dest, convert_to_mut_type_ref::<Reply>(reply), do_call);
+                    } else {
+                        thread.set_thread_state(ThreadStateEnum::ThreadStateInactive);
+                    }
+                }
+            }
+            dest.set_thread_state(ThreadStateEnum::ThreadStateRunning);
+            possible_switch_to(dest);
+        }
+    }
+}
+
+/// with mcs, a call received is answered through `reply`, 0 if the receiver takes no calls
+pub fn receive_ipc(thread: &mut TCB, cap: Cap, is_blocking: bool, #[cfg(feature = "mcs")] reply: Pptr) {
+    let ep_ptr = cap.get_ep_ptr();
+    let ep = convert_to_mut_type_ref::<EndPoint>(ep_ptr);
+    #[cfg(feature = "mcs")]
+    if reply != 0 {
+        let reply_tcb = convert_to_mut_type_ref::<Reply>(reply).reply_tcb;
+        if reply_tcb != 0 && reply_tcb != thread as *mut TCB as Pptr {
+            error!("[receive_ipc] reply object already has an unexecuted reply");
+            convert_to_mut_type_ref::<TCB>(reply_tcb).cancel_ipc();
+        }
+    }
+    match ep.get_state() {
+        EndPointState::EPStateIdle | EndPointState::EPStateRecv => {
+            if is_blocking {
+                thread.tcb_state.set_blocking_object(ep_ptr);
+                thread.tcb_state.set_blocking_ipc_can_grant(cap.get_ep_can_grant());
+                #[cfg(feature = "mcs")]
+                {
+                    thread.tcb_state.set_reply_object(reply);
+                    if reply != 0 {
+                        convert_to_mut_type_ref::<Reply>(reply).reply_tcb = thread as *mut TCB as Pptr;
+                    }
+                }
+                thread.set_thread_state(ThreadStateEnum::ThreadStateBlockedOnReceive);
+                let mut queue = ep.get_queue();
+                queue.en_queue(thread);
+                ep.set_state(EndPointState::EPStateRecv);
+                ep.set_queue(&queue);
+            } else {
+                thread.set_register(BADGE_REGISTER, 0);
+            }
+        }
+
+        EndPointState::EPStateSend => {
+            let sender = ep.pop_queue();
+            let can_grant = sender.tcb_state.is_get_blocking_ipc_can_grant();
+            let can_grant_reply = sender.tcb_state.is_get_blocking_ipc_can_grant_reply();
+            let do_call = sender.tcb_state.is_get_blocking_ipc_is_call();
+            do_ipc_transfer(sender, ep_ptr, sender.tcb_state.get_blocking_ipc_badge(), can_grant, thread);
+            #[cfg(not(feature = "mcs"))]
+            let blocks = do_call;
+            #[cfg(feature = "mcs")]
+            let blocks = do_call || sender.tcb_fault.has_fault();
+            if blocks {
+                #[cfg(not(feature = "mcs"))]
+                if can_grant || can_grant_reply {
+                    sender.setup_caller_cap(thread, cap.get_ep_can_grant());
+                } else {
+                    sender.set_thread_state(ThreadStateEnum::ThreadStateInactive);
+                }
+                #[cfg(feature = "mcs")]
+                if reply != 0 && (can_grant || can_grant_reply) {
+                    let can_donate = sender.tcb_sched_context != 0 && !sender.tcb_fault.is_timeout();
+                    reply_push(sender, thread, convert_to_mut_type_ref::<Reply>(reply), can_donate);
+                } else {
+                    sender.set_thread_state(ThreadStateEnum::ThreadStateInactive);
+                }
+            } else {
+                sender.set_thread_state(ThreadStateEnum::ThreadStateRunning);
+                possible_switch_to(sender);
+            }
+        }
+    }
+}
+
+/// answer `receiver`, blocked on its call since `sender` received it, and drop the caller cap
+/// in `slot` that allowed it
+#[cfg(not(feature = "mcs"))]
+pub fn do_reply_transfer(sender: &mut TCB, receiver: &mut TCB, slot: &mut CapTableEntry, can_grant: bool) {
+    assert_eq!(receiver.get_state(), ThreadStateEnum::ThreadStateBlockedOnReply);
+    do_ipc_transfer(sender, 0, 0, can_grant, receiver);
+    cte_delete(slot, true);
+    receiver.set_thread_state(ThreadStateEnum::ThreadStateRunning);
+    possible_switch_to(receiver);
+}
+
+/// answer the thread blocked on `reply`, if it still is. the reply to a fault carries no message,
+/// the thread just goes on where it stopped
+#[cfg(feature = "mcs")]
+pub fn do_reply_transfer(sender: &mut TCB, reply: &mut Reply, can_grant: bool) {
+    // nobody to answer, or only the receiver waiting for its next call
+    if reply.reply_tcb == 0 {
+        return;
+    }
+    let receiver = convert_to_mut_type_ref::<TCB>(reply.reply_tcb);
+    if receiver.get_state() != ThreadStateEnum::ThreadStateBlockedOnReply {
+        return;
+    }
+    reply_remove(reply, receiver);
+    let was_timeout = receiver.tcb_fault.is_timeout();
+    if receiver.tcb_fault.has_fault() {
+        receiver.tcb_fault.clear();
+        receiver.set_thread_state(ThreadStateEnum::ThreadStateRestart);
+    } else {
+        do_ipc_transfer(sender, 0, 0, can_grant, receiver);
+        receiver.set_thread_state(ThreadStateEnum::ThreadStateRunning);
+    }
+    let Some(sc) = receiver.sched_context() else {
+        return;
+    };
+    if !receiver.is_runnable() {
+        return;
+    }
+    if sc.refill_ready() && sc.refill_sufficient(0) {
+        possible_switch_to(receiver);
+    } else if !was_timeout && timeout_handler(receiver).is_some() {
+        handle_timeout(receiver);
+    } else {
+        postpone(receiver);
+    }
+}
+
+/// restart every thread queued on `ep`, which is going away
+pub fn cancel_all_ipc(ep: &mut EndPoint) {
+    if ep.get_state() == EndPointState::EPStateIdle {
+        return;
+    }
+    let mut thread = ep.get_queue_head();
+    ep.set_state(EndPointState::EPStateIdle);
+    ep.set_queue_head(0);
+    ep.set_queue_tail(0);
+    while thread != 0 {
+        let tcb = convert_to_mut_type_ref::<TCB>(thread);
+        thread = tcb.tcb_ep_next;
+        tcb.set_thread_state(ThreadStateEnum::ThreadStateRestart);
+        tcb.enqueue_to_sched();
+    }
+    re_schedule();
+}
@@ -2,6 +2,11 @@ mod domain_schedule;
 mod tcb;
 mod scheduler;
 mod endpoint;
+mod notification;
+#[cfg(feature = "mcs")]
+mod sched_context;
+#[cfg(feature = "mcs")]
+mod reply;
 
 use core::sync::atomic::{AtomicUsize, Ordering};
 use lazy_static::*;
@@ -12,21 +17,31 @@ use spin::Mutex;
 use domain_schedule::DomainScheduler;
 
 pub use tcb::{TCB, IdleTCB, ThreadStateEnum, TCBCNode};
+pub use notification::{Notification, send_signal, receive_signal, cancel_all_signals};
+pub use endpoint::{EndPoint, send_ipc, receive_ipc, cancel_all_ipc, do_reply_transfer};
+#[cfg(feature = "mcs")]
+pub use sched_context::{SchedContext, update_timestamp, sched_context_bind_tcb, sched_context_unbind_tcb,
+    sched_context_bind_ntfn, sched_context_unbind_ntfn, sched_context_finalise, sched_context_commit_if_current,
+    sched_context_consumed};
+#[cfg(feature = "mcs")]
+pub use reply::{Reply, reply_finalise};
 
 use common::{config::{CPU_NUM, SEL4_IDLE_TCB_SLOT_SIZE, TCB_OFFSET, CONFIG_KERNEL_STACK_BITS, CONFIG_NUM_DOMAINS, NUM_READY_QUEUES,
-    L2_BITMAP_SIZE, WORD_RADIX, WORD_BITS, SEL4_TCB_BITS, CONFIG_NUM_PRIORITIES, CONFIG_TIME_SLICE}, types::Pptr, register::CAP_REGISTER};
+    SEL4_TCB_BITS, CONFIG_NUM_PRIORITIES, CONFIG_TIME_SLICE}, types::Pptr, register::CAP_REGISTER};
 use crate::mm::activate_kernel_vspace;
 use common::config::PPTR_BASE_OFFSET;
 use crate::cspace::{Cap, CNode, create_init_thread_cap, cte_insert, derive_cap, TCBCNodeIndex};
 use crate::cspace::CapTag::CapPageTableCap;
 use common::types::CNodeSlot::{SeL4CapInitThreadCNode, SeL4CapInitThreadIpcBuffer, SeL4CapInitThreadVspace};
 use crate::cspace::TCBCNodeIndex::TCBVTable;
-use crate::mm::set_vspace_root;
+use crate::mm::{set_vspace_root, find_vspace_for_asid, PageTableEntry};
 use crate::root_server::ROOT_SERVER;
 use crate::scheduler::domain_schedule::{KS_CUR_DOMAIN, KS_DOMAIN_TIME, PriorityConst};
 use crate::scheduler::tcb::ThreadStateEnum::ThreadStateRunning;
 use common::types::Vptr;
-use common::utils::{hart_id, prio_2_l1_index, invert_l1_index, bit, mask, l1_index_2_prio, convert_to_mut_type_ref, convert_to_type_ref};
+use crate::smp::{hart_id, mark_reschedule_pending};
+use common::utils::{bit, convert_to_mut_type_ref, convert_to_type_ref};
+use kernel_lib::scheduler::ReadyQueuesBitmap;
 
 use self::tcb::TCBQueue;
 lazy_static!{
@@ -39,17 +54,25 @@ lazy_static!{
 static mut KS_IDLE_THREAD_TCB: [IdleTCB; CPU_NUM] = [IdleTCB {array: [0; SEL4_IDLE_TCB_SLOT_SIZE]}; CPU_NUM];
 
 static mut KS_IDLE_THREAD: [Pptr; CPU_NUM] = [0; CPU_NUM];
-static mut KERNEL_STACK: [[u8; 1 << CONFIG_KERNEL_STACK_BITS]; CPU_NUM] = [[0; 1 << CONFIG_KERNEL_STACK_BITS]; CPU_NUM];
+
+#[derive(Clone, Copy)]
+#[repr(C, align(4096))]
+pub struct KernelStack {
+    pub array: [u8; 1 << CONFIG_KERNEL_STACK_BITS],
+}
+
+#[no_mangle]
+pub static mut KERNEL_STACK: [KernelStack; CPU_NUM] = [KernelStack {array: [0; 1 << CONFIG_KERNEL_STACK_BITS]}; CPU_NUM];
 pub static mut KS_CUR_THREAD: [Pptr; CPU_NUM] = [0; CPU_NUM];
 static mut KS_SCHEDULER_ACTION: [Pptr; CPU_NUM] = [0; CPU_NUM];
 const SCHEDULER_ACTION_RESUME_CURRENT_THREAD: usize = 0;
 const SCHEDULER_ACTION_CHOOSE_NEW_THREAD: usize = 1;
 
-static mut KS_READY_QUEUES: [TCBQueue; NUM_READY_QUEUES] = [TCBQueue { head: 0 as *mut TCB, end: 0 as *mut TCB }; NUM_READY_QUEUES];
-
-static mut KS_READY_QUEUES_L1_BITMAP: [usize; CONFIG_NUM_DOMAINS] = [0; CONFIG_NUM_DOMAINS];
+static mut KS_READY_QUEUES: [[TCBQueue; NUM_READY_QUEUES]; CPU_NUM] =
+    [[TCBQueue { head: 0 as *mut TCB, end: 0 as *mut TCB }; NUM_READY_QUEUES]; CPU_NUM];
 
-static mut KS_READY_QUEUES_L2_BITMAP: [[usize; L2_BITMAP_SIZE]; CONFIG_NUM_DOMAINS] = [[0; L2_BITMAP_SIZE]; CONFIG_NUM_DOMAINS];
+static mut KS_READY_QUEUES_BITMAP: [[ReadyQueuesBitmap; CONFIG_NUM_DOMAINS]; CPU_NUM] =
+    [[ReadyQueuesBitmap::new(); CONFIG_NUM_DOMAINS]; CPU_NUM];
 
 pub fn create_idle_thread() {
     debug!("sizeof TCB: {}", core::mem::size_of::<TCB>());
@@ -60,7 +83,7 @@ pub fn create_idle_thread() {
             KS_IDLE_THREAD[i] = pptr + TCB_OFFSET;
             debug!("KS_IDLE_THREAD[i]: {:#x}", KS_IDLE_THREAD[i]);
             let tcb = convert_to_mut_type_ref::<TCB>(KS_IDLE_THREAD[i]);
-            tcb.configure_idle_thread();
+            tcb.configure_idle_thread(i);
         }
     }
 }
@@ -107,9 +130,13 @@ pub fn create_initial_thread(root_cnode_cap: Cap, vspace_cap: Cap, v_entry: Vptr
     tcb.tcb_priority = PriorityConst::MaxPrio as usize;
     tcb.tcb_mcp = PriorityConst::MaxPrio as usize;
     tcb.tcb_domain = KS_DOM_SCHEDULE.lock()[KS_DOM_SCHEDULE_IDX.load(SeqCst)].domain;
+    tcb.tcb_affinity = hart_id();
 
+    #[cfg(not(feature = "mcs"))]
     tcb.setup_replay_master();
     tcb.set_thread_state(ThreadStateRunning);
+    #[cfg(feature = "mcs")]
+    create_initial_sched_context(root_cnode_cap, tcb);
 
     KS_CUR_DOMAIN.store(KS_DOM_SCHEDULE.lock()[KS_DOM_SCHEDULE_IDX.load(SeqCst)].domain, SeqCst);
     KS_DOMAIN_TIME.store(KS_DOM_SCHEDULE.lock()[KS_DOM_SCHEDULE_IDX.load(SeqCst)].length, SeqCst);
@@ -119,7 +146,26 @@ pub fn create_initial_thread(root_cnode_cap: Cap, vspace_cap: Cap, v_entry: Vptr
     tcb as *const TCB
 }
 
+// the root server runs round robin on the boot core
+#[cfg(feature = "mcs")]
+fn create_initial_sched_context(root_cnode_cap: Cap, tcb: &mut TCB) {
+    use common::sched_context::{DEFAULT_TIMESLICE_US, MIN_REFILLS, SEL4_MIN_SCHED_CONTEXT_BITS};
+    use common::types::CNodeSlot::SeL4CapInitThreadSC;
+
+    let sc_ptr = ROOT_SERVER.lock().sc;
+    let sc = convert_to_mut_type_ref::<SchedContext>(sc_ptr);
+    let budget = crate::interrupt::us_to_ticks(DEFAULT_TIMESLICE_US);
+    sc.refill_new(MIN_REFILLS, budget, budget);
+    sc.sc_core = hart_id();
+    sc.sc_tcb = tcb as *mut TCB as Pptr;
+    tcb.tcb_sched_context = sc_ptr;
+    crate::cspace::write_slot(root_cnode_cap.get_cap_pptr(), SeL4CapInitThreadSC as usize,
+                              Cap::new_sched_context_cap(sc_ptr, SEL4_MIN_SCHED_CONTEXT_BITS));
+}
+
 pub fn init_core_state(tcb: *const TCB) {
+    #[cfg(feature = "mcs")]
+    sched_context::init_timestamp();
     unsafe {
         KS_SCHEDULER_ACTION[hart_id()] = tcb as Pptr;
         debug!("KS_SCHEDULER_ACTION[hart_id()]: {:#x}", KS_SCHEDULER_ACTION[hart_id()]);
@@ -127,13 +173,22 @@ pub fn init_core_state(tcb: *const TCB) {
     }
 }
 
+pub fn init_secondary_core_state() {
+    #[cfg(feature = "mcs")]
+    sched_context::init_timestamp();
+    unsafe {
+        KS_SCHEDULER_ACTION[hart_id()] = SCHEDULER_ACTION_CHOOSE_NEW_THREAD;
+        KS_CUR_THREAD[hart_id()] = KS_IDLE_THREAD[hart_id()];
+    }
+}
+
 pub fn choose_thread() {
     let dom = 0;
 
     unsafe {
-        if KS_READY_QUEUES_L1_BITMAP[dom] != 0 {
+        if !KS_READY_QUEUES_BITMAP[hart_id()][dom].is_empty() {
             let prio = get_highest_prio(dom);
-            let thread = &mut *(KS_READY_QUEUES[ready_queues_index(dom, prio)].head);
+            let thread = &mut *(KS_READY_QUEUES[hart_id()][ready_queues_index(dom, prio)].head);
             assert!(thread.is_schedulable());
             switch_to_thread(thread);
         } else {
@@ -152,6 +207,8 @@ pub fn schedule_choose_new_thread() {
 }
 
 pub fn schedule() {
+    #[cfg(feature = "mcs")]
+    sched_context::awaken();
     unsafe {
         if KS_SCHEDULER_ACTION[hart_id()] != SCHEDULER_ACTION_RESUME_CURRENT_THREAD {
             let mut was_runable: bool = false;
@@ -176,6 +233,8 @@ pub fn schedule() {
         }
         KS_SCHEDULER_ACTION[hart_id()] = SCHEDULER_ACTION_RESUME_CURRENT_THREAD;
     }
+    #[cfg(feature = "mcs")]
+    crate::interrupt::set_next_trigger_before(sched_context::next_deadline());
 }
 
 pub fn set_thread_state(ts: ThreadStateEnum) {
@@ -219,9 +278,13 @@ pub fn set_vm_root(tcb: & TCB) {
         activate_kernel_vspace();
         return;
     }
-    let lvl1pt =thread_root.get_pt_based_ptr();
+    let lvl1pt =thread_root.get_pt_base_ptr();
     let asid = thread_root.get_pt_mapped_asid();
-    // TODO: check page table & asid
+    let found = find_vspace_for_asid(asid).map(|vspace_root| vspace_root as *mut PageTableEntry as usize);
+    if found != Some(lvl1pt) {
+        activate_kernel_vspace();
+        return;
+    }
 
     set_vspace_root(lvl1pt - PPTR_BASE_OFFSET, asid);
 }
@@ -229,15 +292,26 @@ pub fn set_vm_root(tcb: & TCB) {
 pub fn switch_to_thread(tcb: &mut TCB) {
     set_vm_root(tcb);
     tcb.de_queue_from_sched();
+    #[cfg(feature = "benchmark")]
+    crate::benchmark::thread_switch(tcb);
+    #[cfg(feature = "mcs")]
+    sched_context::switch_sched_context(tcb);
     unsafe {
         KS_CUR_THREAD[hart_id()] = tcb as *const TCB as usize;
     }
 }
 
 pub fn possible_switch_to(tcb: &mut TCB) {
+    // passive, or waiting for its budget
+    #[cfg(feature = "mcs")]
+    if !tcb.is_schedulable() {
+        return;
+    }
     unsafe {
         if KS_CUR_DOMAIN.load(Ordering::SeqCst) != tcb.tcb_domain {
             error!("[possible_switch_to] unsupported!");
+        } else if tcb.tcb_affinity != hart_id() {
+            tcb.enqueue_to_sched();
         } else if KS_SCHEDULER_ACTION[hart_id()] != SCHEDULER_ACTION_RESUME_CURRENT_THREAD {
             re_schedule();
             tcb.enqueue_to_sched();
@@ -247,6 +321,20 @@ pub fn possible_switch_to(tcb: &mut TCB) {
     }
 }
 
+/// Asks the core a thread was just queued on to reschedule if it is idle or running
+/// something of lower priority.
+pub fn remote_queue_update(tcb: &TCB) {
+    let cpu = tcb.tcb_affinity;
+    if cpu != hart_id() {
+        unsafe {
+            let target_cur = convert_to_type_ref::<TCB>(KS_CUR_THREAD[cpu]);
+            if KS_CUR_THREAD[cpu] == KS_IDLE_THREAD[cpu] || tcb.tcb_priority > target_cur.tcb_priority {
+                mark_reschedule_pending(cpu);
+            }
+        }
+    }
+}
+
 pub fn switch_to_idle() {
     let idle = unsafe {
         convert_to_mut_type_ref::<TCB>(KS_IDLE_THREAD[hart_id()])
@@ -257,6 +345,12 @@ pub fn switch_to_idle() {
     }
 }
 
+pub fn get_idle_thread(cpu: usize) -> &'static mut TCB {
+    unsafe {
+        convert_to_mut_type_ref::<TCB>(KS_IDLE_THREAD[cpu])
+    }
+}
+
 pub fn get_current_tcb() -> &'static TCB {
     unsafe {
         convert_to_type_ref::<TCB>(KS_CUR_THREAD[hart_id()])
@@ -274,36 +368,32 @@ pub fn ready_queues_index(dom: usize, prio: usize) -> usize {
     dom * CONFIG_NUM_PRIORITIES + prio
 }
 
-pub fn remove_from_bitmap(_hart_id: usize, dom: usize, prio: usize) {
-    let l1_index = prio_2_l1_index(prio);
-    let l1_index_invert = invert_l1_index(l1_index);
+pub fn remove_from_bitmap(hart_id: usize, dom: usize, prio: usize) {
     unsafe {
-        KS_READY_QUEUES_L2_BITMAP[dom][l1_index_invert] &= !bit(prio & mask(WORD_RADIX));
-        if KS_READY_QUEUES_L2_BITMAP[dom][l1_index_invert] == 0 {
-            KS_READY_QUEUES_L1_BITMAP[dom] &= !bit(l1_index);
-        }
+        KS_READY_QUEUES_BITMAP[hart_id][dom].remove(prio);
     }
 }
 
-pub fn add_to_bitmap(_hart_id: usize, dom: usize, prio: usize) {
-    let l1_index = prio_2_l1_index(prio);
-    let l1_index_invert = invert_l1_index(l1_index);
+pub fn add_to_bitmap(hart_id: usize, dom: usize, prio: usize) {
     unsafe {
-        KS_READY_QUEUES_L1_BITMAP[dom] |= bit(l1_index);
-        KS_READY_QUEUES_L2_BITMAP[dom][l1_index_invert] |= bit(prio & mask(WORD_RADIX));
+        KS_READY_QUEUES_BITMAP[hart_id][dom].add(prio);
     }
 }
 
 pub fn get_highest_prio(dom: usize) -> usize {
     unsafe {
-        let l1_index = WORD_BITS - 1 - (KS_READY_QUEUES_L1_BITMAP[dom].leading_zeros() as usize);
-        let l1_index_invert = invert_l1_index(l1_index);
-        assert!(KS_READY_QUEUES_L2_BITMAP[dom][l1_index_invert] != 0);
-        let l2_index = WORD_BITS - 1 - (KS_READY_QUEUES_L2_BITMAP[dom][l1_index_invert].leading_zeros() as usize);
-        l1_index_2_prio(l1_index) | l2_index
+        KS_READY_QUEUES_BITMAP[hart_id()][dom].get_highest_prio()
     }
 }
 
+/// with mcs the timer also fires when the budget runs out, and threads of one priority take turns
+/// as their round robin budgets run out
+#[cfg(feature = "mcs")]
+pub fn timer_tick() {
+    sched_context::check_budget();
+}
+
+#[cfg(not(feature = "mcs"))]
 pub fn timer_tick() {
     let cur_tcb = get_current_mut_tcb();
     if cur_tcb.get_state() == ThreadStateRunning {
@@ -0,0 +1,121 @@
+use common::{types::Pptr, utils::convert_to_mut_type_ref, register::BADGE_REGISTER};
+
+use crate::cspace::Cap;
+pub use crate::structures::Notification;
+use super::{possible_switch_to, re_schedule};
+use super::tcb::{TCB, TCBQueue, ThreadStateEnum};
+
+impl Notification {
+    pub fn get_queue(&self) -> TCBQueue {
+        TCBQueue::new(self.get_queue_head(), self.get_queue_tail())
+    }
+
+    pub fn set_queue(&mut self, queue: &TCBQueue) {
+        self.set_queue_head(queue.head as Pptr);
+        self.set_queue_tail(queue.end as Pptr);
+    }
+
+    pub fn get_state(&self) -> NtfnState {
+        unsafe {
+            core::mem::transmute::<u8, NtfnState>(self.get_ntfn_state() as u8)
+        }
+    }
+
+    pub fn set_state(&mut self, state: NtfnState) {
+        self.set_ntfn_state(state as usize);
+    }
+
+    fn set_active(&mut self, badge: usize) {
+        self.set_state(NtfnState::NtfnStateActive);
+        self.set_msg_identifier(badge);
+    }
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum NtfnState {
+    NtfnStateIdle = 0,
+    NtfnStateWaiting = 1,
+    NtfnStateActive = 2,
+}
+
+pub fn send_signal(ntfn: &mut Notification, badge: usize) {
+    match ntfn.get_state() {
+        NtfnState::NtfnStateIdle => {
+            ntfn.set_active(badge);
+        }
+
+        NtfnState::NtfnStateWaiting => {
+            let mut queue = ntfn.get_queue();
+            let dest = unsafe { &mut *queue.head };
+            queue.de_queue(dest);
+            ntfn.set_queue(&queue);
+            if queue.head.is_null() {
+                ntfn.set_state(NtfnState::NtfnStateIdle);
+            }
+            dest.set_thread_state(ThreadStateEnum::ThreadStateRunning);
+            dest.set_register(BADGE_REGISTER, badge);
+            #[cfg(feature = "mcs")]
+            super::sched_context::maybe_donate_sched_context(dest, ntfn);
+            possible_switch_to(dest);
+        }
+
+        NtfnState::NtfnStateActive => {
+            ntfn.set_msg_identifier(ntfn.get_msg_identifier() | badge);
+        }
+    }
+}
+
+pub fn receive_signal(thread: &mut TCB, cap: Cap, is_blocking: bool) {
+    let ntfn_ptr = cap.get_nt_fn_ptr();
+    let ntfn = convert_to_mut_type_ref::<Notification>(ntfn_ptr);
+    match ntfn.get_state() {
+        NtfnState::NtfnStateIdle | NtfnState::NtfnStateWaiting => {
+            if is_blocking {
+                thread.tcb_state.set_blocking_object(ntfn_ptr);
+                thread.set_thread_state(ThreadStateEnum::ThreadStateBlockedOnNotification);
+                #[cfg(feature = "mcs")]
+                super::sched_context::maybe_return_sched_context(ntfn, thread);
+                let mut queue = ntfn.get_queue();
+                queue.en_queue(thread);
+                ntfn.set_state(NtfnState::NtfnStateWaiting);
+                ntfn.set_queue(&queue);
+            } else {
+                thread.set_register(BADGE_REGISTER, 0);
+            }
+        }
+
+        NtfnState::NtfnStateActive => {
+            thread.set_register(BADGE_REGISTER, ntfn.get_msg_identifier());
+            ntfn.set_state(NtfnState::NtfnStateIdle);
+        }
+    }
+}
+
+pub fn cancel_signal(thread: &mut TCB, ntfn: &mut Notification) {
+    assert_eq!(ntfn.get_state(), NtfnState::NtfnStateWaiting);
+    let mut queue = ntfn.get_queue();
+    queue.de_queue(thread);
+    ntfn.set_queue(&queue);
+    if queue.head.is_null() {
+        ntfn.set_state(NtfnState::NtfnStateIdle);
+    }
+    thread.set_thread_state(ThreadStateEnum::ThreadStateInactive);
+}
+
+/// restart every thread waiting on `ntfn`, which is going away
+pub fn cancel_all_signals(ntfn: &mut Notification) {
+    if ntfn.get_state() != NtfnState::NtfnStateWaiting {
+        return;
+    }
+    let mut thread = ntfn.get_queue_head();
+    ntfn.set_state(NtfnState::NtfnStateIdle);
+    ntfn.set_queue_head(0);
+    ntfn.set_queue_tail(0);
+    while thread != 0 {
+        let tcb = convert_to_mut_type_ref::<TCB>(thread);
+        thread = tcb.tcb_ep_next;
+        tcb.set_thread_state(ThreadStateEnum::ThreadStateRestart);
+        tcb.enqueue_to_sched();
+    }
+    re_schedule();
+}
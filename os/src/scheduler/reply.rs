@@ -0,0 +1,137 @@
+//! reply objects of the mcs kernel: a call blocks the caller on the reply object the receiver
+//! named, and answering through it wakes the caller. a scheduling context lent to a passive
+//! receiver keeps a stack of the replies it went through, and comes back with the last of them
+
+use core::mem::size_of;
+use common::config::SEL4_REPLY_BITS;
+use common::types::Pptr;
+use common::utils::convert_to_mut_type_ref;
+use super::sched_context::{SchedContext, sched_context_donate};
+use super::{ThreadStateEnum, TCB};
+
+#[repr(C)]
+pub struct Reply {
+    // the caller blocked on it, or the receiver while it waits for a call
+    pub reply_tcb: Pptr,
+    // the reply pushed before this one on the same scheduling context
+    pub reply_prev: Pptr,
+    // the reply pushed after this one, or the scheduling context for the top of the stack
+    pub reply_next: Pptr,
+    pub reply_next_is_sc: bool,
+}
+
+const _: () = assert!(size_of::<Reply>() <= 1 << SEL4_REPLY_BITS);
+
+impl Reply {
+    fn clear_call_stack(&mut self) {
+        self.reply_prev = 0;
+        self.reply_next = 0;
+        self.reply_next_is_sc = false;
+    }
+}
+
+fn reply_at(reply: Pptr) -> &'static mut Reply {
+    convert_to_mut_type_ref::<Reply>(reply)
+}
+
+/// block `caller` on `reply`, lending its scheduling context to `callee` if that one has none
+pub fn reply_push(caller: &mut TCB, callee: &mut TCB, reply: &mut Reply, can_donate: bool) {
+    assert_eq!(reply.reply_tcb, 0);
+    reply.clear_call_stack();
+    reply.reply_tcb = caller as *mut TCB as Pptr;
+    caller.tcb_state.set_reply_object(reply as *mut Reply as Pptr);
+    caller.set_thread_state(ThreadStateEnum::ThreadStateBlockedOnReply);
+
+    let Some(sc) = caller.sched_context() else {
+        return;
+    };
+    if !can_donate || callee.tcb_sched_context != 0 {
+        return;
+    }
+    let reply_ptr = reply as *mut Reply as Pptr;
+    if sc.sc_reply != 0 {
+        let top = reply_at(sc.sc_reply);
+        top.reply_next = reply_ptr;
+        top.reply_next_is_sc = false;
+    }
+    reply.reply_prev = sc.sc_reply;
+    reply.reply_next = sc as *mut SchedContext as Pptr;
+    reply.reply_next_is_sc = true;
+    sc.sc_reply = reply_ptr;
+    sched_context_donate(sc, callee);
+}
+
+// the top of the stack: the scheduling context goes back to the caller if it has none
+fn reply_pop(reply: &mut Reply, tcb: &mut TCB) {
+    let sc = convert_to_mut_type_ref::<SchedContext>(reply.reply_next);
+    if tcb.tcb_sched_context == 0 {
+        sched_context_donate(sc, tcb);
+    }
+    sc.sc_reply = reply.reply_prev;
+    if reply.reply_prev != 0 {
+        let prev = reply_at(reply.reply_prev);
+        prev.reply_next = reply.reply_next;
+        prev.reply_next_is_sc = true;
+    }
+    reply.clear_call_stack();
+    reply_unlink(reply, tcb);
+}
+
+/// take `reply` out of its call stack before `tcb`, blocked on it, is answered
+pub fn reply_remove(reply: &mut Reply, tcb: &mut TCB) {
+    assert_eq!(reply.reply_tcb, tcb as *mut TCB as Pptr);
+    if reply.reply_next != 0 && reply.reply_next_is_sc {
+        reply_pop(reply, tcb);
+        return;
+    }
+    if reply.reply_next != 0 {
+        reply_at(reply.reply_next).reply_prev = reply.reply_prev;
+    }
+    if reply.reply_prev != 0 {
+        let prev = reply_at(reply.reply_prev);
+        prev.reply_next = reply.reply_next;
+        prev.reply_next_is_sc = reply.reply_next_is_sc;
+    }
+    reply.clear_call_stack();
+    reply_unlink(reply, tcb);
+}
+
+/// `tcb` stops waiting for its reply, which cuts the call stack there
+pub fn reply_remove_tcb(tcb: &mut TCB) {
+    let reply = reply_at(tcb.tcb_state.get_reply_object());
+    if reply.reply_next != 0 {
+        if reply.reply_next_is_sc {
+            convert_to_mut_type_ref::<SchedContext>(reply.reply_next).sc_reply = 0;
+        } else {
+            reply_at(reply.reply_next).reply_prev = 0;
+        }
+    }
+    if reply.reply_prev != 0 {
+        let prev = reply_at(reply.reply_prev);
+        prev.reply_next = 0;
+        prev.reply_next_is_sc = false;
+    }
+    reply.clear_call_stack();
+    reply_unlink(reply, tcb);
+}
+
+/// break the link between `reply` and `tcb`, which is left inactive
+pub fn reply_unlink(reply: &mut Reply, tcb: &mut TCB) {
+    assert_eq!(reply.reply_tcb, tcb as *mut TCB as Pptr);
+    tcb.tcb_state.set_reply_object(0);
+    reply.reply_tcb = 0;
+    tcb.set_thread_state(ThreadStateEnum::ThreadStateInactive);
+}
+
+/// the reply object is going away
+pub fn reply_finalise(reply: &mut Reply) {
+    if reply.reply_tcb == 0 {
+        return;
+    }
+    let tcb = convert_to_mut_type_ref::<TCB>(reply.reply_tcb);
+    match tcb.get_state() {
+        ThreadStateEnum::ThreadStateBlockedOnReply => reply_remove(reply, tcb),
+        ThreadStateEnum::ThreadStateBlockedOnReceive => tcb.cancel_ipc(),
+        _ => {}
+    }
+}
@@ -0,0 +1,522 @@
+//! scheduling contexts of the mcs kernel: a budget a thread may run for in every period, handed
+//! out as a sporadic server. time is kept in timer ticks, the user interface is in microseconds
+
+use core::mem::size_of;
+use common::config::CPU_NUM;
+use common::sched_context::{SEL4_CORE_SCHED_CONTEXT_BYTES, SEL4_REFILL_SIZE_BYTES, CONFIG_KERNEL_WCET_US, MIN_BUDGET_US};
+use common::types::Pptr;
+use common::utils::convert_to_mut_type_ref;
+use crate::cspace::{Cap, CapTag, TCBCNodeIndex};
+use crate::interrupt::{get_time, us_to_ticks};
+use crate::smp::{hart_id, mark_reschedule_pending};
+use super::reply::Reply;
+use super::{get_current_mut_tcb, re_schedule, EndPoint, Notification, TCBCNode, TCB, send_ipc};
+
+#[derive(Clone, Copy)]
+pub struct Refill {
+    // when the amount may be used from
+    pub r_time: usize,
+    pub r_amount: usize,
+}
+
+#[repr(C)]
+pub struct SchedContext {
+    pub sc_period: usize,
+    pub sc_budget: usize,
+    // ticks run since the last SchedContextConsumed
+    pub sc_consumed: usize,
+    pub sc_core: usize,
+    pub sc_tcb: Pptr,
+    pub sc_notification: Pptr,
+    // what the timeout fault carries to the handler
+    pub sc_badge: usize,
+    // the top of the stack of replies it was lent through
+    pub sc_reply: Pptr,
+    // a ring of refills follows
+    pub sc_refill_max: usize,
+    pub sc_refill_head: usize,
+    pub sc_refill_count: usize,
+}
+
+const _: () = assert!(size_of::<SchedContext>() == SEL4_CORE_SCHED_CONTEXT_BYTES);
+const _: () = assert!(size_of::<Refill>() == SEL4_REFILL_SIZE_BYTES);
+
+// when each core last entered the kernel, and what it ran for since its last commit
+static mut KS_CUR_TIME: [usize; CPU_NUM] = [0; CPU_NUM];
+static mut KS_CONSUMED: [usize; CPU_NUM] = [0; CPU_NUM];
+// the scheduling context each core charges, 0 while idle
+static mut KS_CUR_SC: [Pptr; CPU_NUM] = [0; CPU_NUM];
+// threads whose budget is yet to come back, earliest first, linked through tcb_sched_next
+static mut KS_RELEASE_HEAD: [Pptr; CPU_NUM] = [0; CPU_NUM];
+
+fn cur_time() -> usize {
+    unsafe { KS_CUR_TIME[hart_id()] }
+}
+
+fn min_budget() -> usize {
+    us_to_ticks(MIN_BUDGET_US)
+}
+
+impl SchedContext {
+    fn refill(&self, index: usize) -> &'static mut Refill {
+        let base = self as *const SchedContext as usize + SEL4_CORE_SCHED_CONTEXT_BYTES;
+        convert_to_mut_type_ref::<Refill>(base + index * SEL4_REFILL_SIZE_BYTES)
+    }
+
+    fn refill_index(&self, n: usize) -> usize {
+        (self.sc_refill_head + n) % self.sc_refill_max
+    }
+
+    pub fn refill_head(&self) -> &'static mut Refill {
+        self.refill(self.sc_refill_head)
+    }
+
+    fn refill_tail(&self) -> &'static mut Refill {
+        self.refill(self.refill_index(self.sc_refill_count - 1))
+    }
+
+    fn refill_pop_head(&mut self) -> Refill {
+        let head = *self.refill_head();
+        self.sc_refill_head = self.refill_index(1);
+        self.sc_refill_count -= 1;
+        head
+    }
+
+    fn refill_add_tail(&mut self, refill: Refill) {
+        assert!(self.sc_refill_count < self.sc_refill_max);
+        *self.refill(self.refill_index(self.sc_refill_count)) = refill;
+        self.sc_refill_count += 1;
+    }
+
+    /// configured by SchedControlConfigureFlags, and so able to run a thread
+    pub fn is_active(&self) -> bool {
+        self.sc_refill_max > 0
+    }
+
+    pub fn is_round_robin(&self) -> bool {
+        self.sc_budget == self.sc_period
+    }
+
+    /// start over with the whole budget available now
+    pub fn refill_new(&mut self, max_refills: usize, budget: usize, period: usize) {
+        self.sc_period = period;
+        self.sc_budget = budget;
+        self.sc_refill_max = max_refills;
+        self.sc_refill_head = 0;
+        self.sc_refill_count = 1;
+        *self.refill(0) = Refill { r_time: cur_time(), r_amount: budget };
+    }
+
+    pub fn refill_ready(&self) -> bool {
+        self.refill_head().r_time <= cur_time() + us_to_ticks(CONFIG_KERNEL_WCET_US)
+    }
+
+    /// whether the head refill lasts for `usage` more and still leaves enough to enter the kernel on
+    pub fn refill_sufficient(&self, usage: usize) -> bool {
+        self.refill_head().r_amount.saturating_sub(usage) >= min_budget()
+    }
+
+    // hand `used` back at its time, in the tail if they overlap or there is no room for another
+    fn schedule_used(&mut self, used: Refill) {
+        if self.sc_refill_count == 0 {
+            self.refill_add_tail(used);
+            return;
+        }
+        let tail = self.refill_tail();
+        if used.r_time <= tail.r_time + tail.r_amount {
+            tail.r_amount += used.r_amount;
+        } else if self.sc_refill_count == self.sc_refill_max {
+            // the merged refill ends where `used` would, so nothing comes back early
+            tail.r_time = used.r_time - tail.r_amount;
+            tail.r_amount += used.r_amount;
+        } else {
+            self.refill_add_tail(used);
+        }
+    }
+
+    /// charge `usage` ticks, each used part coming back a period after the refill it came from
+    pub fn budget_check(&mut self, usage: usize) {
+        if self.is_round_robin() {
+            // one refill, topped up whenever it runs low
+            let head = self.refill_head();
+            head.r_amount = head.r_amount.saturating_sub(usage);
+            if head.r_amount < min_budget() {
+                head.r_amount = self.sc_budget;
+            }
+            head.r_time = cur_time();
+            return;
+        }
+
+        let mut usage = usage;
+        while usage > 0 && self.refill_head().r_amount <= usage {
+            let mut used = self.refill_pop_head();
+            usage -= used.r_amount;
+            used.r_time += self.sc_period;
+            self.schedule_used(used);
+        }
+        if usage > 0 {
+            let head = self.refill_head();
+            let used = Refill { r_time: head.r_time + self.sc_period, r_amount: usage };
+            head.r_amount -= usage;
+            head.r_time += usage;
+            self.schedule_used(used);
+        }
+        // a head too small to run on goes with the next refill
+        while self.sc_refill_count > 1 && self.refill_head().r_amount < min_budget() {
+            let head = self.refill_pop_head();
+            self.refill_head().r_amount += head.r_amount;
+        }
+    }
+
+    /// a thread waking up on a ready head starts using it now, along with the refills it overlaps
+    pub fn refill_unblock_check(&mut self) {
+        if self.is_round_robin() || !self.refill_ready() {
+            return;
+        }
+        self.refill_head().r_time = cur_time();
+        while self.sc_refill_count > 1 {
+            let head = *self.refill_head();
+            let next = self.refill(self.refill_index(1));
+            if next.r_time > head.r_time + head.r_amount {
+                break;
+            }
+            self.refill_pop_head();
+            next.r_time = head.r_time;
+            next.r_amount += head.r_amount;
+        }
+    }
+
+    fn is_current(&self) -> bool {
+        unsafe { KS_CUR_SC[hart_id()] == self as *const SchedContext as Pptr }
+    }
+}
+
+impl TCB {
+    pub fn sched_context(&self) -> Option<&'static mut SchedContext> {
+        match self.tcb_sched_context {
+            0 => None,
+            sc => Some(convert_to_mut_type_ref::<SchedContext>(sc)),
+        }
+    }
+
+    // a thread without a scheduling context is passive and stays off the queues, one whose budget
+    // has not come back yet waits in the release queue
+    pub(super) fn sched_context_ready(&mut self) -> bool {
+        if self.tcb_in_release_queue {
+            return false;
+        }
+        let sc = match self.sched_context() {
+            Some(sc) if sc.is_active() => sc,
+            _ => return false,
+        };
+        if sc.refill_ready() {
+            sc.refill_unblock_check();
+            return true;
+        }
+        release_enqueue(self);
+        false
+    }
+}
+
+pub fn update_timestamp() {
+    let now = get_time();
+    unsafe {
+        let cpu = hart_id();
+        KS_CONSUMED[cpu] += now - KS_CUR_TIME[cpu];
+        KS_CUR_TIME[cpu] = now;
+    }
+}
+
+/// the first timestamp of a core
+pub fn init_timestamp() {
+    unsafe {
+        KS_CUR_TIME[hart_id()] = get_time();
+    }
+}
+
+/// charge what the core ran for to the scheduling context it ran on
+pub fn commit_time() {
+    let cpu = hart_id();
+    unsafe {
+        if KS_CUR_SC[cpu] != 0 && KS_CONSUMED[cpu] > 0 {
+            let sc = convert_to_mut_type_ref::<SchedContext>(KS_CUR_SC[cpu]);
+            if sc.is_active() {
+                sc.budget_check(KS_CONSUMED[cpu]);
+                sc.sc_consumed += KS_CONSUMED[cpu];
+            }
+        }
+        KS_CONSUMED[cpu] = 0;
+    }
+}
+
+/// called on the way to `tcb`, whose scheduling context is charged from now on
+pub fn switch_sched_context(tcb: &TCB) {
+    unsafe {
+        if tcb.tcb_sched_context != KS_CUR_SC[hart_id()] {
+            commit_time();
+            KS_CUR_SC[hart_id()] = tcb.tcb_sched_context;
+        }
+    }
+}
+
+/// false if the current thread ran out of budget, which ends its timeslice
+pub fn check_budget() -> bool {
+    let cpu = hart_id();
+    let sc_ptr = unsafe { KS_CUR_SC[cpu] };
+    if sc_ptr == 0 {
+        return true;
+    }
+    let sc = convert_to_mut_type_ref::<SchedContext>(sc_ptr);
+    if !sc.is_active() || sc.refill_sufficient(unsafe { KS_CONSUMED[cpu] }) {
+        return true;
+    }
+    commit_time();
+    let thread = get_current_mut_tcb();
+    if thread.tcb_sched_context == sc_ptr && thread.is_schedulable() {
+        end_timeslice(thread, true);
+        re_schedule();
+    }
+    false
+}
+
+fn end_timeslice(thread: &mut TCB, can_timeout_fault: bool) {
+    let sc = thread.sched_context().unwrap();
+    if can_timeout_fault && !sc.is_round_robin() && timeout_handler(thread).is_some() {
+        handle_timeout(thread);
+    } else {
+        // back of the ready queue, or the release queue if the budget has not come back
+        thread.append_to_sched();
+    }
+}
+
+pub(super) fn timeout_handler(thread: &TCB) -> Option<Cap> {
+    let cnode = convert_to_mut_type_ref::<TCBCNode>(thread.get_cnode_ptr_of_this());
+    let cap = cnode[TCBCNodeIndex::TCBTimeoutHandler as usize].cap;
+    if cap.get_cap_type() == CapTag::CapEndpointCap {
+        return Some(cap);
+    }
+    None
+}
+
+// the thread sends a timeout fault to its handler and goes on where it stopped once answered
+pub(super) fn handle_timeout(thread: &mut TCB) {
+    let handler = timeout_handler(thread).unwrap();
+    thread.tcb_fault.set_timeout(thread.sched_context().unwrap().sc_badge);
+    send_ipc(true, false, handler.get_ep_badge(), handler.get_ep_can_grant(), handler.get_ep_can_grant_reply(),
+             thread, convert_to_mut_type_ref::<EndPoint>(handler.get_ep_ptr()));
+}
+
+/// wait in the release queue for the budget to come back
+pub(super) fn postpone(tcb: &mut TCB) {
+    tcb.de_queue_from_sched();
+    release_enqueue(tcb);
+}
+
+pub fn release_enqueue(tcb: &mut TCB) {
+    let cpu = tcb.tcb_affinity;
+    let time = tcb.sched_context().unwrap().refill_head().r_time;
+    let mut prev: Pptr = 0;
+    let mut next = unsafe { KS_RELEASE_HEAD[cpu] };
+    while next != 0 {
+        let next_tcb = convert_to_mut_type_ref::<TCB>(next);
+        if next_tcb.sched_context().unwrap().refill_head().r_time > time {
+            break;
+        }
+        prev = next;
+        next = next_tcb.tcb_sched_next;
+    }
+    tcb.tcb_sched_prev = prev;
+    tcb.tcb_sched_next = next;
+    if next != 0 {
+        convert_to_mut_type_ref::<TCB>(next).tcb_sched_prev = tcb as *mut TCB as Pptr;
+    }
+    if prev != 0 {
+        convert_to_mut_type_ref::<TCB>(prev).tcb_sched_next = tcb as *mut TCB as Pptr;
+    } else {
+        unsafe {
+            KS_RELEASE_HEAD[cpu] = tcb as *mut TCB as Pptr;
+        }
+        // the core has to program its timer for the new head
+        if cpu != hart_id() {
+            mark_reschedule_pending(cpu);
+        }
+    }
+    tcb.tcb_in_release_queue = true;
+}
+
+pub fn release_remove(tcb: &mut TCB) {
+    if !tcb.tcb_in_release_queue {
+        return;
+    }
+    if tcb.tcb_sched_prev != 0 {
+        convert_to_mut_type_ref::<TCB>(tcb.tcb_sched_prev).tcb_sched_next = tcb.tcb_sched_next;
+    } else {
+        unsafe {
+            KS_RELEASE_HEAD[tcb.tcb_affinity] = tcb.tcb_sched_next;
+        }
+    }
+    if tcb.tcb_sched_next != 0 {
+        convert_to_mut_type_ref::<TCB>(tcb.tcb_sched_next).tcb_sched_prev = tcb.tcb_sched_prev;
+    }
+    tcb.tcb_sched_prev = 0;
+    tcb.tcb_sched_next = 0;
+    tcb.tcb_in_release_queue = false;
+}
+
+/// move the threads whose budget came back to the ready queues
+pub fn awaken() {
+    let cpu = hart_id();
+    loop {
+        let head = unsafe { KS_RELEASE_HEAD[cpu] };
+        if head == 0 {
+            break;
+        }
+        let tcb = convert_to_mut_type_ref::<TCB>(head);
+        if !tcb.sched_context().unwrap().refill_ready() {
+            break;
+        }
+        release_remove(tcb);
+        tcb.append_to_sched();
+        re_schedule();
+    }
+}
+
+/// when the core has to be back in the kernel: the end of the current budget, or the next release
+pub fn next_deadline() -> usize {
+    let cpu = hart_id();
+    let mut deadline = usize::MAX;
+    unsafe {
+        if KS_CUR_SC[cpu] != 0 {
+            let sc = convert_to_mut_type_ref::<SchedContext>(KS_CUR_SC[cpu]);
+            if sc.is_active() {
+                let left = sc.refill_head().r_amount.saturating_sub(KS_CONSUMED[cpu] + min_budget());
+                deadline = KS_CUR_TIME[cpu] + left;
+            }
+        }
+        if KS_RELEASE_HEAD[cpu] != 0 {
+            let tcb = convert_to_mut_type_ref::<TCB>(KS_RELEASE_HEAD[cpu]);
+            deadline = deadline.min(tcb.sched_context().unwrap().refill_head().r_time);
+        }
+    }
+    deadline
+}
+
+pub fn sched_context_bind_tcb(sc: &mut SchedContext, tcb: &mut TCB) {
+    sc.sc_tcb = tcb as *mut TCB as Pptr;
+    tcb.tcb_sched_context = sc as *mut SchedContext as Pptr;
+    if sc.is_active() {
+        // onto the core of the scheduling context, and the queues if it can run
+        tcb.set_affinity(sc.sc_core);
+    }
+}
+
+pub fn sched_context_unbind_tcb(sc: &mut SchedContext) {
+    if sc.sc_tcb == 0 {
+        return;
+    }
+    let tcb = convert_to_mut_type_ref::<TCB>(sc.sc_tcb);
+    tcb.de_queue_from_sched();
+    tcb.tcb_sched_context = 0;
+    sc.sc_tcb = 0;
+    let cpu = tcb.tcb_affinity;
+    if unsafe { super::KS_CUR_THREAD[cpu] } == tcb as *mut TCB as Pptr {
+        if cpu == hart_id() {
+            re_schedule();
+        } else {
+            mark_reschedule_pending(cpu);
+        }
+    }
+}
+
+pub fn sched_context_bind_ntfn(sc: &mut SchedContext, ntfn: &mut Notification) {
+    sc.sc_notification = ntfn as *mut Notification as Pptr;
+    ntfn.set_ntfn_sched_context(sc as *mut SchedContext as Pptr);
+}
+
+pub fn sched_context_unbind_ntfn(sc: &mut SchedContext) {
+    if sc.sc_notification == 0 {
+        return;
+    }
+    convert_to_mut_type_ref::<Notification>(sc.sc_notification).set_ntfn_sched_context(0);
+    sc.sc_notification = 0;
+}
+
+/// move `sc` to `to`, which has none, from the thread it is bound to
+pub fn sched_context_donate(sc: &mut SchedContext, to: &mut TCB) {
+    if sc.sc_tcb != 0 {
+        let from = convert_to_mut_type_ref::<TCB>(sc.sc_tcb);
+        from.de_queue_from_sched();
+        from.tcb_sched_context = 0;
+        if unsafe { super::KS_CUR_THREAD[hart_id()] } == sc.sc_tcb {
+            re_schedule();
+        }
+    }
+    sc.sc_tcb = to as *mut TCB as Pptr;
+    to.tcb_sched_context = sc as *mut SchedContext as Pptr;
+    to.tcb_affinity = sc.sc_core;
+}
+
+/// the scheduling context is going away. every core charging it stops, the others reschedule
+pub fn sched_context_finalise(sc: &mut SchedContext) {
+    let sc_ptr = sc as *mut SchedContext as Pptr;
+    for cpu in 0..CPU_NUM {
+        if unsafe { KS_CUR_SC[cpu] } != sc_ptr {
+            continue;
+        }
+        if cpu == hart_id() {
+            commit_time();
+        } else {
+            mark_reschedule_pending(cpu);
+        }
+        unsafe {
+            KS_CUR_SC[cpu] = 0;
+        }
+    }
+    sched_context_unbind_tcb(sc);
+    sched_context_unbind_ntfn(sc);
+    if sc.sc_reply != 0 {
+        let reply = convert_to_mut_type_ref::<Reply>(sc.sc_reply);
+        reply.reply_next = 0;
+        reply.reply_next_is_sc = false;
+        sc.sc_reply = 0;
+    }
+    sc.sc_refill_max = 0;
+}
+
+/// take the scheduling context in use back before it is configured, so nothing runs on the old one
+pub fn sched_context_commit_if_current(sc: &SchedContext) {
+    if sc.is_current() {
+        commit_time();
+    }
+}
+
+/// the ticks `sc` ran for since the last call, including what is not committed yet
+pub fn sched_context_consumed(sc: &mut SchedContext) -> usize {
+    sched_context_commit_if_current(sc);
+    let consumed = sc.sc_consumed;
+    sc.sc_consumed = 0;
+    consumed
+}
+
+/// a thread without a scheduling context woken through a notification borrows the one bound to
+/// the notification, if nobody else has it
+pub fn maybe_donate_sched_context(tcb: &mut TCB, ntfn: &Notification) {
+    let sc_ptr = ntfn.get_ntfn_sched_context();
+    if tcb.tcb_sched_context != 0 || sc_ptr == 0 {
+        return;
+    }
+    let sc = convert_to_mut_type_ref::<SchedContext>(sc_ptr);
+    if sc.sc_tcb != 0 {
+        return;
+    }
+    sched_context_donate(sc, tcb);
+}
+
+/// and gives it back once it waits on the notification again
+pub fn maybe_return_sched_context(ntfn: &Notification, tcb: &mut TCB) {
+    let sc_ptr = ntfn.get_ntfn_sched_context();
+    if sc_ptr != 0 && sc_ptr == tcb.tcb_sched_context {
+        tcb.tcb_sched_context = 0;
+        convert_to_mut_type_ref::<SchedContext>(sc_ptr).sc_tcb = 0;
+    }
+}
@@ -1,32 +1,38 @@
-use common::{config::{CONTEXT_REGISTERS_NUM, SEL4_IDLE_TCB_SLOT_SIZE, CONFIG_KERNEL_STACK_BITS}, 
+use common::{config::{CONTEXT_REGISTERS_NUM, SEL4_IDLE_TCB_SLOT_SIZE},  
             types::{Pptr, Dom, Prio, Cptr}, utils::page_bits_for_size, register::{Register, SSTATUS_SPIE, SSTATUS_SPP, SP, BADGE_REGISTER, MSG_INFO_REGISTER}};
 use crate::{scheduler::{re_schedule, domain_schedule::PriorityConst}, cspace::MDBNode, mm::VmRights};
-use common::utils::{bit, hart_id, sign_extend, bool2usize, mask, convert_to_mut_type_ref, convert_to_type_ref};
+use crate::smp::{hart_id, kernel_stack_top, mark_reschedule_pending};
+use common::utils::{sign_extend, bool2usize, mask, convert_to_mut_type_ref, convert_to_type_ref};
 use core::ops::{Index, IndexMut};
-use super::{idle_thread, KERNEL_STACK, KS_CUR_THREAD, KS_SCHEDULER_ACTION, SCHEDULER_ACTION_RESUME_CURRENT_THREAD,
-    ready_queues_index, KS_READY_QUEUES, remove_from_bitmap, add_to_bitmap, possible_switch_to};
+use super::{idle_thread, KS_CUR_THREAD, KS_SCHEDULER_ACTION, SCHEDULER_ACTION_RESUME_CURRENT_THREAD,
+    ready_queues_index, KS_READY_QUEUES, remove_from_bitmap, add_to_bitmap, possible_switch_to, remote_queue_update};
 
 use log::{error, debug};
 use common::config::{SEL4_TCB_BITS, WORD_BITS};
+#[cfg(feature = "mcs")]
+use common::sched_context::SEL4_TIMEOUT_FAULT;
 use common::message::InvocationLabel::InvalidInvocation;
 use common::message::MessageInfo;
 use common::register::Register::*;
-use crate::cspace::{Cap, CapTableEntry, CapTag, resolve_address_bits};
-use crate::cspace::TCBCNodeIndex::{TCBBuffer, TCBCTable, TCBReply};
+use crate::cspace::{Cap, CapTableEntry, CapTag, resolve_address_bits, cte_insert, cte_delete};
+use crate::cspace::TCBCNodeIndex::{TCBBuffer, TCBCTable, TCBReply, TCBCaller};
 use crate::scheduler::endpoint::{EndPoint, EndPointState};
+use crate::scheduler::notification::{Notification, cancel_signal};
 use crate::scheduler::ThreadStateEnum::{ThreadStateInactive, ThreadStateRunning};
+use kernel_lib::scheduler::TCBQueueNode;
 
 #[derive(Default)]
 pub struct TCB {
     context: RiscvContext,
     pub tcb_state: ThreadState,
     pub tcb_bound_notification: Pptr,
-    tcb_fault: Fault,
+    pub tcb_fault: Fault,
     lookup_fault: LookUpFault,
     pub tcb_domain: Dom,
     pub tcb_mcp: Prio,
     pub tcb_priority: Prio,
     pub tcb_time_slice: usize,
+    pub tcb_affinity: usize,
     pub tcb_fault_handler: Cptr,
     pub tcb_ipc_buffer: Pptr,
 
@@ -35,18 +41,30 @@ pub struct TCB {
 
     pub tcb_ep_next: Pptr,
     pub tcb_ep_prev: Pptr,
+
+    // the scheduling context the thread runs on, 0 for a passive thread
+    #[cfg(feature = "mcs")]
+    pub tcb_sched_context: Pptr,
+    // waiting for its budget, linked through tcb_sched_next
+    #[cfg(feature = "mcs")]
+    pub tcb_in_release_queue: bool,
+
+    // cycles run and times switched to, for BenchmarkGetThreadUtilisation
+    #[cfg(feature = "benchmark")]
+    pub tcb_utilisation: usize,
+    #[cfg(feature = "benchmark")]
+    pub tcb_schedules: usize,
 }
 
 impl TCB {
-    pub fn configure_idle_thread(&mut self) {        
+    pub fn configure_idle_thread(&mut self, cpu: usize) {
         self.set_register(Register::NextIP as usize, idle_thread as usize);
         
         self.set_register(Register::SSTATUS as usize, SSTATUS_SPIE | SSTATUS_SPP);
         
-        let kernel_stack_ptr = unsafe {
-            &KERNEL_STACK[0][0] as *const u8 as usize + bit(CONFIG_KERNEL_STACK_BITS)
-        };
-        self.set_register(SP, kernel_stack_ptr);
+        // the two words below the stack top are reserved for the trap entry
+        self.set_register(SP, kernel_stack_top(cpu) - 16);
+        self.tcb_affinity = cpu;
         self.set_thread_state(ThreadStateEnum::ThreadStateIdleThreadState);
     }
 
@@ -74,6 +92,7 @@ impl TCB {
         if self.is_stopped() {
             debug!("status: {:?}", self.get_state());
             self.cancel_ipc();
+            #[cfg(not(feature = "mcs"))]
             self.setup_replay_master();
             self.set_thread_state(ThreadStateEnum::ThreadStateRestart);
             self.enqueue_to_sched();
@@ -86,6 +105,9 @@ impl TCB {
     }
 
     pub fn cancel_ipc(&mut self) {
+        // whatever fault the thread was sending or waiting on the answer to is dropped with it
+        #[cfg(feature = "mcs")]
+        self.tcb_fault.clear();
         match self.get_state() {
             ThreadStateEnum::ThreadStateBlockedOnSend | ThreadStateEnum::ThreadStateBlockedOnReceive => {
                 let epptr = self.tcb_state.get_blocking_object();
@@ -95,13 +117,35 @@ impl TCB {
                 let mut queue = endpoint_ref.get_queue();
                 queue.de_queue(self);
                 endpoint_ref.set_queue(&queue);
-                if queue.head as Pptr != 0 {
+                if queue.head.is_null() {
                     endpoint_ref.set_state(EndPointState::EPStateIdle);
                 }
 
+                #[cfg(feature = "mcs")]
+                if self.tcb_state.get_reply_object() != 0 {
+                    let reply = convert_to_mut_type_ref::<super::reply::Reply>(self.tcb_state.get_reply_object());
+                    super::reply::reply_unlink(reply, self);
+                }
                 self.set_thread_state(ThreadStateInactive);
 
             }
+            ThreadStateEnum::ThreadStateBlockedOnNotification => {
+                let ntfn = convert_to_mut_type_ref::<Notification>(self.tcb_state.get_blocking_object());
+                cancel_signal(self, ntfn);
+            }
+            #[cfg(not(feature = "mcs"))]
+            ThreadStateEnum::ThreadStateBlockedOnReply => {
+                // the caller cap the receiver holds is the only child of the reply master
+                let cnode = convert_to_mut_type_ref::<TCBCNode>(self.get_cnode_ptr_of_this());
+                let caller = cnode[TCBReply as usize].mdb_node.get_mdb_next();
+                if caller != 0 {
+                    cte_delete(convert_to_mut_type_ref::<CapTableEntry>(caller), true);
+                }
+            }
+            #[cfg(feature = "mcs")]
+            ThreadStateEnum::ThreadStateBlockedOnReply => {
+                super::reply::reply_remove_tcb(self);
+            }
             _ => {
                 debug!("nothing to do in cancel ipc");
                 // TODO: more state cancel
@@ -110,13 +154,15 @@ impl TCB {
     }
 
     pub fn de_queue_from_sched(&mut self) {
+        #[cfg(feature = "mcs")]
+        super::sched_context::release_remove(self);
         if self.tcb_state.is_get_tcb_queued() {
             
             let dom = self.tcb_domain;
             let prio = self.tcb_priority;
             let idx = ready_queues_index(dom, prio);
             let mut queue = unsafe {
-                KS_READY_QUEUES[idx]
+                KS_READY_QUEUES[self.tcb_affinity][idx]
             };
 
             if self.tcb_sched_prev != 0 {
@@ -125,7 +171,7 @@ impl TCB {
             } else {
                 queue.head = self.tcb_sched_next as *mut TCB;
                 if self.tcb_sched_next == 0 {
-                    remove_from_bitmap(hart_id(), dom, prio);
+                    remove_from_bitmap(self.tcb_affinity, dom, prio);
                 }
             }
             
@@ -138,24 +184,28 @@ impl TCB {
             }
 
             unsafe {
-                KS_READY_QUEUES[idx] = queue;
+                KS_READY_QUEUES[self.tcb_affinity][idx] = queue;
             }
             self.tcb_state.set_tcb_queued(false);
         }
     }
 
     pub fn enqueue_to_sched(&mut self) {
+        #[cfg(feature = "mcs")]
+        if !self.sched_context_ready() {
+            return;
+        }
         if !self.tcb_state.is_get_tcb_queued() {
             let dom =  self.tcb_domain;
             let prio = self.tcb_priority;
             let idx = ready_queues_index(dom, prio);
             let mut queue = unsafe {
-                KS_READY_QUEUES[idx]
+                KS_READY_QUEUES[self.tcb_affinity][idx]
             };
 
             if queue.end as usize == 0 {
                 queue.end = self as *mut TCB;
-                add_to_bitmap(hart_id(), dom, prio);
+                add_to_bitmap(self.tcb_affinity, dom, prio);
             } else {
                 unsafe {
                     (&mut *(queue.head)).tcb_sched_prev = self as *mut TCB as usize;
@@ -166,36 +216,42 @@ impl TCB {
             self.tcb_sched_next = queue.head as usize;
             queue.head = self as *mut TCB;
             unsafe {
-                KS_READY_QUEUES[idx] = queue;
+                KS_READY_QUEUES[self.tcb_affinity][idx] = queue;
             }
             self.tcb_state.set_tcb_queued(true);
+            remote_queue_update(self);
         }
     }
 
     pub fn append_to_sched(&mut self) {
+        #[cfg(feature = "mcs")]
+        if !self.sched_context_ready() {
+            return;
+        }
         if !self.tcb_state.is_get_tcb_queued() {
             let dom =  self.tcb_domain;
             let prio = self.tcb_priority;
             let idx = ready_queues_index(dom, prio);
             let mut queue = unsafe {
-                KS_READY_QUEUES[idx]
+                KS_READY_QUEUES[self.tcb_affinity][idx]
             };
 
             if queue.head as usize == 0 {
                 queue.head = self as *mut TCB;
-                add_to_bitmap(hart_id(), dom, prio);
+                add_to_bitmap(self.tcb_affinity, dom, prio);
             } else {
                 unsafe {
-                    (&mut *(queue.end)).tcb_sched_prev = self as *mut TCB as usize;
+                    (&mut *(queue.end)).tcb_sched_next = self as *mut TCB as usize;
                 }
             }
             self.tcb_sched_prev = queue.end as usize;
             self.tcb_sched_next = 0;
             queue.end = self as *mut TCB;
             unsafe {
-                KS_READY_QUEUES[idx] = queue;
+                KS_READY_QUEUES[self.tcb_affinity][idx] = queue;
             }
             self.tcb_state.set_tcb_queued(true);
+            remote_queue_update(self);
         }
     }
 
@@ -211,6 +267,10 @@ impl TCB {
     }
 
     pub fn is_schedulable(&self) -> bool {
+        #[cfg(feature = "mcs")]
+        if self.sched_context().map_or(true, |sc| !sc.is_active()) || self.tcb_in_release_queue {
+            return false;
+        }
         match self.get_state() {
             ThreadStateEnum::ThreadStateRunning | ThreadStateEnum::ThreadStateRestart => {
                 return true;
@@ -247,6 +307,10 @@ impl TCB {
         self as *const TCB as usize == unsafe { KS_CUR_THREAD[hart_id()] }
     }
 
+    fn is_current_on(&self, cpu: usize) -> bool {
+        self as *const TCB as usize == unsafe { KS_CUR_THREAD[cpu] }
+    }
+
     pub fn get_state(&self) -> ThreadStateEnum {
         unsafe {
             core::mem::transmute::<u8, ThreadStateEnum>(sign_extend(self.tcb_state.words[0] & 0xf, 0x0) as u8)
@@ -278,6 +342,7 @@ impl TCB {
         self.context.registers[NextIP as usize] = entry;
     }
 
+    #[cfg(not(feature = "mcs"))]
     pub fn setup_replay_master(&mut self) {
         let cnode = convert_to_mut_type_ref::<TCBCNode>(self.get_cnode_ptr_of_this());
         let slot = &mut cnode[TCBReply as usize];
@@ -289,6 +354,23 @@ impl TCB {
         }
     }
 
+    /// block the caller of `receiver` until it replies through the cap in its caller slot
+    #[cfg(not(feature = "mcs"))]
+    pub fn setup_caller_cap(&mut self, receiver: &mut TCB, can_grant: bool) {
+        self.set_thread_state(ThreadStateEnum::ThreadStateBlockedOnReply);
+        let reply_slot = &mut convert_to_mut_type_ref::<TCBCNode>(self.get_cnode_ptr_of_this())[TCBReply as usize];
+        assert!(reply_slot.cap.get_reply_master());
+        let caller_slot = &mut convert_to_mut_type_ref::<TCBCNode>(receiver.get_cnode_ptr_of_this())[TCBCaller as usize];
+        assert_eq!(caller_slot.cap.get_cap_type(), CapTag::CapNullCap);
+        cte_insert(Cap::new_reply_cap(can_grant, false, self as *const TCB as Pptr), reply_slot, caller_slot);
+    }
+
+    #[cfg(not(feature = "mcs"))]
+    pub fn delete_caller_cap(&mut self) {
+        let caller_slot = &mut convert_to_mut_type_ref::<TCBCNode>(self.get_cnode_ptr_of_this())[TCBCaller as usize];
+        cte_delete(caller_slot, true);
+    }
+
     pub fn set_priority(&mut self, prio: usize) {
         self.de_queue_from_sched();
         self.tcb_priority = prio;
@@ -301,6 +383,22 @@ impl TCB {
         }
     }
 
+    pub fn set_affinity(&mut self, affinity: usize) {
+        self.de_queue_from_sched();
+        let old_affinity = self.tcb_affinity;
+        self.tcb_affinity = affinity;
+        if self.is_current_on(old_affinity) {
+            // the core it is running on puts it on the new queue at its next schedule
+            if old_affinity == hart_id() {
+                re_schedule();
+            } else {
+                mark_reschedule_pending(old_affinity);
+            }
+        } else if self.is_runnable() {
+            self.append_to_sched();
+        }
+    }
+
     pub fn init_context(&mut self) {
         self.context.registers[SSTATUS as usize] = SSTATUS_SPIE;
     }
@@ -340,7 +438,7 @@ impl TCB {
         
         if vm_right == VmRights::VMReadWrite || (!is_receiver && vm_right == VmRights::VMReadOnly) {
             let base_ptr = buffer_cap.get_frame_base_ptr();
-            let page_bits = page_bits_for_size(buffer_cap.get_frame_size());
+            let page_bits = page_bits_for_size(buffer_cap.get_frame_size())?;
             // error!("w_buffer_ptr: {:#x}, base_ptr: {:#x}, page_bits: {}", w_buffer_ptr, base_ptr, page_bits);
             return Some(base_ptr + (w_buffer_ptr & mask(page_bits)));
         }
@@ -355,6 +453,7 @@ impl TCB {
     }
 }
 
+#[derive(Clone, Copy)]
 pub struct IdleTCB {
     pub array: [u8; SEL4_IDLE_TCB_SLOT_SIZE],
 }
@@ -405,6 +504,11 @@ impl ThreadState {
         sign_extend(self.words[0] & 0x7ffffffff0, 0xffffff8000000000)
     }
 
+    pub fn set_blocking_object(&mut self, pptr: Pptr) {
+        self.words[0] &= !0x7ffffffff0;
+        self.words[0] |= pptr & 0x7ffffffff0;
+    }
+
     pub fn is_get_tcb_queued(&self) -> bool {
         sign_extend(self.words[1] & 0x1, 0x0) == 1
     }
@@ -414,18 +518,85 @@ impl ThreadState {
         self.words[1] &= !0x1;
         self.words[1] |= bool2usize(queued) & 0x1;
     }
-}
 
-#[derive(Default)]
-struct Notification {
-    words: Array<usize, 4>,
+    pub fn is_get_blocking_ipc_is_call(&self) -> bool {
+        self.words[1] & 0x2 != 0
+    }
+
+    pub fn set_blocking_ipc_is_call(&mut self, is_call: bool) {
+        self.words[1] &= !0x2;
+        self.words[1] |= (bool2usize(is_call) << 1) & 0x2;
+    }
+
+    pub fn is_get_blocking_ipc_can_grant_reply(&self) -> bool {
+        self.words[1] & 0x4 != 0
+    }
+
+    pub fn set_blocking_ipc_can_grant_reply(&mut self, can_grant_reply: bool) {
+        self.words[1] &= !0x4;
+        self.words[1] |= (bool2usize(can_grant_reply) << 2) & 0x4;
+    }
+
+    pub fn is_get_blocking_ipc_can_grant(&self) -> bool {
+        self.words[1] & 0x8 != 0
+    }
+
+    pub fn set_blocking_ipc_can_grant(&mut self, can_grant: bool) {
+        self.words[1] &= !0x8;
+        self.words[1] |= (bool2usize(can_grant) << 3) & 0x8;
+    }
+
+    pub fn get_blocking_ipc_badge(&self) -> usize {
+        self.words[2]
+    }
+
+    pub fn set_blocking_ipc_badge(&mut self, badge: usize) {
+        self.words[2] = badge;
+    }
+
+    /// the reply object of a thread blocked on receive or on its reply
+    #[cfg(feature = "mcs")]
+    pub fn get_reply_object(&self) -> Pptr {
+        sign_extend(self.words[1] & 0x7ffffffff0, 0xffffff8000000000)
+    }
+
+    #[cfg(feature = "mcs")]
+    pub fn set_reply_object(&mut self, pptr: Pptr) {
+        self.words[1] &= !0x7ffffffff0;
+        self.words[1] |= pptr & 0x7ffffffff0;
+    }
 }
 
 #[derive(Default)]
-struct Fault {
+pub struct Fault {
     words: Array<usize, 2>,
 }
 
+// the only fault raised so far is the timeout of mcs kernels
+#[cfg(feature = "mcs")]
+impl Fault {
+    pub fn has_fault(&self) -> bool {
+        self.words[0] & 0xf != 0
+    }
+
+    pub fn is_timeout(&self) -> bool {
+        self.words[0] & 0xf == SEL4_TIMEOUT_FAULT
+    }
+
+    pub fn get_timeout_badge(&self) -> usize {
+        self.words[1]
+    }
+
+    pub fn set_timeout(&mut self, badge: usize) {
+        self.words[0] = SEL4_TIMEOUT_FAULT;
+        self.words[1] = badge;
+    }
+
+    pub fn clear(&mut self) {
+        self.words = Array::default();
+    }
+}
+
 #[derive(Default)]
 struct LookUpFault {
     words: Array<usize, 2>,
@@ -445,33 +616,22 @@ pub enum ThreadStateEnum {
     ThreadStateIdleThreadState = 7,
 }
 
-#[derive(Clone, Copy)]
-pub struct TCBQueue {
-    pub head: *mut TCB,
-    pub end: *mut TCB,
-}
+pub type TCBQueue = kernel_lib::scheduler::TCBQueue<TCB>;
 
-impl TCBQueue {
-    pub fn new(head: Pptr, end: Pptr) -> Self {
-        TCBQueue {
-            head: head as *mut TCB,
-            end: end as *mut TCB,
-        }
+impl TCBQueueNode for TCB {
+    fn get_ep_next(&self) -> Pptr {
+        self.tcb_ep_next
     }
 
-    pub fn de_queue(&mut self, tcb: &mut TCB) {
-        if tcb.tcb_ep_prev != 0 {
-            let prev = convert_to_mut_type_ref::<TCB>(tcb.tcb_ep_prev);
-            prev.tcb_ep_next = tcb.tcb_ep_next;
-        } else {
-            self.head = tcb.tcb_ep_next as *mut TCB;
-        }
+    fn set_ep_next(&mut self, next: Pptr) {
+        self.tcb_ep_next = next;
+    }
 
-        if tcb.tcb_ep_next != 0 {
-            let next= convert_to_mut_type_ref::<TCB>(tcb.tcb_ep_next);
-            next.tcb_ep_prev = tcb.tcb_ep_prev;
-        } else {
-            self.end = tcb.tcb_ep_prev as *mut TCB;
-        }
+    fn get_ep_prev(&self) -> Pptr {
+        self.tcb_ep_prev
+    }
+
+    fn set_ep_prev(&mut self, prev: Pptr) {
+        self.tcb_ep_prev = prev;
     }
 }
@@ -0,0 +1,29 @@
+use core::hint::spin_loop;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Ticket lock serialising every hart inside the kernel, taken on kernel entry and
+/// dropped right before returning to user space.
+pub struct BigKernelLock {
+    next_ticket: AtomicUsize,
+    now_serving: AtomicUsize,
+}
+
+impl BigKernelLock {
+    pub const fn new() -> Self {
+        BigKernelLock {
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn acquire(&self) {
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            spin_loop();
+        }
+    }
+
+    pub fn release(&self) {
+        self.now_serving.fetch_add(1, Ordering::Release);
+    }
+}
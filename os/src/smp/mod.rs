@@ -0,0 +1,106 @@
+mod lock;
+
+use core::arch::asm;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use common::config::{CONFIG_KERNEL_STACK_BITS, CPU_NUM, PV_BASE_OFFSET};
+use common::utils::bit;
+use log::{debug, error};
+
+use crate::sbi;
+use crate::scheduler::KERNEL_STACK;
+pub use lock::BigKernelLock;
+
+pub static BKL: BigKernelLock = BigKernelLock::new();
+
+static KS_CPU_ONLINE: AtomicUsize = AtomicUsize::new(0);
+
+static mut KS_IPI_RESCHEDULE_PENDING: [usize; CPU_NUM] = [0; CPU_NUM];
+
+/// While a hart is in the kernel `sscratch` holds the top of its kernel stack, so the
+/// index of the running hart can be recovered from it.
+#[inline]
+pub fn hart_id() -> usize {
+    let sp: usize;
+    unsafe {
+        asm!("csrr {}, sscratch", out(reg) sp);
+    }
+    (sp - kernel_stack_base() - 8) >> CONFIG_KERNEL_STACK_BITS
+}
+
+#[inline]
+fn kernel_stack_base() -> usize {
+    unsafe {
+        &KERNEL_STACK as *const _ as usize
+    }
+}
+
+#[inline]
+pub fn kernel_stack_top(cpu: usize) -> usize {
+    kernel_stack_base() + bit(CONFIG_KERNEL_STACK_BITS) * (cpu + 1)
+}
+
+pub fn init_cpu(cpu: usize) {
+    assert!(cpu < CPU_NUM);
+    unsafe {
+        asm!("csrw sscratch, {}", in(reg) kernel_stack_top(cpu));
+    }
+    KS_CPU_ONLINE.fetch_or(bit(cpu), Ordering::SeqCst);
+}
+
+pub fn is_cpu_online(cpu: usize) -> bool {
+    cpu < CPU_NUM && KS_CPU_ONLINE.load(Ordering::SeqCst) & bit(cpu) != 0
+}
+
+pub fn other_online_harts_mask() -> usize {
+    KS_CPU_ONLINE.load(Ordering::SeqCst) & !bit(hart_id())
+}
+
+pub fn num_nodes() -> usize {
+    KS_CPU_ONLINE.load(Ordering::SeqCst).count_ones() as usize
+}
+
+/// Starts every other hart through SBI HSM. The secondaries spin on the big kernel lock
+/// until the boot hart leaves the kernel.
+pub fn start_secondary_harts(boot_hart: usize) {
+    extern "C" {
+        fn _secondary_start();
+    }
+    for hart in 0..CPU_NUM {
+        if hart == boot_hart {
+            continue;
+        }
+        let start_paddr = _secondary_start as usize - PV_BASE_OFFSET;
+        let stack_paddr = kernel_stack_top(hart) - PV_BASE_OFFSET;
+        if sbi::hart_start(hart, start_paddr, stack_paddr) {
+            debug!("hart {} started", hart);
+            KS_CPU_ONLINE.fetch_or(bit(hart), Ordering::SeqCst);
+        } else {
+            error!("failed to start hart {}", hart);
+        }
+    }
+}
+
+pub fn mark_reschedule_pending(cpu: usize) {
+    unsafe {
+        KS_IPI_RESCHEDULE_PENDING[hart_id()] |= bit(cpu);
+    }
+}
+
+/// Sends the reschedule IPIs queued up during this kernel entry.
+pub fn do_mask_reschedule() {
+    let mask = unsafe {
+        let cpu = hart_id();
+        let mask = KS_IPI_RESCHEDULE_PENDING[cpu] & !bit(cpu);
+        KS_IPI_RESCHEDULE_PENDING[cpu] = 0;
+        mask
+    };
+    if mask != 0 {
+        sbi::send_ipi(mask);
+    }
+}
+
+pub fn clear_ipi() {
+    unsafe {
+        riscv::register::sip::clear_ssoft();
+    }
+}
@@ -0,0 +1,41 @@
+-- word-packed kernel objects, turned into rust by bitfield_gen from build.rs.
+-- fields are listed from the top bit of the last word down, as in seL4's structures_64.bf;
+-- field_high keeps the top bits of a 39 bit canonical address and sign extends it on read.
+
+base 64(39,1)
+
+-- kernel objects, the caps and mdb nodes are in kernel_lib/src/structures.bf
+
+block EndPoint {
+    field queue_head 64
+
+    padding 25
+    field_high queue_tail 37
+    field ep_state 2
+}
+
+block Notification {
+    padding 32 -- bound tcb, not used yet
+    field_high ntfn_sched_context 32 -- donated to passive waiters with mcs, 2^7 aligned
+    field msg_identifier 64
+    field queue_head 64
+
+    padding 25
+    field_high queue_tail 37
+    field ntfn_state 2
+}
+
+-- sv39 page table entry
+block PageTableEntry {
+    padding 10
+    field ppn 44
+    field sw 2
+    field dirty 1
+    field accessed 1
+    field global 1
+    field user 1
+    field execute 1
+    field write 1
+    field read 1
+    field valid 1
+}
@@ -0,0 +1,5 @@
+//! the word-packed structures of structures.bf, generated by build.rs. accessors the kernel
+//! does not use yet are kept, so the spec stays the single description of each layout.
+#![allow(dead_code)]
+
+include!(concat!(env!("OUT_DIR"), "/structures.rs"));
@@ -2,7 +2,7 @@ use core::arch::{asm, global_asm};
 use log::{error, debug};
 use crate::{sbi, interrupt};
 use crate::scheduler::{KS_CUR_THREAD, TCB};
-use common::utils::hart_id;
+use crate::smp::{hart_id, kernel_stack_top, do_mask_reschedule, BKL};
 use riscv::register::stvec;
 use crate::inner_syscall;
 use riscv::register::stvec::TrapMode;
@@ -24,7 +24,14 @@ pub fn restore_user_context() {
         let tcb = &*(KS_CUR_THREAD[hart_id()]as *const TCB);
         tcb.get_context_base_ptr()
     };
-    // TODO: c_exit_hook
+    unsafe {
+        // picked up by trap_entry on the next trap
+        *((kernel_stack_top(hart_id()) - 8) as *mut usize) = cur_thread_reg_ptr;
+    }
+    #[cfg(feature = "benchmark")]
+    crate::benchmark::kernel_exit();
+    do_mask_reschedule();
+    BKL.release();
     unsafe {
         asm!(
             "mv t0, {0}",
@@ -68,8 +75,6 @@ pub fn restore_user_context() {
             // get sepc
             "ld t1, (34*8)(t0)",
             "csrw sepc, t1",
-            // Write back sscratch with cur_thread_reg to get it back on the next trap entry
-            "csrw sscratch, t0",
             "ld t1, (32*8)(t0)",
             "csrw sstatus, t1",
             "ld t1, (5*8)(t0)",
@@ -83,6 +88,11 @@ pub fn restore_user_context() {
 
 #[no_mangle]
 pub fn rust_handle_syscall(cptr: usize, msg_info: usize, syscall: isize) -> ! {
+    #[cfg(feature = "benchmark")]
+    crate::benchmark::kernel_entry(common::benchmark::TraceKind::Syscall, syscall as usize, msg_info);
+    BKL.acquire();
+    #[cfg(feature = "mcs")]
+    crate::scheduler::update_timestamp();
 
     // debug!("hello handle_syscall: cptr: {}, msg_info: {}, inner_syscall: {}", cptr, msg_info, inner_syscall);
     inner_syscall::slowpath(syscall);
@@ -92,6 +102,11 @@ pub fn rust_handle_syscall(cptr: usize, msg_info: usize, syscall: isize) -> ! {
 
 #[no_mangle]
 pub fn rust_handle_interrupt() -> ! {
+    #[cfg(feature = "benchmark")]
+    crate::benchmark::kernel_entry(common::benchmark::TraceKind::Interrupt, riscv::register::scause::read().bits(), 0);
+    BKL.acquire();
+    #[cfg(feature = "mcs")]
+    crate::scheduler::update_timestamp();
     debug!("hello handle_interrupt");
     interrupt::handle_interrupt();
     sbi::shutdown(false)
@@ -99,6 +114,11 @@ pub fn rust_handle_interrupt() -> ! {
 
 #[no_mangle]
 pub fn rust_handle_exception() -> ! {
+    #[cfg(feature = "benchmark")]
+    crate::benchmark::kernel_entry(common::benchmark::TraceKind::Exception, riscv::register::scause::read().bits(), 0);
+    BKL.acquire();
+    #[cfg(feature = "mcs")]
+    crate::scheduler::update_timestamp();
     debug!("hello handle_exception");
     interrupt::handle_interrupt();
     sbi::shutdown(false)
@@ -3,12 +3,13 @@
 .global trap_entry
 
 trap_entry:
-    csrrw t0, sscratch, t0
+    # sscratch holds the kernel stack top of this hart and the
+    # current thread's context pointer sits right below it
+    csrrw sp, sscratch, sp
+    sd t0, -16(sp)
+    ld t0, -8(sp)
 
     sd ra, (0*8)(t0)
-
-    sd sp, (1*8)(t0)
-
     sd gp, (2*8)(t0)
     sd tp, (3*8)(t0)
     sd t1, (5*8)(t0)
@@ -39,14 +40,16 @@ trap_entry:
     sd t6, (30*8)(t0)
 
     csrr  x1, sscratch
+    sd    x1, (1*8)(t0)
+    ld    x1, -16(sp)
     sd    x1, (4*8)(t0)
+    csrw  sscratch, sp
+    addi  sp, sp, -16
     csrr x1, sstatus
     sd x1, (32*8)(t0)
     csrr s0, scause
     sd s0, (31*8)(t0)
 
-    la sp, boot_stack_top
-
     csrr x1,  sepc
     sd   x1, (33*8)(t0)
     bltz s0, interrupt
@@ -1,32 +1,16 @@
 
 use log::{debug, error, warn};
 use crate::boot::{BootInfo, NDKS_BOOT};
-use common::config::{CONFIG_MAX_NUM_BOOT_INFO_UNTYPED_CAPS, MAX_UNTYPED_BITS, MIN_UNTYPED_BITS, PPTR_BASE_OFFSET, WORD_BITS};
-use crate::cspace::{Cap, CapTableEntry, CapTag, create_untyped_cap};
+use common::config::{CONFIG_MAX_NUM_BOOT_INFO_UNTYPED_CAPS, PPTR_BASE_OFFSET};
+use crate::cspace::{Cap, create_untyped_cap};
 use common::types::{Pptr, Region, SlotPos, UntypedDesc};
-use common::utils::{bit, convert_to_mut_type_ref};
+use common::utils::convert_to_mut_type_ref;
+pub use kernel_lib::untyped::{for_each_untyped_in_region, max_free_index, set_untyped_cap_as_full};
 
 pub fn create_untyped_for_region(cnode_cap: Cap, is_device_mem: bool, reg: Region, first_slot: SlotPos) {
-    let mut start = reg.start;
-    while start < reg.end {
-
-        let mut size_bits = WORD_BITS - 1 - ((reg.end - start).leading_zeros() as usize);
-        if size_bits > MAX_UNTYPED_BITS {
-            size_bits = MAX_UNTYPED_BITS;
-        }
-        if reg.start != 0 {
-            let align_bits = start.trailing_zeros() as usize;
-            if size_bits > align_bits {
-                size_bits = align_bits;
-            }
-        }
-
-        if size_bits >= MIN_UNTYPED_BITS {
-            provide_untyped_cap(cnode_cap, is_device_mem, start, size_bits, first_slot);
-        }
-
-        start += bit(size_bits);
-    }
+    for_each_untyped_in_region(reg, |pptr, size_bits| {
+        provide_untyped_cap(cnode_cap, is_device_mem, pptr, size_bits, first_slot);
+    });
 }
 
 
@@ -42,19 +26,3 @@ pub fn provide_untyped_cap(cnode_cap: Cap, is_device_mem: bool, pptr: Pptr, size
         warn!("Kernel init: Too many untyped regions for boot info");
     }
 }
-
-pub fn set_untyped_cap_as_full(src_cap: Cap, new_cap: Cap, src_slot: &mut CapTableEntry) {
-    if src_cap.get_cap_type() == CapTag::CapUntypedCap && new_cap.get_cap_type() == CapTag::CapUntypedCap {
-        if src_cap.get_cap_pptr() == new_cap.get_cap_pptr() &&
-            src_cap.get_untyped_cap_block_size() == new_cap.get_untyped_cap_block_size() {
-            let ref_cap = &mut src_slot.cap;
-            ref_cap.set_untyped_cap_free_index(max_free_index(src_cap.get_untyped_cap_block_size()));
-        }
-    }
-}
-
-
-#[inline]
-fn max_free_index(size_bits: usize) -> usize {
-    bit(size_bits - MIN_UNTYPED_BITS)
-}
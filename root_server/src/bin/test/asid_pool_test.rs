@@ -0,0 +1,91 @@
+use common::{object::ObjectType, types::{CNodeSlot, VMAttributes}, config::{ASID_POOL_INDEX_BITS, PAGE_BITS,
+    SEL4_ASID_POOL_BITS}};
+use user_lib::{cnode::sel4_cnode_delete, cspace::CSlot, untyped::sel4_untyped_retype,
+    vspace::{sel4_asid_control_make_pool, sel4_asid_pool_assign, sel4_page_table_map}, println};
+
+use super::utils::{alloc_obj, get_allocator};
+
+const TEST_VADDR: usize = 0x400_0000;
+// a pool holds this many vspaces, asid 0 only being left out of the first one
+const POOL_SIZE: usize = 1 << ASID_POOL_INDEX_BITS;
+// room for every page table the pool does not already hold
+const FILL_UNTYPED_BITS: usize = ASID_POOL_INDEX_BITS + PAGE_BITS;
+
+fn alloc_slot() -> CSlot {
+    get_allocator().slots().alloc().expect("no root slots")
+}
+
+fn make_pool(untyped: usize, slot: CSlot) -> isize {
+    sel4_asid_control_make_pool(CNodeSlot::SeL4CapASIDControl as usize, untyped, slot.path)
+}
+
+fn map_pt(vspace: usize) -> isize {
+    let pt = alloc_obj(ObjectType::RiscvPageTableObject, 0);
+    let error = sel4_page_table_map(pt, vspace, TEST_VADDR, VMAttributes::DefaultVMAttributes);
+    assert!(get_allocator().free_object(pt));
+    error
+}
+
+pub fn asid_pool_test() {
+    // only an untyped of exactly the pool's size becomes one
+    let wrong_size = alloc_obj(ObjectType::UntypedObject, SEL4_ASID_POOL_BITS + 1);
+    let pool_slot = alloc_slot();
+    assert_eq!(make_pool(wrong_size, pool_slot), -1);
+    assert!(get_allocator().free_object(wrong_size));
+
+    let untyped = alloc_obj(ObjectType::UntypedObject, SEL4_ASID_POOL_BITS);
+    assert_eq!(make_pool(untyped, pool_slot), 0);
+    let pool = pool_slot.cptr().unwrap();
+    // the untyped is used up by the pool
+    let second_slot = alloc_slot();
+    assert_eq!(make_pool(untyped, second_slot), -1);
+    get_allocator().slots().free(second_slot);
+
+    // two vspaces from the new pool, each usable on its own
+    let vspace_a = alloc_obj(ObjectType::RiscvPageTableObject, 0);
+    let vspace_b = alloc_obj(ObjectType::RiscvPageTableObject, 0);
+    assert_eq!(map_pt(vspace_a), -1);
+    assert_eq!(sel4_asid_pool_assign(pool, vspace_a), 0);
+    assert_eq!(sel4_asid_pool_assign(pool, vspace_b), 0);
+    assert_eq!(sel4_asid_pool_assign(pool, vspace_a), -1);
+    assert_eq!(map_pt(vspace_a), 0);
+    assert_eq!(map_pt(vspace_b), 0);
+
+    // fill the rest of the pool, so one more vspace has no asid left
+    let fill_untyped = alloc_obj(ObjectType::UntypedObject, FILL_UNTYPED_BITS);
+    let mut fill_slots = [None; POOL_SIZE - 2];
+    for slot in fill_slots.iter_mut() {
+        let fill_slot = alloc_slot();
+        assert_eq!(sel4_untyped_retype(fill_untyped, ObjectType::RiscvPageTableObject as usize, 0,
+            fill_slot.cnode, fill_slot.offset, 1), 0);
+        assert_eq!(sel4_asid_pool_assign(pool, fill_slot.cptr().unwrap()), 0);
+        *slot = Some(fill_slot);
+    }
+    let vspace_c = alloc_obj(ObjectType::RiscvPageTableObject, 0);
+    assert_eq!(sel4_asid_pool_assign(pool, vspace_c), -1);
+
+    // deleting the vspaces gives their asids back
+    assert!(get_allocator().free_object(fill_untyped));
+    for slot in fill_slots.iter().flatten() {
+        get_allocator().slots().free(*slot);
+    }
+    assert_eq!(sel4_asid_pool_assign(pool, vspace_c), 0);
+
+    // and deleting the pool takes every vspace assigned from it along
+    assert_eq!(sel4_cnode_delete(pool_slot.path), 0);
+    let vspace_d = alloc_obj(ObjectType::RiscvPageTableObject, 0);
+    assert_eq!(sel4_asid_pool_assign(pool, vspace_d), -1);
+    assert_eq!(map_pt(vspace_a), -1);
+    assert_eq!(map_pt(vspace_c), -1);
+    // the untyped and the pool's share of the asids can make a new one
+    assert_eq!(make_pool(untyped, pool_slot), 0);
+    assert_eq!(sel4_asid_pool_assign(pool, vspace_d), 0);
+    assert_eq!(sel4_cnode_delete(pool_slot.path), 0);
+
+    for vspace in [vspace_a, vspace_b, vspace_c, vspace_d] {
+        assert!(get_allocator().free_object(vspace));
+    }
+    assert!(get_allocator().free_object(untyped));
+    get_allocator().slots().free(pool_slot);
+    println!("asid pool test passed");
+}
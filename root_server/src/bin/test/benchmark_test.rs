@@ -0,0 +1,72 @@
+use common::{benchmark::TraceKind, config::PAGE_SIZE, object::ObjectType, types::{CNodeSlot, CapRights, VMAttributes}};
+use syscall::SYS_NB_RECV;
+use user_lib::{benchmark::{log_entries, sel4_benchmark_finalize_log, sel4_benchmark_reset_log, sel4_benchmark_set_log_buffer,
+    sel4_benchmark_get_thread_utilisation, sel4_benchmark_reset_thread_utilisation}, notification::sel4_poll,
+    thread::spawn, println};
+
+use super::utils::{alloc_obj, get_allocator, get_vspace};
+
+const NUM_POLLS: usize = 8;
+const THREAD_PRIORITY: usize = 254;
+
+fn spin(n: usize) -> usize {
+    (0..n).fold(0, |acc, i| core::hint::black_box(acc ^ i))
+}
+
+pub fn benchmark_test() {
+    let frame = alloc_obj(ObjectType::Riscv4kpage, 0);
+    let buffer = get_vspace().reserve_anywhere(PAGE_SIZE).expect("no room to reserve");
+    assert!(get_vspace().map_pages(&mut *get_allocator(), &[frame], buffer, CapRights::new(1, 1, 1, 1),
+                                   VMAttributes::ExecuteNever));
+    let ntfn = alloc_obj(ObjectType::NotificationObject, 0);
+
+    assert_eq!(sel4_benchmark_set_log_buffer(ntfn), -1);
+    assert_eq!(sel4_benchmark_set_log_buffer(frame), 0);
+    assert_eq!(sel4_benchmark_reset_log(), 0);
+    for _ in 0..NUM_POLLS {
+        sel4_poll(ntfn);
+    }
+    let count = sel4_benchmark_finalize_log();
+    assert!(count >= NUM_POLLS);
+
+    let polls = log_entries(buffer, PAGE_SIZE, count)
+        .filter(|entry| entry.get_kind() == Some(TraceKind::Syscall) && entry.number as isize == SYS_NB_RECV)
+        .inspect(|entry| assert!(entry.cycles > 0))
+        .count();
+    assert_eq!(polls, NUM_POLLS);
+    // nothing is logged once finalized
+    sel4_poll(ntfn);
+    assert_eq!(sel4_benchmark_finalize_log(), count);
+
+    assert_eq!(sel4_benchmark_set_log_buffer(CNodeSlot::SeL4CapNull as usize), 0);
+    assert_eq!(sel4_benchmark_reset_log(), -1);
+
+    // a frame freed while still logging into takes the log buffer along
+    assert_eq!(sel4_benchmark_set_log_buffer(frame), 0);
+    assert_eq!(sel4_benchmark_reset_log(), 0);
+    assert!(get_vspace().unmap_pages(&mut *get_allocator(), buffer, 1));
+    assert!(get_vspace().unreserve(buffer));
+    assert!(get_allocator().free_object(frame));
+    assert_eq!(sel4_benchmark_reset_log(), -1);
+    assert!(get_allocator().free_object(ntfn));
+    println!("benchmark test passed");
+}
+
+pub fn utilisation_test() {
+    // no log buffer, but the utilisation is reset all the same
+    assert_eq!(sel4_benchmark_reset_log(), -1);
+    let thread = spawn(&mut *get_allocator(), get_vspace(), spin, 100_000, THREAD_PRIORITY).expect("failed to spawn thread");
+    thread.join();
+
+    let utilisation = sel4_benchmark_get_thread_utilisation(thread.tcb).expect("no utilisation");
+    assert!(utilisation.thread > 0);
+    assert!(utilisation.schedules >= 1);
+    assert!(utilisation.total > utilisation.thread);
+    assert!(sel4_benchmark_get_thread_utilisation(thread.exit_ntfn).is_none());
+
+    assert_eq!(sel4_benchmark_reset_thread_utilisation(thread.tcb), 0);
+    let utilisation = sel4_benchmark_get_thread_utilisation(thread.tcb).expect("no utilisation");
+    assert_eq!((utilisation.thread, utilisation.schedules), (0, 0));
+    assert!(thread.destroy(&mut *get_allocator(), get_vspace()));
+    println!("utilisation test passed");
+}
@@ -0,0 +1,21 @@
+use root_server::boot_module::{boot_modules_iter, find_boot_module, get_boot_modules_info};
+use user_lib::println;
+
+use super::utils::get_boot_info;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+pub fn boot_module_test() {
+    let info = get_boot_info();
+    let modules = get_boot_modules_info(info).expect("no boot modules");
+    assert_ne!(modules.frames.start, modules.frames.end);
+    for module in boot_modules_iter(info) {
+        println!("boot module: {}, size: {:#x}", module.name, module.data.len());
+    }
+    for name in ["client1", "client2"] {
+        let elf = find_boot_module(info, name).expect("boot module not found");
+        assert_eq!(&elf[..ELF_MAGIC.len()], ELF_MAGIC);
+    }
+    assert!(find_boot_module(info, "no_such_module").is_none());
+    println!("boot module test passed");
+}
@@ -0,0 +1,94 @@
+use common::{object::ObjectType, types::{CapRights, SlotRegion}, config::PAGE_BITS};
+use user_lib::{cnode::{sel4_cnode_copy, sel4_cnode_delete}, cspace::{CapPath, CSlot, SlotAllocator},
+    untyped::sel4_untyped_retype, vspace::sel4_page_get_address, println};
+
+use super::utils::{alloc_obj, get_allocator};
+
+const L2_RADIX: usize = 6;
+
+fn all_rights() -> CapRights {
+    CapRights::new(1, 1, 1, 1)
+}
+
+pub fn cspace_test() {
+    // root slots are handed back out before new ones
+    {
+        let mut allocator = get_allocator();
+        let slots = allocator.slots();
+        let num_free = slots.num_free();
+        let first = slots.alloc().expect("no root slots");
+        let second = slots.alloc().expect("no root slots");
+        assert_ne!(first, second);
+        assert_eq!(first.cptr(), Some(first.offset));
+        assert!(slots.free(second));
+        assert_eq!(slots.alloc(), Some(second));
+        assert!(slots.free(second));
+        assert!(!slots.free(second));
+        assert!(slots.free(first));
+        assert_eq!(slots.num_free(), num_free);
+
+        // however many slots are freed, none of them are lost
+        let mut many = [0; 300];
+        for cptr in many.iter_mut() {
+            *cptr = slots.alloc().and_then(|slot| slot.cptr()).expect("no root slots");
+        }
+        for cptr in many {
+            assert!(slots.free(CSlot::root(cptr)));
+        }
+        assert_eq!(slots.num_free(), num_free);
+    }
+
+    // a second level cnode, addressed through its own cap
+    let cnode = alloc_obj(ObjectType::CapTableObject, L2_RADIX);
+    let mut l2_slots = SlotAllocator::new();
+    assert!(l2_slots.add_cnode(cnode, L2_RADIX));
+    assert_eq!(l2_slots.num_free(), 1 << L2_RADIX);
+
+    // a frame copied into the second level and back out is still the same frame
+    let frame = alloc_obj(ObjectType::Riscv4kpage, 0);
+    let (error, paddr) = sel4_page_get_address(frame);
+    assert_eq!(error, 0);
+    let l2_slot = l2_slots.alloc().expect("no second level slots");
+    assert_eq!(l2_slot.cptr(), None);
+    assert_eq!(sel4_cnode_copy(l2_slot.path, CapPath::root_slot(frame), all_rights()), 0);
+    assert_eq!(sel4_cnode_copy(l2_slot.path, CapPath::root_slot(frame), all_rights()), -1);
+    let root_slot = get_allocator().slots().alloc().expect("no root slots");
+    assert_eq!(sel4_cnode_copy(root_slot.path, l2_slot.path, all_rights()), 0);
+    assert_eq!(sel4_page_get_address(root_slot.cptr().unwrap()), (0, paddr));
+    assert_eq!(sel4_cnode_delete(root_slot.path), 0);
+    assert_eq!(sel4_cnode_delete(l2_slot.path), 0);
+    get_allocator().slots().free(root_slot);
+    l2_slots.free(l2_slot);
+
+    // objects retyped straight into every second level slot, gone with their untyped
+    let untyped = alloc_obj(ObjectType::UntypedObject, PAGE_BITS);
+    let mut filled = [None; 1 << L2_RADIX];
+    for slot in filled.iter_mut() {
+        let l2_slot = l2_slots.alloc().expect("second level cnode too small");
+        assert_eq!(l2_slot.cnode, CapPath::root_slot(cnode));
+        assert_eq!(sel4_untyped_retype(untyped, ObjectType::EndpointObject as usize, 0, l2_slot.cnode,
+            l2_slot.offset, 1), 0);
+        *slot = Some(l2_slot);
+    }
+    assert_eq!(l2_slots.alloc(), None);
+    assert!(get_allocator().free_object(untyped));
+    for l2_slot in filled.iter().flatten() {
+        assert_eq!(sel4_cnode_copy(l2_slot.path, CapPath::root_slot(frame), all_rights()), 0);
+        assert_eq!(sel4_cnode_delete(l2_slot.path), 0);
+        l2_slots.free(*l2_slot);
+    }
+
+    // once a cnode runs out the next one is used
+    let next_cnode = alloc_obj(ObjectType::CapTableObject, L2_RADIX);
+    let mut l2_slots = SlotAllocator::new();
+    assert!(l2_slots.add_cnode_slots(CapPath::root_slot(cnode), CapPath::new(cnode, 0, L2_RADIX),
+        SlotRegion { start: 0, end: 1 }));
+    assert!(l2_slots.add_cnode(next_cnode, L2_RADIX));
+    assert_eq!(l2_slots.alloc().map(|slot| slot.path.root), Some(cnode));
+    assert_eq!(l2_slots.alloc().map(|slot| slot.path.root), Some(next_cnode));
+
+    assert!(get_allocator().free_object(next_cnode));
+    assert!(get_allocator().free_object(cnode));
+    assert!(get_allocator().free_object(frame));
+    println!("cspace test passed");
+}
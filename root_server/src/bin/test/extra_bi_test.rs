@@ -0,0 +1,31 @@
+use root_server::extra_bi::{BootInfoID, ExtraBiIter, get_fdt, get_platform_info};
+use user_lib::println;
+
+use super::utils::get_boot_info;
+
+const UART_COMPATIBLE: [&str; 1] = ["ns16550a"];
+
+pub fn extra_bi_test() {
+    let info = get_boot_info();
+    let mut total = 0;
+    let mut last_id = None;
+    for chunk in ExtraBiIter::new(info) {
+        println!("extra bootinfo chunk: id {}, len {:#x}", chunk.id, chunk.payload.len());
+        total += core::mem::size_of::<usize>() * 2 + chunk.payload.len();
+        last_id = Some(chunk.id);
+    }
+    assert_eq!(total, info.extra_len);
+    assert_eq!(last_id, Some(BootInfoID::Sel4BootInfoHeaderPadding as usize));
+
+    let platform = get_platform_info(info).expect("no platform info");
+    assert_ne!(platform.timebase_freq, 0);
+    assert_ne!(platform.num_mem, 0);
+    println!("kernel version {}.{}.{}, timebase {}", platform.kernel_version[0], platform.kernel_version[1],
+        platform.kernel_version[2], platform.timebase_freq);
+
+    let fdt = get_fdt(info).expect("no device tree");
+    let uart = fdt.find_compatible(&UART_COMPATIBLE).expect("no uart in device tree");
+    assert!(uart.start < uart.end);
+    println!("uart: {:#x} ... {:#x}", uart.start, uart.end);
+    println!("extra bootinfo test passed");
+}
@@ -0,0 +1,45 @@
+use alloc::{boxed::Box, collections::BTreeMap, format, string::String, vec::Vec};
+use common::config::PAGE_SIZE;
+use user_lib::{heap::{heap_size, heap_used, HEAP_GROW_PAGES, HEAP_START}, println};
+
+const NUM_WORDS: usize = 4 * HEAP_GROW_PAGES * PAGE_SIZE / 8;
+
+pub fn heap_test() {
+    let used = heap_used();
+
+    let boxed = Box::new(0x1234_5678usize);
+    assert!(&*boxed as *const usize as usize >= HEAP_START);
+    assert_eq!(*boxed, 0x1234_5678);
+
+    // a vector outgrowing several growth steps, reallocated on the way
+    let mut words = Vec::new();
+    for i in 0..NUM_WORDS {
+        words.push(i);
+    }
+    assert!(heap_size() >= NUM_WORDS * 8);
+    assert!(words.iter().enumerate().all(|(i, word)| i == *word));
+
+    let mut text = String::from("hello");
+    text.push_str(" heap");
+    assert_eq!(format!("{} {}", text, *boxed), "hello heap 305419896");
+
+    let mut map = BTreeMap::new();
+    for i in 0..64 {
+        map.insert(i, format!("{}", i));
+    }
+    assert_eq!(map.get(&42).map(String::as_str), Some("42"));
+
+    drop(map);
+    drop(text);
+    drop(words);
+    drop(boxed);
+    assert_eq!(heap_used(), used);
+
+    // freed memory is reused rather than mapping more
+    let size = heap_size();
+    let words: Vec<usize> = (0..NUM_WORDS / 2).collect();
+    assert_eq!(heap_size(), size);
+    drop(words);
+
+    println!("heap test passed");
+}
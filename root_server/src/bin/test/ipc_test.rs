@@ -0,0 +1,83 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use common::{message::{MessageInfo, InvocationLabel, NUM_MSG_REGISTRES, SEL4_CAP_FAULT}, object::ObjectType, types::{CapRights, CNodeSlot}};
+use user_lib::{cnode::{sel4_cnode_delete, sel4_cnode_mint}, cspace::{CapPath, CSlot},
+    endpoint::{sel4_call, sel4_nb_recv, sel4_recv, sel4_reply_recv, sel4_send}, thread::spawn, get_mr, set_mr, println};
+
+use super::utils::{alloc_obj, get_allocator, get_vspace};
+
+const SERVER_PRIORITY: usize = 254;
+const BADGE: usize = 0x61;
+const ROUNDS: usize = 4;
+
+// the reply object the server answers through, with mcs
+static REPLY: AtomicUsize = AtomicUsize::new(0);
+
+fn message(length: usize) -> MessageInfo {
+    MessageInfo::new(InvocationLabel::InvalidInvocation, 0, 0, length)
+}
+
+// answers each call with the sum of its words until it is sent an empty message, returning how
+// many it added up
+fn adder(ep: usize) -> usize {
+    let reply = REPLY.load(Ordering::SeqCst);
+    let mut badge = 0;
+    let mut mrs = [0; NUM_MSG_REGISTRES];
+    let mut info = sel4_recv(ep, &mut badge, &mut mrs, reply);
+    let mut served = 0;
+    while info.get_length() != 0 {
+        assert_eq!(badge, BADGE);
+        mrs[0] = (0..info.get_length()).map(|i| if i < NUM_MSG_REGISTRES { mrs[i] } else { get_mr(i) }).sum();
+        served += 1;
+        info = sel4_reply_recv(ep, message(1), &mut badge, &mut mrs, reply);
+    }
+    served
+}
+
+pub fn ipc_test() {
+    let ep = alloc_obj(ObjectType::EndpointObject, 0);
+    let badged = get_allocator().slots().alloc().and_then(|slot| slot.cptr()).expect("no root slots");
+    assert_eq!(sel4_cnode_mint(CapPath::root_slot(badged), CapPath::root_slot(ep), CapRights::new(1, 1, 1, 1), BADGE), 0);
+
+    #[cfg(feature = "mcs")]
+    REPLY.store(alloc_obj(ObjectType::ReplyObject, 0), Ordering::SeqCst);
+
+    // nobody is sending yet
+    let mut badge = 1;
+    sel4_nb_recv(ep, &mut badge, &mut [0; NUM_MSG_REGISTRES], REPLY.load(Ordering::SeqCst));
+    assert_eq!(badge, 0);
+
+    // there is nothing to receive from a tcb
+    let tcb = CNodeSlot::SeL4CapInitThreadTcb as usize;
+    let mut mrs = [0; NUM_MSG_REGISTRES];
+    let info = sel4_recv(tcb, &mut badge, &mut mrs, REPLY.load(Ordering::SeqCst));
+    assert_eq!(info.get_label(), SEL4_CAP_FAULT);
+    assert_eq!(mrs[0], tcb);
+
+    let server = spawn(&mut *get_allocator(), get_vspace(), adder, ep, SERVER_PRIORITY).expect("failed to spawn server");
+    for i in 1..=ROUNDS {
+        let mut mrs = [i, i * 10, 0, 0];
+        let info = sel4_call(badged, message(2), &mut mrs);
+        assert_eq!(info.get_length(), 1);
+        assert_eq!(mrs[0], i * 11);
+    }
+
+    // the words past the registers go through the ipc buffers
+    set_mr(4, 1000);
+    let mut mrs = [1, 2, 3, 4];
+    let info = sel4_call(badged, message(5), &mut mrs);
+    assert_eq!(info.get_length(), 1);
+    assert_eq!(mrs[0], 1010);
+
+    sel4_send(badged, message(0), &[0; NUM_MSG_REGISTRES]);
+    assert_eq!(server.join(), ROUNDS + 1);
+    assert!(server.destroy(&mut *get_allocator(), get_vspace()));
+    #[cfg(feature = "mcs")]
+    assert!(get_allocator().free_object(REPLY.load(Ordering::SeqCst)));
+
+    assert_eq!(sel4_cnode_delete(CapPath::root_slot(badged)), 0);
+    get_allocator().slots().free(CSlot::root(badged));
+    assert!(get_allocator().free_object(ep));
+
+    println!("ipc test passed");
+}
@@ -0,0 +1,32 @@
+use common::logging::{LogLevel, LOG_SINK_CONSOLE, LOG_SINK_RING};
+use common::object::ObjectType;
+use syscall::sys_debug_set_log_level;
+use user_lib::{logging::{sel4_debug_read_log, sel4_debug_set_log_level, sel4_debug_set_log_sink}, notification::sel4_poll,
+    println};
+
+use super::utils::alloc_obj;
+
+const SLOWPATH: &str = "os::inner_syscall::slowpath";
+
+fn contains(haystack: &[u8], needle: &str) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle.as_bytes())
+}
+
+pub fn logging_test() {
+    let ntfn = alloc_obj(ObjectType::NotificationObject, 0);
+    let mut buf = [0u8; 1024];
+
+    assert_eq!(sel4_debug_set_log_sink(LOG_SINK_RING), 0);
+    while sel4_debug_read_log(&mut buf) != 0 {}
+    assert_eq!(sel4_debug_set_log_level(Some(SLOWPATH), Some(LogLevel::Debug)), 0);
+    sel4_poll(ntfn);
+    assert_eq!(sel4_debug_set_log_level(Some(SLOWPATH), None), 0);
+    let len = sel4_debug_read_log(&mut buf);
+    assert!(contains(&buf[..len], "handle inner_syscall"));
+
+    assert_eq!(sys_debug_set_log_level(LogLevel::Trace as usize + 1, 0), -1);
+    assert_eq!(sel4_debug_set_log_level(Some(SLOWPATH), None), 0);
+    assert_eq!(sel4_debug_set_log_sink(LOG_SINK_RING << 1), -1);
+    assert_eq!(sel4_debug_set_log_sink(LOG_SINK_CONSOLE), 0);
+    println!("logging test passed");
+}
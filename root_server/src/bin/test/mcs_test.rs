@@ -0,0 +1,177 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use common::{message::{InvocationLabel, MessageInfo, NUM_MSG_REGISTRES}, object::ObjectType,
+    sched_context::{MIN_BUDGET_US, SCHED_CONTEXT_SPORADIC, SEL4_MIN_SCHED_CONTEXT_BITS, SEL4_TIMEOUT_CONSUMED,
+    SEL4_TIMEOUT_DATA, SEL4_TIMEOUT_FAULT, SEL4_TIMEOUT_LENGTH, max_extra_refills}};
+use user_lib::{endpoint::{sel4_call, sel4_nb_send, sel4_recv, sel4_reply_recv, sel4_send},
+    notification::{sel4_poll, sel4_signal, sel4_wait}, println, sched_context::{sched_control,
+    sel4_sched_context_bind, sel4_sched_context_consumed, sel4_sched_context_unbind, sel4_sched_context_unbind_object,
+    sel4_sched_control_configure_flags}, thread::{sel4_tcb_set_timeout_endpoint, sel4_tcb_suspend, spawn, Thread}};
+
+use super::utils::{alloc_obj, get_allocator, get_vspace};
+
+const WORKER_PRIORITY: usize = 253;
+const SPINNER_PRIORITY: usize = 252;
+// above the test thread, so the waiter is back waiting before the test goes on
+const SERVER_PRIORITY: usize = 255;
+
+const BUDGET_US: usize = 2000;
+const PERIOD_US: usize = 10000;
+const TIMEOUT_BADGE: usize = 0x77;
+const ROUNDS: usize = 3;
+
+static SPINS: AtomicUsize = AtomicUsize::new(0);
+static SERVER_REPLY: AtomicUsize = AtomicUsize::new(0);
+static SERVED: AtomicUsize = AtomicUsize::new(0);
+static WAKE: AtomicUsize = AtomicUsize::new(0);
+static DONE: AtomicUsize = AtomicUsize::new(0);
+static WOKEN: AtomicUsize = AtomicUsize::new(0);
+
+fn message(length: usize) -> MessageInfo {
+    MessageInfo::new(InvocationLabel::InvalidInvocation, 0, 0, length)
+}
+
+fn spin(_: usize) -> usize {
+    loop {
+        SPINS.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+// answers each call with its word plus one
+fn serve(ep: usize) -> usize {
+    let reply = SERVER_REPLY.load(Ordering::SeqCst);
+    let mut badge = 0;
+    let mut mrs = [0; NUM_MSG_REGISTRES];
+    sel4_recv(ep, &mut badge, &mut mrs, reply);
+    loop {
+        SERVED.fetch_add(1, Ordering::SeqCst);
+        mrs[0] += 1;
+        sel4_reply_recv(ep, message(1), &mut badge, &mut mrs, reply);
+    }
+}
+
+fn wait_loop(_: usize) -> usize {
+    loop {
+        sel4_wait(WAKE.load(Ordering::SeqCst));
+        WOKEN.fetch_add(1, Ordering::SeqCst);
+        sel4_signal(DONE.load(Ordering::SeqCst));
+    }
+}
+
+fn spawn_thread(entry: fn(usize) -> usize, arg: usize, priority: usize) -> Thread {
+    spawn(&mut *get_allocator(), get_vspace(), entry, arg, priority).expect("failed to spawn thread")
+}
+
+fn configure(sched_context: usize, budget: usize, period: usize, extra_refills: usize, badge: usize) -> isize {
+    sel4_sched_control_configure_flags(sched_control(), sched_context, budget, period, extra_refills, badge,
+                                       SCHED_CONTEXT_SPORADIC)
+}
+
+// a worker on a sporadic budget sends a timeout fault each time it runs out, and goes on once its
+// handler replies and the budget is back
+fn budget_test() {
+    let worker = spawn_thread(spin, 0, WORKER_PRIORITY);
+    let spinner = spawn_thread(spin, 0, SPINNER_PRIORITY);
+    let timeout = alloc_obj(ObjectType::EndpointObject, 0);
+    let reply = alloc_obj(ObjectType::ReplyObject, 0);
+
+    assert_eq!(configure(worker.sched_context, PERIOD_US, BUDGET_US, 0, 0), -1);
+    assert_eq!(configure(worker.sched_context, MIN_BUDGET_US - 1, PERIOD_US, 0, 0), -1);
+    assert_eq!(configure(worker.sched_context, BUDGET_US, PERIOD_US, max_extra_refills(SEL4_MIN_SCHED_CONTEXT_BITS) + 1, 0), -1);
+    assert_eq!(sel4_sched_context_bind(worker.sched_context, spinner.tcb), -1);
+    // only endpoints take timeout faults
+    let ntfn = alloc_obj(ObjectType::NotificationObject, 0);
+    assert_eq!(sel4_tcb_set_timeout_endpoint(worker.tcb, ntfn), -1);
+    assert!(get_allocator().free_object(ntfn));
+
+    assert_eq!(sel4_tcb_set_timeout_endpoint(worker.tcb, timeout), 0);
+    assert_eq!(configure(worker.sched_context, BUDGET_US, PERIOD_US, 0, TIMEOUT_BADGE), 0);
+    for _ in 0..ROUNDS {
+        let mut badge = 0;
+        let mut mrs = [0; NUM_MSG_REGISTRES];
+        let info = sel4_recv(timeout, &mut badge, &mut mrs, reply);
+        assert_eq!((info.get_label(), info.get_length()), (SEL4_TIMEOUT_FAULT, SEL4_TIMEOUT_LENGTH));
+        assert_eq!(mrs[SEL4_TIMEOUT_DATA], TIMEOUT_BADGE);
+        let consumed = mrs[SEL4_TIMEOUT_CONSUMED];
+        assert!(consumed >= BUDGET_US / 2 && consumed <= 2 * BUDGET_US, "consumed {}us", consumed);
+        // runs again once the budget is back
+        sel4_send(reply, message(0), &mrs);
+    }
+    assert_eq!(sel4_tcb_suspend(worker.tcb), 0);
+    // the spinner got what the worker left of each period
+    assert!(sel4_sched_context_consumed(spinner.sched_context) > 0);
+    assert_eq!(sel4_tcb_suspend(spinner.tcb), 0);
+    assert!(SPINS.load(Ordering::SeqCst) > 0);
+
+    assert!(worker.destroy(&mut *get_allocator(), get_vspace()));
+    assert!(spinner.destroy(&mut *get_allocator(), get_vspace()));
+    assert!(get_allocator().free_object(reply));
+    assert!(get_allocator().free_object(timeout));
+}
+
+// a passive server has no scheduling context of its own and runs on the one of each caller
+fn passive_server_test() {
+    let ep = alloc_obj(ObjectType::EndpointObject, 0);
+    SERVER_REPLY.store(alloc_obj(ObjectType::ReplyObject, 0), Ordering::SeqCst);
+
+    // waits for its first call before spawn returns
+    let server = spawn_thread(serve, ep, SERVER_PRIORITY);
+    assert_eq!(sel4_sched_context_unbind(server.sched_context), 0);
+    for i in 0..ROUNDS {
+        let mut mrs = [i, 0, 0, 0];
+        let info = sel4_call(ep, message(1), &mut mrs);
+        assert_eq!(info.get_length(), 1);
+        assert_eq!(mrs[0], i + 1);
+    }
+    assert_eq!(SERVED.load(Ordering::SeqCst), ROUNDS);
+
+    // a message that is not a call lends nothing, so the server gets it but cannot run
+    sel4_nb_send(ep, message(1), &[0; NUM_MSG_REGISTRES]);
+    assert_eq!(SERVED.load(Ordering::SeqCst), ROUNDS);
+
+    assert!(server.destroy(&mut *get_allocator(), get_vspace()));
+    assert!(get_allocator().free_object(SERVER_REPLY.load(Ordering::SeqCst)));
+    assert!(get_allocator().free_object(ep));
+}
+
+// a thread without a scheduling context woken through a notification borrows the one bound to it
+fn notification_donation_test() {
+    let wake = alloc_obj(ObjectType::NotificationObject, 0);
+    let done = alloc_obj(ObjectType::NotificationObject, 0);
+    WAKE.store(wake, Ordering::SeqCst);
+    DONE.store(done, Ordering::SeqCst);
+
+    // waits to be woken before spawn returns
+    let waiter = spawn_thread(wait_loop, 0, SERVER_PRIORITY);
+    assert_eq!(sel4_sched_context_unbind(waiter.sched_context), 0);
+
+    let sched_context = alloc_obj(ObjectType::SchedContextObject, SEL4_MIN_SCHED_CONTEXT_BITS);
+    assert_eq!(configure(sched_context, BUDGET_US, PERIOD_US, 0, 0), 0);
+    assert_eq!(sel4_sched_context_bind(sched_context, wake), 0);
+    for i in 0..ROUNDS {
+        sel4_signal(wake);
+        sel4_wait(done);
+        assert_eq!(WOKEN.load(Ordering::SeqCst), i + 1);
+    }
+    // handed back each time the waiter waits again
+    assert_eq!(sel4_sched_context_unbind_object(sched_context, waiter.tcb), -1);
+    assert!(sel4_sched_context_consumed(sched_context) > 0);
+
+    // and without one the waiter stays put
+    assert_eq!(sel4_sched_context_unbind_object(sched_context, wake), 0);
+    sel4_signal(wake);
+    assert_eq!(sel4_poll(done), 0);
+    assert_eq!(WOKEN.load(Ordering::SeqCst), ROUNDS);
+
+    assert!(waiter.destroy(&mut *get_allocator(), get_vspace()));
+    assert!(get_allocator().free_object(sched_context));
+    assert!(get_allocator().free_object(wake));
+    assert!(get_allocator().free_object(done));
+}
+
+pub fn mcs_test() {
+    budget_test();
+    passive_server_test();
+    notification_donation_test();
+    println!("mcs test passed");
+}
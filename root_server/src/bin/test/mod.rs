@@ -1,3 +1,45 @@
 pub mod utils;
+pub mod runner;
 pub mod tcb_test;
-pub mod process_test;
\ No newline at end of file
+pub mod vspace_test;
+pub mod asid_pool_test;
+pub mod process_test;
+pub mod boot_module_test;
+pub mod extra_bi_test;
+pub mod untyped_allocator_test;
+pub mod cspace_test;
+pub mod vspace_manager_test;
+pub mod heap_test;
+pub mod thread_test;
+pub mod ipc_test;
+pub mod logging_test;
+#[cfg(feature = "benchmark")]
+pub mod benchmark_test;
+#[cfg(feature = "mcs")]
+pub mod mcs_test;
+
+use runner::TestCase;
+
+/// every test the root server runs, in order
+pub static TESTS: &[TestCase] = &[
+    TestCase::new("extra_bi", extra_bi_test::extra_bi_test),
+    TestCase::new("boot_module", boot_module_test::boot_module_test),
+    TestCase::new("untyped_allocator", untyped_allocator_test::untyped_allocator_test),
+    TestCase::new("cspace", cspace_test::cspace_test),
+    TestCase::new("heap", heap_test::heap_test),
+    TestCase::new("vspace", vspace_test::vspace_test),
+    TestCase::new("large_frame", vspace_test::large_frame_test),
+    TestCase::new("asid_pool", asid_pool_test::asid_pool_test),
+    TestCase::new("vspace_manager", vspace_manager_test::vspace_manager_test),
+    TestCase::new("thread", thread_test::thread_test),
+    TestCase::new("ipc", ipc_test::ipc_test),
+    TestCase::new("process", process_test::process_test),
+    TestCase::new("tcb", tcb_test::tcb_test),
+    TestCase::new("logging", logging_test::logging_test),
+    #[cfg(feature = "benchmark")]
+    TestCase::new("benchmark", benchmark_test::benchmark_test),
+    #[cfg(feature = "benchmark")]
+    TestCase::new("utilisation", benchmark_test::utilisation_test),
+    #[cfg(feature = "mcs")]
+    TestCase::new("mcs", mcs_test::mcs_test),
+];
@@ -0,0 +1,47 @@
+use common::{object::ObjectType, types::CapRights};
+use root_server::boot_module::find_boot_module;
+use user_lib::{cnode::{sel4_cnode_delete, sel4_cnode_mint}, cspace::CapPath, notification::sel4_wait,
+    process::{spawn, ProcessConfig}, println};
+
+use super::utils::{get_allocator, get_boot_info};
+
+const CLIENT_PRIORITY: usize = 254;
+const CLIENT_BADGE: usize = 1;
+// no thread may have it
+const BAD_PRIORITY: usize = 256;
+
+pub fn process_test() {
+    let elf = find_boot_module(get_boot_info(), "client1").expect("client1 not found");
+    let mut alloc = get_allocator();
+    let fault_ep = alloc.alloc_object(ObjectType::EndpointObject, 0).expect("failed to create fault endpoint");
+    // client1 signals the first cap it is handed once it runs
+    let ntfn = alloc.alloc_object(ObjectType::NotificationObject, 0).expect("failed to create notification");
+    let badged_ntfn = alloc.slots().alloc().expect("no root slots");
+    assert_eq!(sel4_cnode_mint(badged_ntfn.path, CapPath::root_slot(ntfn), CapRights::new(1, 1, 1, 1),
+        CLIENT_BADGE), 0);
+    let caps = [badged_ntfn.cptr().unwrap()];
+
+    let config = ProcessConfig {
+        elf,
+        priority: CLIENT_PRIORITY,
+        fault_ep,
+        fault_ep_badge: CLIENT_BADGE,
+        caps: &caps,
+    };
+    let process = spawn(&mut *alloc, &config).expect("failed to spawn client1");
+    println!("client1: {:?}", process);
+    assert_eq!(sel4_wait(ntfn), CLIENT_BADGE);
+    assert!(process.destroy(&mut *alloc));
+
+    assert!(spawn(&mut *alloc, &ProcessConfig { elf: &elf[1..], ..config }).is_none());
+    // failing only once everything is in place leaves nothing behind either
+    let free_bytes = alloc.free_bytes();
+    assert!(spawn(&mut *alloc, &ProcessConfig { priority: BAD_PRIORITY, ..config }).is_none());
+    assert_eq!(alloc.free_bytes(), free_bytes);
+
+    assert_eq!(sel4_cnode_delete(badged_ntfn.path), 0);
+    alloc.slots().free(badged_ntfn);
+    assert!(alloc.free_object(ntfn));
+    assert!(alloc.free_object(fault_ep));
+    println!("process test passed");
+}
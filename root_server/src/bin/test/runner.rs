@@ -0,0 +1,155 @@
+use common::{object::ObjectType, types::Cptr};
+use user_lib::{cnode::{sel4_cnode_delete, sel4_cnode_revoke}, cspace::CapPath, get_ipc_buffer, heap::force_unlock_heap,
+    notification::sel4_signal, println, sel4_debug_halt, thread::{create, sel4_tcb_suspend, Thread},
+    vspace_manager::VSpace};
+use root_server::set_panic_hook;
+
+use super::utils::{device_untypeds, force_unlock_allocator, get_allocator, get_root_allocator, get_test_slots,
+    get_vspace, reset_allocator};
+
+// below the root server, so the runner gets the result as soon as a test is done
+const TEST_PRIORITY: usize = 254;
+const TEST_PASSED: usize = 0;
+const TEST_FAILED: usize = 1;
+// the ram of one test, the largest of these the root server can spare
+const TEST_UNTYPED_MAX_BITS: usize = 25;
+const TEST_UNTYPED_MIN_BITS: usize = 22;
+
+/// a test, which fails by panicking
+pub struct TestCase {
+    pub name: &'static str,
+    pub run: fn(),
+}
+
+impl TestCase {
+    pub const fn new(name: &'static str, run: fn()) -> Self {
+        Self { name, run }
+    }
+}
+
+// the test in flight, for the panic hook
+struct Running {
+    name: &'static str,
+    tcb: usize,
+    exit_ntfn: usize,
+    ipc_buffer: usize,
+}
+
+static mut RUNNING: Option<Running> = None;
+static mut TESTS: &[TestCase] = &[];
+
+fn get_running() -> &'static mut Option<Running> {
+    unsafe {
+        &mut *core::ptr::addr_of_mut!(RUNNING)
+    }
+}
+
+// what a test starts out with, so all it leaves behind can go when it is done
+struct Sandbox {
+    untyped: Cptr,
+    free_bytes: usize,
+    vspace: VSpace,
+}
+
+fn enter_sandbox() -> Sandbox {
+    let mut root_allocator = get_root_allocator();
+    let (untyped, size_bits) = (TEST_UNTYPED_MIN_BITS..=TEST_UNTYPED_MAX_BITS).rev()
+        .find_map(|bits| root_allocator.alloc_object(ObjectType::UntypedObject, bits).map(|untyped| (untyped, bits)))
+        .expect("no memory left for a test");
+    reset_allocator(untyped, root_allocator.object_paddr(untyped).unwrap(), size_bits);
+    Sandbox { untyped, free_bytes: get_allocator().free_bytes(), vspace: get_vspace().clone() }
+}
+
+// revoke everything the test made, threads it left running included, and put the root server's
+// vspace and slots back the way they were
+fn leave_sandbox(sandbox: Sandbox, name: &str, passed: bool) {
+    for untyped in device_untypeds() {
+        sel4_cnode_revoke(CapPath::root_slot(untyped));
+    }
+    if !get_root_allocator().free_object(sandbox.untyped) {
+        println!("[root server] failed to revoke the untyped of {}", name);
+    }
+    // copies of caps the test did not make itself
+    let slots = get_test_slots();
+    for slot in slots.start..slots.end {
+        sel4_cnode_delete(CapPath::root_slot(slot));
+    }
+    *get_vspace() = sandbox.vspace;
+
+    // with every thread of the test gone, a lock still held is one a failed test stopped with
+    unsafe {
+        if force_unlock_allocator() {
+            println!("[root server] {} left the allocator locked", name);
+        }
+        if force_unlock_heap() {
+            println!("[root server] {} left the heap locked", name);
+        }
+    }
+    let leaked = sandbox.free_bytes - get_allocator().free_bytes();
+    if passed && leaked != 0 {
+        println!("[root server] {} leaked {} bytes", name, leaked);
+    }
+}
+
+fn run_case(index: usize) -> usize {
+    (unsafe { TESTS[index].run })();
+    TEST_PASSED
+}
+
+// a panic in the test thread fails the test the way thread_start would have ended it. anywhere else
+// the state of the run is unknown, so it stops there
+fn on_panic() -> ! {
+    if let Some(running) = get_running() {
+        if get_ipc_buffer() as *mut _ as usize == running.ipc_buffer {
+            get_ipc_buffer().msg[0] = TEST_FAILED;
+            sel4_signal(running.exit_ntfn);
+            sel4_tcb_suspend(running.tcb);
+            loop {}
+        }
+        println!("[test] fail {}", running.name);
+    }
+    println!("[test] abort");
+    sel4_debug_halt(TEST_FAILED)
+}
+
+/// run each test in a thread of its own, on memory and slots of its own that are revoked once it
+/// is done, then halt with the number of failures as the
+/// exit code. the `[test]` lines are what scripts/run_tests.sh reads off the console
+pub fn run_tests(tests: &'static [TestCase]) -> ! {
+    unsafe {
+        TESTS = tests;
+    }
+    set_panic_hook(on_panic);
+    let mut failed = 0;
+    for (i, test) in tests.iter().enumerate() {
+        println!("[test] start {}", test.name);
+        let thread = create_test(i);
+        let sandbox = enter_sandbox();
+        // before it runs, as it may well fail on another hart before the runner gets here again
+        *get_running() = Some(Running {
+            name: test.name,
+            tcb: thread.tcb,
+            exit_ntfn: thread.exit_ntfn,
+            ipc_buffer: thread.ipc_buffer(),
+        });
+        assert!(thread.start(), "failed to start test thread");
+        let result = thread.join();
+        *get_running() = None;
+        leave_sandbox(sandbox, test.name, result == TEST_PASSED);
+        if !thread.destroy(&mut *get_root_allocator(), get_vspace()) {
+            println!("[root server] failed to clean up after {}", test.name);
+        }
+        if result == TEST_PASSED {
+            println!("[test] pass {}", test.name);
+        } else {
+            println!("[test] fail {}", test.name);
+            failed += 1;
+        }
+    }
+    println!("[test] summary {} passed {} failed", tests.len() - failed, failed);
+    sel4_debug_halt(failed)
+}
+
+fn create_test(index: usize) -> Thread {
+    create(&mut *get_root_allocator(), get_vspace(), run_case, index, TEST_PRIORITY).expect("failed to create test thread")
+}
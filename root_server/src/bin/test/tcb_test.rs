@@ -1,13 +1,28 @@
-use core::mem::size_of;
+use core::{mem::size_of, sync::atomic::{AtomicUsize, Ordering}};
 
 use common::{object::ObjectType, types::{CNodeSlot, CapRights}, register::UserContext};
-use user_lib::{vspace::{sel4_page_table_map, sel4_page_map}, thread::{sel4_tcb_configure, sel4_tcb_set_priority, sel4_tcb_read_registers, sel4_init_context_with_args, sel4_tcb_write_registers, sel4_tcb_resume, sel4_tcb_suspend}, println};
+use user_lib::{vspace::{sel4_page_table_map, sel4_page_map}, thread::{sel4_tcb_configure, sel4_tcb_set_priority, sel4_tcb_read_registers, sel4_init_context_with_args, sel4_tcb_write_registers, sel4_tcb_resume, sel4_tcb_suspend, sel4_tcb_set_affinity}, println};
 
-use super::utils::alloc_obj;
+#[cfg(feature = "mcs")]
+use common::sched_context::{DEFAULT_TIMESLICE_US, SCHED_CONTEXT_SPORADIC, SEL4_MIN_SCHED_CONTEXT_BITS};
+#[cfg(feature = "mcs")]
+use user_lib::sched_context::{sel4_sched_control_configure_flags, sel4_sched_context_bind};
+
+use super::utils::{alloc_obj, get_boot_info};
 
 static CHILD_TCB_IPC_BUF_VADDR: usize = 0x100_0000;
 
 static mut NEW_STACK: [u8; 4096] = [0u8; 4096];
+static NEW_THREAD_ARG: AtomicUsize = AtomicUsize::new(0);
+static NEW_THREAD_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+// the test thread runs at 254, so the child only gets the cpu from it when both share a hart and
+// the timer goes off at the same priority
+const SHARED_HART_PRIORITY: usize = 254;
+const OTHER_HART_PRIORITY: usize = 253;
+const SPIN_LIMIT: usize = 100_000_000;
+// long enough for the other hart to take the reschedule ipi
+const SETTLE_SPINS: usize = 1_000_000;
 
 
 fn test_mapped_ipc_buffer_frame() {
@@ -20,7 +35,21 @@ fn test_mapped_ipc_buffer_frame() {
 
 fn new_thread(arg: usize) {
     println!("hello new thread: {}", arg);
-    loop {}
+    NEW_THREAD_ARG.store(arg, Ordering::SeqCst);
+    loop {
+        NEW_THREAD_COUNTER.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+// spin without giving up the cpu, true once the child has counted past `from`
+fn counter_moves_from(from: usize) -> bool {
+    for _ in 0..SPIN_LIMIT {
+        if NEW_THREAD_COUNTER.load(Ordering::SeqCst) != from {
+            return true;
+        }
+        core::hint::spin_loop();
+    }
+    false
 }
 
 pub fn tcb_test() {
@@ -50,7 +79,9 @@ pub fn tcb_test() {
         CHILD_TCB_IPC_BUF_VADDR, new_ipc_buffer_frame);
     assert_eq!(error, 0);
 
-    error = sel4_tcb_set_priority(child_tcb, CNodeSlot::SeL4CapInitThreadTcb as usize, 254);
+    let num_nodes = get_boot_info().num_nodes;
+    let priority = if num_nodes > 1 { OTHER_HART_PRIORITY } else { SHARED_HART_PRIORITY };
+    error = sel4_tcb_set_priority(child_tcb, CNodeSlot::SeL4CapInitThreadTcb as usize, priority);
     assert_eq!(error, 0);
 
     let mut user_context = UserContext::new();
@@ -74,9 +105,54 @@ pub fn tcb_test() {
     assert_eq!(error, 0);
     println!("User Context: {:?}", user_context2);
     
+    error = sel4_tcb_set_affinity(child_tcb, num_nodes);
+    assert_eq!(error, -1);
+    if num_nodes > 1 {
+        error = sel4_tcb_set_affinity(child_tcb, num_nodes - 1);
+        assert_eq!(error, 0);
+    }
+
+    // nothing runs without a scheduling context, which takes the thread to its core
+    #[cfg(feature = "mcs")]
+    let sched_context = alloc_obj(ObjectType::SchedContextObject, SEL4_MIN_SCHED_CONTEXT_BITS);
+    #[cfg(feature = "mcs")]
+    {
+        let sched_control = get_boot_info().schedcontrol.start + num_nodes - 1;
+        error = sel4_sched_control_configure_flags(sched_control, sched_context, DEFAULT_TIMESLICE_US,
+            DEFAULT_TIMESLICE_US, 0, 0, SCHED_CONTEXT_SPORADIC);
+        assert_eq!(error, 0);
+        error = sel4_sched_context_bind(sched_context, child_tcb);
+        assert_eq!(error, 0);
+        // the core is the scheduling context's from now on
+        assert_eq!(sel4_tcb_set_affinity(child_tcb, 0), -1);
+    }
 
     error = sel4_tcb_resume(child_tcb);
     assert_eq!(error, 0);
-    
-    sel4_tcb_suspend(CNodeSlot::SeL4CapInitThreadTcb as usize);
+
+    if num_nodes > 1 {
+        // below the test thread, which never blocks here, so the child can only be counting on the
+        // last hart
+        assert!(counter_moves_from(0), "thread did not run on hart {}", num_nodes - 1);
+        assert_eq!(NEW_THREAD_ARG.load(Ordering::SeqCst), 1024);
+
+        // and once moved onto the test thread's hart it stops
+        #[cfg(not(feature = "mcs"))]
+        assert_eq!(sel4_tcb_set_affinity(child_tcb, 0), 0);
+        #[cfg(feature = "mcs")]
+        assert_eq!(sel4_sched_control_configure_flags(get_boot_info().schedcontrol.start, sched_context,
+            DEFAULT_TIMESLICE_US, DEFAULT_TIMESLICE_US, 0, 0, SCHED_CONTEXT_SPORADIC), 0);
+        for _ in 0..SETTLE_SPINS {
+            core::hint::spin_loop();
+        }
+        let stopped_at = NEW_THREAD_COUNTER.load(Ordering::SeqCst);
+        assert!(!counter_moves_from(stopped_at), "thread still running off hart 0");
+    } else {
+        // same priority as the test thread, so the timer gets it to run
+        while NEW_THREAD_ARG.load(Ordering::SeqCst) != 1024 {
+            core::hint::spin_loop();
+        }
+    }
+    assert_eq!(sel4_tcb_suspend(child_tcb), 0);
+    println!("tcb test passed");
 }
\ No newline at end of file
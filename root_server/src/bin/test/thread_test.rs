@@ -0,0 +1,70 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use common::{object::ObjectType, types::CapRights};
+use user_lib::{cnode::sel4_cnode_copy, cspace::{CapPath, CSlot}, notification::{sel4_poll, sel4_signal, sel4_wait},
+    thread::{spawn, Thread}, vspace::sel4_page_get_address, println};
+
+use super::utils::{alloc_obj, get_allocator, get_vspace};
+
+const NUM_THREADS: usize = 4;
+const THREAD_PRIORITY: usize = 254;
+// no thread may have it
+const BAD_PRIORITY: usize = 256;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+static COPY_SRC: AtomicUsize = AtomicUsize::new(0);
+
+fn sum(n: usize) -> usize {
+    COUNTER.fetch_add(1, Ordering::SeqCst);
+    (1..=n).sum()
+}
+
+// an invocation with an extra cap only works through the thread's own ipc buffer
+fn copy_frame(dest: usize) -> usize {
+    let src = COPY_SRC.load(Ordering::SeqCst);
+    sel4_cnode_copy(CapPath::root_slot(dest), CapPath::root_slot(src), CapRights::new(1, 1, 1, 1)) as usize
+}
+
+fn spawn_thread(entry: fn(usize) -> usize, arg: usize) -> Thread {
+    spawn(&mut *get_allocator(), get_vspace(), entry, arg, THREAD_PRIORITY).expect("failed to spawn thread")
+}
+
+pub fn thread_test() {
+    // notifications: badges accumulate until someone waits
+    let ntfn = alloc_obj(ObjectType::NotificationObject, 0);
+    assert_eq!(sel4_poll(ntfn), 0);
+    sel4_signal(ntfn);
+    assert_eq!(sel4_wait(ntfn), 0);
+    assert!(get_allocator().free_object(ntfn));
+
+    // several threads at once, each joined for its result
+    let threads: [Thread; NUM_THREADS] = core::array::from_fn(|i| spawn_thread(sum, (i + 1) * 10));
+    for (i, thread) in threads.into_iter().enumerate() {
+        let n = (i + 1) * 10;
+        assert_eq!(thread.join(), n * (n + 1) / 2);
+        assert_eq!(get_vspace().frame_at(thread.guard_page()), None);
+        assert!(thread.destroy(&mut *get_allocator(), get_vspace()));
+    }
+    assert_eq!(COUNTER.load(Ordering::SeqCst), NUM_THREADS);
+
+    let frame = alloc_obj(ObjectType::Riscv4kpage, 0);
+    let dest = get_allocator().slots().alloc().and_then(|slot| slot.cptr()).expect("no root slots");
+    COPY_SRC.store(frame, Ordering::SeqCst);
+    let thread = spawn_thread(copy_frame, dest);
+    assert_eq!(thread.join(), 0);
+    assert_eq!(sel4_page_get_address(dest), sel4_page_get_address(frame));
+    assert!(thread.destroy(&mut *get_allocator(), get_vspace()));
+    // the copy goes with the frame
+    assert!(get_allocator().free_object(frame));
+    get_allocator().slots().free(CSlot::root(dest));
+
+    // failing only once everything is in place leaves nothing behind, the region included
+    let free_bytes = get_allocator().free_bytes();
+    assert!(spawn(&mut *get_allocator(), get_vspace(), sum, 0, BAD_PRIORITY).is_none());
+    assert_eq!(get_allocator().free_bytes(), free_bytes);
+    let thread = spawn_thread(sum, 1);
+    assert_eq!(thread.join(), 1);
+    assert!(thread.destroy(&mut *get_allocator(), get_vspace()));
+
+    println!("thread test passed");
+}
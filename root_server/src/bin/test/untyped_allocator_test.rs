@@ -0,0 +1,64 @@
+use common::{object::ObjectType, types::{CNodeSlot, CapRights, VMAttributes}, config::PAGE_BITS, utils::round_down};
+use root_server::extra_bi::get_fdt;
+use user_lib::{vspace::{sel4_page_get_address, sel4_page_map, sel4_page_table_map, sel4_page_unmap}, println};
+
+use super::utils::{get_allocator, get_boot_info};
+
+const NUM_FRAMES: usize = 256;
+const TEST_VADDR: usize = 0x300_0000;
+const UART_COMPATIBLE: [&str; 1] = ["ns16550a"];
+
+fn map(frame: usize, vaddr: usize) -> isize {
+    sel4_page_map(frame, CNodeSlot::SeL4CapInitThreadVspace as usize, vaddr,
+        CapRights::new(1, 1, 1, 1), VMAttributes::ExecuteNever)
+}
+
+pub fn untyped_allocator_test() {
+    let mut allocator = get_allocator();
+    let free_bytes = allocator.free_bytes();
+
+    // more objects than there are untypeds, handed back so buddies merge again
+    let mut frames = [0; NUM_FRAMES];
+    for frame in frames.iter_mut() {
+        *frame = allocator.alloc_object(ObjectType::Riscv4kpage, 0).expect("out of memory");
+    }
+    for frame in frames.iter() {
+        assert!(allocator.free_object(*frame));
+    }
+    assert_eq!(allocator.free_bytes(), free_bytes);
+    assert!(!allocator.free_object(frames[0]));
+
+    // a freed frame is unmapped and its memory is handed out again
+    let frame = allocator.alloc_object(ObjectType::Riscv4kpage, 0).expect("out of memory");
+    let (error, paddr) = sel4_page_get_address(frame);
+    assert_eq!(error, 0);
+    let pt = allocator.alloc_object(ObjectType::RiscvPageTableObject, 0).expect("out of memory");
+    assert_eq!(sel4_page_table_map(pt, CNodeSlot::SeL4CapInitThreadVspace as usize,
+        TEST_VADDR, VMAttributes::DefaultVMAttributes), 0);
+    assert_eq!(map(frame, TEST_VADDR), 0);
+    assert!(allocator.free_object(frame));
+    let frame = allocator.alloc_object(ObjectType::Riscv4kpage, 0).expect("out of memory");
+    assert_eq!(sel4_page_get_address(frame), (0, paddr));
+    assert_eq!(map(frame, TEST_VADDR), 0);
+    assert_eq!(sel4_page_unmap(frame), 0);
+    assert!(allocator.free_object(frame));
+    assert!(allocator.free_object(pt));
+
+    // objects of every kind, from the same pool
+    for t in [ObjectType::TCBObject, ObjectType::EndpointObject, ObjectType::CapTableObject, ObjectType::RiscvMegaPage] {
+        let object = allocator.alloc_object(t, 4).expect("out of memory");
+        assert!(allocator.free_object(object));
+    }
+    assert_eq!(allocator.free_bytes(), free_bytes);
+
+    // device memory only comes out of device untypeds, at the address asked for
+    let fdt = get_fdt(get_boot_info()).expect("no device tree");
+    let uart = fdt.find_compatible(&UART_COMPATIBLE).expect("no uart in device tree");
+    let uart_page = round_down(uart.start, PAGE_BITS);
+    let frame = allocator.alloc_device_object(ObjectType::Riscv4kpage, 0, uart_page).expect("no device untyped for the uart");
+    assert_eq!(sel4_page_get_address(frame), (0, uart_page));
+    assert!(allocator.free_object(frame));
+    assert_eq!(allocator.free_bytes(), free_bytes);
+
+    println!("untyped allocator test passed");
+}
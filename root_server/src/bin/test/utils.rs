@@ -1,11 +1,17 @@
 use core::arch::asm;
 
-use common::{types::{CNodeSlot, Cptr, IpcBuffer}, object::ObjectType};
+use common::{types::{CNodeSlot, Cptr, Paddr, SlotRegion}, object::ObjectType};
 use root_server::BootInfo;
-use user_lib::untyped::sel4_untyped_retype;
+use spin::{Mutex, MutexGuard};
+use user_lib::{heap::init_heap, set_ipc_buffer, untyped_allocator::UntypedAllocator, vspace_manager::VSpace};
 
 static mut BOOT_INFO: usize = 0;
-static mut IPC_BUFFER: usize = 0;
+// the root server's own, shared with the heap, which takes the lock to grow
+static ROOT_ALLOCATOR: Mutex<UntypedAllocator> = Mutex::new(UntypedAllocator::new());
+// the running test's, handed a fresh untyped and the test slots by the runner
+static ALLOCATOR: Mutex<UntypedAllocator> = Mutex::new(UntypedAllocator::new());
+static mut TEST_SLOTS: SlotRegion = SlotRegion { start: 0, end: 0 };
+static mut VSPACE: VSpace = VSpace::new(CNodeSlot::SeL4CapInitThreadVspace as usize);
 
 
 pub fn set_env () {
@@ -13,44 +19,96 @@ pub fn set_env () {
     unsafe {
         asm!("mv {}, a0", out(reg) reg_val);
         BOOT_INFO = reg_val;
-        IPC_BUFFER = get_boot_info().ipc_buf_ptr;
     }
+    set_ipc_buffer(get_boot_info().ipc_buf_ptr);
+    #[cfg(feature = "mcs")]
+    user_lib::sched_context::set_sched_control(get_boot_info().schedcontrol.start);
+    init_allocator();
+    init_heap(&ROOT_ALLOCATOR, CNodeSlot::SeL4CapInitThreadVspace as usize);
 }
 
-pub fn alloc_obj(t: ObjectType, user_obj_size: usize) -> Cptr {
-    let untyped_size_bits = t.get_size(user_obj_size) as u8;
-    let mut parent_untyped: Cptr = 0;
+pub fn init_allocator() {
     let info = get_boot_info();
-    let child_slot = info.empty.start;
+    let mut allocator = get_root_allocator();
+    // the upper half of the empty slots is left for the tests
+    let middle = info.empty.start + (info.empty.end - info.empty.start) / 2;
+    allocator.set_slots(SlotRegion { start: info.empty.start, end: middle });
+    unsafe {
+        TEST_SLOTS = SlotRegion { start: middle, end: info.empty.end };
+    }
+    // device untypeds only ever go to the tests
     for i in 0..(info.untyped.end - info.untyped.start) {
-        if info.untyped_list[i].size_bits >= untyped_size_bits && info.untyped_list[i].is_device == 0 {
-            parent_untyped = info.untyped.start + i;
-            break;
+        let desc = &info.untyped_list[i];
+        if desc.is_device == 0 {
+            allocator.add_untyped(info.untyped.start + i, desc.paddr, desc.size_bits as usize, false);
         }
     }
+    // every empty slot belongs to the allocators from now on
+    info.empty.start = info.empty.end;
+}
 
-    assert_ne!(parent_untyped, 0);
+/// hand the tests' allocator `untyped` of ram, every device untyped and the test slots, dropping
+/// whatever it held before. the caller makes sure nothing of that is still in use
+pub fn reset_allocator(untyped: Cptr, paddr: Paddr, size_bits: usize) {
+    let info = get_boot_info();
+    let mut allocator = get_allocator();
+    allocator.reset();
+    allocator.set_slots(get_test_slots());
+    allocator.add_untyped(untyped, paddr, size_bits, false);
+    for i in 0..(info.untyped.end - info.untyped.start) {
+        let desc = &info.untyped_list[i];
+        if desc.is_device != 0 {
+            allocator.add_untyped(info.untyped.start + i, desc.paddr, desc.size_bits as usize, true);
+        }
+    }
+}
 
-    let error = sel4_untyped_retype(parent_untyped, t as usize, user_obj_size,
-        CNodeSlot::SeL4CapInitThreadCNode as usize, 0, 0, child_slot, 1);
+/// the device untypeds from the boot info, as caps of the root cnode
+pub fn device_untypeds() -> impl Iterator<Item = Cptr> {
+    let info = get_boot_info();
+    (info.untyped.start..info.untyped.end).filter(move |cptr| info.untyped_list[cptr - info.untyped.start].is_device != 0)
+}
 
-    assert_eq!(error, 0);
-    info.empty.start += 1;
-    info.untyped_list[parent_untyped - info.untyped.start].size_bits = 0;
+pub fn get_test_slots() -> SlotRegion {
+    unsafe { TEST_SLOTS }
+}
 
-    return child_slot;
+/// release the tests' allocator from a thread deleted while holding it, returning whether it was
+/// held
+///
+/// # Safety
+/// no thread that could still be using the allocator may be left running
+pub unsafe fn force_unlock_allocator() -> bool {
+    let held = ALLOCATOR.is_locked();
+    if held {
+        ALLOCATOR.force_unlock();
+    }
+    held
+}
+
+pub fn alloc_obj(t: ObjectType, user_obj_size: usize) -> Cptr {
+    get_allocator().alloc_object(t, user_obj_size).expect("out of memory")
 }
 
+/// the running test's allocator, locked until the guard goes
+pub fn get_allocator() -> MutexGuard<'static, UntypedAllocator> {
+    ALLOCATOR.lock()
+}
 
-pub fn get_boot_info() -> &'static mut BootInfo {
+/// the allocator of the root server itself, locked until the guard goes: the heap cannot grow
+/// meanwhile
+pub fn get_root_allocator() -> MutexGuard<'static, UntypedAllocator> {
+    ROOT_ALLOCATOR.lock()
+}
+
+pub fn get_vspace() -> &'static mut VSpace {
     unsafe {
-        &mut *(BOOT_INFO as *mut BootInfo)
+        &mut *core::ptr::addr_of_mut!(VSPACE)
     }
 }
 
-#[no_mangle]
-pub fn get_ipc_buffer() -> &'static mut IpcBuffer {
+pub fn get_boot_info() -> &'static mut BootInfo {
     unsafe {
-        &mut *(IPC_BUFFER as *mut IpcBuffer)
+        &mut *(BOOT_INFO as *mut BootInfo)
     }
 }
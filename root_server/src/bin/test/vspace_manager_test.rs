@@ -0,0 +1,81 @@
+use common::{object::ObjectType, types::{CNodeSlot, CapRights, VMAttributes}, config::PAGE_SIZE};
+use user_lib::{vspace::{sel4_asid_pool_assign, sel4_page_get_address}, println,
+    vspace_manager::{VSpace, VSPACE_ALLOC_START}};
+
+use super::utils::{alloc_obj, get_allocator};
+
+const NUM_PAGES: usize = 4;
+const MAGIC: usize = 0x5a5a_a5a5;
+
+fn read(vaddr: usize) -> usize {
+    unsafe { core::ptr::read_volatile(vaddr as *const usize) }
+}
+
+fn write(vaddr: usize, value: usize) {
+    unsafe { core::ptr::write_volatile(vaddr as *mut usize, value) }
+}
+
+pub fn vspace_manager_test() {
+    let rw = CapRights::new(1, 1, 1, 1);
+    let attr = VMAttributes::ExecuteNever;
+    let mut vspace = VSpace::new(CNodeSlot::SeL4CapInitThreadVspace as usize);
+
+    // reservations never overlap
+    let vaddr = vspace.reserve_anywhere(NUM_PAGES * PAGE_SIZE).expect("no room to reserve");
+    assert_eq!(vaddr, VSPACE_ALLOC_START);
+    assert!(!vspace.reserve(vaddr + PAGE_SIZE, PAGE_SIZE));
+    let shared_vaddr = vspace.reserve_anywhere(PAGE_SIZE).expect("no room to reserve");
+    assert_eq!(shared_vaddr, vaddr + NUM_PAGES * PAGE_SIZE);
+
+    // a fresh range needs its page tables made on the way
+    let mut frames = [0; NUM_PAGES];
+    for frame in frames.iter_mut() {
+        *frame = alloc_obj(ObjectType::Riscv4kpage, 0);
+    }
+    assert!(vspace.map_pages(&mut *get_allocator(), &frames, vaddr, rw, attr));
+    assert!(vspace.page_tables().count() > 0);
+    for i in 0..NUM_PAGES {
+        write(vaddr + i * PAGE_SIZE, MAGIC + i);
+    }
+    for i in 0..NUM_PAGES {
+        assert_eq!(read(vaddr + i * PAGE_SIZE), MAGIC + i);
+        assert_eq!(vspace.frame_at(vaddr + i * PAGE_SIZE + 8), Some(frames[i]));
+    }
+    // only reserved and unmapped pages can be mapped
+    let spare = alloc_obj(ObjectType::Riscv4kpage, 0);
+    assert!(!vspace.map_pages(&mut *get_allocator(), &[spare], vaddr, rw, attr));
+    assert!(!vspace.map_pages(&mut *get_allocator(), &[spare], shared_vaddr + PAGE_SIZE, rw, attr));
+    assert!(!vspace.unreserve(vaddr));
+
+    // a frame mapped twice shows the same memory at both addresses
+    assert!(vspace.map_copies(&mut *get_allocator(), &frames[..1], shared_vaddr, rw, attr));
+    assert_ne!(vspace.frame_at(shared_vaddr), Some(frames[0]));
+    assert_eq!(read(shared_vaddr), MAGIC);
+    write(shared_vaddr, MAGIC + NUM_PAGES);
+    assert_eq!(read(vaddr), MAGIC + NUM_PAGES);
+
+    // and into another vspace too
+    let child_root = alloc_obj(ObjectType::RiscvPageTableObject, 0);
+    assert_eq!(sel4_asid_pool_assign(CNodeSlot::SeL4CapInitThreadASIDPool as usize, child_root), 0);
+    let mut child = VSpace::new(child_root);
+    assert!(child.reserve(vaddr, PAGE_SIZE));
+    assert!(child.map_copies(&mut *get_allocator(), &frames[..1], vaddr, rw, attr));
+    let copy = child.frame_at(vaddr).unwrap();
+    assert_eq!(sel4_page_get_address(copy), sel4_page_get_address(frames[0]));
+
+    assert!(child.unmap_pages(&mut *get_allocator(), vaddr, 1));
+    assert!(child.unreserve(vaddr));
+    assert!(vspace.unmap_pages(&mut *get_allocator(), shared_vaddr, 1));
+    assert!(vspace.unmap_pages(&mut *get_allocator(), vaddr, NUM_PAGES));
+    assert!(!vspace.unmap_pages(&mut *get_allocator(), vaddr, 1));
+    assert!(vspace.unreserve(vaddr));
+    assert!(vspace.unreserve(shared_vaddr));
+
+    for pt in child.page_tables().chain(vspace.page_tables()) {
+        assert!(get_allocator().free_object(pt));
+    }
+    for frame in frames.iter().chain([spare, child_root].iter()) {
+        assert!(get_allocator().free_object(*frame));
+    }
+    println!("vspace manager test passed");
+}
@@ -0,0 +1,120 @@
+use common::{object::ObjectType, message::SEL4_FAILED_LOOKUP, types::{CNodeSlot, CapRights, VMAttributes}, config::{PAGE_SIZE, SEL4_LARGE_PAGE_BITS,
+    SEL4_HUGE_PAGE_BITS}, utils::{bit, mask}};
+use user_lib::{vspace::{sel4_page_table_map, sel4_page_map, sel4_page_unmap, sel4_page_table_unmap, sel4_page_get_address}, println};
+
+use super::utils::{alloc_obj, get_allocator};
+
+static TEST_VADDR: usize = 0x200_0000;
+// each in a 1 GiB range of its own, above the heap
+const LARGE_PAGE_VADDR: usize = 0x30_0000_0000;
+const HUGE_PAGE_VADDR: usize = 0x38_0000_0000;
+const MAGIC_A: usize = 0xaaaa_5555;
+const MAGIC_B: usize = 0x5555_aaaa;
+
+fn map(frame: usize, vaddr: usize) {
+    let error = sel4_page_map(frame, CNodeSlot::SeL4CapInitThreadVspace as usize, vaddr,
+        CapRights::new(1, 1, 1, 1), VMAttributes::DefaultVMAttributes);
+    assert_eq!(error, 0);
+}
+
+fn map_pt(pt: usize, vaddr: usize) {
+    let error = sel4_page_table_map(pt, CNodeSlot::SeL4CapInitThreadVspace as usize,
+        vaddr, VMAttributes::DefaultVMAttributes);
+    assert_eq!(error, 0);
+}
+
+fn read(vaddr: usize) -> usize {
+    unsafe { core::ptr::read_volatile(vaddr as *const usize) }
+}
+
+fn write(vaddr: usize, value: usize) {
+    unsafe { core::ptr::write_volatile(vaddr as *mut usize, value) }
+}
+
+pub fn vspace_test() {
+    let pt = alloc_obj(ObjectType::RiscvPageTableObject, 0);
+    let frame_a = alloc_obj(ObjectType::Riscv4kpage, 0);
+    let frame_b = alloc_obj(ObjectType::Riscv4kpage, 0);
+
+    let (error, paddr_a) = sel4_page_get_address(frame_a);
+    assert_eq!(error, 0);
+    let (error, paddr_b) = sel4_page_get_address(frame_b);
+    assert_eq!(error, 0);
+    assert_ne!(paddr_a, paddr_b);
+
+    // no frame goes where its page table is missing
+    let error = sel4_page_map(frame_a, CNodeSlot::SeL4CapInitThreadVspace as usize, TEST_VADDR,
+        CapRights::new(1, 1, 1, 1), VMAttributes::DefaultVMAttributes);
+    assert_eq!(error, SEL4_FAILED_LOOKUP as isize);
+    map_pt(pt, TEST_VADDR);
+
+    // fill both frames through different addresses, touching them so the TLB caches them
+    map(frame_a, TEST_VADDR);
+    write(TEST_VADDR, MAGIC_A);
+    map(frame_b, TEST_VADDR + PAGE_SIZE);
+    write(TEST_VADDR + PAGE_SIZE, MAGIC_B);
+    assert_eq!(read(TEST_VADDR), MAGIC_A);
+    assert_eq!(read(TEST_VADDR + PAGE_SIZE), MAGIC_B);
+
+    // swap them: a stale entry would still return the old frame's contents
+    assert_eq!(sel4_page_unmap(frame_a), 0);
+    assert_eq!(sel4_page_unmap(frame_b), 0);
+    map(frame_b, TEST_VADDR);
+    map(frame_a, TEST_VADDR + PAGE_SIZE);
+    assert_eq!(read(TEST_VADDR), MAGIC_B);
+    assert_eq!(read(TEST_VADDR + PAGE_SIZE), MAGIC_A);
+
+    // replace the whole page table under the same address
+    assert_eq!(sel4_page_unmap(frame_a), 0);
+    assert_eq!(sel4_page_unmap(frame_b), 0);
+    assert_eq!(sel4_page_table_unmap(pt), 0);
+    let new_pt = alloc_obj(ObjectType::RiscvPageTableObject, 0);
+    map_pt(new_pt, TEST_VADDR);
+    map(frame_a, TEST_VADDR);
+    assert_eq!(read(TEST_VADDR), MAGIC_A);
+    assert_eq!(sel4_page_unmap(frame_a), 0);
+
+    println!("vspace test passed");
+}
+
+// write both ends of a frame of `bits` mapped at `vaddr`, and its address is aligned to its size
+fn check_large_frame(frame: usize, vaddr: usize, bits: usize) {
+    let (error, paddr) = sel4_page_get_address(frame);
+    assert_eq!(error, 0);
+    assert_ne!(paddr, 0);
+    assert_eq!(paddr & mask(bits), 0);
+
+    map(frame, vaddr);
+    let last = vaddr + bit(bits) - core::mem::size_of::<usize>();
+    write(vaddr, MAGIC_A);
+    write(last, MAGIC_B);
+    assert_eq!(read(vaddr), MAGIC_A);
+    assert_eq!(read(last), MAGIC_B);
+    assert_eq!(sel4_page_unmap(frame), 0);
+    assert_eq!(sel4_page_get_address(frame), (0, paddr));
+}
+
+pub fn large_frame_test() {
+    // a 2 MiB frame goes one level up from the page tables a 4 KiB one needs
+    let pt = alloc_obj(ObjectType::RiscvPageTableObject, 0);
+    map_pt(pt, LARGE_PAGE_VADDR);
+    let large = alloc_obj(ObjectType::RiscvMegaPage, 0);
+    let error = sel4_page_map(large, CNodeSlot::SeL4CapInitThreadVspace as usize, LARGE_PAGE_VADDR + PAGE_SIZE,
+        CapRights::new(1, 1, 1, 1), VMAttributes::DefaultVMAttributes);
+    assert_eq!(error, -1);
+    check_large_frame(large, LARGE_PAGE_VADDR, SEL4_LARGE_PAGE_BITS);
+    assert_eq!(sel4_page_table_unmap(pt), 0);
+    assert!(get_allocator().free_object(large));
+    assert!(get_allocator().free_object(pt));
+
+    // and a 1 GiB one straight into the root page table, on a machine with that much to spare
+    match get_allocator().alloc_object(ObjectType::RiscvGigaPage, 0) {
+        Some(huge) => {
+            check_large_frame(huge, HUGE_PAGE_VADDR, SEL4_HUGE_PAGE_BITS);
+            assert!(get_allocator().free_object(huge));
+        }
+        None => println!("no memory for a 1 GiB frame, skipped"),
+    }
+
+    println!("large frame test passed");
+}
@@ -0,0 +1,28 @@
+use common::cpio::{self, CpioIter};
+
+use crate::BootInfo;
+use crate::extra_bi::{find_extra_bi_chunk, BootInfoID};
+pub use common::boot_info::BootInfoBootModules;
+
+pub fn get_boot_modules_info(bi: &BootInfo) -> Option<&'static BootInfoBootModules> {
+    let payload = find_extra_bi_chunk(bi, BootInfoID::Sel4BootInfoHeaderBootModules)?;
+    if payload.len() < core::mem::size_of::<BootInfoBootModules>() {
+        return None;
+    }
+    Some(unsafe { &*(payload.as_ptr() as *const BootInfoBootModules) })
+}
+
+/// the cpio archive holding the boot modules, mapped read-only by the kernel
+pub fn get_boot_modules(bi: &BootInfo) -> Option<&'static [u8]> {
+    get_boot_modules_info(bi).map(|modules| unsafe {
+        core::slice::from_raw_parts(modules.vptr as *const u8, modules.size)
+    })
+}
+
+pub fn boot_modules_iter(bi: &BootInfo) -> CpioIter<'static> {
+    CpioIter::new(get_boot_modules(bi).unwrap_or(&[]))
+}
+
+pub fn find_boot_module(bi: &BootInfo, name: &str) -> Option<&'static [u8]> {
+    cpio::find(get_boot_modules(bi)?, name)
+}
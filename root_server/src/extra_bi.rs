@@ -0,0 +1,63 @@
+use common::config::BI_FRAME_SIZE_BITS;
+use common::fdt::Fdt;
+
+use crate::BootInfo;
+pub use common::boot_info::{BootInfoHeader, BootInfoID, BootInfoPlatform};
+
+pub struct ExtraBiChunk {
+    pub id: usize,
+    pub payload: &'static [u8],
+}
+
+/// walks the chunks of the extra bootinfo region that follows the bootinfo frame
+pub struct ExtraBiIter {
+    base: usize,
+    len: usize,
+    offset: usize,
+}
+
+impl ExtraBiIter {
+    pub fn new(bi: &BootInfo) -> Self {
+        Self {
+            base: bi as *const BootInfo as usize + (1 << BI_FRAME_SIZE_BITS),
+            len: bi.extra_len,
+            offset: 0,
+        }
+    }
+}
+
+impl Iterator for ExtraBiIter {
+    type Item = ExtraBiChunk;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header_size = core::mem::size_of::<BootInfoHeader>();
+        if self.offset + header_size > self.len {
+            return None;
+        }
+        let header = unsafe { &*((self.base + self.offset) as *const BootInfoHeader) };
+        if header.len < header_size || self.offset + header.len > self.len {
+            return None;
+        }
+        let payload = unsafe {
+            core::slice::from_raw_parts((self.base + self.offset + header_size) as *const u8, header.len - header_size)
+        };
+        self.offset += header.len;
+        Some(ExtraBiChunk { id: header.id, payload })
+    }
+}
+
+pub fn find_extra_bi_chunk(bi: &BootInfo, id: BootInfoID) -> Option<&'static [u8]> {
+    ExtraBiIter::new(bi).find(|chunk| chunk.id == id as usize).map(|chunk| chunk.payload)
+}
+
+pub fn get_fdt(bi: &BootInfo) -> Option<Fdt<'static>> {
+    Fdt::new(find_extra_bi_chunk(bi, BootInfoID::Sel4BootInfoHeaderFdt)?)
+}
+
+pub fn get_platform_info(bi: &BootInfo) -> Option<&'static BootInfoPlatform> {
+    let payload = find_extra_bi_chunk(bi, BootInfoID::Sel4BootInfoHeaderPlatform)?;
+    if payload.len() < core::mem::size_of::<BootInfoPlatform>() {
+        return None;
+    }
+    Some(unsafe { &*(payload.as_ptr() as *const BootInfoPlatform) })
+}
@@ -1,6 +1,25 @@
+use core::{panic::PanicInfo, sync::atomic::{AtomicUsize, Ordering}};
 
+use user_lib::{println, sel4_debug_halt};
+
+static PANIC_HOOK: AtomicUsize = AtomicUsize::new(0);
+
+/// run `hook` once a panic is reported, instead of halting the machine
+pub fn set_panic_hook(hook: fn() -> !) {
+    PANIC_HOOK.store(hook as usize, Ordering::SeqCst);
+}
 
 #[panic_handler]
-fn panic_handler(_panic_info: &core::panic::PanicInfo) -> ! {
-    panic!("sys_exit never returns!");
+fn panic_handler(info: &PanicInfo) -> ! {
+    if let Some(location) = info.location() {
+        println!("[root server] panicked at {}:{} {}", location.file(), location.line(), info.message().unwrap());
+    } else {
+        println!("[root server] panicked: {}", info.message().unwrap());
+    }
+    let hook = PANIC_HOOK.load(Ordering::SeqCst);
+    if hook != 0 {
+        let hook: fn() -> ! = unsafe { core::mem::transmute(hook) };
+        hook();
+    }
+    sel4_debug_halt(1)
 }
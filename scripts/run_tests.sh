@@ -0,0 +1,51 @@
+#!/usr/bin/env bash
+# run the root server's tests in qemu and judge them by the [test] lines on the console
+#   usage: run_tests.sh <timeout seconds> <qemu command...>
+# exits 0 only if every test passed and the machine halted with exit code 0
+set -u
+
+if [ $# -lt 2 ]; then
+    echo "usage: $0 <timeout seconds> <qemu command...>" >&2
+    exit 2
+fi
+timeout_secs=$1
+shift
+
+log=$(mktemp)
+trap 'rm -f "$log"' EXIT
+
+timeout --foreground "$timeout_secs" "$@" < /dev/null 2>&1 | tee "$log"
+status=${PIPESTATUS[0]}
+
+results=$(tr -d '\r' < "$log" | grep -ao '\[test\] .*')
+started=$(grep -c '^\[test\] start ' <<< "$results")
+passed=$(grep -c '^\[test\] pass ' <<< "$results")
+failed=$(grep '^\[test\] fail ' <<< "$results" | cut -d' ' -f3)
+summary=$(grep '^\[test\] summary ' <<< "$results" | tail -n 1)
+
+echo
+echo "==== $passed/$started tests passed ===="
+ok=1
+for name in $failed; do
+    echo "FAILED: $name"
+    ok=0
+done
+if grep -q '^\[test\] abort' <<< "$results"; then
+    echo "ABORTED: a panic outside a test thread stopped the run"
+    ok=0
+fi
+if [ "$status" -eq 124 ]; then
+    last=$(grep '^\[test\] start ' <<< "$results" | tail -n 1 | cut -d' ' -f3)
+    echo "TIMEOUT: no halt after ${timeout_secs}s${last:+, last test started: $last}"
+    ok=0
+elif [ "$status" -ne 0 ]; then
+    echo "qemu exited with status $status"
+    ok=0
+fi
+if [ -z "$summary" ]; then
+    echo "no test summary on the console"
+    ok=0
+fi
+
+[ $ok -eq 1 ] && echo "ALL TESTS PASSED" && exit 0
+exit 1
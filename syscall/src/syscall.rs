@@ -1,35 +1,249 @@
 use core::arch::asm;
 
-pub const SYS_PUT_CHAR: isize = -9;
+use common::{message::{MessageInfo, NUM_MSG_REGISTRES}, types::Cptr};
+
 pub const SYS_CALL: isize = -1;
+pub const SYS_REPLY_RECV: isize = -2;
 pub const SYS_SEND: isize = -3;
+pub const SYS_NB_SEND: isize = -4;
+pub const SYS_RECV: isize = -5;
+pub const SYS_REPLY: isize = -6;
+pub const SYS_YIELD: isize = -7;
+pub const SYS_NB_RECV: isize = -8;
+pub const SYS_PUT_CHAR: isize = -9;
+pub const SYS_DEBUG_HALT: isize = -11;
+pub const SYS_DEBUG_SET_LOG_LEVEL: isize = -15;
+pub const SYS_DEBUG_SET_LOG_SINK: isize = -16;
+pub const SYS_DEBUG_READ_LOG: isize = -17;
+// only handled by kernels built with the benchmark feature
+pub const SYS_BENCHMARK_RESET_LOG: isize = -20;
+pub const SYS_BENCHMARK_FINALIZE_LOG: isize = -21;
+pub const SYS_BENCHMARK_SET_LOG_BUFFER: isize = -22;
+pub const SYS_BENCHMARK_GET_THREAD_UTILISATION: isize = -23;
+pub const SYS_BENCHMARK_RESET_THREAD_UTILISATION: isize = -24;
+
+// every syscall passes a cptr or badge in a0, the message info in a1, the message registers in
+// a2-a5 and the syscall number in a7. with mcs, receiving from an endpoint also passes the reply
+// object in a6. the kernel writes all of a0-a5 back on return, so each
+// stub below is one asm block that marks them as outputs, even where the results are dropped.
+
+pub fn sysc_send(sys: isize, dest: Cptr, info: usize, mr0: usize, mr1: usize, mr2: usize, mr3: usize) {
+    unsafe {
+        asm!(
+            "ecall",
+            inout("a0") dest => _,
+            inout("a1") info => _,
+            inout("a2") mr0 => _,
+            inout("a3") mr1 => _,
+            inout("a4") mr2 => _,
+            inout("a5") mr3 => _,
+            in("a7") sys,
+            options(nostack),
+        );
+    }
+}
+
+pub fn sysc_recv(sys: isize, src: Cptr, out_badge: &mut usize, out_info: &mut usize,
+                 out_mr0: &mut usize, out_mr1: &mut usize, out_mr2: &mut usize, out_mr3: &mut usize, reply: Cptr) {
+    unsafe {
+        asm!(
+            "ecall",
+            inout("a0") src => *out_badge,
+            lateout("a1") *out_info,
+            lateout("a2") *out_mr0,
+            lateout("a3") *out_mr1,
+            lateout("a4") *out_mr2,
+            lateout("a5") *out_mr3,
+            in("a6") reply,
+            in("a7") sys,
+            options(nostack),
+        );
+    }
+}
 
-pub fn sysc_send_recv(sys: isize, dest: usize, out_badge: &mut usize, info: usize, out_info: &mut usize,
-                      in_out_mr0: &mut usize, in_out_mr1: &mut usize, in_out_mr2: &mut usize, in_out_mr3: &mut usize) {
+pub fn sysc_send_recv(sys: isize, dest: Cptr, out_badge: &mut usize, info: usize, out_info: &mut usize,
+                      in_out_mr0: &mut usize, in_out_mr1: &mut usize, in_out_mr2: &mut usize, in_out_mr3: &mut usize,
+                      reply: Cptr) {
     unsafe {
-        core::arch::asm!(
+        asm!(
             "ecall",
-            in("a0") dest,
-            in("a1") info,
-            in("a2") *in_out_mr0,
-            in("a3") *in_out_mr1,
-            in("a4") *in_out_mr2,
-            in("a5") *in_out_mr3,
+            inout("a0") dest => *out_badge,
+            inout("a1") info => *out_info,
+            inout("a2") *in_out_mr0,
+            inout("a3") *in_out_mr1,
+            inout("a4") *in_out_mr2,
+            inout("a5") *in_out_mr3,
+            in("a6") reply,
             in("a7") sys,
+            options(nostack),
         );
     }
+}
+
+pub fn sysc_null(sys: isize) {
     unsafe {
-        asm!("mv {}, a1", out(reg) *out_info);
-        asm!("mv {}, a0", out(reg) *out_badge);
-        asm!("mv {}, a2", out(reg) *in_out_mr0);
-        asm!("mv {}, a3", out(reg) *in_out_mr1);
-        asm!("mv {}, a4", out(reg) *in_out_mr2);
-        asm!("mv {}, a5", out(reg) *in_out_mr3);
+        asm!(
+            "ecall",
+            lateout("a0") _,
+            lateout("a1") _,
+            lateout("a2") _,
+            lateout("a3") _,
+            lateout("a4") _,
+            lateout("a5") _,
+            in("a7") sys,
+            options(nostack),
+        );
+    }
+}
+
+// only the first `info.get_length()` message registers are sent, the rest go as 0
+fn send_mrs(info: MessageInfo, mrs: &[usize; NUM_MSG_REGISTRES]) -> [usize; NUM_MSG_REGISTRES] {
+    let mut msg = [0; NUM_MSG_REGISTRES];
+    for (i, mr) in msg.iter_mut().enumerate().take(info.get_length()) {
+        *mr = mrs[i];
     }
+    msg
+}
+
+/// seL4_Send: block until `info` and `mrs` are delivered to `dest`
+pub fn sys_send(dest: Cptr, info: MessageInfo, mrs: &[usize; NUM_MSG_REGISTRES]) {
+    let msg = send_mrs(info, mrs);
+    sysc_send(SYS_SEND, dest, info.to_word(), msg[0], msg[1], msg[2], msg[3]);
+}
+
+/// seL4_NBSend: like sys_send, but the message is dropped if nobody is waiting
+pub fn sys_nb_send(dest: Cptr, info: MessageInfo, mrs: &[usize; NUM_MSG_REGISTRES]) {
+    let msg = send_mrs(info, mrs);
+    sysc_send(SYS_NB_SEND, dest, info.to_word(), msg[0], msg[1], msg[2], msg[3]);
+}
+
+/// seL4_Call: send to `dest` and wait for the reply, which comes back in `mrs`
+pub fn sys_call(dest: Cptr, info: MessageInfo, mrs: &mut [usize; NUM_MSG_REGISTRES]) -> MessageInfo {
+    let [mut mr0, mut mr1, mut mr2, mut mr3] = send_mrs(info, mrs);
+    let mut out_info = 0;
+    sysc_send_recv(SYS_CALL, dest, &mut 0, info.to_word(), &mut out_info, &mut mr0, &mut mr1, &mut mr2, &mut mr3, 0);
+    *mrs = [mr0, mr1, mr2, mr3];
+    MessageInfo::from_word(out_info)
+}
+
+/// seL4_Recv: wait for a message on `src`, storing the sender's badge in `sender`. with mcs a call
+/// is answered through the reply object `reply`
+pub fn sys_recv(src: Cptr, sender: &mut usize, mrs: &mut [usize; NUM_MSG_REGISTRES], reply: Cptr) -> MessageInfo {
+    let [mr0, mr1, mr2, mr3] = mrs;
+    let mut out_info = 0;
+    sysc_recv(SYS_RECV, src, sender, &mut out_info, mr0, mr1, mr2, mr3, reply);
+    MessageInfo::from_word(out_info)
+}
+
+/// seL4_NBRecv: like sys_recv, but returns straight away if nothing is pending
+pub fn sys_nb_recv(src: Cptr, sender: &mut usize, mrs: &mut [usize; NUM_MSG_REGISTRES], reply: Cptr) -> MessageInfo {
+    let [mr0, mr1, mr2, mr3] = mrs;
+    let mut out_info = 0;
+    sysc_recv(SYS_NB_RECV, src, sender, &mut out_info, mr0, mr1, mr2, mr3, reply);
+    MessageInfo::from_word(out_info)
+}
+
+/// seL4_Reply: answer the last caller
+pub fn sys_reply(info: MessageInfo, mrs: &[usize; NUM_MSG_REGISTRES]) {
+    let msg = send_mrs(info, mrs);
+    sysc_send(SYS_REPLY, 0, info.to_word(), msg[0], msg[1], msg[2], msg[3]);
+}
+
+/// seL4_ReplyRecv: answer the last caller, then wait on `src` with the next message in `mrs`. with
+/// mcs the caller answered is the one of `reply`, which then takes the next call
+pub fn sys_reply_recv(src: Cptr, info: MessageInfo, sender: &mut usize, mrs: &mut [usize; NUM_MSG_REGISTRES],
+                      reply: Cptr) -> MessageInfo {
+    let [mut mr0, mut mr1, mut mr2, mut mr3] = send_mrs(info, mrs);
+    let mut out_info = 0;
+    sysc_send_recv(SYS_REPLY_RECV, src, sender, info.to_word(), &mut out_info, &mut mr0, &mut mr1, &mut mr2, &mut mr3,
+                   reply);
+    *mrs = [mr0, mr1, mr2, mr3];
+    MessageInfo::from_word(out_info)
+}
+
+/// seL4_Yield: give the rest of the time slice to threads of the same priority
+pub fn sys_yield() {
+    sysc_null(SYS_YIELD);
+}
+
+/// seL4_Signal: or the notification cap's badge into `dest`
+pub fn sys_signal(dest: Cptr) {
+    sysc_send(SYS_SEND, dest, 0, 0, 0, 0, 0);
+}
+
+/// seL4_Wait: block until `src` is signalled, returning the badges signalled since the last wait
+pub fn sys_wait(src: Cptr) -> usize {
+    let mut badge = 0;
+    sysc_recv(SYS_RECV, src, &mut badge, &mut 0, &mut 0, &mut 0, &mut 0, &mut 0, 0);
+    badge
+}
+
+/// seL4_Poll: like sys_wait, but 0 if nothing was signalled
+pub fn sys_poll(src: Cptr) -> usize {
+    let mut badge = 0;
+    sysc_recv(SYS_NB_RECV, src, &mut badge, &mut 0, &mut 0, &mut 0, &mut 0, &mut 0, 0);
+    badge
 }
 
 pub fn sys_put_char(v8: u8) {
-    sysc_send_recv(SYS_PUT_CHAR, v8 as usize, &mut 0, 0,
-                   &mut 0, &mut 0, &mut 0, &mut 0, &mut 0);
+    sysc_send(SYS_PUT_CHAR, v8 as usize, 0, 0, 0, 0, 0);
+}
+
+/// seL4_DebugHalt: shut the machine down, reporting failure to the host unless `exit_code` is 0
+pub fn sys_debug_halt(exit_code: usize) -> ! {
+    sysc_send(SYS_DEBUG_HALT, exit_code, 0, 0, 0, 0, 0);
+    unreachable!()
 }
 
+// the debug and benchmark syscalls answer in a0
+fn sysc_ret(sys: isize, arg: usize) -> usize {
+    let mut ret = 0;
+    sysc_recv(sys, arg, &mut ret, &mut 0, &mut 0, &mut 0, &mut 0, &mut 0, 0);
+    ret
+}
+
+/// set the kernel log level of the module whose name is the first `module_len` bytes of the ipc
+/// buffer message, or the default level if `module_len` is 0. a `level` of LOG_LEVEL_UNSET drops the
+/// filter of the module. 0 on success
+pub fn sys_debug_set_log_level(level: usize, module_len: usize) -> isize {
+    let mut ret = 0;
+    sysc_send_recv(SYS_DEBUG_SET_LOG_LEVEL, level, &mut ret, module_len, &mut 0, &mut 0, &mut 0, &mut 0, &mut 0, 0);
+    ret as isize
+}
+
+/// send kernel log records to a mask of LOG_SINK_CONSOLE and LOG_SINK_RING, 0 on success
+pub fn sys_debug_set_log_sink(sinks: usize) -> isize {
+    sysc_ret(SYS_DEBUG_SET_LOG_SINK, sinks) as isize
+}
+
+/// move up to `max_len` bytes of the kernel log ring into the ipc buffer message, returning how many
+pub fn sys_debug_read_log(max_len: usize) -> usize {
+    sysc_ret(SYS_DEBUG_READ_LOG, max_len)
+}
+
+/// seL4_BenchmarkSetLogBuffer: log kernel entries into the frame `frame_cptr`, or into nothing if
+/// it is a null cap, 0 on success
+pub fn sys_benchmark_set_log_buffer(frame_cptr: Cptr) -> isize {
+    sysc_ret(SYS_BENCHMARK_SET_LOG_BUFFER, frame_cptr) as isize
+}
+
+/// seL4_BenchmarkResetLog: empty the log and start logging, 0 on success
+pub fn sys_benchmark_reset_log() -> isize {
+    sysc_ret(SYS_BENCHMARK_RESET_LOG, 0) as isize
+}
+
+/// seL4_BenchmarkFinalizeLog: stop logging, returning the number of entries logged since the reset
+pub fn sys_benchmark_finalize_log() -> usize {
+    sysc_ret(SYS_BENCHMARK_FINALIZE_LOG, 0)
+}
+
+/// seL4_BenchmarkGetThreadUtilisation: leave the cycles `tcb` ran for and more in the ipc buffer,
+/// 0 on success
+pub fn sys_benchmark_get_thread_utilisation(tcb: Cptr) -> isize {
+    sysc_ret(SYS_BENCHMARK_GET_THREAD_UTILISATION, tcb) as isize
+}
+
+/// seL4_BenchmarkResetThreadUtilisation: 0 on success
+pub fn sys_benchmark_reset_thread_utilisation(tcb: Cptr) -> isize {
+    sysc_ret(SYS_BENCHMARK_RESET_THREAD_UTILISATION, tcb) as isize
+}
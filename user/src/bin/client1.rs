@@ -5,10 +5,12 @@
 extern crate user_lib;
 extern crate user;
 
-use user_lib::println;
+use user_lib::{notification::sel4_signal, println, process::PROCESS_FIRST_FREE_SLOT};
 
+// the root server's process test hands over a notification to tell it we ran
 #[no_mangle]
 pub fn main() -> i32 {
     println!("hello client1");
+    sel4_signal(PROCESS_FIRST_FREE_SLOT);
     0
-}
\ No newline at end of file
+}
@@ -5,8 +5,22 @@
 
 mod lang_item;
 
+use common::types::CNodeSlot;
+use user_lib::{set_ipc_buffer, thread::sel4_tcb_suspend};
+
+// user_lib::process starts us with the stack set up and the ipc buffer address in a0
+#[no_mangle]
+#[link_section = ".text.entry"]
+pub extern "C" fn _start(ipc_buffer: usize) -> ! {
+    set_ipc_buffer(ipc_buffer);
+    main();
+    sel4_tcb_suspend(CNodeSlot::SeL4CapInitThreadTcb as usize);
+    loop {}
+}
+
+
 #[linkage = "weak"]
 #[no_mangle]
-fn main(_argc: usize, _argv: &[&str]) -> i32 {
+fn main() -> i32 {
     panic!("Cannot find main!");
-}
\ No newline at end of file
+}
@@ -0,0 +1,60 @@
+use common::benchmark::{TraceEntry, TRACE_ENTRY_BITS, BENCHMARK_TCB_UTILISATION, BENCHMARK_IDLE_LOCALCPU_UTILISATION,
+    BENCHMARK_IDLE_TCBCPU_UTILISATION, BENCHMARK_TOTAL_UTILISATION, BENCHMARK_TCB_NUMBER_SCHEDULES};
+use common::types::{Cptr, Vptr};
+use syscall::{sys_benchmark_set_log_buffer, sys_benchmark_reset_log, sys_benchmark_finalize_log,
+    sys_benchmark_get_thread_utilisation, sys_benchmark_reset_thread_utilisation};
+use crate::get_mr;
+
+// seL4_BenchmarkSetLogBuffer, the frame has to outlive the logging: set a null cap before freeing it
+pub fn sel4_benchmark_set_log_buffer(frame: Cptr) -> isize {
+    sys_benchmark_set_log_buffer(frame)
+}
+
+// seL4_BenchmarkResetLog, which also starts the total and idle utilisation of this hart over
+pub fn sel4_benchmark_reset_log() -> isize {
+    sys_benchmark_reset_log()
+}
+
+// seL4_BenchmarkFinalizeLog, the number of entries logged since the reset
+pub fn sel4_benchmark_finalize_log() -> usize {
+    sys_benchmark_finalize_log()
+}
+
+/// the entries still in a finalized log of `count` entries, oldest first, read through the log
+/// buffer frame of `size` bytes mapped at `buffer`
+pub fn log_entries(buffer: Vptr, size: usize, count: usize) -> impl Iterator<Item = TraceEntry> {
+    let capacity = size >> TRACE_ENTRY_BITS;
+    (count.saturating_sub(capacity)..count).map(move |i| unsafe {
+        *((buffer + ((i % capacity) << TRACE_ENTRY_BITS)) as *const TraceEntry)
+    })
+}
+
+/// cycles counted by a kernel built with the benchmark feature. the idle and total ones are since
+/// the last sel4_benchmark_reset_log on that hart
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadUtilisation {
+    pub thread: usize,
+    pub idle_local_cpu: usize,
+    pub idle_thread_cpu: usize,
+    pub total: usize,
+    pub schedules: usize,
+}
+
+// seL4_BenchmarkGetThreadUtilisation
+pub fn sel4_benchmark_get_thread_utilisation(tcb: Cptr) -> Option<ThreadUtilisation> {
+    if sys_benchmark_get_thread_utilisation(tcb) != 0 {
+        return None;
+    }
+    Some(ThreadUtilisation {
+        thread: get_mr(BENCHMARK_TCB_UTILISATION),
+        idle_local_cpu: get_mr(BENCHMARK_IDLE_LOCALCPU_UTILISATION),
+        idle_thread_cpu: get_mr(BENCHMARK_IDLE_TCBCPU_UTILISATION),
+        total: get_mr(BENCHMARK_TOTAL_UTILISATION),
+        schedules: get_mr(BENCHMARK_TCB_NUMBER_SCHEDULES),
+    })
+}
+
+// seL4_BenchmarkResetThreadUtilisation
+pub fn sel4_benchmark_reset_thread_utilisation(tcb: Cptr) -> isize {
+    sys_benchmark_reset_thread_utilisation(tcb)
+}
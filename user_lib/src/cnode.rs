@@ -0,0 +1,98 @@
+use common::types::CapRights;
+use common::message::{MessageInfo, InvocationLabel::{CNodeCopy, CNodeDelete, CNodeMint, CNodeRevoke}};
+
+use crate::{set_cap, set_mr, call_with_mrs, cspace::CapPath};
+
+// seL4_CNode_CapData_new
+pub fn sel4_cnode_cap_data(guard: usize, guard_size: usize) -> usize {
+    (guard << 6) | (guard_size & 0x3f)
+}
+
+// seL4_CNode_Copy
+pub fn sel4_cnode_copy(dest: CapPath, src: CapPath, rights: CapRights) -> isize {
+    let tag = MessageInfo::new(CNodeCopy, 0, 1, 5);
+    let mut mr0: usize = dest.index;
+    let mut mr1: usize = dest.depth & 0xff;
+    let mut mr2: usize = src.index;
+    let mut mr3: usize = src.depth & 0xff;
+    set_cap(0, src.root);
+    set_mr(4, rights.word[0]);
+
+    let output_tag = call_with_mrs(dest.root, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+
+    result as isize
+}
+
+// seL4_CNode_Mint
+pub fn sel4_cnode_mint(dest: CapPath, src: CapPath, rights: CapRights, badge: usize) -> isize {
+    let tag = MessageInfo::new(CNodeMint, 0, 1, 6);
+    let mut mr0: usize = dest.index;
+    let mut mr1: usize = dest.depth & 0xff;
+    let mut mr2: usize = src.index;
+    let mut mr3: usize = src.depth & 0xff;
+    set_cap(0, src.root);
+    set_mr(4, rights.word[0]);
+    set_mr(5, badge);
+
+    let output_tag = call_with_mrs(dest.root, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+
+    result as isize
+}
+
+// seL4_CNode_Revoke
+pub fn sel4_cnode_revoke(path: CapPath) -> isize {
+    let tag = MessageInfo::new(CNodeRevoke, 0, 0, 2);
+    let mut mr0: usize = path.index;
+    let mut mr1: usize = path.depth & 0xff;
+    let mut mr2: usize = 0;
+    let mut mr3: usize = 0;
+
+    let output_tag = call_with_mrs(path.root, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+
+    result as isize
+}
+
+// seL4_CNode_Delete
+pub fn sel4_cnode_delete(path: CapPath) -> isize {
+    let tag = MessageInfo::new(CNodeDelete, 0, 0, 2);
+    let mut mr0: usize = path.index;
+    let mut mr1: usize = path.depth & 0xff;
+    let mut mr2: usize = 0;
+    let mut mr3: usize = 0;
+
+    let output_tag = call_with_mrs(path.root, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+
+    result as isize
+}
@@ -0,0 +1,172 @@
+use common::{config::{CONFIG_ROOT_CNODE_SIZE_BITS, WORD_BITS}, types::{CNodeSlot, Cptr, SlotRegion}};
+
+pub const MAX_SLOT_CNODES: usize = 8;
+// across all the cnodes of one allocator
+pub const MAX_SLOTS: usize = 1 << CONFIG_ROOT_CNODE_SIZE_BITS;
+
+/// a cap addressed the way cnode operations do it: `index` resolved through `depth` bits
+/// of the cspace whose root cnode is `root`. depth 0 names the root cnode itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CapPath {
+    pub root: Cptr,
+    pub index: usize,
+    pub depth: usize,
+}
+
+impl CapPath {
+    pub const fn new(root: Cptr, index: usize, depth: usize) -> Self {
+        CapPath { root, index, depth }
+    }
+
+    /// the caller's root cnode
+    pub const fn root_cnode() -> Self {
+        CapPath::new(CNodeSlot::SeL4CapInitThreadCNode as usize, 0, 0)
+    }
+
+    /// a cptr of the caller's cspace, resolved through a full word
+    pub const fn root_slot(cptr: Cptr) -> Self {
+        CapPath::new(CNodeSlot::SeL4CapInitThreadCNode as usize, cptr, WORD_BITS)
+    }
+}
+
+/// an empty slot: `path` for the cnode operations, `cnode` and `offset` for retyping into it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CSlot {
+    pub cnode: CapPath,
+    pub offset: usize,
+    pub path: CapPath,
+}
+
+impl CSlot {
+    pub const fn root(cptr: Cptr) -> Self {
+        CSlot { cnode: CapPath::root_cnode(), offset: cptr, path: CapPath::root_slot(cptr) }
+    }
+
+    /// the cptr to invoke the cap in this slot with, if the caller's cspace can resolve it
+    pub fn cptr(&self) -> Option<Cptr> {
+        if self.path.root == CNodeSlot::SeL4CapInitThreadCNode as usize && self.path.depth == WORD_BITS {
+            Some(self.path.index)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CNodeSlots {
+    cnode: CapPath,
+    // path of the slot at offset 0
+    first: CapPath,
+    slots: SlotRegion,
+    // bit of `used` for slots.start
+    base: usize,
+}
+
+impl CNodeSlots {
+    fn contains(&self, slot: &CSlot) -> bool {
+        slot.cnode == self.cnode && slot.offset >= self.slots.start && slot.offset < self.slots.end
+    }
+}
+
+/// hands out the empty slots of one or more cnodes, lowest first. a bit per slot tells which are
+/// in use, so every slot freed can be handed out again
+pub struct SlotAllocator {
+    cnodes: [Option<CNodeSlots>; MAX_SLOT_CNODES],
+    num_cnodes: usize,
+    used: [usize; MAX_SLOTS / WORD_BITS],
+    num_slots: usize,
+    num_used: usize,
+}
+
+impl SlotAllocator {
+    pub const fn new() -> Self {
+        SlotAllocator {
+            cnodes: [None; MAX_SLOT_CNODES],
+            num_cnodes: 0,
+            used: [0; MAX_SLOTS / WORD_BITS],
+            num_slots: 0,
+            num_used: 0,
+        }
+    }
+
+    /// slots `slots` of the cnode at `cnode`, slot i being addressed by `first` plus i
+    pub fn add_cnode_slots(&mut self, cnode: CapPath, first: CapPath, slots: SlotRegion) -> bool {
+        let len = slots.end.saturating_sub(slots.start);
+        if self.num_cnodes == MAX_SLOT_CNODES || self.num_slots + len > MAX_SLOTS {
+            return false;
+        }
+        self.cnodes[self.num_cnodes] = Some(CNodeSlots { cnode, first, slots, base: self.num_slots });
+        self.num_cnodes += 1;
+        self.num_slots += len;
+        true
+    }
+
+    /// empty slots of the caller's root cnode
+    pub fn add_root_slots(&mut self, slots: SlotRegion) -> bool {
+        self.add_cnode_slots(CapPath::root_cnode(), CapPath::root_slot(0), slots)
+    }
+
+    /// a second level cnode of `1 << radix` slots held at `cnode` of the root cnode, its slots
+    /// addressed with the cnode itself as root
+    pub fn add_cnode(&mut self, cnode: Cptr, radix: usize) -> bool {
+        self.add_cnode_slots(CapPath::root_slot(cnode), CapPath::new(cnode, 0, radix),
+                             SlotRegion { start: 0, end: 1 << radix })
+    }
+
+    pub fn alloc(&mut self) -> Option<CSlot> {
+        self.alloc_from(|_| true)
+    }
+
+    /// a slot the caller can invoke the cap in straight from its own cspace
+    pub fn alloc_root(&mut self) -> Option<CSlot> {
+        self.alloc_from(|cnode| cnode.first.root == CNodeSlot::SeL4CapInitThreadCNode as usize
+            && cnode.first.depth == WORD_BITS)
+    }
+
+    fn alloc_from(&mut self, usable: impl Fn(&CNodeSlots) -> bool) -> Option<CSlot> {
+        for cnode in self.cnodes[..self.num_cnodes].iter().flatten().filter(|cnode| usable(cnode)) {
+            let end = cnode.base + cnode.slots.end - cnode.slots.start;
+            if let Some(bit) = self.first_unused(cnode.base, end) {
+                self.used[bit / WORD_BITS] |= 1 << (bit % WORD_BITS);
+                self.num_used += 1;
+                let offset = cnode.slots.start + bit - cnode.base;
+                let path = CapPath::new(cnode.first.root, cnode.first.index + offset, cnode.first.depth);
+                return Some(CSlot { cnode: cnode.cnode, offset, path });
+            }
+        }
+        None
+    }
+
+    /// give back a slot the caller has emptied. false if it was not handed out by this allocator
+    pub fn free(&mut self, slot: CSlot) -> bool {
+        let Some(cnode) = self.cnodes[..self.num_cnodes].iter().flatten().find(|cnode| cnode.contains(&slot)) else {
+            return false;
+        };
+        let bit = cnode.base + slot.offset - cnode.slots.start;
+        let mask = 1 << (bit % WORD_BITS);
+        if self.used[bit / WORD_BITS] & mask == 0 {
+            return false;
+        }
+        self.used[bit / WORD_BITS] &= !mask;
+        self.num_used -= 1;
+        true
+    }
+
+    pub fn num_free(&self) -> usize {
+        self.num_slots - self.num_used
+    }
+
+    // the first clear bit of `used` in [start, end)
+    fn first_unused(&self, start: usize, end: usize) -> Option<usize> {
+        let mut i = start;
+        while i < end {
+            let unused = !self.used[i / WORD_BITS] >> (i % WORD_BITS);
+            if unused != 0 {
+                let bit = i + unused.trailing_zeros() as usize;
+                return if bit < end { Some(bit) } else { None };
+            }
+            i = (i / WORD_BITS + 1) * WORD_BITS;
+        }
+        None
+    }
+}
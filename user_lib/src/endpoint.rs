@@ -0,0 +1,40 @@
+use common::{message::{MessageInfo, NUM_MSG_REGISTRES}, types::Cptr};
+use syscall::{sys_send, sys_nb_send, sys_call, sys_recv, sys_nb_recv, sys_reply_recv};
+
+// seL4_Send
+pub fn sel4_send(dest: Cptr, info: MessageInfo, mrs: &[usize; NUM_MSG_REGISTRES]) {
+    sys_send(dest, info, mrs);
+}
+
+// seL4_NBSend, dropped if nobody is waiting on `dest`
+pub fn sel4_nb_send(dest: Cptr, info: MessageInfo, mrs: &[usize; NUM_MSG_REGISTRES]) {
+    sys_nb_send(dest, info, mrs);
+}
+
+// seL4_Call, with the reply in `mrs`
+pub fn sel4_call(dest: Cptr, info: MessageInfo, mrs: &mut [usize; NUM_MSG_REGISTRES]) -> MessageInfo {
+    sys_call(dest, info, mrs)
+}
+
+// seL4_Recv, with the badge of the cap the message came through in `sender`. with mcs a call is
+// answered by sending on the reply object `reply`, without it `reply` is unused
+pub fn sel4_recv(src: Cptr, sender: &mut usize, mrs: &mut [usize; NUM_MSG_REGISTRES], reply: Cptr) -> MessageInfo {
+    sys_recv(src, sender, mrs, reply)
+}
+
+// seL4_NBRecv, `sender` is 0 if nothing was waiting
+pub fn sel4_nb_recv(src: Cptr, sender: &mut usize, mrs: &mut [usize; NUM_MSG_REGISTRES], reply: Cptr) -> MessageInfo {
+    sys_nb_recv(src, sender, mrs, reply)
+}
+
+// seL4_Reply, to the thread whose call was received last
+#[cfg(not(feature = "mcs"))]
+pub fn sel4_reply(info: MessageInfo, mrs: &[usize; NUM_MSG_REGISTRES]) {
+    syscall::sys_reply(info, mrs);
+}
+
+// seL4_ReplyRecv, answering the call received through `reply` with mcs
+pub fn sel4_reply_recv(src: Cptr, info: MessageInfo, sender: &mut usize, mrs: &mut [usize; NUM_MSG_REGISTRES],
+                       reply: Cptr) -> MessageInfo {
+    sys_reply_recv(src, info, sender, mrs, reply)
+}
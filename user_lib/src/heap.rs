@@ -0,0 +1,127 @@
+use core::{alloc::{GlobalAlloc, Layout}, ptr::{null_mut, NonNull}};
+
+use common::{object::ObjectType, config::{PAGE_BITS, PAGE_SIZE}, types::{Cptr, Vptr}, utils::round_up};
+use linked_list_allocator::Heap;
+use spin::Mutex;
+
+use crate::process::{map_page, ObjectAllocator};
+
+pub const HEAP_START: Vptr = 0x20_0000_0000;
+pub const HEAP_MAX_SIZE: usize = 0x1_0000_0000;
+// the heap grows by at least this much at a time
+pub const HEAP_GROW_PAGES: usize = 16;
+
+/// an allocator the heap grows out of, locked by whoever else uses it too
+pub type SharedAllocator = Mutex<dyn ObjectAllocator + Send>;
+
+struct HeapSource {
+    alloc: &'static SharedAllocator,
+    vspace: Cptr,
+}
+
+struct HeapInner {
+    heap: Heap,
+    source: Option<HeapSource>,
+    // end of the mapped part of the heap
+    top: Vptr,
+}
+
+impl HeapInner {
+    /// map enough new frames at the top of the heap for `layout`
+    fn grow(&mut self, layout: Layout) -> Option<()> {
+        let source = self.source.as_ref()?;
+        // the allocator may be held by the very code allocating, so no frames rather than a deadlock
+        let mut alloc = source.alloc.try_lock()?;
+        let size = round_up(layout.size() + layout.align(), PAGE_BITS).max(HEAP_GROW_PAGES * PAGE_SIZE);
+        if self.top + size > HEAP_START + HEAP_MAX_SIZE {
+            return None;
+        }
+
+        let old_top = self.top;
+        while self.top < old_top + size {
+            let frame = match alloc.alloc_object(ObjectType::Riscv4kpage, 0) {
+                Some(frame) => frame,
+                None => break,
+            };
+            if map_page(&mut *alloc, frame, source.vspace, self.top, true, false).is_none() {
+                break;
+            }
+            self.top += PAGE_SIZE;
+        }
+        // whatever got mapped is handed to the heap, even when it falls short
+        if self.top == old_top {
+            return None;
+        }
+        unsafe {
+            if self.heap.size() == 0 {
+                self.heap.init(HEAP_START as *mut u8, self.top - HEAP_START);
+            } else {
+                self.heap.extend(self.top - old_top);
+            }
+        }
+        Some(())
+    }
+}
+
+/// the global allocator of user programs: a linked list heap at HEAP_START, growing by frames
+/// from the allocator given to init_heap. without one every allocation fails.
+pub struct UserHeap {
+    inner: Mutex<HeapInner>,
+}
+
+impl UserHeap {
+    pub const fn new() -> Self {
+        UserHeap {
+            inner: Mutex::new(HeapInner { heap: Heap::empty(), source: None, top: HEAP_START }),
+        }
+    }
+}
+
+unsafe impl GlobalAlloc for UserHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut inner = self.inner.lock();
+        if let Ok(ptr) = inner.heap.allocate_first_fit(layout) {
+            return ptr.as_ptr();
+        }
+        if inner.grow(layout).is_none() {
+            return null_mut();
+        }
+        inner.heap.allocate_first_fit(layout).map_or(null_mut(), |ptr| ptr.as_ptr())
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.lock().heap.deallocate(NonNull::new_unchecked(ptr), layout);
+    }
+}
+
+#[global_allocator]
+static HEAP: UserHeap = UserHeap::new();
+
+/// let the heap grow into `vspace` with frames and page tables from `alloc`, which it locks while
+/// growing. an allocation needing more frames while `alloc` is held elsewhere fails.
+pub fn init_heap(alloc: &'static SharedAllocator, vspace: Cptr) {
+    HEAP.inner.lock().source = Some(HeapSource { alloc, vspace });
+}
+
+/// release the heap from a thread deleted while holding it, returning whether it was held. what that
+/// thread was doing to the heap is not undone
+///
+/// # Safety
+/// no thread that could still be using the heap may be left running
+pub unsafe fn force_unlock_heap() -> bool {
+    let held = HEAP.inner.is_locked();
+    if held {
+        HEAP.inner.force_unlock();
+    }
+    held
+}
+
+/// bytes of the heap handed out
+pub fn heap_used() -> usize {
+    HEAP.inner.lock().heap.used()
+}
+
+/// bytes mapped for the heap so far
+pub fn heap_size() -> usize {
+    HEAP.inner.lock().heap.size()
+}
@@ -1,38 +1,51 @@
 #![no_std]
-#![feature(linkage)]
 
 extern crate syscall;
 extern crate common;
-use common::{message::MessageInfo, types::{IpcBuffer, Cptr}};
-use syscall::SYS_CALL;
+use core::arch::asm;
 
+use common::{message::MessageInfo, types::{IpcBuffer, Cptr, Vptr}};
+
+pub mod benchmark;
+pub mod cnode;
 pub mod console;
+pub mod cspace;
+pub mod endpoint;
+pub mod heap;
+pub mod logging;
+pub mod notification;
+pub mod process;
+#[cfg(feature = "mcs")]
+pub mod sched_context;
 pub mod thread;
 pub mod untyped;
+pub mod untyped_allocator;
 pub mod vspace;
+pub mod vspace_manager;
 
 pub fn call_with_mrs(dest: usize, msg_info: MessageInfo, mr0: &mut usize, mr1: &mut usize, mr2: &mut usize, mr3: &mut usize)
     -> MessageInfo {
-    let mut local_dest = dest;
-    let mut info = MessageInfo {words: [0; 1]};
-    let mut msg0 = if msg_info.get_length() > 0 { *mr0 } else { 0 };
-    let mut msg1 = if msg_info.get_length() > 1 { *mr1 } else { 0 };
-    let mut msg2 = if msg_info.get_length() > 2 { *mr2 } else { 0 };
-    let mut msg3 = if msg_info.get_length() > 3 { *mr3 } else { 0 };
-    
-    syscall::sysc_send_recv(SYS_CALL, dest, &mut local_dest, msg_info.words[0], &mut info.words[0],
-                            &mut msg0, &mut msg1, &mut msg2, &mut msg3);
-    *mr0 = msg0;
-    *mr1 = msg1;
-    *mr2 = msg2;
-    *mr3 = msg3;
+    let mut mrs = [*mr0, *mr1, *mr2, *mr3];
+    let info = syscall::sys_call(dest, msg_info, &mut mrs);
+    [*mr0, *mr1, *mr2, *mr3] = mrs;
     info
 }
 
-#[linkage = "weak"]
-#[no_mangle]
+/// the calling thread's ipc buffer. threads share the address space, so its address lives in
+/// tp, which the kernel saves and restores per thread
 pub fn get_ipc_buffer() -> &'static mut IpcBuffer {
-    panic!("invoke weak function!")
+    let ptr: usize;
+    unsafe {
+        asm!("mv {}, tp", out(reg) ptr);
+        &mut *(ptr as *mut IpcBuffer)
+    }
+}
+
+/// tell user_lib where the calling thread's ipc buffer is mapped, first thing in a new program
+pub fn set_ipc_buffer(ptr: Vptr) {
+    unsafe {
+        asm!("mv tp, {}", in(reg) ptr);
+    }
 }
 
 pub fn set_cap(index: usize, cptr: Cptr) {
@@ -45,4 +58,9 @@ pub fn set_mr(index: usize, mr: usize) {
 
 pub fn get_mr(index: usize) -> usize {
     get_ipc_buffer().msg[index]
-}
\ No newline at end of file
+}
+
+/// shut the machine down, with a non-zero `exit_code` reported to the host as a failure
+pub fn sel4_debug_halt(exit_code: usize) -> ! {
+    syscall::sys_debug_halt(exit_code)
+}
@@ -0,0 +1,43 @@
+use core::mem::size_of;
+use common::config::SEL4_MSG_MAX_LEN;
+use common::logging::{LogLevel, LOG_LEVEL_UNSET, MAX_LOG_MODULE_LEN};
+use syscall::{sys_debug_read_log, sys_debug_set_log_level, sys_debug_set_log_sink};
+use crate::get_ipc_buffer;
+
+fn msg_bytes() -> &'static mut [u8] {
+    let msg = &mut get_ipc_buffer().msg;
+    unsafe {
+        core::slice::from_raw_parts_mut(msg.as_mut_ptr() as *mut u8, SEL4_MSG_MAX_LEN * size_of::<usize>())
+    }
+}
+
+/// set the kernel log level of `module` (a path like `os::scheduler`, covering the modules under it)
+/// or the default level if it is `None`. a `level` of `None` drops the filter of the module
+pub fn sel4_debug_set_log_level(module: Option<&str>, level: Option<LogLevel>) -> isize {
+    let module = module.unwrap_or("").as_bytes();
+    if module.len() > MAX_LOG_MODULE_LEN {
+        return -1;
+    }
+    msg_bytes()[..module.len()].copy_from_slice(module);
+    sys_debug_set_log_level(level.map_or(LOG_LEVEL_UNSET, |level| level as usize), module.len())
+}
+
+// a mask of LOG_SINK_CONSOLE and LOG_SINK_RING
+pub fn sel4_debug_set_log_sink(sinks: usize) -> isize {
+    sys_debug_set_log_sink(sinks)
+}
+
+/// move the oldest bytes of the kernel log ring into `buf`, returning how many. the ring keeps
+/// the last 16 KiB of records
+pub fn sel4_debug_read_log(buf: &mut [u8]) -> usize {
+    let mut read = 0;
+    while read < buf.len() {
+        let count = sys_debug_read_log(buf.len() - read);
+        if count == 0 {
+            break;
+        }
+        buf[read..read + count].copy_from_slice(&msg_bytes()[..count]);
+        read += count;
+    }
+    read
+}
@@ -0,0 +1,17 @@
+use common::types::Cptr;
+use syscall::{sys_signal, sys_wait, sys_poll};
+
+// seL4_Signal
+pub fn sel4_signal(dest: Cptr) {
+    sys_signal(dest);
+}
+
+// seL4_Wait, returning the badges signalled since the last wait
+pub fn sel4_wait(src: Cptr) -> usize {
+    sys_wait(src)
+}
+
+// seL4_Poll, 0 if nothing was signalled
+pub fn sel4_poll(src: Cptr) -> usize {
+    sys_poll(src)
+}
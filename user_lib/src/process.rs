@@ -0,0 +1,277 @@
+use core::mem::size_of;
+
+use common::{object::ObjectType, register::UserContext, config::{PAGE_BITS, PAGE_SIZE, WORD_BITS},
+    types::{CNodeSlot, CapRights, Cptr, VMAttributes, Vptr}, utils::{round_down, round_up}};
+use xmas_elf::{ElfFile, program::Type};
+
+use crate::cnode::{sel4_cnode_cap_data, sel4_cnode_copy, sel4_cnode_mint};
+use crate::cspace::CapPath;
+use crate::thread::{sel4_init_context_with_args, sel4_tcb_configure, sel4_tcb_resume, sel4_tcb_set_priority,
+    sel4_tcb_write_registers};
+#[cfg(feature = "mcs")]
+use common::sched_context::SEL4_MIN_SCHED_CONTEXT_BITS;
+#[cfg(feature = "mcs")]
+use crate::sched_context::{configure_round_robin, sel4_sched_context_bind};
+use crate::vspace::{sel4_asid_pool_assign, sel4_page_map, sel4_page_table_map, sel4_page_unmap};
+
+pub const PROCESS_CNODE_SIZE_BITS: usize = 12;
+pub const PROCESS_STACK_PAGES: usize = 16;
+pub const PROCESS_STACK_TOP: Vptr = 0x1000_0000;
+// the page right above the stack stays unmapped as a guard
+pub const PROCESS_IPC_BUFFER_VADDR: Vptr = PROCESS_STACK_TOP + PAGE_SIZE;
+// the child's cspace mirrors the root server's initial slots, the fault endpoint follows them
+pub const PROCESS_FAULT_EP_SLOT: Cptr = CNodeSlot::SeL4NumInitialCaps as usize;
+pub const PROCESS_FIRST_FREE_SLOT: Cptr = PROCESS_FAULT_EP_SLOT + 1;
+// where the loader maps frames into its own vspace while filling them
+pub const PROCESS_LOAD_VADDR: Vptr = 0x4000_0000;
+// frames and page tables included
+pub const MAX_PROCESS_OBJECTS: usize = 256;
+
+pub trait ObjectAllocator {
+    /// a new object of type `t` in an empty slot of the caller's root cnode
+    fn alloc_object(&mut self, t: ObjectType, user_obj_size: usize) -> Option<Cptr>;
+    /// delete an object from alloc_object along with every cap derived from it
+    fn free_object(&mut self, object: Cptr) -> bool;
+    /// an empty slot of the caller's root cnode, for copies of caps
+    fn alloc_slot(&mut self) -> Option<Cptr>;
+    fn free_slot(&mut self, slot: Cptr);
+}
+
+pub struct ProcessConfig<'a> {
+    pub elf: &'a [u8],
+    pub priority: usize,
+    // endpoint in the caller's cspace the child's faults go to, SeL4CapNull for none
+    pub fault_ep: Cptr,
+    pub fault_ep_badge: usize,
+    // caps of the caller's cspace copied into the child from PROCESS_FIRST_FREE_SLOT on
+    pub caps: &'a [Cptr],
+}
+
+/// every object spawn allocated for a process, in order
+#[derive(Clone, Copy)]
+struct ProcessObjects {
+    objects: [Cptr; MAX_PROCESS_OBJECTS],
+    len: usize,
+}
+
+impl core::fmt::Debug for ProcessObjects {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "{} objects", self.len)
+    }
+}
+
+// the caller's allocator, noting down what it hands out
+struct Recorder<'a, A: ObjectAllocator> {
+    alloc: &'a mut A,
+    objects: &'a mut ProcessObjects,
+}
+
+impl<A: ObjectAllocator> ObjectAllocator for Recorder<'_, A> {
+    fn alloc_object(&mut self, t: ObjectType, user_obj_size: usize) -> Option<Cptr> {
+        let objects = &mut *self.objects;
+        if objects.len == MAX_PROCESS_OBJECTS {
+            return None;
+        }
+        let object = self.alloc.alloc_object(t, user_obj_size)?;
+        objects.objects[objects.len] = object;
+        objects.len += 1;
+        Some(object)
+    }
+
+    fn free_object(&mut self, object: Cptr) -> bool {
+        self.alloc.free_object(object)
+    }
+
+    fn alloc_slot(&mut self) -> Option<Cptr> {
+        self.alloc.alloc_slot()
+    }
+
+    fn free_slot(&mut self, slot: Cptr) {
+        self.alloc.free_slot(slot)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Process {
+    pub tcb: Cptr,
+    pub cnode: Cptr,
+    pub vspace: Cptr,
+    pub ipc_buffer_frame: Cptr,
+    pub entry: Vptr,
+    // also in the child's SeL4CapInitThreadSC
+    #[cfg(feature = "mcs")]
+    pub sched_context: Cptr,
+    objects: ProcessObjects,
+}
+
+impl Process {
+    /// stop the process and give back everything spawn allocated: its cnode takes the caps
+    /// copied into it along, its vspace the asid
+    pub fn destroy(self, alloc: &mut impl ObjectAllocator) -> bool {
+        let mut ok = true;
+        if self.tcb != 0 {
+            ok &= alloc.free_object(self.tcb);
+        }
+        for object in self.objects.objects[..self.objects.len].iter().rev() {
+            if *object != self.tcb {
+                ok &= alloc.free_object(*object);
+            }
+        }
+        ok
+    }
+}
+
+fn ok(error: isize) -> Option<()> {
+    if error == 0 { Some(()) } else { None }
+}
+
+fn rights(writable: bool) -> CapRights {
+    CapRights::new(0, 0, 1, writable as usize)
+}
+
+fn attr(executable: bool) -> VMAttributes {
+    if executable { VMAttributes::DefaultVMAttributes } else { VMAttributes::ExecuteNever }
+}
+
+/// map `frame` at `vaddr` of `vspace`, creating the missing page tables on the way
+pub fn map_page(alloc: &mut (impl ObjectAllocator + ?Sized), frame: Cptr, vspace: Cptr, vaddr: Vptr,
+    writable: bool, executable: bool) -> Option<()> {
+
+    // an empty sv39 vspace lacks up to two levels of page tables
+    for _ in 0..3 {
+        if sel4_page_map(frame, vspace, vaddr, rights(writable), attr(executable)) == 0 {
+            return Some(());
+        }
+        let pt = alloc.alloc_object(ObjectType::RiscvPageTableObject, 0)?;
+        ok(sel4_page_table_map(pt, vspace, vaddr, VMAttributes::DefaultVMAttributes))?;
+    }
+    None
+}
+
+/// a zeroed frame holding `data` at `offset`, mapped at `vaddr` of the child
+fn load_page(alloc: &mut impl ObjectAllocator, vspace: Cptr, vaddr: Vptr, data: &[u8], offset: usize,
+    writable: bool, executable: bool) -> Option<Cptr> {
+
+    let frame = alloc.alloc_object(ObjectType::Riscv4kpage, 0)?;
+    map_page(alloc, frame, CNodeSlot::SeL4CapInitThreadVspace as usize, PROCESS_LOAD_VADDR, true, false)?;
+    unsafe {
+        core::ptr::write_bytes(PROCESS_LOAD_VADDR as *mut u8, 0, PAGE_SIZE);
+        core::ptr::copy_nonoverlapping(data.as_ptr(), (PROCESS_LOAD_VADDR + offset) as *mut u8, data.len());
+    }
+    ok(sel4_page_unmap(frame))?;
+    map_page(alloc, frame, vspace, vaddr, writable, executable)?;
+    Some(frame)
+}
+
+fn load_elf(alloc: &mut impl ObjectAllocator, vspace: Cptr, elf: &ElfFile) -> Option<()> {
+    let mut loaded_end = 0;
+    for ph in elf.program_iter() {
+        if ph.get_type() != Ok(Type::Load) || ph.mem_size() == 0 {
+            continue;
+        }
+        let start = ph.virtual_addr() as usize;
+        let file_end = start + ph.file_size() as usize;
+        let file = elf.input.get(ph.offset() as usize..(ph.offset() + ph.file_size()) as usize)?;
+        // segments sharing a page would need the same frame twice
+        if round_down(start, PAGE_BITS) < loaded_end {
+            return None;
+        }
+        let end = round_up(start + ph.mem_size() as usize, PAGE_BITS);
+        let mut vaddr = round_down(start, PAGE_BITS);
+        while vaddr < end {
+            let copy_start = start.max(vaddr).min(file_end);
+            let copy_end = file_end.min(vaddr + PAGE_SIZE).max(copy_start);
+            load_page(alloc, vspace, vaddr, &file[copy_start - start..copy_end - start], copy_start - vaddr,
+                      ph.flags().is_write(), ph.flags().is_execute())?;
+            vaddr += PAGE_SIZE;
+        }
+        loaded_end = end;
+    }
+    Some(())
+}
+
+// `slot` of the child's cnode, as seen from the parent
+fn child_slot(cnode: Cptr, slot: Cptr) -> CapPath {
+    CapPath::new(cnode, slot, PROCESS_CNODE_SIZE_BITS)
+}
+
+fn copy_to_child(cnode: Cptr, slot: Cptr, src: Cptr) -> Option<()> {
+    ok(sel4_cnode_copy(child_slot(cnode, slot), CapPath::root_slot(src), CapRights::new(1, 1, 1, 1)))
+}
+
+/// create a process running `config.elf` in its own cspace and vspace, and start it. on failure
+/// whatever was created on the way is deleted again
+pub fn spawn(alloc: &mut impl ObjectAllocator, config: &ProcessConfig) -> Option<Process> {
+    let elf = ElfFile::new(config.elf).ok()?;
+    let mut process = Process {
+        tcb: 0, cnode: 0, vspace: 0, ipc_buffer_frame: 0, entry: 0,
+        #[cfg(feature = "mcs")]
+        sched_context: 0,
+        objects: ProcessObjects { objects: [0; MAX_PROCESS_OBJECTS], len: 0 },
+    };
+    let mut objects = process.objects;
+    let started = start(&mut Recorder { alloc, objects: &mut objects }, config, &elf, &mut process);
+    process.objects = objects;
+    if started.is_none() {
+        process.destroy(alloc);
+        return None;
+    }
+    Some(process)
+}
+
+fn start(alloc: &mut impl ObjectAllocator, config: &ProcessConfig, elf: &ElfFile, process: &mut Process) -> Option<()> {
+    let cnode = alloc.alloc_object(ObjectType::CapTableObject, PROCESS_CNODE_SIZE_BITS)?;
+    process.cnode = cnode;
+    let vspace = alloc.alloc_object(ObjectType::RiscvPageTableObject, 0)?;
+    process.vspace = vspace;
+    ok(sel4_asid_pool_assign(CNodeSlot::SeL4CapInitThreadASIDPool as usize, vspace))?;
+    let tcb = alloc.alloc_object(ObjectType::TCBObject, 0)?;
+    process.tcb = tcb;
+
+    load_elf(alloc, vspace, elf)?;
+
+    for i in 0..PROCESS_STACK_PAGES {
+        let frame = alloc.alloc_object(ObjectType::Riscv4kpage, 0)?;
+        map_page(alloc, frame, vspace, PROCESS_STACK_TOP - (i + 1) * PAGE_SIZE, true, false)?;
+    }
+    let ipc_buffer_frame = alloc.alloc_object(ObjectType::Riscv4kpage, 0)?;
+    process.ipc_buffer_frame = ipc_buffer_frame;
+    map_page(alloc, ipc_buffer_frame, vspace, PROCESS_IPC_BUFFER_VADDR, true, false)?;
+
+    copy_to_child(cnode, CNodeSlot::SeL4CapInitThreadTcb as usize, tcb)?;
+    copy_to_child(cnode, CNodeSlot::SeL4CapInitThreadVspace as usize, vspace)?;
+    copy_to_child(cnode, CNodeSlot::SeL4CapInitThreadIpcBuffer as usize, ipc_buffer_frame)?;
+    // the child resolves full-word cptrs through its cnode, so pad the radix out with a guard
+    let cnode_data = sel4_cnode_cap_data(0, WORD_BITS - PROCESS_CNODE_SIZE_BITS);
+    ok(sel4_cnode_mint(child_slot(cnode, CNodeSlot::SeL4CapInitThreadCNode as usize), CapPath::root_slot(cnode),
+                       CapRights::new(1, 1, 1, 1), cnode_data))?;
+
+    let mut fault_ep = CNodeSlot::SeL4CapNull as usize;
+    if config.fault_ep != CNodeSlot::SeL4CapNull as usize {
+        ok(sel4_cnode_mint(child_slot(cnode, PROCESS_FAULT_EP_SLOT), CapPath::root_slot(config.fault_ep),
+                           CapRights::new(1, 1, 1, 1), config.fault_ep_badge))?;
+        fault_ep = PROCESS_FAULT_EP_SLOT;
+    }
+    for (i, cap) in config.caps.iter().enumerate() {
+        copy_to_child(cnode, PROCESS_FIRST_FREE_SLOT + i, *cap)?;
+    }
+
+    ok(sel4_tcb_configure(tcb, fault_ep, cnode, cnode_data, vspace, 0,
+                          PROCESS_IPC_BUFFER_VADDR, ipc_buffer_frame))?;
+    ok(sel4_tcb_set_priority(tcb, CNodeSlot::SeL4CapInitThreadTcb as usize, config.priority))?;
+
+    let entry = elf.header.pt2.entry_point() as Vptr;
+    process.entry = entry;
+    let mut user_context = UserContext::new();
+    sel4_init_context_with_args(entry, PROCESS_IPC_BUFFER_VADDR, 0, 0, PROCESS_STACK_TOP, &mut user_context);
+    ok(sel4_tcb_write_registers(tcb, 0, 0, size_of::<UserContext>() / size_of::<usize>(), &user_context))?;
+    #[cfg(feature = "mcs")]
+    {
+        let sched_context = alloc.alloc_object(ObjectType::SchedContextObject, SEL4_MIN_SCHED_CONTEXT_BITS)?;
+        process.sched_context = sched_context;
+        ok(configure_round_robin(sched_context))?;
+        ok(sel4_sched_context_bind(sched_context, tcb))?;
+        copy_to_child(cnode, CNodeSlot::SeL4CapInitThreadSC as usize, sched_context)?;
+    }
+    ok(sel4_tcb_resume(tcb))
+}
@@ -0,0 +1,81 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use common::message::{InvocationLabel, MessageInfo};
+use common::sched_context::{DEFAULT_TIMESLICE_US, SCHED_CONTEXT_SPORADIC};
+use common::types::{CNodeSlot, Cptr};
+use crate::{call_with_mrs, set_cap, set_mr};
+
+// the SchedControl cap threads and processes get their scheduling contexts configured through
+static SCHED_CONTROL: AtomicUsize = AtomicUsize::new(CNodeSlot::SeL4CapNull as usize);
+
+/// the SchedControl cap of the core spawn starts threads on, the first one in the bootinfo for the root server
+pub fn set_sched_control(sched_control: Cptr) {
+    SCHED_CONTROL.store(sched_control, Ordering::Relaxed);
+}
+
+pub fn sched_control() -> Cptr {
+    SCHED_CONTROL.load(Ordering::Relaxed)
+}
+
+fn invoke(service: Cptr, tag: MessageInfo, mut mr0: usize, mut mr1: usize, mut mr2: usize, mut mr3: usize) -> isize {
+    let output_tag = call_with_mrs(service, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+    result as isize
+}
+
+// seL4_SchedControl_ConfigureFlags, budget and period in microseconds. a budget equal to the
+// period runs round robin
+pub fn sel4_sched_control_configure_flags(service: Cptr, sched_context: Cptr, budget: usize, period: usize,
+    extra_refills: usize, badge: usize, flags: usize) -> isize {
+    let tag = MessageInfo::new(InvocationLabel::SchedControlConfigureFlags, 0, 1, 5);
+    set_cap(0, sched_context);
+    set_mr(4, flags);
+    invoke(service, tag, budget, period, extra_refills, badge)
+}
+
+/// a round robin scheduling context with the default timeslice
+pub fn configure_round_robin(sched_context: Cptr) -> isize {
+    sel4_sched_control_configure_flags(sched_control(), sched_context, DEFAULT_TIMESLICE_US, DEFAULT_TIMESLICE_US,
+                                       0, 0, SCHED_CONTEXT_SPORADIC)
+}
+
+// seL4_SchedContext_Bind, to a thread or a notification
+pub fn sel4_sched_context_bind(service: Cptr, cap: Cptr) -> isize {
+    let tag = MessageInfo::new(InvocationLabel::SchedContextBind, 0, 1, 0);
+    set_cap(0, cap);
+    invoke(service, tag, 0, 0, 0, 0)
+}
+
+// seL4_SchedContext_Unbind, from both the thread and the notification
+pub fn sel4_sched_context_unbind(service: Cptr) -> isize {
+    let tag = MessageInfo::new(InvocationLabel::SchedContextUnbind, 0, 0, 0);
+    invoke(service, tag, 0, 0, 0, 0)
+}
+
+// seL4_SchedContext_UnbindObject
+pub fn sel4_sched_context_unbind_object(service: Cptr, cap: Cptr) -> isize {
+    let tag = MessageInfo::new(InvocationLabel::SchedContextUnbindObject, 0, 1, 0);
+    set_cap(0, cap);
+    invoke(service, tag, 0, 0, 0, 0)
+}
+
+// seL4_SchedContext_Consumed, the microseconds run since the last call, -1 on failure
+pub fn sel4_sched_context_consumed(service: Cptr) -> isize {
+    let tag = MessageInfo::new(InvocationLabel::SchedContextConsumed, 0, 0, 0);
+    let mut mr0 = 0;
+    let mut mr1 = 0;
+    let mut mr2 = 0;
+    let mut mr3 = 0;
+    let output_tag = call_with_mrs(service, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    if output_tag.get_label() != 0 {
+        return -1;
+    }
+    mr0 as isize
+}
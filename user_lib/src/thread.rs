@@ -1,6 +1,18 @@
-use common::{message::{InvocationLabel, MessageInfo}, register::UserContext};
-use crate::{call_with_mrs, set_cap, set_mr, get_mr};
+use core::mem::size_of;
+
+use common::{message::{InvocationLabel, MessageInfo}, register::UserContext, object::ObjectType,
+    config::PAGE_SIZE, types::{CNodeSlot, CapRights, IpcBuffer, VMAttributes}};
+use crate::{call_with_mrs, set_cap, set_mr, get_mr, get_ipc_buffer};
+use crate::notification::{sel4_signal, sel4_wait};
+use crate::process::ObjectAllocator;
+use crate::vspace_manager::VSpace;
 use common::types::{Cptr, Vptr};
+#[cfg(feature = "mcs")]
+use common::sched_context::SEL4_MIN_SCHED_CONTEXT_BITS;
+#[cfg(feature = "mcs")]
+use crate::sched_context::{configure_round_robin, sel4_sched_context_bind};
+
+pub const THREAD_STACK_PAGES: usize = 4;
 
 pub fn sel4_tcb_suspend(service: Cptr) -> usize {
     let tag = MessageInfo::new(InvocationLabel::TCBSuspend, 0, 0, 0);
@@ -60,6 +72,25 @@ pub fn sel4_tcb_set_priority(service: Cptr, authority: Cptr, priority: usize) ->
     result as isize
 }
 
+pub fn sel4_tcb_set_affinity(service: Cptr, affinity: usize) -> isize {
+    let tag = MessageInfo::new(InvocationLabel::TCBSetAffinity, 0, 0, 1);
+    let mut mr0 = affinity;
+    let mut mr1 = 0;
+    let mut mr2 = 0;
+    let mut mr3 = 0;
+
+    let output_tag = call_with_mrs(service, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+    result as isize
+}
+
 pub fn sel4_tcb_read_registers(service: Cptr, suspend_source: usize, arch_flags: u8, count: usize,
     regs: &mut UserContext) -> isize {
 
@@ -189,6 +220,29 @@ pub fn sel4_tcb_resume(service: Cptr) -> isize {
     result as isize
 }
 
+// seL4_TCB_SetTimeoutEndpoint: an endpoint with send and grant rights, or the null cap to clear
+// it. a thread out of budget sends a SEL4_TIMEOUT_FAULT message through it and waits for the reply
+#[cfg(feature = "mcs")]
+pub fn sel4_tcb_set_timeout_endpoint(service: Cptr, timeout_ep: Cptr) -> isize {
+    let tag = MessageInfo::new(InvocationLabel::TCBSetTimeoutEndpoint, 0, 1, 0);
+    set_cap(0, timeout_ep);
+    let mut mr0 = 0;
+    let mut mr1 = 0;
+    let mut mr2 = 0;
+    let mut mr3 = 0;
+
+    let output_tag = call_with_mrs(service, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+    result as isize
+}
+
 pub fn sel4_init_context_with_args(entry_point: usize, arg0: usize, arg1: usize, arg2: usize,
     local_stack: usize, context: &mut UserContext) {
     context.pc = entry_point;
@@ -196,4 +250,161 @@ pub fn sel4_init_context_with_args(entry_point: usize, arg0: usize, arg1: usize,
     context.a0 = arg0;
     context.a1 = arg1;
     context.a2 = arg2;
-}
\ No newline at end of file
+}
+
+/// a thread sharing the caller's cspace and vspace, started by spawn
+#[derive(Debug)]
+pub struct Thread {
+    pub tcb: Cptr,
+    // signalled once the entry function returns
+    pub exit_ntfn: Cptr,
+    pub ipc_buffer_frame: Cptr,
+    // round robin on the default timeslice until reconfigured
+    #[cfg(feature = "mcs")]
+    pub sched_context: Cptr,
+    stack_frames: [Cptr; THREAD_STACK_PAGES],
+    // guard page, stack and ipc buffer, from the bottom up
+    region: Vptr,
+}
+
+impl Thread {
+    /// the unmapped page below the stack
+    pub fn guard_page(&self) -> Vptr {
+        self.region
+    }
+
+    pub fn stack_top(&self) -> Vptr {
+        self.region + (THREAD_STACK_PAGES + 1) * PAGE_SIZE
+    }
+
+    pub fn ipc_buffer(&self) -> Vptr {
+        self.stack_top()
+    }
+
+    /// let a created thread run
+    pub fn start(&self) -> bool {
+        sel4_tcb_resume(self.tcb) == 0
+    }
+
+    /// wait for the entry function to return, and hand back its result
+    pub fn join(&self) -> usize {
+        sel4_wait(self.exit_ntfn);
+        unsafe { (*(self.ipc_buffer() as *const IpcBuffer)).msg[0] }
+    }
+
+    /// stop the thread if it still runs, and give back everything spawn allocated. parts spawn
+    /// never got to are left out, so a half built thread goes the same way
+    pub fn destroy(self, alloc: &mut impl ObjectAllocator, vspace: &mut VSpace) -> bool {
+        let mut ok = free_if_set(alloc, self.tcb);
+        #[cfg(feature = "mcs")]
+        {
+            ok &= free_if_set(alloc, self.sched_context);
+        }
+        for i in 1..THREAD_STACK_PAGES + 2 {
+            let vaddr = self.region + i * PAGE_SIZE;
+            if vspace.frame_at(vaddr).is_some() {
+                ok &= vspace.unmap_pages(alloc, vaddr, 1);
+            }
+        }
+        ok &= vspace.unreserve(self.region);
+        for frame in self.stack_frames.iter().chain([self.ipc_buffer_frame, self.exit_ntfn].iter()) {
+            ok &= free_if_set(alloc, *frame);
+        }
+        ok
+    }
+}
+
+// 0 being the null cap, nothing was allocated for it
+fn free_if_set(alloc: &mut impl ObjectAllocator, object: Cptr) -> bool {
+    object == 0 || alloc.free_object(object)
+}
+
+// the first code a spawned thread runs, with the ipc buffer already in tp
+extern "C" fn thread_start(entry: usize, arg: usize, tcb: Cptr, exit_ntfn: Cptr) -> ! {
+    let entry: fn(usize) -> usize = unsafe { core::mem::transmute(entry) };
+    let result = entry(arg);
+    // the buffer is no longer needed for ipc, so it carries the result to join
+    get_ipc_buffer().msg[0] = result;
+    sel4_signal(exit_ntfn);
+    sel4_tcb_suspend(tcb);
+    loop {}
+}
+
+/// run `entry(arg)` in a new thread of the caller's cspace and of `vspace`, whose stack sits
+/// above an unmapped guard page. on failure whatever was created on the way is given back
+pub fn spawn(alloc: &mut impl ObjectAllocator, vspace: &mut VSpace, entry: fn(usize) -> usize, arg: usize,
+    priority: usize) -> Option<Thread> {
+
+    let thread = create(alloc, vspace, entry, arg, priority)?;
+    if !thread.start() {
+        thread.destroy(alloc, vspace);
+        return None;
+    }
+    Some(thread)
+}
+
+/// like spawn, but the thread only runs once started
+pub fn create(alloc: &mut impl ObjectAllocator, vspace: &mut VSpace, entry: fn(usize) -> usize, arg: usize,
+    priority: usize) -> Option<Thread> {
+
+    let region = vspace.reserve_anywhere((THREAD_STACK_PAGES + 2) * PAGE_SIZE)?;
+    let mut thread = Thread {
+        tcb: 0,
+        exit_ntfn: 0,
+        ipc_buffer_frame: 0,
+        #[cfg(feature = "mcs")]
+        sched_context: 0,
+        stack_frames: [0; THREAD_STACK_PAGES],
+        region,
+    };
+    if configure(alloc, vspace, entry, arg, priority, &mut thread).is_none() {
+        thread.destroy(alloc, vspace);
+        return None;
+    }
+    Some(thread)
+}
+
+fn configure(alloc: &mut impl ObjectAllocator, vspace: &mut VSpace, entry: fn(usize) -> usize, arg: usize,
+    priority: usize, thread: &mut Thread) -> Option<()> {
+
+    let region = thread.region;
+    let rights = CapRights::new(1, 1, 1, 1);
+    for frame in thread.stack_frames.iter_mut() {
+        *frame = alloc.alloc_object(ObjectType::Riscv4kpage, 0)?;
+    }
+    if !vspace.map_pages(alloc, &thread.stack_frames, region + PAGE_SIZE, rights, VMAttributes::ExecuteNever) {
+        return None;
+    }
+    thread.ipc_buffer_frame = alloc.alloc_object(ObjectType::Riscv4kpage, 0)?;
+    if !vspace.map_pages(alloc, &[thread.ipc_buffer_frame], thread.ipc_buffer(), rights, VMAttributes::ExecuteNever) {
+        return None;
+    }
+    thread.exit_ntfn = alloc.alloc_object(ObjectType::NotificationObject, 0)?;
+    thread.tcb = alloc.alloc_object(ObjectType::TCBObject, 0)?;
+
+    if sel4_tcb_configure(thread.tcb, CNodeSlot::SeL4CapNull as usize, CNodeSlot::SeL4CapInitThreadCNode as usize, 0,
+                          vspace.root(), 0, thread.ipc_buffer(), thread.ipc_buffer_frame) != 0 {
+        return None;
+    }
+    if sel4_tcb_set_priority(thread.tcb, CNodeSlot::SeL4CapInitThreadTcb as usize, priority) != 0 {
+        return None;
+    }
+
+    let mut user_context = UserContext::new();
+    sel4_init_context_with_args(thread_start as usize, entry as usize, arg, thread.tcb, thread.stack_top(),
+                                &mut user_context);
+    user_context.a3 = thread.exit_ntfn;
+    user_context.tp = thread.ipc_buffer();
+    if sel4_tcb_write_registers(thread.tcb, 0, 0, size_of::<UserContext>() / size_of::<usize>(), &user_context) != 0 {
+        return None;
+    }
+    #[cfg(feature = "mcs")]
+    {
+        thread.sched_context = alloc.alloc_object(ObjectType::SchedContextObject, SEL4_MIN_SCHED_CONTEXT_BITS)?;
+        if configure_round_robin(thread.sched_context) != 0
+            || sel4_sched_context_bind(thread.sched_context, thread.tcb) != 0 {
+            return None;
+        }
+    }
+    Some(())
+}
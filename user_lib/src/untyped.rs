@@ -1,18 +1,19 @@
 use common::types::Cptr;
 use common::message::{InvocationLabel, MessageInfo};
 
-use crate::{set_cap, set_mr, call_with_mrs};
+use crate::{set_cap, set_mr, call_with_mrs, cspace::CapPath};
 
 
-pub fn sel4_untyped_retype(service: Cptr, dest_type: usize, size_bits: usize, root: Cptr, node_index: usize,
-    node_depth: usize, node_offset: usize, num_objects: usize) -> isize {
+// `dest` names the cnode the objects go to, from slot `node_offset` on
+pub fn sel4_untyped_retype(service: Cptr, dest_type: usize, size_bits: usize, dest: CapPath, node_offset: usize,
+    num_objects: usize) -> isize {
     
     let tag = MessageInfo::new(InvocationLabel::UntypedRetype, 0, 1, 6);
     let mut mr0: usize = dest_type;
     let mut mr1: usize = size_bits;
-    let mut mr2: usize = node_index;
-    let mut mr3: usize = node_depth;
-    set_cap(0, root);
+    let mut mr2: usize = dest.index;
+    let mut mr3: usize = dest.depth;
+    set_cap(0, dest.root);
     set_mr(4, node_offset);
     set_mr(5, num_objects);
 
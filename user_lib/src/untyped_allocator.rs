@@ -0,0 +1,258 @@
+use common::{object::ObjectType, config::MIN_UNTYPED_BITS, utils::bit, types::{Cptr, Paddr, SlotRegion}};
+
+use crate::cnode::sel4_cnode_revoke;
+use crate::cspace::{CapPath, CSlot, SlotAllocator};
+use crate::process::ObjectAllocator;
+use crate::untyped::sel4_untyped_retype;
+
+pub const MAX_UNTYPED_NODES: usize = 1024;
+
+const NO_PARENT: usize = usize::MAX;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum NodeState {
+    Unused,
+    Free,
+    // retyped into two halves of one bit less
+    Split,
+    // retyped into the object held in the slot
+    Allocated(Cptr),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct UntypedNode {
+    cptr: Cptr,
+    paddr: Paddr,
+    size_bits: usize,
+    is_device: bool,
+    parent: usize,
+    state: NodeState,
+}
+
+impl UntypedNode {
+    const UNUSED: UntypedNode = UntypedNode {
+        cptr: 0,
+        paddr: 0,
+        size_bits: 0,
+        is_device: false,
+        parent: NO_PARENT,
+        state: NodeState::Unused,
+    };
+
+    fn contains(&self, paddr: Paddr, size_bits: usize) -> bool {
+        paddr >= self.paddr && paddr + bit(size_bits) <= self.paddr + bit(self.size_bits)
+    }
+}
+
+/// a buddy allocator over the untypeds of the root cnode: every object gets an untyped of
+/// exactly its size, split off a larger one on demand, and freeing revokes that untyped and
+/// merges it back with its buddy.
+pub struct UntypedAllocator {
+    nodes: [UntypedNode; MAX_UNTYPED_NODES],
+    slots: SlotAllocator,
+}
+
+fn revoke(cptr: Cptr) -> bool {
+    sel4_cnode_revoke(CapPath::root_slot(cptr)) == 0
+}
+
+impl UntypedAllocator {
+    pub const fn new() -> Self {
+        UntypedAllocator {
+            nodes: [UntypedNode::UNUSED; MAX_UNTYPED_NODES],
+            slots: SlotAllocator::new(),
+        }
+    }
+
+    /// forget every untyped and slot, as if just made
+    pub fn reset(&mut self) {
+        self.nodes.fill(UntypedNode::UNUSED);
+        self.slots = SlotAllocator::new();
+    }
+
+    /// the empty slots of the root cnode the allocator may fill
+    pub fn set_slots(&mut self, slots: SlotRegion) {
+        self.slots = SlotAllocator::new();
+        self.slots.add_root_slots(slots);
+    }
+
+    /// the slots the allocator fills, shared with callers that need empty root slots of their own
+    pub fn slots(&mut self) -> &mut SlotAllocator {
+        &mut self.slots
+    }
+
+    pub fn add_untyped(&mut self, cptr: Cptr, paddr: Paddr, size_bits: usize, is_device: bool) -> bool {
+        match self.new_node(cptr, paddr, size_bits, is_device, NO_PARENT) {
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// where the memory behind `object` starts
+    pub fn object_paddr(&self, object: Cptr) -> Option<Paddr> {
+        self.nodes.iter().find(|node| node.state == NodeState::Allocated(object)).map(|node| node.paddr)
+    }
+
+    /// bytes of ram not handed out, in free untypeds
+    pub fn free_bytes(&self) -> usize {
+        self.nodes.iter()
+            .filter(|node| node.state == NodeState::Free && !node.is_device)
+            .map(|node| bit(node.size_bits))
+            .sum()
+    }
+
+    pub fn alloc_object(&mut self, t: ObjectType, user_obj_size: usize) -> Option<Cptr> {
+        self.alloc(t, user_obj_size, false, None)
+    }
+
+    /// an object backed by the device memory at `paddr`, frames for mmio mostly
+    pub fn alloc_device_object(&mut self, t: ObjectType, user_obj_size: usize, paddr: Paddr) -> Option<Cptr> {
+        self.alloc(t, user_obj_size, true, Some(paddr))
+    }
+
+    /// revoke the untyped behind `object`, deleting every cap derived from it
+    pub fn free_object(&mut self, object: Cptr) -> bool {
+        let index = match self.nodes.iter().position(|node| node.state == NodeState::Allocated(object)) {
+            Some(index) => index,
+            None => return false,
+        };
+        if !revoke(self.nodes[index].cptr) {
+            return false;
+        }
+        self.free_slot(object);
+        self.nodes[index].state = NodeState::Free;
+        self.merge(index);
+        true
+    }
+
+    fn alloc(&mut self, t: ObjectType, user_obj_size: usize, is_device: bool, paddr: Option<Paddr>) -> Option<Cptr> {
+        let size_bits = t.get_size(user_obj_size).max(MIN_UNTYPED_BITS);
+        let mut index = self.find_free(size_bits, is_device, paddr)?;
+        while self.nodes[index].size_bits > size_bits {
+            index = self.split(index, paddr)?;
+        }
+
+        let slot = self.alloc_slot()?;
+        let error = sel4_untyped_retype(self.nodes[index].cptr, t as usize, user_obj_size,
+            CapPath::root_cnode(), slot, 1);
+        if error != 0 {
+            self.free_slot(slot);
+            return None;
+        }
+        self.nodes[index].state = NodeState::Allocated(slot);
+        Some(slot)
+    }
+
+    /// the smallest free untyped that fits, so larger ones are only split when needed
+    fn find_free(&self, size_bits: usize, is_device: bool, paddr: Option<Paddr>) -> Option<usize> {
+        self.nodes.iter()
+            .enumerate()
+            .filter(|(_, node)| node.state == NodeState::Free && node.is_device == is_device && node.size_bits >= size_bits)
+            .filter(|(_, node)| paddr.map_or(true, |paddr| node.contains(paddr, size_bits)))
+            .min_by_key(|(_, node)| node.size_bits)
+            .map(|(index, _)| index)
+    }
+
+    /// retype `index` into two halves, returning the one holding `paddr`, or the lower one. on
+    /// failure `index` is left free and whole
+    fn split(&mut self, index: usize, paddr: Option<Paddr>) -> Option<usize> {
+        let parent = self.nodes[index];
+        let half_bits = parent.size_bits - 1;
+        // everything the halves need, before the untyped is touched
+        let mut unused = self.nodes.iter().enumerate()
+            .filter(|(_, node)| node.state == NodeState::Unused)
+            .map(|(i, _)| i);
+        let halves = [unused.next()?, unused.next()?];
+        let lower_slot = self.alloc_slot()?;
+        let upper_slot = match self.alloc_slot() {
+            Some(slot) => slot,
+            None => {
+                self.free_slot(lower_slot);
+                return None;
+            }
+        };
+        let slots = [lower_slot, upper_slot];
+
+        for slot in slots {
+            // the second retype lands right after the first one, at the upper half
+            let error = sel4_untyped_retype(parent.cptr, ObjectType::UntypedObject as usize, half_bits,
+                CapPath::root_cnode(), slot, 1);
+            if error != 0 {
+                revoke(parent.cptr);
+                self.free_slot(upper_slot);
+                self.free_slot(lower_slot);
+                return None;
+            }
+        }
+        for i in 0..2 {
+            self.nodes[halves[i]] = UntypedNode {
+                cptr: slots[i],
+                paddr: parent.paddr + i * bit(half_bits),
+                size_bits: half_bits,
+                is_device: parent.is_device,
+                parent: index,
+                state: NodeState::Free,
+            };
+        }
+        self.nodes[index].state = NodeState::Split;
+        match paddr {
+            Some(paddr) if paddr >= parent.paddr + bit(half_bits) => Some(halves[1]),
+            _ => Some(halves[0]),
+        }
+    }
+
+    /// fold a free node back into its parent for as long as its buddy is free too
+    fn merge(&mut self, index: usize) {
+        let mut index = index;
+        while self.nodes[index].parent != NO_PARENT {
+            let parent = self.nodes[index].parent;
+            let all_free = self.nodes.iter()
+                .filter(|node| node.state != NodeState::Unused && node.parent == parent)
+                .all(|node| node.state == NodeState::Free);
+            if !all_free || !revoke(self.nodes[parent].cptr) {
+                return;
+            }
+            for i in 0..MAX_UNTYPED_NODES {
+                if self.nodes[i].state != NodeState::Unused && self.nodes[i].parent == parent {
+                    self.free_slot(self.nodes[i].cptr);
+                    self.nodes[i] = UntypedNode::UNUSED;
+                }
+            }
+            self.nodes[parent].state = NodeState::Free;
+            index = parent;
+        }
+    }
+
+    fn new_node(&mut self, cptr: Cptr, paddr: Paddr, size_bits: usize, is_device: bool, parent: usize) -> Option<usize> {
+        let index = self.nodes.iter().position(|node| node.state == NodeState::Unused)?;
+        self.nodes[index] = UntypedNode { cptr, paddr, size_bits, is_device, parent, state: NodeState::Free };
+        Some(index)
+    }
+
+    // objects must stay invocable, so only slots of the root cnode will do
+    fn alloc_slot(&mut self) -> Option<Cptr> {
+        self.slots.alloc_root()?.cptr()
+    }
+
+    fn free_slot(&mut self, slot: Cptr) {
+        self.slots.free(CSlot::root(slot));
+    }
+}
+
+impl ObjectAllocator for UntypedAllocator {
+    fn alloc_object(&mut self, t: ObjectType, user_obj_size: usize) -> Option<Cptr> {
+        UntypedAllocator::alloc_object(self, t, user_obj_size)
+    }
+
+    fn free_object(&mut self, object: Cptr) -> bool {
+        UntypedAllocator::free_object(self, object)
+    }
+
+    fn alloc_slot(&mut self) -> Option<Cptr> {
+        UntypedAllocator::alloc_slot(self)
+    }
+
+    fn free_slot(&mut self, slot: Cptr) {
+        UntypedAllocator::free_slot(self, slot)
+    }
+}
@@ -1,9 +1,10 @@
 use common::{types::{VMAttributes, Cptr, CapRights}};
-use common::message::{MessageInfo, InvocationLabel::{PageMap, PageTableMap}};
+use common::message::{MessageInfo, SEL4_FAILED_LOOKUP, InvocationLabel::{PageMap, PageUnmap, PageGetAddress, PageTableMap, PageTableUnmap,
+    ASIDControlMakePool, ASIDPoolAssign}};
 
-use crate::{set_cap, call_with_mrs, set_mr};
+use crate::{set_cap, call_with_mrs, set_mr, cspace::CapPath};
 
-// seL4_RISCV_Page_Map
+// seL4_RISCV_Page_Map, SEL4_FAILED_LOOKUP when a page table is missing at vaddr
 pub fn sel4_page_map(service: Cptr, vspace: Cptr, vaddr: usize, rights: CapRights, attr: VMAttributes) -> isize {
     let tag = MessageInfo::new(PageMap, 0, 1, 3);
     let mut mr0: usize = vaddr;
@@ -19,7 +20,7 @@ pub fn sel4_page_map(service: Cptr, vspace: Cptr, vaddr: usize, rights: CapRight
         set_mr(1, mr1);
         set_mr(2, mr2);
         set_mr(3, mr3);
-        return -1;
+        return if result == SEL4_FAILED_LOOKUP { SEL4_FAILED_LOOKUP as isize } else { -1 };
     }
 
     result as isize
@@ -45,4 +46,111 @@ pub fn sel4_page_table_map(service: Cptr, vspace: Cptr, vaddr: usize, attr: VMAt
     }
 
     result as isize
-}
\ No newline at end of file
+}
+// seL4_RISCV_Page_Unmap
+pub fn sel4_page_unmap(service: Cptr) -> isize {
+    let tag = MessageInfo::new(PageUnmap, 0, 0, 0);
+    let mut mr0: usize = 0;
+    let mut mr1: usize = 0;
+    let mut mr2: usize = 0;
+    let mut mr3: usize = 0;
+
+    let output_tag = call_with_mrs(service, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+
+    result as isize
+}
+
+// seL4_RISCV_Page_GetAddress, returns (error, paddr)
+pub fn sel4_page_get_address(service: Cptr) -> (isize, usize) {
+    let tag = MessageInfo::new(PageGetAddress, 0, 0, 0);
+    let mut mr0: usize = 0;
+    let mut mr1: usize = 0;
+    let mut mr2: usize = 0;
+    let mut mr3: usize = 0;
+
+    let output_tag = call_with_mrs(service, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return (-1, 0);
+    }
+
+    (result as isize, mr0)
+}
+
+// seL4_RISCV_PageTable_Unmap
+pub fn sel4_page_table_unmap(service: Cptr) -> isize {
+    let tag = MessageInfo::new(PageTableUnmap, 0, 0, 0);
+    let mut mr0: usize = 0;
+    let mut mr1: usize = 0;
+    let mut mr2: usize = 0;
+    let mut mr3: usize = 0;
+
+    let output_tag = call_with_mrs(service, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+
+    result as isize
+}
+
+// seL4_RISCV_ASIDControl_MakePool
+pub fn sel4_asid_control_make_pool(service: Cptr, untyped: Cptr, dest: CapPath) -> isize {
+    let tag = MessageInfo::new(ASIDControlMakePool, 0, 2, 2);
+    let mut mr0: usize = dest.index;
+    let mut mr1: usize = dest.depth & 0xff;
+    let mut mr2: usize = 0;
+    let mut mr3: usize = 0;
+    set_cap(0, untyped);
+    set_cap(1, dest.root);
+
+    let output_tag = call_with_mrs(service, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+
+    result as isize
+}
+
+// seL4_RISCV_ASIDPool_Assign
+pub fn sel4_asid_pool_assign(service: Cptr, vspace: Cptr) -> isize {
+    let tag = MessageInfo::new(ASIDPoolAssign, 0, 1, 0);
+    let mut mr0: usize = 0;
+    let mut mr1: usize = 0;
+    let mut mr2: usize = 0;
+    let mut mr3: usize = 0;
+    set_cap(0, vspace);
+
+    let output_tag = call_with_mrs(service, tag, &mut mr0, &mut mr1, &mut mr2, &mut mr3);
+    let result = output_tag.get_label();
+    if result != 0 {
+        set_mr(0, mr0);
+        set_mr(1, mr1);
+        set_mr(2, mr2);
+        set_mr(3, mr3);
+        return -1;
+    }
+
+    result as isize
+}
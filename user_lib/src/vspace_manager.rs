@@ -0,0 +1,224 @@
+use common::{object::ObjectType, message::SEL4_FAILED_LOOKUP, config::{PAGE_BITS, PAGE_SIZE, USER_TOP},
+    types::{CapRights, Cptr, VMAttributes, Vptr}, utils::{round_down, round_up}};
+
+use crate::cnode::{sel4_cnode_copy, sel4_cnode_delete};
+use crate::cspace::CapPath;
+use crate::process::ObjectAllocator;
+use crate::vspace::{sel4_page_map, sel4_page_table_map, sel4_page_unmap};
+
+pub const MAX_RESERVATIONS: usize = 32;
+pub const MAX_MAPPINGS: usize = 256;
+pub const MAX_PAGE_TABLES: usize = 64;
+// where reserve_anywhere looks, clear of the images and the fixed addresses loaders and tests use
+pub const VSPACE_ALLOC_START: Vptr = 0x10_0000_0000;
+pub const VSPACE_ALLOC_END: Vptr = 0x20_0000_0000;
+
+#[derive(Clone, Copy, Debug)]
+struct Reservation {
+    start: Vptr,
+    end: Vptr,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Mapping {
+    vaddr: Vptr,
+    frame: Cptr,
+    // a copy of the caller's cap, made and owned by the vspace
+    copied: bool,
+}
+
+/// bookkeeping for one vspace: frames only go into reserved ranges, and the page tables they
+/// need are allocated on the way and kept here for the owner to free.
+#[derive(Clone)]
+pub struct VSpace {
+    root: Cptr,
+    reservations: [Option<Reservation>; MAX_RESERVATIONS],
+    mappings: [Option<Mapping>; MAX_MAPPINGS],
+    page_tables: [Option<Cptr>; MAX_PAGE_TABLES],
+}
+
+impl VSpace {
+    /// `root` is the vspace's root page table, already assigned an asid
+    pub const fn new(root: Cptr) -> Self {
+        VSpace {
+            root,
+            reservations: [None; MAX_RESERVATIONS],
+            mappings: [None; MAX_MAPPINGS],
+            page_tables: [None; MAX_PAGE_TABLES],
+        }
+    }
+
+    pub fn root(&self) -> Cptr {
+        self.root
+    }
+
+    /// keep [vaddr, vaddr + size), rounded out to pages, for the caller
+    pub fn reserve(&mut self, vaddr: Vptr, size: usize) -> bool {
+        if size == 0 || vaddr + size > USER_TOP {
+            return false;
+        }
+        let start = round_down(vaddr, PAGE_BITS);
+        let end = round_up(vaddr + size, PAGE_BITS);
+        if self.reservations.iter().flatten().any(|r| r.start < end && start < r.end) {
+            return false;
+        }
+        match self.reservations.iter_mut().find(|r| r.is_none()) {
+            Some(free) => {
+                *free = Some(Reservation { start, end });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// reserve `size` bytes at the lowest free address of the allocation window
+    pub fn reserve_anywhere(&mut self, size: usize) -> Option<Vptr> {
+        if size == 0 {
+            return None;
+        }
+        let size = round_up(size, PAGE_BITS);
+        let mut start = VSPACE_ALLOC_START;
+        while start + size <= VSPACE_ALLOC_END {
+            let overlap_end = self.reservations.iter().flatten()
+                .filter(|r| r.start < start + size && start < r.end)
+                .map(|r| r.end)
+                .max();
+            match overlap_end {
+                Some(end) => start = end,
+                None => return if self.reserve(start, size) { Some(start) } else { None },
+            }
+        }
+        None
+    }
+
+    /// give back the reservation starting at `vaddr`, once nothing is mapped in it
+    pub fn unreserve(&mut self, vaddr: Vptr) -> bool {
+        let index = match self.reservations.iter().position(|r| r.map_or(false, |r| r.start == vaddr)) {
+            Some(index) => index,
+            None => return false,
+        };
+        let r = self.reservations[index].unwrap();
+        if self.mappings.iter().flatten().any(|m| m.vaddr >= r.start && m.vaddr < r.end) {
+            return false;
+        }
+        self.reservations[index] = None;
+        true
+    }
+
+    /// map `frames` one after the other from `vaddr`, which must be reserved
+    pub fn map_pages(&mut self, alloc: &mut impl ObjectAllocator, frames: &[Cptr], vaddr: Vptr,
+        rights: CapRights, attr: VMAttributes) -> bool {
+        self.map(alloc, frames, vaddr, rights, attr, false)
+    }
+
+    /// like map_pages, but maps copies of the caps, so frames mapped somewhere else already can
+    /// be shared; the copies carry `rights` and are deleted again on unmap
+    pub fn map_copies(&mut self, alloc: &mut impl ObjectAllocator, frames: &[Cptr], vaddr: Vptr,
+        rights: CapRights, attr: VMAttributes) -> bool {
+        self.map(alloc, frames, vaddr, rights, attr, true)
+    }
+
+    /// unmap `num_pages` pages from `vaddr`, failing on the ones that were not mapped here
+    pub fn unmap_pages(&mut self, alloc: &mut impl ObjectAllocator, vaddr: Vptr, num_pages: usize) -> bool {
+        let mut ok = true;
+        for i in 0..num_pages {
+            let mapping = match self.find_mapping(vaddr + i * PAGE_SIZE) {
+                Some(index) => self.mappings[index].take().unwrap(),
+                None => {
+                    ok = false;
+                    continue;
+                }
+            };
+            if mapping.copied {
+                // deleting the copy unmaps it
+                ok &= sel4_cnode_delete(CapPath::root_slot(mapping.frame)) == 0;
+                alloc.free_slot(mapping.frame);
+            } else {
+                ok &= sel4_page_unmap(mapping.frame) == 0;
+            }
+        }
+        ok
+    }
+
+    /// the frame mapped at the page holding `vaddr`
+    pub fn frame_at(&self, vaddr: Vptr) -> Option<Cptr> {
+        self.find_mapping(round_down(vaddr, PAGE_BITS)).map(|index| self.mappings[index].unwrap().frame)
+    }
+
+    /// page tables created for this vspace, which stay mapped until freed
+    pub fn page_tables(&self) -> impl Iterator<Item = Cptr> + '_ {
+        self.page_tables.iter().flatten().copied()
+    }
+
+    fn map(&mut self, alloc: &mut impl ObjectAllocator, frames: &[Cptr], vaddr: Vptr,
+        rights: CapRights, attr: VMAttributes, copy: bool) -> bool {
+
+        if vaddr % PAGE_SIZE != 0 || !self.is_reserved(vaddr, vaddr + frames.len() * PAGE_SIZE) {
+            return false;
+        }
+        for (i, frame) in frames.iter().enumerate() {
+            if self.map_one(alloc, *frame, vaddr + i * PAGE_SIZE, rights, attr, copy).is_none() {
+                self.unmap_pages(alloc, vaddr, i);
+                return false;
+            }
+        }
+        true
+    }
+
+    fn map_one(&mut self, alloc: &mut impl ObjectAllocator, frame: Cptr, vaddr: Vptr,
+        rights: CapRights, attr: VMAttributes, copy: bool) -> Option<()> {
+
+        if self.find_mapping(vaddr).is_some() {
+            return None;
+        }
+        let index = self.mappings.iter().position(|m| m.is_none())?;
+        let frame = if copy {
+            let slot = alloc.alloc_slot()?;
+            if sel4_cnode_copy(CapPath::root_slot(slot), CapPath::root_slot(frame), rights) != 0 {
+                alloc.free_slot(slot);
+                return None;
+            }
+            slot
+        } else {
+            frame
+        };
+        if self.map_frame(alloc, frame, vaddr, rights, attr).is_none() {
+            if copy {
+                sel4_cnode_delete(CapPath::root_slot(frame));
+                alloc.free_slot(frame);
+            }
+            return None;
+        }
+        self.mappings[index] = Some(Mapping { vaddr, frame, copied: copy });
+        Some(())
+    }
+
+    fn map_frame(&mut self, alloc: &mut impl ObjectAllocator, frame: Cptr, vaddr: Vptr,
+        rights: CapRights, attr: VMAttributes) -> Option<()> {
+
+        // sv39 may lack both levels of page tables below the root
+        for _ in 0..3 {
+            match sel4_page_map(frame, self.root, vaddr, rights, attr) {
+                0 => return Some(()),
+                error if error == SEL4_FAILED_LOOKUP as isize => {}
+                _ => return None,
+            }
+            let index = self.page_tables.iter().position(|pt| pt.is_none())?;
+            let pt = alloc.alloc_object(ObjectType::RiscvPageTableObject, 0)?;
+            if sel4_page_table_map(pt, self.root, vaddr, VMAttributes::DefaultVMAttributes) != 0 {
+                alloc.free_object(pt);
+                return None;
+            }
+            self.page_tables[index] = Some(pt);
+        }
+        None
+    }
+
+    fn is_reserved(&self, start: Vptr, end: Vptr) -> bool {
+        self.reservations.iter().flatten().any(|r| r.start <= start && end <= r.end)
+    }
+
+    fn find_mapping(&self, vaddr: Vptr) -> Option<usize> {
+        self.mappings.iter().position(|m| m.map_or(false, |m| m.vaddr == vaddr))
+    }
+}